@@ -0,0 +1,362 @@
+use super::{
+    repair::root_stack,
+    rstd::{string::String, vec::Vec},
+    tree::Proof,
+    ChildSelector, DBValue, DataError, HashDB, Key, KeyMap, KeyedTree, KeyedTreeMut, MemDb, Node,
+    NodeHash, TreeDB, TreeDBBuilder, TreeDBMutBuilder, TreeError, TreeHasher, Write,
+};
+use core::future::Future;
+use hash_db::{AsHashDB, Prefix, EMPTY_PREFIX};
+
+// AsyncHashDB
+// ================================================================================================
+
+/// An immutable key-value database whose reads are async, e.g. a networked key-value store. The
+/// async counterpart of HashDBRef.
+pub trait AsyncHashDBRef<H: TreeHasher>: Sync {
+    /// Looks up the data of the provided hash, None if the hash is not known
+    fn get(&self, key: &H::Out, prefix: Prefix) -> impl Future<Output = Option<DBValue>> + Send;
+}
+
+/// A mutable key-value database whose reads and writes are async, the async counterpart of
+/// HashDB. Records are reference counted: each emplace adds a reference and each remove drops a
+/// reference to the record. Writes return a description of the error if they failed, see
+/// TryHashDB.
+pub trait AsyncHashDB<H: TreeHasher>: AsyncHashDBRef<H> {
+    /// Adds a reference to the record with the provided hash and data
+    fn emplace(
+        &mut self,
+        key: H::Out,
+        prefix: Prefix,
+        value: DBValue,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+
+    /// Drops a reference to the record with the provided hash
+    fn remove(
+        &mut self,
+        key: &H::Out,
+        prefix: Prefix,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+}
+
+// AsyncTreeDBBuilder
+// ================================================================================================
+
+/// Used to construct an AsyncTreeDB
+pub struct AsyncTreeDBBuilder<'db, const D: usize, H: TreeHasher, DB: AsyncHashDBRef<H>> {
+    db: &'db DB,
+    root: H::Out,
+    salt: DBValue,
+}
+
+impl<'db, const D: usize, H: TreeHasher, DB: AsyncHashDBRef<H>> AsyncTreeDBBuilder<'db, D, H, DB> {
+    /// Construct a new AsyncTreeDBBuilder
+    pub fn new(db: &'db DB, root: &H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
+            db,
+            root: *root,
+            salt: DBValue::default(),
+        }
+    }
+
+    /// Salt the leaf hashes of the tree with the provided salt, see TreeDBMutBuilder::with_salt
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Build an AsyncTreeDB
+    pub fn build(self) -> AsyncTreeDB<'db, D, H, DB> {
+        AsyncTreeDB {
+            db: self.db,
+            root: self.root,
+            salt: self.salt,
+        }
+    }
+}
+
+// AsyncTreeDB
+// ================================================================================================
+
+/// An immutable merkle tree over an async database. The nodes along the path of a key are fetched
+/// from the database before the key is read, such that no read blocks on the database.
+pub struct AsyncTreeDB<'db, const D: usize, H: TreeHasher, DB: AsyncHashDBRef<H>> {
+    db: &'db DB,
+    root: H::Out,
+    salt: DBValue,
+}
+
+impl<'db, const D: usize, H: TreeHasher, DB: AsyncHashDBRef<H>> AsyncTreeDB<'db, D, H, DB> {
+    /// Returns the root of the tree
+    pub fn root(&self) -> &H::Out {
+        &self.root
+    }
+
+    /// Returns the value at the provided key, see KeyedTree::value
+    pub async fn value(&self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        self.read(key, |tree| tree.value(key)).await
+    }
+
+    /// Returns the hash of the leaf at the provided key, see KeyedTree::leaf
+    pub async fn leaf(&self, key: &[u8]) -> Result<Option<H::Out>, TreeError> {
+        self.read(key, |tree| tree.leaf(key)).await
+    }
+
+    /// Returns an inclusion proof of the value at the provided key, see KeyedTree::proof
+    pub async fn proof(&self, key: &[u8]) -> Result<Proof<H>, TreeError> {
+        self.read(key, |tree| tree.proof(key)).await
+    }
+
+    /// Fetches the nodes along the path of the provided key and reads the tree with the provided
+    /// function
+    async fn read<T>(
+        &self,
+        key: &[u8],
+        read: impl FnOnce(&TreeDB<'_, D, H>) -> Result<T, TreeError>,
+    ) -> Result<T, TreeError> {
+        let path = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let mut cache = MemDb::new();
+        fetch_path(self.db, &self.root, &path, &self.salt, &mut cache).await?;
+        let tree = TreeDBBuilder::<D, H>::new(&cache, &self.root)
+            .with_salt(&self.salt)
+            .build();
+        read(&tree)
+    }
+}
+
+// AsyncTreeDBMutBuilder
+// ================================================================================================
+
+/// Used to construct an AsyncTreeDBMut
+pub struct AsyncTreeDBMutBuilder<'db, const D: usize, H: TreeHasher, DB: AsyncHashDB<H>> {
+    db: &'db mut DB,
+    root: &'db mut H::Out,
+    salt: DBValue,
+}
+
+impl<'db, const D: usize, H: TreeHasher, DB: AsyncHashDB<H>> AsyncTreeDBMutBuilder<'db, D, H, DB> {
+    /// Construct a new AsyncTreeDBMutBuilder
+    pub fn new(db: &'db mut DB, root: &'db mut H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
+            db,
+            root,
+            salt: DBValue::default(),
+        }
+    }
+
+    /// Salt the leaf hashes of the tree with the provided salt, see TreeDBMutBuilder::with_salt
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Build an AsyncTreeDBMut
+    pub fn build(self) -> AsyncTreeDBMut<'db, D, H, DB> {
+        AsyncTreeDBMut {
+            db: self.db,
+            root: self.root,
+            salt: self.salt,
+            changes: KeyMap::default(),
+        }
+    }
+}
+
+// AsyncTreeDBMut
+// ================================================================================================
+
+/// A mutable merkle tree over an async database. Inserts are staged in memory, a commit fetches
+/// the nodes along the paths of the changed keys, computes the new nodes of the tree and writes
+/// them to the database. The root is updated once every write of the commit succeeded.
+pub struct AsyncTreeDBMut<'db, const D: usize, H: TreeHasher, DB: AsyncHashDB<H>> {
+    db: &'db mut DB,
+    root: &'db mut H::Out,
+    salt: DBValue,
+    changes: KeyMap<DBValue, DBValue>,
+}
+
+impl<'db, const D: usize, H: TreeHasher, DB: AsyncHashDB<H>> AsyncTreeDBMut<'db, D, H, DB> {
+    /// Returns the root of the tree as of the last commit
+    pub fn root(&self) -> &H::Out {
+        self.root
+    }
+
+    /// Returns the value at the provided key including the uncommitted changes
+    pub async fn value(&self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        if let Some(value) = self.changes.get(key) {
+            return Ok((!value.is_empty()).then(|| value.clone()));
+        }
+        AsyncTreeDBBuilder::<D, H, DB>::new(self.db, self.root)
+            .with_salt(&self.salt)
+            .build()
+            .value(key)
+            .await
+    }
+
+    /// Stages the provided value at the provided key and returns the old value if it exists. An
+    /// empty value removes the leaf.
+    pub async fn insert(
+        &mut self,
+        key: &[u8],
+        value: DBValue,
+    ) -> Result<Option<DBValue>, TreeError> {
+        let old_value = self.value(key).await?;
+        self.changes.insert(DBValue::from(key), value);
+        Ok(old_value)
+    }
+
+    /// Stages the removal of the leaf at the provided key and returns its value if it exists
+    pub async fn remove(&mut self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        self.insert(key, DBValue::default()).await
+    }
+
+    /// Commits the staged changes to the database and returns the new root. The writes applied
+    /// before a failed write are reverted and the changes remain staged.
+    ///
+    /// Errors:
+    /// - WriteFailed: if a write to the database failed
+    pub async fn commit(&mut self) -> Result<H::Out, TreeError> {
+        let mut staging = StagingDB {
+            cache: MemDb::new(),
+            writes: Vec::new(),
+        };
+        for key in self.changes.keys() {
+            let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+            fetch_path(&*self.db, self.root, &key, &self.salt, &mut staging.cache).await?;
+        }
+
+        let mut root = *self.root;
+        let mut tree = TreeDBMutBuilder::<D, H>::new(&mut staging, &mut root)
+            .with_salt(&self.salt)
+            .build();
+        for (key, value) in self.changes.iter() {
+            tree.insert(key, value.clone())?;
+        }
+        tree.commit();
+        drop(tree);
+
+        for (applied, write) in staging.writes.iter().enumerate() {
+            let result = match write {
+                Write::Emplace(hash, data) => {
+                    self.db.emplace(*hash, EMPTY_PREFIX, data.clone()).await
+                }
+                Write::Remove(hash, _) => self.db.remove(hash, EMPTY_PREFIX).await,
+            };
+            if let Err(err) = result {
+                // the removed nodes are on the paths of the changed keys, as such in the cache
+                for write in staging.writes[..applied].iter().rev() {
+                    let _ = match write {
+                        Write::Emplace(hash, _) => self.db.remove(hash, EMPTY_PREFIX).await,
+                        Write::Remove(hash, _) => {
+                            let data = HashDB::get(&staging.cache, hash, EMPTY_PREFIX);
+                            let data = data.unwrap_or_default();
+                            self.db.emplace(*hash, EMPTY_PREFIX, data).await
+                        }
+                    };
+                }
+                return Err(TreeError::DataError(DataError::WriteFailed(err)));
+            }
+        }
+
+        self.changes.clear();
+        *self.root = root;
+        Ok(root)
+    }
+}
+
+// StagingDB
+// ================================================================================================
+
+/// A database which reads the fetched nodes of a commit and records its writes
+struct StagingDB<H: TreeHasher> {
+    cache: MemDb<H>,
+    writes: Vec<Write<H>>,
+}
+
+impl<H: TreeHasher> HashDB<H, DBValue> for StagingDB<H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(&self.cache, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(&self.cache, key, prefix)
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        let key = H::hash(value);
+        self.emplace(key, prefix, DBValue::from(value));
+        key
+    }
+
+    fn emplace(&mut self, key: H::Out, _prefix: Prefix, value: DBValue) {
+        self.writes.push(Write::Emplace(key, value));
+    }
+
+    fn remove(&mut self, key: &H::Out, _prefix: Prefix) {
+        self.writes.push(Write::Remove(*key, None));
+    }
+}
+
+impl<H: TreeHasher> AsHashDB<H, DBValue> for StagingDB<H> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<H, DBValue> + 'a) {
+        self
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Fetches the nodes along the path of the provided key in the tree with the provided root from
+/// the provided database into the provided cache, down to the leaf or the first default node.
+/// Nodes held by the cache already are not fetched again.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a node along the path is missing from the database
+/// - NodeError: if a node along the path can not be decoded
+async fn fetch_path<const D: usize, H: TreeHasher, DB: AsyncHashDBRef<H>>(
+    db: &DB,
+    root: &H::Out,
+    key: &Key<D>,
+    salt: &[u8],
+    cache: &mut MemDb<H>,
+) -> Result<(), TreeError> {
+    let mut next = root_stack::<D, H>(root, salt).pop();
+    let mut bits = key.iter();
+    while let Some(hash) = next {
+        let data = match HashDB::get(cache, &hash, EMPTY_PREFIX) {
+            Some(data) => data,
+            None => {
+                let data = db
+                    .get(&hash, EMPTY_PREFIX)
+                    .await
+                    .ok_or(TreeError::DataError(DataError::DatabaseDataNotFound(
+                        hash.as_ref().to_vec(),
+                    )))?;
+                cache.emplace(hash, EMPTY_PREFIX, data.clone());
+                data
+            }
+        };
+        next = match bits.next() {
+            Some(bit) => {
+                let node = Node::<H>::decode(data, salt).map_err(TreeError::NodeError)?;
+                match node
+                    .child_hash(&ChildSelector::new(bit))
+                    .map_err(TreeError::NodeError)?
+                {
+                    NodeHash::Database(child) => Some(*child),
+                    _ => None,
+                }
+            }
+            None => None,
+        };
+    }
+    Ok(())
+}
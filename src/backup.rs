@@ -0,0 +1,101 @@
+use super::{
+    repair::{push_children, root_stack},
+    rstd::vec::Vec,
+    DBValue, DataError, HashDBRef, HashSet, Monitor, Node, TreeError, TreeHasher,
+};
+use hash_db::EMPTY_PREFIX;
+
+// NODE ITERATOR
+// ================================================================================================
+
+/// Returns an iterator over every node reachable from the provided root of a tree of depth D * 8.
+/// Each node is yielded once as a tuple of form: (hash, encoded node). Nodes are yielded in pre
+/// order, a node precedes its children and the left subtree precedes the right subtree, such that
+/// the order only depends on the content of the tree. Default nodes are not stored in the
+/// database and are not yielded, the iterator over an empty tree is empty.
+pub fn nodes<'db, const D: usize, H: TreeHasher>(
+    db: &'db dyn HashDBRef<H, DBValue>,
+    root: &H::Out,
+    salt: &[u8],
+) -> NodeIter<'db, H> {
+    NodeIter {
+        db,
+        salt: DBValue::from(salt),
+        stack: root_stack::<D, H>(root, salt),
+        visited: HashSet::default(),
+        monitor: None,
+    }
+}
+
+/// An iterator over the nodes reachable from a root, see nodes.
+pub struct NodeIter<'db, H: TreeHasher> {
+    db: &'db dyn HashDBRef<H, DBValue>,
+    salt: DBValue,
+    stack: Vec<H::Out>,
+    visited: HashSet<H::Out>,
+    monitor: Option<Monitor<'db>>,
+}
+
+impl<'db, H: TreeHasher> NodeIter<'db, H> {
+    /// Reports progress of the iteration to and checks for cancellation with the provided
+    /// monitor. Each yielded node counts as processed, the final report is made once the
+    /// iterator is exhausted.
+    pub fn with_monitor(mut self, monitor: Monitor<'db>) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+}
+
+impl<'db, H: TreeHasher> Iterator for NodeIter<'db, H> {
+    type Item = Result<(H::Out, Vec<u8>), TreeError>;
+
+    /// Returns the next reachable node. Once an error is returned the iterator is exhausted.
+    ///
+    /// Errors:
+    /// - DatabaseDataNotFound: if a reachable node is missing from the database
+    /// - NodeError: if a reachable node can not be decoded
+    /// - Cancelled: if the cancellation token of the monitor is cancelled
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash = loop {
+            let hash = match self.stack.pop() {
+                Some(hash) => hash,
+                None => {
+                    if let Some(mut monitor) = self.monitor.take() {
+                        monitor.finish();
+                    }
+                    return None;
+                }
+            };
+            // identical subtrees share nodes, each node is only yielded once
+            if self.visited.insert(hash) {
+                break hash;
+            }
+        };
+
+        if let Some(Err(error)) = self.monitor.as_mut().map(|monitor| monitor.tick()) {
+            self.stack.clear();
+            self.monitor = None;
+            return Some(Err(error));
+        }
+
+        let data = match self.db.get(&hash, EMPTY_PREFIX) {
+            Some(data) => data,
+            None => {
+                self.stack.clear();
+                return Some(Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+                    hash.as_ref().to_vec(),
+                ))));
+            }
+        };
+        let node = match Node::<H>::decode(data.clone(), &self.salt) {
+            Ok(node) => node,
+            Err(error) => {
+                self.stack.clear();
+                return Some(Err(TreeError::NodeError(error)));
+            }
+        };
+
+        push_children(&node, &mut self.stack);
+        Some(Ok((hash, data.to_vec())))
+    }
+}
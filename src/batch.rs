@@ -0,0 +1,105 @@
+use super::{
+    rstd::{string::String, vec::Vec},
+    DBValue, HashDB, HashMap, Hasher, TreeHasher, Write,
+};
+use hash_db::{AsHashDB, Prefix};
+
+// WriteBatch
+// ================================================================================================
+
+/// The writes of a commit consolidated into a single batch under the storage prefix of the tree.
+/// Each record is of form: (hash, data, reference count delta), a record with a positive delta is
+/// emplaced delta times and a record with a negative delta is removed -delta times. The records
+/// are ordered by hash and records whose writes cancel out are omitted.
+pub struct WriteBatch<H: Hasher> {
+    prefix: (DBValue, Option<u8>),
+    writes: Vec<(H::Out, DBValue, i32)>,
+}
+
+impl<H: TreeHasher> WriteBatch<H> {
+    /// Construct a batch of the provided writes of a commit under the provided prefix
+    pub(crate) fn from_writes(prefix: Prefix, writes: &[Write<H>]) -> Self {
+        let mut records: HashMap<H::Out, (DBValue, i32)> = HashMap::default();
+        for write in writes {
+            match write {
+                Write::Emplace(hash, data) => {
+                    let (record, rc) = records.entry(*hash).or_default();
+                    if *rc <= 0 {
+                        *record = data.clone();
+                    }
+                    *rc += 1;
+                }
+                Write::Remove(hash, _) => records.entry(*hash).or_default().1 -= 1,
+            }
+        }
+        let mut writes: Vec<_> = records
+            .into_iter()
+            .filter(|(_, (_, rc))| *rc != 0)
+            .map(|(hash, (data, rc))| (hash, data, rc))
+            .collect();
+        writes.sort_by(|(a, _, _), (b, _, _)| a.as_ref().cmp(b.as_ref()));
+        Self {
+            prefix: (DBValue::from(prefix.0), prefix.1),
+            writes,
+        }
+    }
+}
+
+impl<H: Hasher> WriteBatch<H> {
+    /// Construct a batch of a single record under the provided prefix
+    pub(crate) fn single(prefix: Prefix, hash: H::Out, data: DBValue, rc: i32) -> Self {
+        Self {
+            prefix: (DBValue::from(prefix.0), prefix.1),
+            writes: Vec::from([(hash, data, rc)]),
+        }
+    }
+
+    /// Returns the prefix the records are written under
+    pub fn prefix(&self) -> Prefix<'_> {
+        (&self.prefix.0, self.prefix.1)
+    }
+
+    /// Returns the records of the batch of form: (hash, data, reference count delta)
+    pub fn writes(&self) -> &[(H::Out, DBValue, i32)] {
+        &self.writes
+    }
+
+    /// Returns the number of records of the batch
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns true if the batch holds no records
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Applies the records of the batch to the provided database one emplace or remove at a time,
+    /// e.g. for an in-memory backend whose writes are atomic under its exclusive borrow
+    pub fn apply(&self, db: &mut dyn HashDB<H, DBValue>) {
+        let prefix = self.prefix();
+        for (hash, data, rc) in self.writes.iter() {
+            for _ in 0..rc.unsigned_abs() {
+                if *rc > 0 {
+                    db.emplace(*hash, prefix, data.clone());
+                } else {
+                    db.remove(hash, prefix);
+                }
+            }
+        }
+    }
+}
+
+// BatchHashDB
+// ================================================================================================
+
+/// An extension of HashDB for backends which apply a batch of writes atomically, e.g. the write
+/// batches of RocksDB or the commits of ParityDB. A tree built with
+/// TreeDBMutBuilder::new_batched writes each commit as a single WriteBatch, such that a failed or
+/// interrupted commit leaves none of its writes behind. The HashDB interface of the backend is
+/// used for reads.
+pub trait BatchHashDB<H: Hasher>: AsHashDB<H, DBValue> + Send + Sync {
+    /// Applies all records of the batch or none of them, returns a description of the error if
+    /// the batch failed
+    fn write_batch(&mut self, batch: &WriteBatch<H>) -> Result<(), String>;
+}
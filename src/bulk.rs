@@ -0,0 +1,192 @@
+use super::{
+    rstd::vec::Vec, tree::null_hashes, DBValue, DataError, HashDB, Key, KeyError, Node, NodeHash,
+    TreeError, TreeHasher,
+};
+
+// TreeBuilder
+// ================================================================================================
+
+/// Constructs a tree of depth D * 8 from leaves sorted by key in a single pass, writing the nodes
+/// bottom-up directly to the database. The resulting database is identical to the one produced by
+/// inserting the leaves into a TreeDBMut and committing, without hashing the path of every leaf
+/// once per insert. Intended for the initial sync of large trees.
+pub struct TreeBuilder<'db, const D: usize, H: TreeHasher> {
+    db: &'db mut dyn HashDB<H, DBValue>,
+    salt: DBValue,
+    prefix: DBValue,
+}
+
+impl<'db, const D: usize, H: TreeHasher> TreeBuilder<'db, D, H> {
+    /// Construct a TreeBuilder writing to the provided database
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
+            db,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
+        }
+    }
+
+    /// Salt the leaf hashes of the tree with the provided salt, see TreeDBMutBuilder::with_salt
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Write the nodes of the tree under the provided storage prefix, see
+    /// TreeDBMutBuilder::with_prefix
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = DBValue::from(prefix);
+        self
+    }
+
+    /// Builds the tree from the provided (key, value) pairs and returns its root. Leaves with an
+    /// empty value are absent and skipped.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if a key is not D bytes long
+    /// - UnsortedKey: if the keys are not strictly increasing
+    pub fn build<K: AsRef<[u8]>>(
+        self,
+        leaves: impl IntoIterator<Item = (K, DBValue)>,
+    ) -> Result<H::Out, TreeError> {
+        self.build_keys(leaves.into_iter().map(|(key, value)| {
+            let key = Key::<D>::new(key.as_ref()).map_err(TreeError::KeyError)?;
+            Ok((key, value))
+        }))
+    }
+
+    /// Builds the tree from the provided (index, value) pairs and returns its root, see build.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if an index is out of range for the depth of the tree
+    /// - UnsortedKey: if the indices are not strictly increasing
+    pub fn build_indexed(
+        self,
+        leaves: impl IntoIterator<Item = (u64, DBValue)>,
+    ) -> Result<H::Out, TreeError> {
+        self.build_keys(leaves.into_iter().map(|(index, value)| {
+            let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+            Ok((key, value))
+        }))
+    }
+
+    /// Builds the tree from the provided sorted (key, value) pairs, see build_nodes
+    fn build_keys(
+        self,
+        leaves: impl Iterator<Item = Result<(Key<D>, DBValue), TreeError>>,
+    ) -> Result<H::Out, TreeError> {
+        let salt = self.salt.clone();
+        self.build_nodes(leaves.map(|leaf| {
+            let (key, value) = leaf?;
+            let node = if cfg!(feature = "key-bound") && !value.is_empty() {
+                Node::<H>::new_bound_value(key.as_slice(), &salt, &value, None)
+            } else {
+                Node::<H>::new_salted_value(&salt, &value)
+            };
+            Ok((key, node))
+        }))
+    }
+
+    /// Builds the tree from the provided leaf nodes sorted by key. The stack holds the roots of
+    /// the completed subtrees along the right edge of the tree built so far, together with their
+    /// depth and the key of a leaf in the subtree. Before a leaf is pushed, the subtrees below the
+    /// first bit in which its key differs from the previous key are complete and folded into their
+    /// parents. Leaves with an empty value are absent and skipped.
+    pub(crate) fn build_nodes(
+        mut self,
+        leaves: impl Iterator<Item = Result<(Key<D>, Node<H>), TreeError>>,
+    ) -> Result<H::Out, TreeError> {
+        let bits = Key::<D>::BITS;
+        let mut null_hashes = null_hashes::<H>(bits, &self.salt);
+        // index the null hashes by depth
+        null_hashes.reverse();
+
+        let mut stack: Vec<(usize, Key<D>, H::Out)> = Vec::new();
+        let mut previous: Option<Key<D>> = None;
+        for leaf in leaves {
+            let (key, node) = leaf?;
+            if let Some(previous) = previous {
+                if key.as_slice() <= previous.as_slice() {
+                    return Err(TreeError::KeyError(KeyError::UnsortedKey(
+                        key.as_slice().to_vec(),
+                    )));
+                }
+            }
+            previous = Some(key);
+            if matches!(&node, Node::Value { value, .. } if value.is_empty()) {
+                continue;
+            }
+            // the top of the stack holds the subtree of the previous leaf
+            if let Some(&(_, last, _)) = stack.last() {
+                let shared = last
+                    .iter()
+                    .zip(key.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                self.fold(&mut stack, shared + 1, &null_hashes)?;
+            }
+
+            let hash = *node.hash();
+            self.db.emplace(hash, (&self.prefix, None), node.into());
+            stack.push((bits, key, hash));
+        }
+
+        self.fold(&mut stack, 0, &null_hashes)?;
+        match stack.pop() {
+            Some((_, _, root)) => {
+                // the root node holds an additional reference in trees built by insertion, see
+                // TreeDBMut::insert_leaf
+                let node = self
+                    .db
+                    .get(&root, (&self.prefix, None))
+                    .ok_or(TreeError::DataError(DataError::DatabaseDataNotFound(
+                        root.as_ref().to_vec(),
+                    )))?;
+                self.db.emplace(root, (&self.prefix, None), node);
+                Ok(root)
+            }
+            None => Ok(null_hashes[0]),
+        }
+    }
+
+    /// Folds the subtrees on the stack into their parents until the top of the stack is a subtree
+    /// at the provided depth. Siblings at the same depth are combined, subtrees without a sibling
+    /// on the stack are combined with the null node of their level.
+    fn fold(
+        &mut self,
+        stack: &mut Vec<(usize, Key<D>, H::Out)>,
+        depth: usize,
+        null_hashes: &[H::Out],
+    ) -> Result<(), TreeError> {
+        while let Some((child_depth, key, hash)) = stack.pop() {
+            if child_depth <= depth {
+                stack.push((child_depth, key, hash));
+                break;
+            }
+
+            let child = NodeHash::<H>::Database(hash);
+            let (left, right) = match stack.last() {
+                Some((sibling_depth, _, sibling)) if *sibling_depth == child_depth => {
+                    let sibling = NodeHash::<H>::Database(*sibling);
+                    stack.pop();
+                    (sibling, child)
+                }
+                _ => {
+                    let null = NodeHash::<H>::Default(null_hashes[child_depth]);
+                    match key.bit(child_depth - 1).map_err(TreeError::KeyError)? {
+                        true => (null, child),
+                        false => (child, null),
+                    }
+                }
+            };
+
+            let node = Node::<H>::new_inner(left, right).map_err(TreeError::NodeError)?;
+            let hash = *node.hash();
+            self.db.emplace(hash, (&self.prefix, None), node.into());
+            stack.push((child_depth - 1, key, hash));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use super::{
+    rstd::collections::VecDeque, DBValue, DataError, HashDBRef, HashMap, Node, TreeError,
+    TreeHasher,
+};
+use core::cell::RefCell;
+use hash_db::{Prefix, EMPTY_PREFIX};
+
+// CachedDB
+// ================================================================================================
+
+/// A database wrapper which keeps the decoded nodes read from the wrapped database in a least
+/// recently used cache keyed by node hash, such that the hot upper-level nodes of a tree are not
+/// fetched and decoded again on every lookup. Trees built with TreeDBBuilder::new_cached share the
+/// decoded nodes across tree instances, other readers of the wrapper share the cached nodes in
+/// their encoded form. The nodes are decoded using the salt of the wrapper, see with_salt.
+pub struct CachedDB<'db, H: TreeHasher> {
+    db: &'db dyn HashDBRef<H, DBValue>,
+    salt: DBValue,
+    capacity: usize,
+    lru: RefCell<Lru<H>>,
+}
+
+impl<'db, H: TreeHasher> CachedDB<'db, H> {
+    /// Construct a new CachedDB over the provided database holding at most capacity nodes
+    pub fn new(db: &'db dyn HashDBRef<H, DBValue>, capacity: usize) -> Self {
+        Self {
+            db,
+            salt: DBValue::default(),
+            capacity,
+            lru: RefCell::new(Lru::default()),
+        }
+    }
+
+    /// Decode the nodes using the provided salt, see TreeDBBuilder::with_salt
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Returns the salt used to decode the nodes
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    /// Returns the maximum number of cached nodes
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of cached nodes
+    pub fn len(&self) -> usize {
+        self.lru.borrow().nodes.len()
+    }
+
+    /// Returns true if no nodes are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Evicts all cached nodes, e.g. after the nodes of the wrapped database have been removed
+    pub fn clear(&self) {
+        let mut lru = self.lru.borrow_mut();
+        lru.nodes.clear();
+        lru.order.clear();
+    }
+
+    /// Returns the decoded node with the provided hash, reading and decoding it from the wrapped
+    /// database if it is not cached. Returns a tuple of form: (node, cache hit).
+    ///
+    /// Errors:
+    /// - DatabaseDataNotFound: if the node is missing from the wrapped database
+    /// - NodeError: if the node can not be decoded
+    pub(crate) fn node(&self, hash: &H::Out) -> Result<(Node<H>, bool), TreeError> {
+        if let Some(node) = self.lru.borrow_mut().get(hash) {
+            return Ok((node, true));
+        }
+        let data = self.db.get(hash, EMPTY_PREFIX).ok_or(TreeError::DataError(
+            DataError::DatabaseDataNotFound(hash.as_ref().to_vec()),
+        ))?;
+        let node = Node::decode(data, &self.salt).map_err(TreeError::NodeError)?;
+        self.lru
+            .borrow_mut()
+            .insert(*hash, node.clone(), self.capacity);
+        Ok((node, false))
+    }
+}
+
+impl<'db, H: TreeHasher> HashDBRef<H, DBValue> for CachedDB<'db, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        if let Some(node) = self.lru.borrow_mut().get(key) {
+            return Some(node.into());
+        }
+        let data = self.db.get(key, prefix)?;
+        if let Ok(node) = Node::decode(data.clone(), &self.salt) {
+            self.lru.borrow_mut().insert(*key, node, self.capacity);
+        }
+        Some(data)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        self.lru.borrow().nodes.contains_key(key) || self.db.contains(key, prefix)
+    }
+}
+
+// Lru
+// ================================================================================================
+
+/// The cached nodes together with the tick of their last use. The order queue holds an entry per
+/// use of a node, entries whose tick is not the last use of the node are stale and skipped on
+/// eviction. The queue is compacted once it holds twice as many entries as there are nodes.
+struct Lru<H: TreeHasher> {
+    nodes: HashMap<H::Out, (Node<H>, u64)>,
+    order: VecDeque<(H::Out, u64)>,
+    tick: u64,
+}
+
+impl<H: TreeHasher> Default for Lru<H> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::default(),
+            order: VecDeque::new(),
+            tick: 0,
+        }
+    }
+}
+
+impl<H: TreeHasher> Lru<H> {
+    /// Returns the node with the provided hash and marks it as most recently used
+    fn get(&mut self, hash: &H::Out) -> Option<Node<H>> {
+        self.tick += 1;
+        let (node, tick) = self.nodes.get_mut(hash)?;
+        *tick = self.tick;
+        let node = node.clone();
+        self.order.push_back((*hash, self.tick));
+        self.compact();
+        Some(node)
+    }
+
+    /// Inserts the provided node as most recently used and evicts the least recently used nodes
+    /// beyond the provided capacity
+    fn insert(&mut self, hash: H::Out, node: Node<H>, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        self.nodes.insert(hash, (node, self.tick));
+        self.order.push_back((hash, self.tick));
+        while self.nodes.len() > capacity {
+            let Some((hash, tick)) = self.order.pop_front() else {
+                break;
+            };
+            if matches!(self.nodes.get(&hash), Some((_, last)) if *last == tick) {
+                self.nodes.remove(&hash);
+            }
+        }
+        self.compact();
+    }
+
+    /// Drops the stale entries of the order queue once it grew to twice the number of nodes
+    fn compact(&mut self) {
+        if self.order.len() <= 2 * self.nodes.len().max(1) {
+            return;
+        }
+        let nodes = &self.nodes;
+        self.order
+            .retain(|(hash, tick)| matches!(nodes.get(hash), Some((_, last)) if last == tick));
+    }
+}
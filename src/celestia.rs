@@ -0,0 +1,174 @@
+use super::{rstd::vec::Vec, DBValue, Hasher};
+
+// CONSTANTS
+// ================================================================================================
+
+/// The domain separation prefix of leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+
+/// The domain separation prefix of inner node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+// CELESTIA SMT
+// ================================================================================================
+
+/// A leaf of a Celestia sparse merkle tree of form: (path, value hash)
+pub(crate) type CelestiaLeaf<H> = (<H as Hasher>::Out, <H as Hasher>::Out);
+
+/// Returns the hash of a leaf of a Celestia (LazyLedger) sparse merkle tree with the provided path
+/// and value hash, i.e. HASH(0x00 || path || value hash). The path of a key is HASH(key) and the
+/// value hash of a value is HASH(value).
+pub fn celestia_leaf_hash<H: Hasher>(path: &H::Out, value_hash: &H::Out) -> H::Out {
+    H::hash(&[&[LEAF_PREFIX][..], path.as_ref(), value_hash.as_ref()].concat())
+}
+
+/// Returns the hash of an inner node of a Celestia sparse merkle tree with the provided children,
+/// i.e. HASH(0x01 || left || right).
+pub fn celestia_node_hash<H: Hasher>(left: &H::Out, right: &H::Out) -> H::Out {
+    H::hash(&[&[NODE_PREFIX][..], left.as_ref(), right.as_ref()].concat())
+}
+
+/// Returns the (path, value hash) leaves of the provided key-value pairs, sorted by path.
+pub(crate) fn celestia_leaves<H: Hasher>(entries: Vec<(DBValue, DBValue)>) -> Vec<CelestiaLeaf<H>> {
+    let mut leaves: Vec<CelestiaLeaf<H>> = entries
+        .into_iter()
+        .map(|(key, value)| (H::hash(&key), H::hash(&value)))
+        .collect();
+    leaves.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+    leaves
+}
+
+/// Returns the root of the Celestia sparse merkle tree of the provided (path, value hash) leaves,
+/// sorted and unique by path. An empty subtree is the placeholder of zero bytes and a subtree
+/// holding a single leaf is replaced by the leaf.
+pub(crate) fn celestia_root<H: Hasher>(leaves: &[CelestiaLeaf<H>]) -> H::Out {
+    subtree_root::<H>(leaves, 0)
+}
+
+/// Returns the root and the Celestia proof of the provided path of the Celestia sparse merkle
+/// tree of the provided (path, value hash) leaves, sorted and unique by path. Both are computed
+/// in a single pass, the root is folded from the side nodes such that every leaf is hashed once.
+pub(crate) fn celestia_proof<H: Hasher>(
+    path: &H::Out,
+    leaves: &[CelestiaLeaf<H>],
+) -> (H::Out, CelestiaProof<H>) {
+    let mut leaves = leaves;
+    let mut side_nodes = Vec::new();
+    while leaves.len() > 1 {
+        let depth = side_nodes.len();
+        let split = leaves.partition_point(|(leaf_path, _)| !bit::<H>(leaf_path, depth));
+        let (path_leaves, side_leaves) = match bit::<H>(path, depth) {
+            true => (&leaves[split..], &leaves[..split]),
+            false => (&leaves[..split], &leaves[split..]),
+        };
+        side_nodes.push(subtree_root::<H>(side_leaves, depth + 1));
+        leaves = path_leaves;
+    }
+
+    // the path ends in a placeholder, the leaf of the path or an unrelated leaf
+    let mut root = subtree_root::<H>(leaves, side_nodes.len());
+    for (depth, side_node) in side_nodes.iter().enumerate().rev() {
+        root = match bit::<H>(path, depth) {
+            true => celestia_node_hash::<H>(side_node, &root),
+            false => celestia_node_hash::<H>(&root, side_node),
+        };
+    }
+    side_nodes.reverse();
+    let non_membership_leaf = leaves.first().filter(|(leaf_path, _)| leaf_path != path);
+    (
+        root,
+        CelestiaProof::new(side_nodes, non_membership_leaf.copied()),
+    )
+}
+
+/// Returns the root of the subtree at the provided depth holding the provided leaves, sorted and
+/// unique by path. The recursion ends at the depth at which the paths of the leaves diverge, i.e.
+/// it is logarithmic in the number of leaves for hashed paths.
+fn subtree_root<H: Hasher>(leaves: &[CelestiaLeaf<H>], depth: usize) -> H::Out {
+    match leaves {
+        [] => H::Out::default(),
+        [(path, value_hash)] => celestia_leaf_hash::<H>(path, value_hash),
+        _ => {
+            let split = leaves.partition_point(|(path, _)| !bit::<H>(path, depth));
+            celestia_node_hash::<H>(
+                &subtree_root::<H>(&leaves[..split], depth + 1),
+                &subtree_root::<H>(&leaves[split..], depth + 1),
+            )
+        }
+    }
+}
+
+/// Returns the bit of the provided path at the provided depth, counted from the most significant
+/// bit of the first byte.
+fn bit<H: Hasher>(path: &H::Out, depth: usize) -> bool {
+    (path.as_ref()[depth / 8] >> (7 - depth % 8)) & 1 == 1
+}
+
+// CelestiaProof
+// ================================================================================================
+
+/// A proof of a key in the layout of the Celestia sparse merkle tree specification, which is
+/// verified by the SparseMerkleProof verifiers of the Go and TypeScript implementations. The
+/// side nodes are ordered from the leaf to the root. A proof of non-membership whose path ends
+/// at a leaf of another key carries the path and value hash of that leaf.
+pub struct CelestiaProof<H: Hasher> {
+    side_nodes: Vec<H::Out>,
+    non_membership_leaf: Option<CelestiaLeaf<H>>,
+}
+
+impl<H: Hasher> CelestiaProof<H> {
+    /// Creates a new proof from the provided side nodes, ordered from the leaf to the root, and
+    /// the (path, value hash) of the unrelated leaf of a proof of non-membership.
+    pub fn new(side_nodes: Vec<H::Out>, non_membership_leaf: Option<CelestiaLeaf<H>>) -> Self {
+        Self {
+            side_nodes,
+            non_membership_leaf,
+        }
+    }
+
+    /// Returns the side nodes of the proof, ordered from the leaf to the root
+    pub fn side_nodes(&self) -> &[H::Out] {
+        &self.side_nodes
+    }
+
+    /// Returns the (path, value hash) of the unrelated leaf of a proof of non-membership
+    pub fn non_membership_leaf(&self) -> Option<&CelestiaLeaf<H>> {
+        self.non_membership_leaf.as_ref()
+    }
+
+    /// Returns the NonMembershipLeafData of the proof as encoded by the specification, i.e.
+    /// 0x00 || path || value hash of the unrelated leaf.
+    pub fn non_membership_leaf_data(&self) -> Option<Vec<u8>> {
+        self.non_membership_leaf.as_ref().map(|(path, value_hash)| {
+            [&[LEAF_PREFIX][..], path.as_ref(), value_hash.as_ref()].concat()
+        })
+    }
+}
+
+/// Verifies a Celestia proof of the provided value at the provided key against the provided root
+/// of a Celestia sparse merkle tree, following the verification of the specification. An empty
+/// value verifies a proof of non-membership.
+pub fn verify_celestia_proof<H: Hasher>(
+    key: &[u8],
+    value: &[u8],
+    proof: &CelestiaProof<H>,
+    root: &H::Out,
+) -> bool {
+    if proof.side_nodes.len() > H::LENGTH * 8 {
+        return false;
+    }
+    let path = H::hash(key);
+    let mut hash = match (value.is_empty(), &proof.non_membership_leaf) {
+        (false, _) => celestia_leaf_hash::<H>(&path, &H::hash(value)),
+        (true, None) => H::Out::default(),
+        (true, Some((leaf_path, _))) if *leaf_path == path => return false,
+        (true, Some((leaf_path, value_hash))) => celestia_leaf_hash::<H>(leaf_path, value_hash),
+    };
+    for (index, side_node) in proof.side_nodes.iter().enumerate() {
+        hash = match bit::<H>(&path, proof.side_nodes.len() - 1 - index) {
+            true => celestia_node_hash::<H>(side_node, &hash),
+            false => celestia_node_hash::<H>(&hash, side_node),
+        };
+    }
+    hash == *root
+}
@@ -0,0 +1,30 @@
+use super::{rstd::vec::Vec, DBValue, Hasher};
+
+// ChangeSet
+// ================================================================================================
+
+/// A change of the value of a leaf of form: (key, old value, new value), where None is an absent
+/// value
+pub type LeafChange = (DBValue, Option<DBValue>, Option<DBValue>);
+
+/// The changes written to the database by a commit of a mutable tree, e.g. to update secondary
+/// indexes or to replicate state deltas. The node hashes are sorted and the leaf changes are
+/// ordered by key.
+pub struct ChangeSet<H: Hasher> {
+    /// The root of the tree after the commit
+    pub root: H::Out,
+    /// The hashes of the nodes inserted into the database
+    pub inserted: Vec<H::Out>,
+    /// The hashes of the nodes removed from the database
+    pub removed: Vec<H::Out>,
+    /// The leaves whose value changed since the previous commit, a leaf that was written and
+    /// restored to its old value is omitted
+    pub leaves: Vec<LeafChange>,
+}
+
+impl<H: Hasher> ChangeSet<H> {
+    /// Returns true if the commit did not change the database
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty()
+    }
+}
@@ -0,0 +1,131 @@
+use super::{DBValue, FxBuildHasher, Key, KeyedTreeMut, TreeDBMut, TreeError, TreeHasher};
+use core::hash::BuildHasher;
+
+// Entry
+// ================================================================================================
+
+/// A view into a single leaf of a mutable tree, which is either occupied or vacant, mirroring the
+/// entry API of HashMap. Constructed with TreeDBMut::entry. As the values are stored in the tree
+/// rather than in memory, the methods return owned values and writes are staged immediately.
+pub enum Entry<'a, 'db, const D: usize, H: TreeHasher, S = FxBuildHasher> {
+    /// A leaf which holds a value
+    Occupied(OccupiedEntry<'a, 'db, D, H, S>),
+    /// A leaf which holds no value
+    Vacant(VacantEntry<'a, 'db, D, H, S>),
+}
+
+impl<'a, 'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> Entry<'a, 'db, D, H, S> {
+    /// Returns the key of the entry
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts the provided value if the entry is vacant and returns the value of the entry
+    pub fn or_insert(self, default: DBValue) -> Result<DBValue, TreeError> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the value returned by the provided function if the entry is vacant and returns the
+    /// value of the entry. The function is only called for a vacant entry.
+    pub fn or_insert_with<F: FnOnce() -> DBValue>(self, default: F) -> Result<DBValue, TreeError> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.value),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modifies the value of an occupied entry with the provided function and writes the modified
+    /// value to the tree. A value modified to be empty removes the leaf, the entry remains
+    /// occupied until it is dropped. Vacant entries are returned unchanged.
+    pub fn and_modify<F: FnOnce(&mut DBValue)>(self, f: F) -> Result<Self, TreeError> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(&mut entry.value);
+                entry
+                    .tree
+                    .insert(entry.key.as_slice(), entry.value.clone())?;
+                Ok(Entry::Occupied(entry))
+            }
+            Entry::Vacant(entry) => Ok(Entry::Vacant(entry)),
+        }
+    }
+}
+
+// OccupiedEntry
+// ================================================================================================
+
+/// A view into an occupied leaf of a mutable tree, see Entry
+pub struct OccupiedEntry<'a, 'db, const D: usize, H: TreeHasher, S = FxBuildHasher> {
+    tree: &'a mut TreeDBMut<'db, D, H, S>,
+    key: Key<D>,
+    value: DBValue,
+}
+
+impl<'a, 'db, const D: usize, H: TreeHasher, S: BuildHasher + Default>
+    OccupiedEntry<'a, 'db, D, H, S>
+{
+    /// Returns the key of the entry
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Returns the value of the entry
+    pub fn get(&self) -> &DBValue {
+        &self.value
+    }
+
+    /// Replaces the value of the entry with the provided value and returns the old value
+    pub fn insert(&mut self, value: DBValue) -> Result<DBValue, TreeError> {
+        self.tree.insert(self.key.as_slice(), value.clone())?;
+        Ok(core::mem::replace(&mut self.value, value))
+    }
+
+    /// Removes the leaf of the entry and returns its value
+    pub fn remove(self) -> Result<DBValue, TreeError> {
+        self.tree.remove(self.key.as_slice())?;
+        Ok(self.value)
+    }
+}
+
+// VacantEntry
+// ================================================================================================
+
+/// A view into a vacant leaf of a mutable tree, see Entry
+pub struct VacantEntry<'a, 'db, const D: usize, H: TreeHasher, S = FxBuildHasher> {
+    tree: &'a mut TreeDBMut<'db, D, H, S>,
+    key: Key<D>,
+}
+
+impl<'a, 'db, const D: usize, H: TreeHasher, S: BuildHasher + Default>
+    VacantEntry<'a, 'db, D, H, S>
+{
+    /// Returns the key of the entry
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Inserts the provided value at the key of the entry and returns it
+    pub fn insert(self, value: DBValue) -> Result<DBValue, TreeError> {
+        self.tree.insert(self.key.as_slice(), value.clone())?;
+        Ok(value)
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Returns the entry of the provided tree at the provided key holding the provided value, None or
+/// an empty value being a vacant entry.
+pub(crate) fn entry<'a, 'db, const D: usize, H: TreeHasher, S: BuildHasher + Default>(
+    tree: &'a mut TreeDBMut<'db, D, H, S>,
+    key: Key<D>,
+    value: Option<DBValue>,
+) -> Entry<'a, 'db, D, H, S> {
+    match value.filter(|value| !value.is_empty()) {
+        Some(value) => Entry::Occupied(OccupiedEntry { tree, key, value }),
+        None => Entry::Vacant(VacantEntry { tree, key }),
+    }
+}
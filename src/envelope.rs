@@ -0,0 +1,245 @@
+use super::{
+    node::decode_hash, rstd::vec::Vec, DBValue, Key, KeyedTree, ProofError, TreeDB, TreeError,
+    TreeHasher,
+};
+
+// CONSTANTS
+// ================================================================================================
+
+/// The format version of encoded proof envelopes
+pub const PROOF_ENVELOPE_VERSION: u8 = 1;
+
+/// The number of bytes of the hasher identifier
+const HASHER_ID_SIZE: usize = 4;
+
+/// The preimage hashed to derive the identifier of a hasher
+const HASHER_ID_PREIMAGE: &[u8] = b"merkle-tree-db";
+
+// ProofEnvelope
+// ================================================================================================
+
+/// A self-describing inclusion proof of a tree of depth D * 8. The encoded envelope carries the
+/// format version, the depth of the tree, an identifier of the hasher and the key being proven
+/// alongside the value, root and sibling hashes, such that a verifier does not have to know the
+/// parameters of the tree out-of-band. Decoding rejects an envelope produced with different
+/// parameters than those of the verifier.
+///
+/// The envelope is encoded as follows, all integers are big endian:
+/// - version (1 byte)
+/// - depth in bits (4 bytes)
+/// - hasher id (4 bytes)
+/// - key (D bytes)
+/// - root (H::LENGTH bytes)
+/// - value flag (1 byte), followed by the value length (4 bytes) and value if the flag is 1
+/// - number of sibling hashes (4 bytes), followed by the sibling hashes (H::LENGTH bytes each)
+pub struct ProofEnvelope<const D: usize, H: TreeHasher> {
+    key: Key<D>,
+    value: Option<DBValue>,
+    root: H::Out,
+    proof: Vec<DBValue>,
+}
+
+impl<const D: usize, H: TreeHasher> ProofEnvelope<D, H> {
+    /// Constructs a new envelope of the proof of the provided key. The proof is a tuple of form
+    /// (value, root, proof) as returned by KeyedTree::proof, an empty value is a proof of
+    /// non-inclusion.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn new(
+        key: &[u8],
+        (value, root, proof): (Option<DBValue>, H::Out, Vec<DBValue>),
+    ) -> Result<Self, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        Ok(Self {
+            key,
+            value: value.filter(|value| !value.is_empty()),
+            root,
+            proof,
+        })
+    }
+
+    /// Constructs a new envelope of the proof of the provided index. The proof is a tuple of form
+    /// (value, root, proof) as returned by IndexTree::proof.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if the index is out of bounds for the depth of the tree
+    pub fn from_index(
+        index: u64,
+        proof: (Option<DBValue>, H::Out, Vec<DBValue>),
+    ) -> Result<Self, TreeError> {
+        let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+        Self::new(key.as_slice(), proof)
+    }
+
+    /// Returns the key being proven
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Returns the value being proven, None for a proof of non-inclusion
+    pub fn value(&self) -> Option<&DBValue> {
+        self.value.as_ref()
+    }
+
+    /// Returns the root the proof is verified against
+    pub fn root(&self) -> &H::Out {
+        &self.root
+    }
+
+    /// Returns the sibling hashes of the proof
+    pub fn proof(&self) -> &[DBValue] {
+        &self.proof
+    }
+
+    /// Verifies the proof against the root of the envelope. A proof without a value is verified
+    /// as a proof of non-inclusion.
+    pub fn verify(&self) -> Result<bool, TreeError> {
+        let value: &[u8] = self.value.as_deref().unwrap_or(&[]);
+        TreeDB::<D, H>::verify(self.key.as_slice(), value, &self.proof, &self.root)
+    }
+
+    /// Encodes the envelope, see the struct documentation for the encoding.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(PROOF_ENVELOPE_VERSION);
+        bytes.extend_from_slice(&(Key::<D>::BITS as u32).to_be_bytes());
+        bytes.extend_from_slice(&hasher_id::<H>());
+        bytes.extend_from_slice(self.key.as_slice());
+        bytes.extend_from_slice(self.root.as_ref());
+        match &self.value {
+            Some(value) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(value);
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend_from_slice(&(self.proof.len() as u32).to_be_bytes());
+        for sibling in self.proof.iter() {
+            bytes.extend_from_slice(sibling);
+        }
+        bytes
+    }
+
+    /// Decodes an envelope produced by encode.
+    ///
+    /// Errors:
+    /// - UnsupportedVersion: if the envelope was encoded with an unknown format version
+    /// - DepthMismatch: if the envelope was produced for a tree of a different depth
+    /// - HasherMismatch: if the envelope was produced using a different hasher
+    /// - UnexpectedEnd: if the envelope is truncated
+    /// - TrailingBytes: if the envelope is followed by unexpected bytes
+    pub fn decode(data: &[u8]) -> Result<Self, TreeError> {
+        let mut reader = Reader::new(data);
+
+        let version = reader.read(1)?[0];
+        if version != PROOF_ENVELOPE_VERSION {
+            return Err(TreeError::ProofError(ProofError::UnsupportedVersion(
+                version,
+            )));
+        }
+        let depth = reader.read_u32()? as usize;
+        if depth != Key::<D>::BITS {
+            return Err(TreeError::ProofError(ProofError::DepthMismatch(
+                Key::<D>::BITS,
+                depth,
+            )));
+        }
+        let id = reader.read(HASHER_ID_SIZE)?;
+        if id != hasher_id::<H>() {
+            return Err(TreeError::ProofError(ProofError::HasherMismatch(
+                hasher_id::<H>().to_vec(),
+                id.to_vec(),
+            )));
+        }
+
+        let key = Key::<D>::new(reader.read(D)?).map_err(TreeError::KeyError)?;
+        let root = decode_hash::<H>(reader.read(H::LENGTH)?).map_err(TreeError::NodeError)?;
+        let value = match reader.read(1)?[0] {
+            0 => None,
+            _ => {
+                let length = reader.read_u32()? as usize;
+                Some(DBValue::from(reader.read(length)?))
+            }
+        };
+        let siblings = reader.read_u32()? as usize;
+        let mut proof = Vec::with_capacity(siblings.min(Key::<D>::BITS));
+        for _ in 0..siblings {
+            proof.push(DBValue::from(reader.read(H::LENGTH)?));
+        }
+
+        reader.finish()?;
+
+        Ok(Self {
+            key,
+            value,
+            root,
+            proof,
+        })
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Returns the identifier of a hasher, derived from the leaf and inner node hashes of a fixed
+/// preimage such that hashers that differ in either hash function have different identifiers.
+pub fn hasher_id<H: TreeHasher>() -> [u8; HASHER_ID_SIZE] {
+    let leaf = H::hash_leaf(HASHER_ID_PREIMAGE);
+    let hash = H::hash_inner(leaf.as_ref(), leaf.as_ref());
+    let mut id = [0u8; HASHER_ID_SIZE];
+    let length = HASHER_ID_SIZE.min(hash.as_ref().len());
+    id[..length].copy_from_slice(&hash.as_ref()[..length]);
+    id
+}
+
+/// A cursor over the bytes of an encoded envelope or storage proof
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Returns a cursor at the start of the provided bytes
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    /// Returns the next `length` bytes
+    pub(crate) fn read(&mut self, length: usize) -> Result<&'a [u8], TreeError> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(TreeError::ProofError(ProofError::UnexpectedEnd(
+                self.data.len(),
+            )))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    /// Returns the next big endian u32
+    pub(crate) fn read_u32(&mut self) -> Result<u32, TreeError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.read(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// Returns the number of bytes that have not been read
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.offset
+    }
+
+    /// Checks that all bytes have been read.
+    ///
+    /// Errors:
+    /// - TrailingBytes: if bytes remain to be read
+    pub(crate) fn finish(self) -> Result<(), TreeError> {
+        match self.remaining() {
+            0 => Ok(()),
+            remaining => Err(TreeError::ProofError(ProofError::TrailingBytes(remaining))),
+        }
+    }
+}
@@ -5,16 +5,19 @@
 /// errors returned by the underlying components, these include:
 /// - DataError - errors associated with the underlying data the tree is built on
 /// - NodeError - errors associated with the nodes in the tree
-/// - DepthTooLarge - error returned when the specified tree depth is too large
 /// - KeyError - error associated with the key used to access the tree
+/// - ProofError - errors associated with decoding a proof envelope
+/// - Cancelled - a long-running operation was cancelled after processing the provided number of
+///   nodes
 use super::rstd::{string::String, vec::Vec};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum TreeError {
     DataError(DataError),
     NodeError(NodeError),
-    DepthTooLarge(usize, usize),
     KeyError(KeyError),
+    ProofError(ProofError),
+    Cancelled(usize),
 }
 
 impl core::fmt::Display for TreeError {
@@ -23,10 +26,11 @@ impl core::fmt::Display for TreeError {
         match self {
             DataError(err) => write!(f, "Data Error: {err}"),
             NodeError(err) => write!(f, "Node Error: {err}"),
-            DepthTooLarge(actual, max) => {
-                write!(f, "depth {actual} too large - max supported depth is {max}",)
-            }
             KeyError(err) => write!(f, "key error: {err}"),
+            ProofError(err) => write!(f, "proof error: {err}"),
+            Cancelled(processed) => {
+                write!(f, "operation cancelled after processing {processed} nodes")
+            }
         }
     }
 }
@@ -41,6 +45,20 @@ pub enum DataError {
     NullNodeDataNotFound(Vec<u8>),
     InMemoryDataNotFound(Vec<u8>),
     InMemoryNotSupported,
+    ValueTooLarge(usize, usize),
+    NoTransaction,
+    StaleFork,
+    CommitPrepared,
+    NoPreparedCommit,
+    UnknownVersion(u64),
+    PrunedVersion(u64),
+    ValueMismatch(Vec<u8>),
+    WriteFailed(String),
+    CorruptJournal,
+    PendingJournal,
+    UnhashedLeaves,
+    #[cfg(feature = "json")]
+    JsonError(String),
 }
 
 impl core::fmt::Display for DataError {
@@ -54,9 +72,36 @@ impl core::fmt::Display for DataError {
                 write!(f, "null node data not found for hash {hash:?}")
             }
             InMemoryNotSupported => write!(f, "in-memory data not supported for immutable tree"),
+            NoTransaction => write!(f, "no transaction is open"),
+            StaleFork => write!(f, "the tree was committed since the fork was taken"),
+            CommitPrepared => write!(f, "a commit is prepared, finalize or abort it first"),
+            NoPreparedCommit => write!(f, "no commit is prepared"),
+            UnknownVersion(version) => write!(f, "version {version} has not been committed"),
+            PrunedVersion(version) => write!(f, "version {version} has been pruned"),
+            ValueMismatch(key) => {
+                write!(
+                    f,
+                    "value mismatch - the value at key {key:?} is not the expected value"
+                )
+            }
+            WriteFailed(err) => write!(f, "database write failed: {err}"),
+            CorruptJournal => write!(f, "the commit journal is corrupt"),
+            PendingJournal => write!(f, "the journal of an interrupted commit must be recovered"),
+            UnhashedLeaves => write!(
+                f,
+                "the paths of written leaves are not rehashed, call rehash"
+            ),
             InMemoryDataNotFound(hash) => {
                 write!(f, "in-memory data not found for hash {hash:?}")
             }
+            #[cfg(feature = "json")]
+            JsonError(err) => write!(f, "json error: {err}"),
+            ValueTooLarge(size, max) => {
+                write!(
+                    f,
+                    "value of {size} bytes exceeds the maximum value size of {max} bytes"
+                )
+            }
         }
     }
 }
@@ -119,6 +164,8 @@ pub enum KeyError {
     IncorrectKeySize(usize, usize),
     BitIndexOutOfBounds(usize, usize),
     LeafIndexOutOfBounds(u64, u64),
+    NamespaceIdOutOfBounds(u64, usize),
+    UnsortedKey(Vec<u8>),
 }
 
 impl core::fmt::Display for KeyError {
@@ -140,6 +187,109 @@ impl core::fmt::Display for KeyError {
                     "leaf index out of bounds - index {index} is out of range - max {max}",
                 )
             }
+            NamespaceIdOutOfBounds(id, bits) => {
+                write!(
+                    f,
+                    "namespace id out of bounds - id {id} does not fit in {bits} bits",
+                )
+            }
+            UnsortedKey(key) => {
+                write!(
+                    f,
+                    "unsorted key - key {key:?} does not follow the previous key"
+                )
+            }
+        }
+    }
+}
+
+// PROOF ERROR
+// ================================================================================================
+
+/// Errors associated with decoding a proof envelope, compact proof or storage proof, verifying a
+/// storage proof and applying a write to a proof.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProofError {
+    UnsupportedVersion(u8),
+    DepthMismatch(usize, usize),
+    HasherMismatch(Vec<u8>, Vec<u8>),
+    UnexpectedEnd(usize),
+    TrailingBytes(usize),
+    InvalidBitmask(Vec<u8>),
+    SiblingCountMismatch(usize, usize),
+    InvalidNode(Vec<u8>),
+    MissingNodes(Vec<Vec<u8>>),
+    RootMismatch(Vec<u8>, Vec<u8>),
+    UnorderedNodes(usize),
+    UnreachableNode(Vec<u8>),
+    NodeCountMismatch(usize, usize),
+}
+
+impl core::fmt::Display for ProofError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use ProofError::*;
+        match self {
+            UnsupportedVersion(version) => {
+                write!(f, "unsupported proof envelope version {version}")
+            }
+            DepthMismatch(expected, actual) => {
+                write!(f, "tree depth mismatch - expected {expected}, got {actual}",)
+            }
+            HasherMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "hasher mismatch - expected hasher id {expected:?}, got {actual:?}",
+                )
+            }
+            UnexpectedEnd(offset) => {
+                write!(f, "proof envelope ended unexpectedly at byte {offset}")
+            }
+            TrailingBytes(count) => {
+                write!(f, "proof envelope has {count} trailing bytes")
+            }
+            InvalidBitmask(bitmask) => {
+                write!(f, "invalid compact proof bitmask {bitmask:?}")
+            }
+            SiblingCountMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "compact proof sibling count mismatch - expected {expected}, got {actual}",
+                )
+            }
+            InvalidNode(node) => {
+                write!(f, "storage proof contains an invalid node {node:?}")
+            }
+            MissingNodes(hashes) => {
+                write!(
+                    f,
+                    "storage proof is missing {} nodes {hashes:?}",
+                    hashes.len()
+                )
+            }
+            RootMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "proof root mismatch - expected {expected:?}, got {actual:?}",
+                )
+            }
+            UnorderedNodes(index) => {
+                write!(
+                    f,
+                    "storage proof node {index} is out of order or duplicated",
+                )
+            }
+            UnreachableNode(hash) => {
+                write!(
+                    f,
+                    "storage proof contains node {hash:?} which is unreachable from the root",
+                )
+            }
+            NodeCountMismatch(expected, actual) => {
+                write!(
+                    f,
+                    "level cache node count mismatch - expected {expected}, got {actual}",
+                )
+            }
         }
     }
 }
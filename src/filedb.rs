@@ -0,0 +1,247 @@
+use super::{node::decode_hash, DBValue, HashDB, HashDBRef, HashMap, TreeHasher, TryHashDB};
+use hash_db::{AsHashDB, Prefix};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    string::{String, ToString},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The tag of a record which adds a reference to a node
+const EMPLACE: u8 = 0;
+
+/// The tag of a record which drops a reference to a node
+const REMOVE: u8 = 1;
+
+/// The size of the header of a record: tag || hash || data length (u32 little endian)
+const fn header_size<H: TreeHasher>() -> usize {
+    1 + H::LENGTH + 4
+}
+
+/// The location of the data of a node in the log of form: (offset, length)
+type Location = (u64, usize);
+
+// FileDB
+// ================================================================================================
+
+/// A persistent database which appends every write to a single log file and keeps an in-memory
+/// index of the reference count and log offset of every node, such that small tools can persist
+/// trees without an external key-value store. Each record of the log is encoded as: tag || hash ||
+/// data length (u32 little endian) || data || checksum, where the checksum is the hash of the
+/// preceding bytes of the record. The log is replayed to rebuild the index on open, a torn tail
+/// record left by a crash is truncated. Records are never rewritten, the log grows with every
+/// write. Prefixes are ignored. The writes of the HashDB interface panic on an I/O error, build
+/// the tree with TreeDBMutBuilder::new_fallible to surface the errors, see TryHashDB.
+pub struct FileDB<H: TreeHasher> {
+    file: Mutex<File>,
+    index: HashMap<H::Out, (Option<Location>, i32)>,
+    length: u64,
+}
+
+impl<H: TreeHasher> FileDB<H> {
+    /// Opens the log at the provided path, creating it if it does not exist, and replays it. A
+    /// tail record which is incomplete or whose checksum does not match is truncated.
+    ///
+    /// Errors:
+    /// - InvalidData: if a record other than the tail record is corrupt
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut log = Vec::new();
+        file.read_to_end(&mut log)?;
+
+        let mut index = HashMap::default();
+        let mut offset = 0;
+        while offset < log.len() {
+            let Some((tag, hash, data, size)) = decode_record::<H>(&log[offset..]) else {
+                // a record is torn by a crash only at the tail of the log
+                if !is_tail::<H>(&log[offset..]) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("corrupt record at offset {offset}"),
+                    ));
+                }
+                file.set_len(offset as u64)?;
+                file.sync_data()?;
+                break;
+            };
+            let (location, rc) = index.entry(hash).or_insert((None, 0));
+            match tag {
+                EMPLACE => {
+                    let data_offset = (offset + header_size::<H>()) as u64;
+                    *location = Some((data_offset, data.len()));
+                    *rc += 1;
+                }
+                _ => *rc -= 1,
+            }
+            offset += size;
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            index,
+            length: offset as u64,
+        })
+    }
+
+    /// Returns the number of nodes with a positive reference count
+    pub fn len(&self) -> usize {
+        self.index.values().filter(|(_, rc)| *rc > 0).count()
+    }
+
+    /// Returns true if the database holds no nodes with a positive reference count
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Flushes the appended records to the disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.lock().sync_data()
+    }
+
+    /// Locks and returns the log file, a lock poisoned by a panicking holder is recovered as
+    /// records are appended with a single write
+    fn lock(&self) -> MutexGuard<'_, File> {
+        self.file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Appends a record with the provided tag, hash and data to the log and returns the offset
+    /// of the data
+    fn append(&mut self, tag: u8, hash: &H::Out, data: &[u8]) -> io::Result<u64> {
+        let mut record = Vec::with_capacity(header_size::<H>() + data.len() + H::LENGTH);
+        record.push(tag);
+        record.extend_from_slice(hash.as_ref());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+        let checksum = H::hash(&record);
+        record.extend_from_slice(checksum.as_ref());
+
+        let mut file = self.lock();
+        if let Err(err) = file.write_all(&record) {
+            // drops a partially appended record such that the log stays valid
+            let _ = file.set_len(self.length);
+            return Err(err);
+        }
+        drop(file);
+        let data_offset = self.length + header_size::<H>() as u64;
+        self.length += record.len() as u64;
+        Ok(data_offset)
+    }
+
+    /// Reads the data at the provided location of the log
+    fn read(&self, (offset, length): Location) -> io::Result<DBValue> {
+        let mut file = self.lock();
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = vec![0; length];
+        file.read_exact(&mut data)?;
+        Ok(DBValue::from(data))
+    }
+}
+
+impl<H: TreeHasher> TryHashDB<H, DBValue> for FileDB<H> {
+    fn try_emplace(&mut self, key: H::Out, _prefix: Prefix, value: DBValue) -> Result<(), String> {
+        let data_offset = self
+            .append(EMPLACE, &key, &value)
+            .map_err(|err| err.to_string())?;
+        let (location, rc) = self.index.entry(key).or_insert((None, 0));
+        *location = Some((data_offset, value.len()));
+        *rc += 1;
+        Ok(())
+    }
+
+    fn try_remove(&mut self, key: &H::Out, _prefix: Prefix) -> Result<(), String> {
+        self.append(REMOVE, key, &[])
+            .map_err(|err| err.to_string())?;
+        let (_, rc) = self.index.entry(*key).or_insert((None, 0));
+        *rc -= 1;
+        Ok(())
+    }
+}
+
+impl<H: TreeHasher> HashDB<H, DBValue> for FileDB<H> {
+    fn get(&self, key: &H::Out, _prefix: Prefix) -> Option<DBValue> {
+        match self.index.get(key) {
+            Some((Some(location), rc)) if *rc > 0 => self.read(*location).ok(),
+            _ => None,
+        }
+    }
+
+    fn contains(&self, key: &H::Out, _prefix: Prefix) -> bool {
+        matches!(self.index.get(key), Some((_, rc)) if *rc > 0)
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        let key = H::hash(value);
+        HashDB::emplace(self, key, prefix, DBValue::from(value));
+        key
+    }
+
+    fn emplace(&mut self, key: H::Out, prefix: Prefix, value: DBValue) {
+        self.try_emplace(key, prefix, value)
+            .expect("failed to append to the log");
+    }
+
+    fn remove(&mut self, key: &H::Out, prefix: Prefix) {
+        self.try_remove(key, prefix)
+            .expect("failed to append to the log");
+    }
+}
+
+impl<H: TreeHasher> HashDBRef<H, DBValue> for FileDB<H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(self, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(self, key, prefix)
+    }
+}
+
+impl<H: TreeHasher> AsHashDB<H, DBValue> for FileDB<H> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<H, DBValue> + 'a) {
+        self
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Decodes the record at the start of the provided bytes. Returns a tuple of form: (tag, hash,
+/// data, record size), None if the record is incomplete, has an unknown tag or its checksum does
+/// not match.
+fn decode_record<H: TreeHasher>(log: &[u8]) -> Option<(u8, H::Out, &[u8], usize)> {
+    let header = log.get(..header_size::<H>())?;
+    let length = u32::from_le_bytes(header[1 + H::LENGTH..].try_into().ok()?) as usize;
+    let body = log.get(..header_size::<H>() + length)?;
+    let checksum = log.get(body.len()..body.len() + H::LENGTH)?;
+    if !matches!(header[0], EMPLACE | REMOVE) || H::hash(body).as_ref() != checksum {
+        return None;
+    }
+    let hash = decode_hash::<H>(&header[1..1 + H::LENGTH]).ok()?;
+    Some((
+        header[0],
+        hash,
+        &body[header_size::<H>()..],
+        body.len() + H::LENGTH,
+    ))
+}
+
+/// Returns true if the invalid record at the start of the provided bytes is the tail record of
+/// the log, i.e. it is incomplete or ends with the log.
+fn is_tail<H: TreeHasher>(log: &[u8]) -> bool {
+    let Some(header) = log.get(..header_size::<H>()) else {
+        return true;
+    };
+    let length = u32::from_le_bytes(header[1 + H::LENGTH..].try_into().unwrap_or_default());
+    header_size::<H>() + length as usize + H::LENGTH >= log.len()
+}
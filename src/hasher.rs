@@ -0,0 +1,232 @@
+use super::Hasher;
+use core::{hash, marker::PhantomData};
+
+#[cfg(feature = "digest")]
+use digest::{typenum::Unsigned, Digest};
+
+// TreeHasher
+// ================================================================================================
+
+/// Specifies how the leaf and inner node hashes of a tree are computed. The default
+/// implementations hash the leaf value and the concatenation of the child hashes respectively
+/// using the underlying Hasher, as such for most hashers the implementation is empty:
+///
+/// ```ignore
+/// impl TreeHasher for Sha3 {}
+/// ```
+pub trait TreeHasher: Hasher {
+    /// Returns the hash of a leaf with the provided value.
+    fn hash_leaf(value: &[u8]) -> Self::Out {
+        Self::hash(value)
+    }
+
+    /// Returns the hash of an inner node with the provided child hashes.
+    fn hash_inner(left: &[u8], right: &[u8]) -> Self::Out {
+        Self::hash(&[left, right].concat())
+    }
+}
+
+// DualHasher
+// ================================================================================================
+
+/// A TreeHasher that uses the hasher `L` for leaf values and the hasher `N` for inner nodes. The
+/// node hasher is used as the Hasher of the database backend. This allows a fast hash function to
+/// be used for large leaf values while a circuit friendly hash function is used for the tree.
+pub struct DualHasher<L, N>(PhantomData<fn() -> (L, N)>);
+
+impl<L, N> core::fmt::Debug for DualHasher<L, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "DualHasher")
+    }
+}
+
+impl<L, N> Hasher for DualHasher<L, N>
+where
+    L: Hasher<Out = N::Out>,
+    N: Hasher,
+{
+    type Out = N::Out;
+
+    type StdHasher = N::StdHasher;
+
+    const LENGTH: usize = N::LENGTH;
+
+    fn hash(data: &[u8]) -> Self::Out {
+        N::hash(data)
+    }
+}
+
+impl<L, N> TreeHasher for DualHasher<L, N>
+where
+    L: Hasher<Out = N::Out>,
+    N: Hasher,
+{
+    fn hash_leaf(value: &[u8]) -> Self::Out {
+        L::hash(value)
+    }
+}
+
+// OutputStdHasher
+// ================================================================================================
+
+/// A std hasher used to key maps with hash outputs of arbitrary length. Hash outputs are already
+/// uniformly distributed and as such the bytes written are simply folded into a u64.
+#[derive(Default)]
+pub struct OutputStdHasher {
+    state: u64,
+}
+
+impl hash::Hasher for OutputStdHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.state ^= (*byte as u64) << ((i % 8) * 8);
+        }
+    }
+}
+
+// FxHasher
+// ================================================================================================
+
+/// The multiplicative constant of the FxHash algorithm
+const FX_SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// A fast non-cryptographic std hasher based on the FxHash algorithm used by rustc. It is the
+/// default hasher of the maps used internally by the trees and the recorder, which are keyed by
+/// node hashes and are hot during large batch updates.
+#[derive(Default, Clone, Copy)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    fn add_to_hash(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(FX_SEED);
+    }
+}
+
+impl hash::Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(word);
+            self.add_to_hash(u64::from_le_bytes(buf));
+        }
+        for byte in words.remainder() {
+            self.add_to_hash(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add_to_hash(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add_to_hash(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add_to_hash(i as u64);
+    }
+}
+
+/// The BuildHasher of FxHasher, the default BuildHasher of the internal maps
+pub type FxBuildHasher = hash::BuildHasherDefault<FxHasher>;
+
+// DigestHasher
+// ================================================================================================
+
+/// An implementation of the Hasher trait for any RustCrypto `Digest` with an output of `L` bytes.
+/// A mismatch between `L` and the output size of the digest results in a compile time error.
+///
+/// ```ignore
+/// type Sha3 = DigestHasher<sha3::Sha3_256, 32>;
+/// ```
+#[cfg(feature = "digest")]
+pub struct DigestHasher<D, const L: usize>(PhantomData<fn() -> D>);
+
+#[cfg(feature = "digest")]
+impl<D, const L: usize> core::fmt::Debug for DigestHasher<D, L> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "DigestHasher<{L}>")
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D, const L: usize> Hasher for DigestHasher<D, L>
+where
+    D: Digest,
+    [u8; L]: Default,
+{
+    type Out = [u8; L];
+
+    type StdHasher = OutputStdHasher;
+
+    const LENGTH: usize = {
+        assert!(
+            D::OutputSize::USIZE == L,
+            "digest output size does not match hasher length"
+        );
+        L
+    };
+
+    fn hash(data: &[u8]) -> Self::Out {
+        let mut out = [0u8; L];
+        out.copy_from_slice(&D::digest(data)[..Self::LENGTH]);
+        out
+    }
+}
+
+#[cfg(feature = "digest")]
+impl<D, const L: usize> TreeHasher for DigestHasher<D, L>
+where
+    D: Digest,
+    [u8; L]: Default,
+{
+}
+
+// MACROS
+// ================================================================================================
+
+/// Generates a unit struct implementing the Hasher and TreeHasher traits from a hash function closure and an
+/// output size in bytes. The closure is provided the data to be hashed and must return an array
+/// of the specified output size. This is intended for hash functions that do not implement the
+/// `digest::Digest` trait, for example circuit friendly hash functions.
+///
+/// ```ignore
+/// impl_hasher!(
+///     /// Poseidon hasher over the bn254 scalar field.
+///     pub Poseidon, 32, |data: &[u8]| poseidon::hash_bytes(data)
+/// );
+/// ```
+#[macro_export]
+macro_rules! impl_hasher {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $length:expr, $hash:expr) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        $vis struct $name;
+
+        impl $crate::Hasher for $name {
+            type Out = [u8; $length];
+
+            type StdHasher = $crate::OutputStdHasher;
+
+            const LENGTH: usize = $length;
+
+            fn hash(data: &[u8]) -> Self::Out {
+                let hash: fn(&[u8]) -> [u8; $length] = $hash;
+                hash(data)
+            }
+        }
+
+        impl $crate::TreeHasher for $name {}
+    };
+}
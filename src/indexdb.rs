@@ -1,29 +1,50 @@
 use super::{
-    rstd::vec::Vec, DBValue, HashDBRef, Hasher, IndexTree, Key, KeyedTree, TreeDB, TreeDBBuilder,
-    TreeError, TreeRecorder,
+    node::null_leaf_hash,
+    rfc6962::{rfc6962_audit_path, rfc6962_leaf_hash, rfc6962_root},
+    rstd::vec::Vec,
+    ConsistencyProof, DBValue, HashDBRef, Hasher, IndexTree, Key, KeyError, KeyedTree, MerkleProof,
+    NeighborProof, NeighborProofOf, StorageProof, TreeDB, TreeDBBuilder, TreeError, TreeHasher,
+    TreeMetrics, TreeRecorder, TypedProof,
 };
 
 // IndexTreeDBBuilder
 // ================================================================================================
 
 /// Used to construct an IndexTreeDB
-pub struct IndexTreeDBBuilder<'db, const D: usize, H: Hasher> {
+pub struct IndexTreeDBBuilder<'db, const D: usize, H: TreeHasher> {
     db: &'db dyn HashDBRef<H, DBValue>,
     root: &'db H::Out,
     recorder: Option<&'db mut dyn TreeRecorder<H>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
 }
 
-impl<'db, const D: usize, H: Hasher> IndexTreeDBBuilder<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher> IndexTreeDBBuilder<'db, D, H> {
     /// Construct a new IndexTreeDBBuilder
-    pub fn new(db: &'db dyn HashDBRef<H, DBValue>, root: &'db H::Out) -> Result<Self, TreeError> {
-        if D > usize::MAX / 8 {
-            return Err(TreeError::DepthTooLarge(D, usize::MAX / 8));
-        }
-        Ok(Self {
+    pub fn new(db: &'db dyn HashDBRef<H, DBValue>, root: &'db H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
             db,
             root,
             recorder: None,
-        })
+            metrics: None,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
+        }
+    }
+
+    /// Add a salt to the IndexTreeDBBuilder. See TreeDBBuilder::with_salt.
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Add a storage prefix to the IndexTreeDBBuilder. See TreeDBBuilder::with_prefix.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = DBValue::from(prefix);
+        self
     }
 
     /// Add a recorder to the IndexTreeDBBuilder
@@ -41,11 +62,19 @@ impl<'db, const D: usize, H: Hasher> IndexTreeDBBuilder<'db, D, H> {
         self
     }
 
+    /// Add metrics to the IndexTreeDBBuilder. See TreeDBBuilder::with_metrics.
+    pub fn with_metrics(mut self, metrics: &'db dyn TreeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// build an IndexTreeDB
     pub fn build(self) -> IndexTreeDB<'db, D, H> {
         let keyed_db = TreeDBBuilder::new(self.db, self.root)
-            .expect("checks are applied in IndexTreeDBBuilder constructor")
             .with_optional_recorder(self.recorder)
+            .with_optional_metrics(self.metrics)
+            .with_salt(&self.salt)
+            .with_prefix(&self.prefix)
             .build();
         IndexTreeDB { keyed_db }
     }
@@ -56,11 +85,109 @@ impl<'db, const D: usize, H: Hasher> IndexTreeDBBuilder<'db, D, H> {
 
 /// An immutable merkle tree db that uses a u64 index to specify the leaves in the tree. Wraps a KeyedTreeDB
 /// and converts a u64 index to a Key of the appropriate depth to access the underlying TreeDB.
-pub struct IndexTreeDB<'db, const D: usize, H: Hasher> {
+pub struct IndexTreeDB<'db, const D: usize, H: TreeHasher> {
     keyed_db: TreeDB<'db, D, H>,
 }
 
-impl<'db, H: Hasher + 'db, const D: usize> IndexTree<H, D> for IndexTreeDB<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher> IndexTreeDB<'db, D, H> {
+    /// Returns the underlying keyed tree, such that index based and key based access can be mixed
+    /// on the same tree
+    pub fn as_keyed(&self) -> &TreeDB<'db, D, H> {
+        &self.keyed_db
+    }
+
+    /// Returns the key of the leaf at the provided index
+    pub fn index_to_key(index: &u64) -> Result<Key<D>, TreeError> {
+        Key::<D>::try_from(index).map_err(TreeError::KeyError)
+    }
+
+    /// Returns a proof that this append-only tree extends the tree holding its first old size
+    /// leaves, see ConsistencyProof.
+    pub fn consistency_proof(&self, old_size: u64) -> Result<ConsistencyProof<H, D>, TreeError> {
+        let (_, _, proof) = self.leaf_proof(&old_size)?;
+        ConsistencyProof::new(old_size, proof)
+    }
+
+    /// Returns a typed inclusion proof of the leaf hash at the provided index. Unlike merkle_proof,
+    /// which hashes the value, the null leaf of a salted tree is proven by its salted hash.
+    /// Returns a tuple of form: (value, root, proof).
+    fn leaf_proof(&self, index: &u64) -> Result<TypedProof<H, D>, TreeError> {
+        let key = Self::index_to_key(index)?;
+        let (value, root, proof) = self.proof(index)?;
+        let leaf = match self.leaf(index)? {
+            Some(leaf) => leaf,
+            None => null_leaf_hash::<H>(self.keyed_db.salt()),
+        };
+        Ok((value, root, MerkleProof::new(key.as_slice(), leaf, proof)?))
+    }
+
+    /// Returns the value at the provided index together with a proof of the nearest occupied
+    /// indices before and after it, see NeighborProof. Returns a tuple of form: (value, root,
+    /// proof).
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if a neighbor is a key above the u64 index range, i.e. it was
+    ///   written through the keyed tree
+    pub fn neighbor_proof(&self, index: u64) -> Result<NeighborProofOf<H, D>, TreeError> {
+        let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+        let (predecessor, successor) = self.keyed_db.neighbors(key.as_slice())?;
+        let neighbor = |key: Option<DBValue>| {
+            key.map(|key| {
+                let key = Key::<D>::new(&key).map_err(TreeError::KeyError)?;
+                let index = u64::try_from(&key).map_err(TreeError::KeyError)?;
+                let (_, _, proof) = self.leaf_proof(&index)?;
+                Ok((index, proof))
+            })
+            .transpose()
+        };
+        let (value, root, proof) = self.leaf_proof(&index)?;
+        let proof = NeighborProof::new(index, proof, neighbor(predecessor)?, neighbor(successor)?)?;
+        Ok((value, root, proof))
+    }
+
+    /// Returns the RFC 6962 (certificate transparency) Merkle Tree Hash of the values at the
+    /// indices below the provided size, an index without a value is hashed as an empty value.
+    /// The hash is computed from all values and as such is linear in the size.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if the size exceeds the number of leaves of the tree
+    pub fn rfc6962_root(&self, size: u64) -> Result<H::Out, TreeError> {
+        Ok(rfc6962_root::<H>(&self.rfc6962_leaves(size)?))
+    }
+
+    /// Returns the RFC 6962 audit path of the value at the provided index in the certificate
+    /// transparency tree of the values at the indices below the provided size, ordered from the
+    /// leaf to the root. The path is verified using verify_rfc6962_audit_path against the
+    /// rfc6962_root of the size.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if the index is not below the size or the size exceeds the number
+    ///   of leaves of the tree
+    pub fn rfc6962_audit_path(&self, index: u64, size: u64) -> Result<Vec<H::Out>, TreeError> {
+        if index >= size {
+            return Err(TreeError::KeyError(KeyError::LeafIndexOutOfBounds(
+                index, size,
+            )));
+        }
+        let leaves = self.rfc6962_leaves(size)?;
+        Ok(rfc6962_audit_path::<H>(index as usize, &leaves))
+    }
+
+    /// Returns the RFC 6962 leaf hashes of the values at the indices below the provided size.
+    fn rfc6962_leaves(&self, size: u64) -> Result<Vec<H::Out>, TreeError> {
+        if size > 0 {
+            Key::<D>::try_from(&(size - 1)).map_err(TreeError::KeyError)?;
+        }
+        (0..size)
+            .map(|index| {
+                let value = self.value(&index)?.unwrap_or_default();
+                Ok(rfc6962_leaf_hash::<H>(&value))
+            })
+            .collect()
+    }
+}
+
+impl<'db, H: TreeHasher + 'db, const D: usize> IndexTree<H, D> for IndexTreeDB<'db, D, H> {
     /// Returns the root of the tree
     fn root(&self) -> &<H as Hasher>::Out {
         self.keyed_db.root()
@@ -85,6 +212,26 @@ impl<'db, H: Hasher + 'db, const D: usize> IndexTree<H, D> for IndexTreeDB<'db,
         self.keyed_db.proof(key.as_slice())
     }
 
+    /// Returns a single witness of the values at the given indices.
+    /// Returns a tuple of form: (values, root, proof)
+    fn proof_indices(
+        &self,
+        indices: &[u64],
+    ) -> Result<(Vec<Option<DBValue>>, H::Out, StorageProof), TreeError> {
+        let keys = indices
+            .iter()
+            .map(Key::<D>::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(TreeError::KeyError)?;
+        let values = keys
+            .iter()
+            .map(|key| self.keyed_db.value(key.as_slice()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_slice()).collect();
+        let (root, proof) = self.keyed_db.storage_proof(&keys)?;
+        Ok((values, root, proof))
+    }
+
     /// Verifies that the given value is in the tree with the given root at the given index
     fn verify(
         index: &u64,
@@ -1,36 +1,95 @@
 use super::{
-    rstd::vec::Vec, DBValue, HashDB, Hasher, IndexTreeMut, Key, KeyedTreeMut, TreeDBMut,
-    TreeDBMutBuilder, TreeError, TreeRecorder,
+    node::null_leaf_hash, rstd::vec::Vec, ChangeSet, ConsistencyProof, DBValue, DynTreeRecorder,
+    Entry, HashDB, Hasher, IndexTreeMut, Key, KeyedTreeMut, MerkleProof, Monitor, NeighborProof,
+    NeighborProofOf, StagedStats, StorageProof, TreeDBMut, TreeDBMutBuilder, TreeError, TreeHasher,
+    TreeMetrics, TypedProof,
 };
 
 // IndexTreeDBMutBuilder
 // ================================================================================================
 
 /// Used to construct a IndexTreeDBMut
-pub struct IndexTreeDBMutBuilder<'db, const D: usize, H: Hasher> {
+pub struct IndexTreeDBMutBuilder<'db, const D: usize, H: TreeHasher> {
     db: &'db mut dyn HashDB<H, DBValue>,
     root: &'db mut H::Out,
-    recorder: Option<&'db mut dyn TreeRecorder<H>>,
+    recorder: Option<&'db mut DynTreeRecorder<'db, H>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
+    max_value_size: Option<usize>,
+    max_staged_nodes: Option<usize>,
+    staged_capacity: usize,
+    journaling: bool,
+    deferred_hashing: bool,
 }
 
-impl<'db, const D: usize, H: Hasher> IndexTreeDBMutBuilder<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher> IndexTreeDBMutBuilder<'db, D, H> {
     /// Construct a IndexTreeDBMutBuilder
-    pub fn new(
-        db: &'db mut dyn HashDB<H, DBValue>,
-        root: &'db mut H::Out,
-    ) -> Result<Self, TreeError> {
-        if D > usize::MAX / 8 {
-            return Err(TreeError::DepthTooLarge(D, usize::MAX / 8));
-        }
-        Ok(Self {
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>, root: &'db mut H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
             db,
             root,
             recorder: None,
-        })
+            metrics: None,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
+            max_value_size: None,
+            max_staged_nodes: None,
+            staged_capacity: 0,
+            journaling: false,
+            deferred_hashing: false,
+        }
+    }
+
+    /// Add a salt to the IndexTreeDBMutBuilder. See TreeDBMutBuilder::with_salt.
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Add a storage prefix to the IndexTreeDBMutBuilder. See TreeDBMutBuilder::with_prefix.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = DBValue::from(prefix);
+        self
+    }
+
+    /// Add a maximum value size to the IndexTreeDBMutBuilder. See
+    /// TreeDBMutBuilder::with_max_value_size.
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = Some(max_value_size);
+        self
+    }
+
+    /// Add a maximum number of staged nodes to the IndexTreeDBMutBuilder. See
+    /// TreeDBMutBuilder::with_max_staged_nodes.
+    pub fn with_max_staged_nodes(mut self, max_staged_nodes: usize) -> Self {
+        self.max_staged_nodes = Some(max_staged_nodes);
+        self
+    }
+
+    /// Reserve room for the provided number of staged nodes in the IndexTreeDBMutBuilder. See
+    /// TreeDBMutBuilder::with_staged_capacity.
+    pub fn with_staged_capacity(mut self, capacity: usize) -> Self {
+        self.staged_capacity = capacity;
+        self
+    }
+
+    /// Enable the commit journal. See TreeDBMutBuilder::with_journal.
+    pub fn with_journal(mut self) -> Self {
+        self.journaling = true;
+        self
+    }
+
+    /// Enable deferred hashing. See TreeDBMutBuilder::with_deferred_hashing.
+    pub fn with_deferred_hashing(mut self) -> Self {
+        self.deferred_hashing = true;
+        self
     }
 
     /// Add a recorder to the IndexTreeDBMutBuilder
-    pub fn with_recorder(mut self, recorder: &'db mut dyn TreeRecorder<H>) -> Self {
+    pub fn with_recorder(mut self, recorder: &'db mut DynTreeRecorder<'db, H>) -> Self {
         self.recorder = Some(recorder);
         self
     }
@@ -38,41 +97,316 @@ impl<'db, const D: usize, H: Hasher> IndexTreeDBMutBuilder<'db, D, H> {
     /// Add an optional recorder to the IndexTreeDBMutBuilder
     pub fn with_optional_recorder<'recorder: 'db>(
         mut self,
-        recorder: Option<&'recorder mut dyn TreeRecorder<H>>,
+        recorder: Option<&'recorder mut DynTreeRecorder<'recorder, H>>,
     ) -> Self {
         self.recorder = recorder.map(|r| r as _);
         self
     }
 
+    /// Add metrics to the IndexTreeDBMutBuilder. See TreeDBMutBuilder::with_metrics.
+    pub fn with_metrics(mut self, metrics: &'db dyn TreeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// build a IndexTreeDBMut
     pub fn build(self) -> IndexTreeDBMut<'db, D, H> {
-        let keyed_db = TreeDBMutBuilder::new(self.db, self.root)
-            .expect("checks are done in the IndexTreeDBBuilder constructor")
+        let mut builder = TreeDBMutBuilder::new(self.db, self.root)
             .with_optional_recorder(self.recorder)
-            .build();
+            .with_optional_metrics(self.metrics)
+            .with_salt(&self.salt)
+            .with_prefix(&self.prefix)
+            .with_staged_capacity(self.staged_capacity);
+        if let Some(max_value_size) = self.max_value_size {
+            builder = builder.with_max_value_size(max_value_size);
+        }
+        if let Some(max_staged_nodes) = self.max_staged_nodes {
+            builder = builder.with_max_staged_nodes(max_staged_nodes);
+        }
+        if self.journaling {
+            builder = builder.with_journal();
+        }
+        if self.deferred_hashing {
+            builder = builder.with_deferred_hashing();
+        }
+        let keyed_db = builder.build();
         IndexTreeDBMut { keyed_db }
     }
 }
 
 /// A mutable merkle tree db that uses a u64 index to specify the leaves in the tree. Wraps a KeyedTreeDBMut
 ///  and converts a u64 index to a Key of the appropriate depth to access the underlying TreeDB.
-pub struct IndexTreeDBMut<'db, const D: usize, H: Hasher> {
+pub struct IndexTreeDBMut<'db, const D: usize, H: TreeHasher> {
     keyed_db: TreeDBMut<'db, D, H>,
 }
 
-impl<'db, const D: usize, H: Hasher> IndexTreeDBMut<'db, D, H> {
-    /// Commit the changes to the underlying database
-    pub fn commit(&mut self) {
+impl<'db, const D: usize, H: TreeHasher> IndexTreeDBMut<'db, D, H> {
+    /// Returns statistics of the changes staged in memory, see TreeDBMut::staged_stats
+    pub fn staged_stats(&self) -> StagedStats {
+        self.keyed_db.staged_stats()
+    }
+
+    /// Releases the unused capacity of the staged changes, see TreeDBMut::shrink_to_fit
+    pub fn shrink_to_fit(&mut self) {
+        self.keyed_db.shrink_to_fit()
+    }
+
+    /// Rehashes the paths of the dirty leaves, see TreeDBMut::rehash
+    pub fn rehash(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.rehash()
+    }
+
+    /// Commit the changes to the underlying database, see TreeDBMut::commit
+    pub fn commit(&mut self) -> ChangeSet<H> {
         self.keyed_db.commit()
     }
+
+    /// Commit the changes to the provided database, see TreeDBMut::commit_to
+    pub fn commit_to(&mut self, db: &mut dyn HashDB<H, DBValue>) -> ChangeSet<H> {
+        self.keyed_db.commit_to(db)
+    }
+
+    /// Prepares a commit of the changes, see TreeDBMut::prepare
+    pub fn prepare(&mut self) -> Result<ChangeSet<H>, TreeError> {
+        self.keyed_db.prepare()
+    }
+
+    /// Completes the prepared commit, see TreeDBMut::finalize
+    pub fn finalize(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.finalize()
+    }
+
+    /// Discards the prepared commit, see TreeDBMut::abort
+    pub fn abort(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.abort()
+    }
+
+    /// Resets the tree to the empty tree, see TreeDBMut::clear
+    pub fn clear(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.clear()
+    }
+
+    /// Points the tree at an earlier root, see TreeDBMut::reset_to
+    pub fn reset_to(&mut self, root: H::Out) -> Result<(), TreeError> {
+        self.keyed_db.reset_to(root)
+    }
+
+    /// Returns the entry at the provided index for in-place manipulation, see Entry
+    pub fn entry(&mut self, index: &u64) -> Result<Entry<'_, 'db, D, H>, TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        self.keyed_db.entry(key.as_slice())
+    }
+
+    /// Inserts the provided value at the provided index if the current value is the expected
+    /// value, see TreeDBMut::compare_and_swap
+    pub fn compare_and_swap(
+        &mut self,
+        index: &u64,
+        expected: Option<&[u8]>,
+        new: DBValue,
+    ) -> Result<(), TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        self.keyed_db
+            .compare_and_swap(key.as_slice(), expected, new)
+    }
+
+    /// Inserts the provided (index, value) pairs into the tree without stopping at the first
+    /// failed entry, see TreeDBMut::insert_many
+    pub fn insert_many(
+        &mut self,
+        entries: impl IntoIterator<Item = (u64, DBValue)>,
+    ) -> Vec<Result<Option<DBValue>, TreeError>> {
+        self.keyed_db.insert_each(entries, |tree, (index, value)| {
+            let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+            tree.insert(key.as_slice(), value)
+        })
+    }
+
+    /// Inserts the provided (index, value) pairs into the tree hashing each touched inner node
+    /// once, see TreeDBMut::insert_batch
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if an index is out of range for the depth of the tree
+    pub fn insert_batch(
+        &mut self,
+        entries: impl IntoIterator<Item = (u64, DBValue)>,
+    ) -> Result<(), TreeError> {
+        let entries = entries
+            .into_iter()
+            .map(|(index, value)| {
+                let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+                Ok((key, value))
+            })
+            .collect::<Result<Vec<_>, TreeError>>()?;
+        self.keyed_db.insert_batch(entries)
+    }
+
+    /// Removes the provided indices from the tree hashing each touched inner node once and returns
+    /// the old values aligned with the indices, see TreeDBMut::remove_batch
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if an index is out of range for the depth of the tree
+    pub fn remove_batch(
+        &mut self,
+        indices: impl IntoIterator<Item = u64>,
+    ) -> Result<Vec<Option<DBValue>>, TreeError> {
+        let keys = indices
+            .into_iter()
+            .map(|index| Key::<D>::try_from(&index).map_err(TreeError::KeyError))
+            .collect::<Result<Vec<_>, TreeError>>()?;
+        self.keyed_db.remove_batch(keys)
+    }
+
+    /// Removes the values at the indices from start up to but excluding end. Subtrees that lie
+    /// entirely within the range are replaced by default nodes rather than removing each index,
+    /// see TreeDBMut::remove_key_range.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if an index of the range is out of range for the depth of the tree
+    pub fn remove_range(&mut self, start: &u64, end: &u64) -> Result<(), TreeError> {
+        if start >= end {
+            return Ok(());
+        }
+        let first = Key::<D>::try_from(start).map_err(TreeError::KeyError)?;
+        let last = Key::<D>::try_from(&(end - 1)).map_err(TreeError::KeyError)?;
+        self.keyed_db.remove_key_range(&first, &last)
+    }
+
+    /// Removes the values at the indices from len onwards, e.g. to roll back an append-only log
+    /// stored in the tree. The subtrees right of the path of len are replaced by default nodes,
+    /// such that only the nodes on the path are rebuilt, see TreeDBMut::remove_key_range. A len
+    /// beyond the last index of the tree removes nothing.
+    pub fn truncate(&mut self, len: &u64) -> Result<(), TreeError> {
+        let Ok(first) = Key::<D>::try_from(len) else {
+            return Ok(());
+        };
+        let last = Key::<D>::new(&[u8::MAX; D]).map_err(TreeError::KeyError)?;
+        self.keyed_db.remove_key_range(&first, &last)
+    }
+
+    /// Stages the leaf changes of the provided ChangeSet, see TreeDBMut::apply_changeset
+    pub fn apply_changeset(&mut self, change_set: &ChangeSet<H>) -> Result<(), TreeError> {
+        self.keyed_db.apply_changeset(change_set)
+    }
+
+    /// Begins a transaction, see TreeDBMut::begin_transaction
+    pub fn begin_transaction(&mut self) {
+        self.keyed_db.begin_transaction()
+    }
+
+    /// Commits the innermost open transaction, see TreeDBMut::commit_transaction
+    pub fn commit_transaction(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.commit_transaction()
+    }
+
+    /// Discards the changes of the innermost open transaction, see TreeDBMut::rollback
+    pub fn rollback(&mut self) -> Result<(), TreeError> {
+        self.keyed_db.rollback()
+    }
+
+    /// Returns the underlying keyed tree, such that index based and key based access can be mixed
+    /// on the same tree
+    pub fn as_keyed(&self) -> &TreeDBMut<'db, D, H> {
+        &self.keyed_db
+    }
+
+    /// Returns the key of the leaf at the provided index
+    pub fn index_to_key(index: &u64) -> Result<Key<D>, TreeError> {
+        Key::<D>::try_from(index).map_err(TreeError::KeyError)
+    }
+
+    /// Returns a proof that this append-only tree extends the tree holding its first old size
+    /// leaves, see ConsistencyProof.
+    pub fn consistency_proof(&self, old_size: u64) -> Result<ConsistencyProof<H, D>, TreeError> {
+        let (_, _, proof) = self.leaf_proof(&old_size)?;
+        ConsistencyProof::new(old_size, proof)
+    }
+
+    /// Returns a typed inclusion proof of the leaf hash at the provided index. Unlike merkle_proof,
+    /// which hashes the value, the null leaf of a salted tree is proven by its salted hash.
+    /// Returns a tuple of form: (value, root, proof).
+    fn leaf_proof(&self, index: &u64) -> Result<TypedProof<H, D>, TreeError> {
+        let key = Self::index_to_key(index)?;
+        let (value, root, proof) = self.proof(index)?;
+        let leaf = match self.leaf(index)? {
+            Some(leaf) => leaf,
+            None => null_leaf_hash::<H>(self.keyed_db.salt()),
+        };
+        Ok((value, root, MerkleProof::new(key.as_slice(), leaf, proof)?))
+    }
+
+    /// Returns the value at the provided index together with a proof of the nearest occupied
+    /// indices before and after it, see NeighborProof. Returns a tuple of form: (value, root,
+    /// proof).
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if a neighbor is a key above the u64 index range, i.e. it was
+    ///   written through the keyed tree
+    pub fn neighbor_proof(&self, index: u64) -> Result<NeighborProofOf<H, D>, TreeError> {
+        let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+        let (predecessor, successor) = self.keyed_db.neighbors(key.as_slice())?;
+        let neighbor = |key: Option<DBValue>| {
+            key.map(|key| {
+                let key = Key::<D>::new(&key).map_err(TreeError::KeyError)?;
+                let index = u64::try_from(&key).map_err(TreeError::KeyError)?;
+                let (_, _, proof) = self.leaf_proof(&index)?;
+                Ok((index, proof))
+            })
+            .transpose()
+        };
+        let (value, root, proof) = self.leaf_proof(&index)?;
+        let proof = NeighborProof::new(index, proof, neighbor(predecessor)?, neighbor(successor)?)?;
+        Ok((value, root, proof))
+    }
+
+    /// Inserts the provided value at the provided index with an expiry timestamp and returns the
+    /// old value if it exists. See TreeDBMut::insert_with_expiry.
+    pub fn insert_with_expiry(
+        &mut self,
+        index: &u64,
+        value: DBValue,
+        expiry: u64,
+    ) -> Result<Option<DBValue>, TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        self.keyed_db
+            .insert_with_expiry(key.as_slice(), value, expiry)
+    }
+
+    /// Returns the expiry timestamp of the leaf at the provided index, None if the leaf does not
+    /// exist or does not expire.
+    pub fn expiry(&self, index: &u64) -> Result<Option<u64>, TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        self.keyed_db.expiry(key.as_slice())
+    }
+
+    /// Removes all leaves that have expired at the provided timestamp and returns the number of
+    /// leaves removed. See TreeDBMut::sweep.
+    pub fn sweep(&mut self, now: u64) -> Result<usize, TreeError> {
+        self.keyed_db.sweep(now)
+    }
+
+    /// Removes all leaves that have expired at the provided timestamp, reporting progress to and
+    /// checking for cancellation with the provided monitor. See TreeDBMut::sweep_with_monitor.
+    pub fn sweep_with_monitor(
+        &mut self,
+        now: u64,
+        monitor: &mut Monitor,
+    ) -> Result<usize, TreeError> {
+        self.keyed_db.sweep_with_monitor(now, monitor)
+    }
 }
 
-impl<'db, H: Hasher + 'db, const D: usize> IndexTreeMut<H, D> for IndexTreeDBMut<'db, D, H> {
+impl<'db, H: TreeHasher + 'db, const D: usize> IndexTreeMut<H, D> for IndexTreeDBMut<'db, D, H> {
     /// Returns the root of the tree
     fn root(&mut self) -> &<H as Hasher>::Out {
         self.keyed_db.root()
     }
 
+    /// Returns the root of the tree including the uncommitted changes
+    fn root_hint(&self) -> <H as Hasher>::Out {
+        self.keyed_db.root_hint()
+    }
+
     /// Returns the value at the given index
     fn value(&self, index: &u64) -> Result<Option<DBValue>, TreeError> {
         let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
@@ -92,6 +426,26 @@ impl<'db, H: Hasher + 'db, const D: usize> IndexTreeMut<H, D> for IndexTreeDBMut
         self.keyed_db.proof(key.as_slice())
     }
 
+    /// Returns a single witness of the values at the given indices.
+    /// Returns a tuple of form: (values, root, proof)
+    fn proof_indices(
+        &self,
+        indices: &[u64],
+    ) -> Result<(Vec<Option<DBValue>>, H::Out, StorageProof), TreeError> {
+        let keys = indices
+            .iter()
+            .map(Key::<D>::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(TreeError::KeyError)?;
+        let values = keys
+            .iter()
+            .map(|key| self.keyed_db.value(key.as_slice()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_slice()).collect();
+        let (root, proof) = self.keyed_db.storage_proof(&keys)?;
+        Ok((values, root, proof))
+    }
+
     /// Inserts the given value at the given index and returns the old value
     fn insert(&mut self, index: &u64, value: DBValue) -> Result<Option<DBValue>, TreeError> {
         let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
@@ -115,3 +469,17 @@ impl<'db, H: Hasher + 'db, const D: usize> IndexTreeMut<H, D> for IndexTreeDBMut
         TreeDBMut::<'db, D, H>::verify(key.as_slice(), value, proof, root)
     }
 }
+
+/// Inserts the (index, value) pairs of the iterator into the tree, see IndexTreeMut::insert.
+///
+/// Panics if an index is out of bounds for the depth of the tree or a value exceeds the maximum
+/// value size.
+impl<'db, H: TreeHasher + 'db, const D: usize> Extend<(u64, DBValue)>
+    for IndexTreeDBMut<'db, D, H>
+{
+    fn extend<I: IntoIterator<Item = (u64, DBValue)>>(&mut self, iter: I) {
+        for (index, value) in iter {
+            self.insert(&index, value).expect("failed to insert entry");
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use super::{
+    node::decode_hash, rstd::vec::Vec, DBMut, DBValue, DataError, HashDB, TreeError, TreeHasher,
+};
+use hash_db::{Prefix, EMPTY_PREFIX};
+
+/// The preimage of the key of the journal record in the database
+const JOURNAL_KEY: &[u8] = b"merkle-tree-db/journal";
+
+/// The prefix of the preimages of the keys of the cursor records in the database
+const CURSOR_KEY: &[u8] = b"merkle-tree-db/journal/cursor";
+
+/// The tag of an emplace in the journal encoding
+const EMPLACE: u8 = 0;
+
+/// The tag of a remove in the journal encoding
+const REMOVE: u8 = 1;
+
+// Write
+// ================================================================================================
+
+/// A write of a commit to the database. Removes carry the data of the removed node once it has
+/// been read, such that the remove can be reverted.
+pub(crate) enum Write<H: TreeHasher> {
+    Emplace(H::Out, DBValue),
+    Remove(H::Out, Option<DBValue>),
+}
+
+impl<H: TreeHasher> Write<H> {
+    /// Applies the write to the provided database under the provided prefix
+    fn apply(&self, db: &mut DBMut<'_, H>, prefix: Prefix) -> Result<(), TreeError> {
+        match self {
+            Write::Emplace(hash, data) => db.try_emplace(*hash, prefix, data.clone()),
+            Write::Remove(hash, _) => db.try_remove(hash, prefix),
+        }
+    }
+
+    /// Reverts the write in the provided database under the provided prefix
+    fn revert(&self, db: &mut dyn HashDB<H, DBValue>, prefix: Prefix) {
+        match self {
+            Write::Emplace(hash, _) => db.remove(hash, prefix),
+            Write::Remove(hash, data) => {
+                db.emplace(*hash, prefix, data.clone().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Reads the data of the nodes removed by the provided writes from the provided database under
+/// the provided prefix, such that the removes can be reverted.
+pub(crate) fn read_removed<H: TreeHasher>(
+    db: &dyn HashDB<H, DBValue>,
+    prefix: Prefix,
+    writes: &mut [Write<H>],
+) {
+    for write in writes.iter_mut() {
+        if let Write::Remove(hash, data) = write {
+            *data = db.get(hash, prefix);
+        }
+    }
+}
+
+// Journal
+// ================================================================================================
+
+/// The writes of a commit under the storage prefix of the tree and the number of writes applied
+/// so far. A journal is persisted to the
+/// database with begin, see TreeDBMutBuilder::with_journal. The cursor of a persisted journal is
+/// kept in a record whose key is derived from the hash of the journal and the cursor, the record
+/// of the next cursor is written before the record of the previous cursor is removed such that
+/// the largest cursor with a record is the number of writes applied.
+pub(crate) struct Journal<H: TreeHasher> {
+    roots: (H::Out, H::Out),
+    prefix: DBValue,
+    writes: Vec<Write<H>>,
+    cursor: usize,
+    id: Option<H::Out>,
+}
+
+impl<H: TreeHasher> Journal<H> {
+    /// Construct an in-memory journal of the provided writes of a commit from the old root to the
+    /// new root of the provided roots under the provided prefix, see TreeDBMutBuilder::with_prefix
+    pub fn new(roots: (H::Out, H::Out), prefix: &[u8], writes: Vec<Write<H>>) -> Self {
+        Self {
+            roots,
+            prefix: DBValue::from(prefix),
+            writes,
+            cursor: 0,
+            id: None,
+        }
+    }
+
+    /// Persists the journal to the provided database before any of the writes is applied. The
+    /// data of the removed nodes must have been read, see read_removed.
+    ///
+    /// Errors:
+    /// - PendingJournal: if the journal of an interrupted commit has not been recovered
+    pub fn begin(&mut self, db: &mut DBMut<'_, H>) -> Result<(), TreeError> {
+        let key = H::hash(JOURNAL_KEY);
+        if db.as_hash_db().contains(&key, EMPTY_PREFIX) {
+            return Err(TreeError::DataError(DataError::PendingJournal));
+        }
+        let record = self.encode();
+        self.id = Some(H::hash(&record));
+        // the last cursor record of an identical journal may remain from an interrupted end
+        remove_record(db, self.cursor_key(self.writes.len()))?;
+        db.try_emplace(key, EMPTY_PREFIX, record)
+    }
+
+    /// Applies the writes after the cursor to the provided database, advancing the cursor after
+    /// each write. On error the cursor is the number of writes applied.
+    pub fn apply(&mut self, db: &mut DBMut<'_, H>) -> Result<(), TreeError> {
+        while self.cursor < self.writes.len() {
+            self.writes[self.cursor].apply(db, (&self.prefix, None))?;
+            self.cursor += 1;
+            if self.id.is_some() {
+                let key = self.cursor_key(self.cursor);
+                db.try_emplace(key, EMPTY_PREFIX, DBValue::default())?;
+                remove_record(db, self.cursor_key(self.cursor - 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reverts the writes before the cursor in reverse order, moving the cursor back after each
+    /// write. The writes are reverted through the HashDB interface of the database, as the
+    /// fallible writes may keep failing, e.g. on a full disk.
+    pub fn revert(&mut self, db: &mut dyn HashDB<H, DBValue>) {
+        while self.cursor > 0 {
+            self.writes[self.cursor - 1].revert(db, (&self.prefix, None));
+            self.cursor -= 1;
+            if self.id.is_some() {
+                let key = self.cursor_key(self.cursor);
+                if self.cursor > 0 && !db.contains(&key, EMPTY_PREFIX) {
+                    db.emplace(key, EMPTY_PREFIX, DBValue::default());
+                }
+                let _ = remove_record(
+                    &mut DBMut::Infallible(&mut *db),
+                    self.cursor_key(self.cursor + 1),
+                );
+            }
+        }
+    }
+
+    /// Removes the persisted journal from the provided database once the commit is complete or
+    /// reverted. The journal is removed before the records of its cursor.
+    pub fn end(&self, db: &mut dyn HashDB<H, DBValue>) {
+        if self.id.is_none() {
+            return;
+        }
+        let mut db = DBMut::Infallible(db);
+        // a failed write may leave the record of the cursor after the current cursor
+        for key in [
+            H::hash(JOURNAL_KEY),
+            self.cursor_key(self.cursor),
+            self.cursor_key(self.cursor + 1),
+        ] {
+            let _ = remove_record(&mut db, key);
+        }
+    }
+
+    /// Reads the persisted journal from the provided database, None if no commit is in progress
+    ///
+    /// Errors:
+    /// - CorruptJournal: if the journal can not be decoded
+    fn read(db: &dyn HashDB<H, DBValue>) -> Result<Option<Self>, TreeError> {
+        let Some(record) = db.get(&H::hash(JOURNAL_KEY), EMPTY_PREFIX) else {
+            return Ok(None);
+        };
+        let mut journal =
+            Self::decode(&record).ok_or(TreeError::DataError(DataError::CorruptJournal))?;
+        journal.id = Some(H::hash(&record));
+        journal.cursor = (1..=journal.writes.len())
+            .rev()
+            .find(|cursor| db.contains(&journal.cursor_key(*cursor), EMPTY_PREFIX))
+            .unwrap_or(0);
+        Ok(Some(journal))
+    }
+
+    /// Returns the key of the record of the provided cursor of the persisted journal
+    fn cursor_key(&self, cursor: usize) -> H::Out {
+        let id = self.id.unwrap_or_default();
+        H::hash(&[CURSOR_KEY, id.as_ref(), &(cursor as u64).to_le_bytes()].concat())
+    }
+
+    /// Encodes the journal as: old root || new root || prefix length (u32 little endian) ||
+    /// prefix || writes, where each write is encoded as: tag || hash || data length (u32 little
+    /// endian) || data. The data of a remove is the data of the removed node.
+    fn encode(&self) -> DBValue {
+        let mut record = DBValue::new();
+        record.extend_from_slice(self.roots.0.as_ref());
+        record.extend_from_slice(self.roots.1.as_ref());
+        record.extend_from_slice(&(self.prefix.len() as u32).to_le_bytes());
+        record.extend_from_slice(&self.prefix);
+        for write in self.writes.iter() {
+            let (tag, hash, data) = match write {
+                Write::Emplace(hash, data) => (EMPLACE, hash, &data[..]),
+                Write::Remove(hash, data) => (REMOVE, hash, data.as_deref().unwrap_or_default()),
+            };
+            record.push(tag);
+            record.extend_from_slice(hash.as_ref());
+            record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            record.extend_from_slice(data);
+        }
+        record
+    }
+
+    /// Decodes a journal encoded with encode, None if the encoding is invalid
+    fn decode(record: &[u8]) -> Option<Self> {
+        let (old_root, rest) = split(record, H::LENGTH)?;
+        let (new_root, rest) = split(rest, H::LENGTH)?;
+        let (length, rest) = split(rest, 4)?;
+        let length = u32::from_le_bytes(length.try_into().ok()?) as usize;
+        let (prefix, mut rest) = split(rest, length)?;
+        let mut writes = Vec::new();
+        while !rest.is_empty() {
+            let (tag, remaining) = split(rest, 1)?;
+            let (hash, remaining) = split(remaining, H::LENGTH)?;
+            let (length, remaining) = split(remaining, 4)?;
+            let length = u32::from_le_bytes(length.try_into().ok()?) as usize;
+            let (data, remaining) = split(remaining, length)?;
+            let hash = decode_hash::<H>(hash).ok()?;
+            writes.push(match tag[0] {
+                EMPLACE => Write::Emplace(hash, DBValue::from(data)),
+                REMOVE => Write::Remove(hash, Some(DBValue::from(data))),
+                _ => return None,
+            });
+            rest = remaining;
+        }
+        let roots = (
+            decode_hash::<H>(old_root).ok()?,
+            decode_hash::<H>(new_root).ok()?,
+        );
+        Some(Self::new(roots, prefix, writes))
+    }
+}
+
+// RECOVERY
+// ================================================================================================
+
+/// Completes a commit interrupted by a crash using the journal in the provided database, see
+/// TreeDBMutBuilder::with_journal. The writes that were not applied are applied and the journal is
+/// removed. Returns the root of the tree after the commit, None if no commit was in progress.
+///
+/// Errors:
+/// - CorruptJournal: if the journal can not be decoded
+pub fn replay_journal<H: TreeHasher>(
+    db: &mut dyn HashDB<H, DBValue>,
+) -> Result<Option<H::Out>, TreeError> {
+    let Some(mut journal) = Journal::<H>::read(db)? else {
+        return Ok(None);
+    };
+    journal.apply(&mut DBMut::Infallible(&mut *db))?;
+    journal.end(db);
+    Ok(Some(journal.roots.1))
+}
+
+/// Rolls back a commit interrupted by a crash using the journal in the provided database, see
+/// TreeDBMutBuilder::with_journal. The writes that were applied are reverted and the journal is
+/// removed. Returns the root of the tree before the commit, None if no commit was in progress.
+///
+/// Errors:
+/// - CorruptJournal: if the journal can not be decoded
+pub fn rollback_journal<H: TreeHasher>(
+    db: &mut dyn HashDB<H, DBValue>,
+) -> Result<Option<H::Out>, TreeError> {
+    let Some(mut journal) = Journal::<H>::read(db)? else {
+        return Ok(None);
+    };
+    journal.revert(db);
+    journal.end(db);
+    Ok(Some(journal.roots.0))
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Removes the record with the provided key if it is in the provided database
+fn remove_record<H: TreeHasher>(db: &mut DBMut<'_, H>, key: H::Out) -> Result<(), TreeError> {
+    if db.as_hash_db().contains(&key, EMPTY_PREFIX) {
+        db.try_remove(&key, EMPTY_PREFIX)?;
+    }
+    Ok(())
+}
+
+/// Splits the provided bytes at the provided index, None if there are fewer bytes
+fn split(data: &[u8], at: usize) -> Option<(&[u8], &[u8])> {
+    (data.len() >= at).then(|| data.split_at(at))
+}
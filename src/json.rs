@@ -0,0 +1,71 @@
+use super::{DBValue, DataError, KeyedTreeMut, TreeDB, TreeDBMut, TreeError, TreeHasher};
+use core::hash::BuildHasher;
+use serde_json::{Map, Value};
+use std::{
+    io::{Read, Write},
+    string::{String, ToString},
+    vec::Vec,
+};
+
+// JSON MAP
+// ================================================================================================
+
+/// The JSON map interchange format is a JSON object that maps hex encoded keys to hex encoded
+/// values, e.g. `{"0001": "76616c756531"}`. A `0x` prefix is accepted on import.
+impl<'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> TreeDBMut<'db, D, H, S> {
+    /// Inserts every key-value pair of the JSON map read from the provided reader into the tree
+    /// and returns the number of entries inserted. The entries are inserted in key order and
+    /// persisted on the next commit.
+    pub fn from_json_map(&mut self, reader: impl Read) -> Result<usize, TreeError> {
+        let map: Map<String, Value> = serde_json::from_reader(reader).map_err(json_error)?;
+        let mut entries = Vec::with_capacity(map.len());
+        for (key, value) in map.iter() {
+            let value = value
+                .as_str()
+                .ok_or_else(|| json_error(format!("value of key {key} is not a string")))?;
+            entries.push((decode_hex(key)?, decode_hex(value)?));
+        }
+        entries.sort();
+
+        for (key, value) in entries.iter() {
+            self.insert(key, DBValue::from(&value[..]))?;
+        }
+        Ok(entries.len())
+    }
+
+    /// Writes all leaves of the tree, including uncommitted changes, to the provided writer as a
+    /// JSON map.
+    pub fn to_json_map(&self, writer: impl Write) -> Result<(), TreeError> {
+        write_json_map(self.entries()?, writer)
+    }
+}
+
+impl<'db, const D: usize, H: TreeHasher> TreeDB<'db, D, H> {
+    /// Writes all leaves of the tree to the provided writer as a JSON map.
+    pub fn to_json_map(&self, writer: impl Write) -> Result<(), TreeError> {
+        write_json_map(self.entries()?, writer)
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Writes the provided key-value pairs to the writer as a JSON map.
+fn write_json_map(entries: Vec<(DBValue, DBValue)>, writer: impl Write) -> Result<(), TreeError> {
+    let map: Map<String, Value> = entries
+        .into_iter()
+        .map(|(key, value)| (hex::encode(key), Value::String(hex::encode(value))))
+        .collect();
+    serde_json::to_writer(writer, &map).map_err(json_error)
+}
+
+/// Decodes a hex string with an optional `0x` prefix.
+fn decode_hex(data: &str) -> Result<Vec<u8>, TreeError> {
+    let data = data.strip_prefix("0x").unwrap_or(data);
+    hex::decode(data).map_err(json_error)
+}
+
+/// Converts an error into a TreeError.
+fn json_error(err: impl ToString) -> TreeError {
+    TreeError::DataError(DataError::JsonError(err.to_string()))
+}
@@ -10,10 +10,17 @@ const BYTE_SIZE: usize = 8;
 // ================================================================================================
 
 /// stores a key of N bytes
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Key<const N: usize>([u8; N]);
 
 impl<const N: usize> Key<N> {
+    /// The number of bits in the key, which is the depth of a tree addressed by the key. Using
+    /// this constant results in a compile time error if the number of bits overflows a usize.
+    pub const BITS: usize = {
+        assert!(N <= usize::MAX / BYTE_SIZE, "key size too large");
+        N * BYTE_SIZE
+    };
+
     /// Create a new key from a byte slice
     pub fn new(key: &[u8]) -> Result<Key<N>, KeyError> {
         let key = key
@@ -26,7 +33,7 @@ impl<const N: usize> Key<N> {
     pub fn bit(&self, i: usize) -> Result<bool, KeyError> {
         let byte_pos = i / BYTE_SIZE;
         if byte_pos >= N {
-            return Err(KeyError::BitIndexOutOfBounds(i, N * 8));
+            return Err(KeyError::BitIndexOutOfBounds(i, Self::BITS));
         }
 
         let bit_pos = i % BYTE_SIZE;
@@ -34,6 +41,27 @@ impl<const N: usize> Key<N> {
         Ok(bit != 0)
     }
 
+    /// Sets the bit at the i'th index of the key
+    pub fn set_bit(&mut self, i: usize, bit: bool) -> Result<(), KeyError> {
+        let byte_pos = i / BYTE_SIZE;
+        if byte_pos >= N {
+            return Err(KeyError::BitIndexOutOfBounds(i, Self::BITS));
+        }
+
+        let mask = 1 << (7 - i % BYTE_SIZE);
+        if bit {
+            self.0[byte_pos] |= mask;
+        } else {
+            self.0[byte_pos] &= !mask;
+        }
+        Ok(())
+    }
+
+    /// Returns the key as a byte array
+    pub fn into_bytes(self) -> [u8; N] {
+        self.0
+    }
+
     /// Returns the key as a byte slice
     pub fn as_slice(&self) -> &[u8] {
         &self.0[..]
@@ -44,14 +72,16 @@ impl<const N: usize> Key<N> {
         KeyIter {
             key: self,
             element: 0,
+            end: Self::BITS,
         }
     }
 }
 
-/// Key iterator
+/// Key iterator, iterates over the bits of the key from the most significant bit
 pub struct KeyIter<'a, const N: usize> {
     key: &'a Key<N>,
     element: usize,
+    end: usize,
 }
 
 /// Key iterator implementation
@@ -59,7 +89,7 @@ impl<'a, const N: usize> Iterator for KeyIter<'a, N> {
     type Item = bool;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.element >= N * 8 {
+        if self.element >= self.end {
             return None;
         }
 
@@ -70,6 +100,25 @@ impl<'a, const N: usize> Iterator for KeyIter<'a, N> {
     }
 }
 
+/// Key iterator implementation from the least significant bit
+impl<'a, const N: usize> DoubleEndedIterator for KeyIter<'a, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.end <= self.element {
+            return None;
+        }
+
+        self.end -= 1;
+        Some(self.key.bit(self.end).expect("end is checked"))
+    }
+}
+
+/// The default key is the key with all bits set to zero
+impl<const N: usize> Default for Key<N> {
+    fn default() -> Self {
+        Key([0u8; N])
+    }
+}
+
 impl<const D: usize> AsRef<[u8]> for Key<D> {
     fn as_ref(&self) -> &[u8] {
         self.as_slice()
@@ -81,13 +130,43 @@ impl<const D: usize> TryFrom<&u64> for Key<D> {
     type Error = KeyError;
 
     fn try_from(value: &u64) -> Result<Self, Self::Error> {
-        let max = 2u64.pow(D as u32 * 8);
-        if value > &max {
+        let bytes = value.to_be_bytes();
+        let mut key = [0u8; D];
+
+        // keys of 8 bytes or more can address any u64 index, the index is right aligned
+        if D >= bytes.len() {
+            key[D - bytes.len()..].copy_from_slice(&bytes);
+            return Ok(Key(key));
+        }
+
+        let max = 1u64 << Self::BITS;
+        if value >= &max {
             return Err(KeyError::LeafIndexOutOfBounds(*value, max));
         }
 
-        let mut key = [0u8; D];
-        key.copy_from_slice(&value.to_be_bytes()[8 - D..]);
+        key.copy_from_slice(&bytes[bytes.len() - D..]);
         Ok(Key(key))
     }
 }
+
+/// Covert a `&Key<D>` to the `u64` index it addresses, the inverse of the conversion from a `&u64`
+impl<const D: usize> TryFrom<&Key<D>> for u64 {
+    type Error = KeyError;
+
+    fn try_from(key: &Key<D>) -> Result<Self, Self::Error> {
+        let mut bytes = [0u8; 8];
+        if D <= bytes.len() {
+            bytes[8 - D..].copy_from_slice(&key.0);
+            return Ok(u64::from_be_bytes(bytes));
+        }
+
+        // the bytes above the right aligned index must be zero
+        let (high, low) = key.0.split_at(D - bytes.len());
+        if let Some(position) = high.iter().position(|byte| *byte != 0) {
+            let bit = position * BYTE_SIZE + high[position].leading_zeros() as usize;
+            return Err(KeyError::BitIndexOutOfBounds(bit, Key::<D>::BITS - 64));
+        }
+        bytes.copy_from_slice(low);
+        Ok(u64::from_be_bytes(bytes))
+    }
+}
@@ -11,17 +11,52 @@
 #[cfg(not(feature = "std"))]
 extern crate alloc;
 
+mod asyncdb;
+mod backup;
+mod batch;
+mod bulk;
+mod cache;
+mod celestia;
+mod changeset;
+mod entry;
+mod envelope;
 mod error;
+#[cfg(feature = "std")]
+mod filedb;
+mod hasher;
 mod indexdb;
 mod indexdbmut;
+mod journal;
+#[cfg(feature = "json")]
+mod json;
 mod key;
+mod memdb;
+mod metrics;
+mod migrate;
+mod multiget;
+mod namespace;
 mod node;
+mod overlay;
+mod progress;
 mod proof;
 mod recorder;
+mod refcount;
+mod registry;
+mod repair;
+mod rfc6962;
+#[cfg(feature = "send")]
+mod shared;
+mod snapshot;
 mod storage;
+#[cfg(feature = "substrate")]
+mod substrate;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 mod tree;
 mod treedb;
 mod treedbmut;
+mod trydb;
+mod versioned;
 
 #[cfg(test)]
 mod tests;
@@ -31,35 +66,105 @@ mod tests;
 
 #[cfg(feature = "std")]
 mod rstd {
-    pub use std::{fmt, iter, string, vec};
+    pub use std::{collections, fmt, iter, string, sync, vec};
 }
 
 #[cfg(not(feature = "std"))]
 mod rstd {
-    pub use alloc::{string, vec};
+    pub use alloc::{collections, string, sync, vec};
     pub use core::{fmt, iter};
 }
 
-use error::{DataError, KeyError, NodeError};
-use key::Key;
+use entry::entry;
+use journal::{read_removed, Journal, Write};
 use node::{ChildSelector, Node, NodeHash};
-use storage::NodeStorage;
-use tree::null_nodes;
+use storage::{NodeMemo, NodeStorage};
+use tree::{
+    absence_proof, descend, first_divergence, for_each_leaf, level_hashes, neighbor, null_nodes,
+    path_nodes, proof_size_hint, AbsenceProof, AuditSample, CelestiaProofOf, ChainedProofOf,
+    NeighborProofOf, Proof, SubtreeProof, TypedProof,
+};
+use trydb::DBMut;
 
+#[cfg(not(feature = "smallvec"))]
 use self::rstd::vec::Vec;
-use hashbrown::{HashMap, HashSet};
+
+/// The recorder of the mutable trees. With the `send` feature the recorder is required to be Send
+/// such that the mutable trees are Send, the database backend is always Send as required by
+/// HashDB.
+#[cfg(feature = "send")]
+pub type DynTreeRecorder<'a, H> = dyn TreeRecorder<H> + Send + 'a;
+#[cfg(not(feature = "send"))]
+pub type DynTreeRecorder<'a, H> = dyn TreeRecorder<H> + 'a;
+
+/// Maps and sets used internally, hashed with FxBuildHasher unless specified otherwise
+type HashMap<K, V, S = FxBuildHasher> = hashbrown::HashMap<K, V, S>;
+type HashSet<K, S = FxBuildHasher> = hashbrown::HashSet<K, S>;
+
+/// Maps keyed by caller supplied keys, hashed with the seeded hasher of hashbrown as FxBuildHasher
+/// is not resistant to keys crafted to collide
+type KeyMap<K, V> = hashbrown::HashMap<K, V, hashbrown::hash_map::DefaultHashBuilder>;
 
 // RE-EXPORTS
 // ================================================================================================
 
-pub use error::TreeError;
+pub use asyncdb::{
+    AsyncHashDB, AsyncHashDBRef, AsyncTreeDB, AsyncTreeDBBuilder, AsyncTreeDBMut,
+    AsyncTreeDBMutBuilder,
+};
+pub use backup::{nodes, NodeIter};
+pub use batch::{BatchHashDB, WriteBatch};
+pub use bulk::TreeBuilder;
+pub use cache::CachedDB;
+pub use celestia::{celestia_leaf_hash, celestia_node_hash, verify_celestia_proof, CelestiaProof};
+pub use changeset::{ChangeSet, LeafChange};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use envelope::{hasher_id, ProofEnvelope, PROOF_ENVELOPE_VERSION};
+pub use error::{DataError, KeyError, NodeError, ProofError, TreeError};
+#[cfg(feature = "std")]
+pub use filedb::FileDB;
+#[cfg(feature = "digest")]
+pub use hasher::DigestHasher;
+pub use hasher::{DualHasher, FxBuildHasher, FxHasher, OutputStdHasher, TreeHasher};
 pub use indexdb::{IndexTreeDB, IndexTreeDBBuilder};
 pub use indexdbmut::{IndexTreeDBMut, IndexTreeDBMutBuilder};
-pub use proof::StorageProof;
-pub use recorder::Recorder;
-pub use tree::{IndexTree, IndexTreeMut, KeyedTree, KeyedTreeMut, TreeRecorder};
+pub use journal::{replay_journal, rollback_journal};
+pub use key::{Key, KeyIter};
+pub use memdb::MemDb;
+pub use metrics::TreeMetrics;
+pub use migrate::{migrate, migrate_with_monitor};
+pub use multiget::MultiHashDBRef;
+pub use namespace::Namespace;
+#[cfg(feature = "leaf-keys")]
+pub use node::key_preimage_value;
+pub use overlay::{OverlayBatch, OverlayDB};
+pub use progress::{CancellationToken, Monitor, Progress, PROGRESS_INTERVAL};
+pub use proof::{
+    insert_from_proof, verify_multi, verify_salted_multi, verify_update, ChainedProof,
+    CircuitWitness, CompactProof, CompactStorageProof, ConsistencyProof, DivergenceProof,
+    LevelCache, MerkleProof, NeighborProof, StorageProof, UpdateProof,
+    COMPACT_STORAGE_PROOF_VERSION, STORAGE_PROOF_VERSION,
+};
+pub use recorder::{Recorder, RecorderStats};
+pub use refcount::RefCountedDB;
+pub use registry::Registry;
+pub use repair::{
+    compare_backends, compare_backends_with_monitor, destroy, repair_backend,
+    repair_backend_with_monitor, Backend, Divergence,
+};
+pub use rfc6962::{rfc6962_leaf_hash, rfc6962_node_hash, verify_rfc6962_audit_path};
+#[cfg(feature = "send")]
+pub use shared::SharedDB;
+pub use snapshot::TreeSnapshot;
+pub use storage::StagedStats;
+pub use tree::{
+    verify_at_depth, verify_empty_subtree, verify_subtree, IndexTree, IndexTreeMut, KeyedTree,
+    KeyedTreeMut, TreeRecorder, MAX_LEVEL_DEPTH,
+};
 pub use treedb::{TreeDB, TreeDBBuilder};
-pub use treedbmut::{TreeDBMut, TreeDBMutBuilder};
+pub use treedbmut::{Fork, TreeDBMut, TreeDBMutBuilder};
+pub use trydb::TryHashDB;
+pub use versioned::{VersionedTreeDBMut, VersionedTreeDBMutBuilder};
 
 pub use hash_db::{HashDB, HashDBRef, Hasher};
 
@@ -67,4 +172,10 @@ pub use hash_db::{HashDB, HashDBRef, Hasher};
 // ================================================================================================
 
 /// The type of value stored in the database backend.
+#[cfg(not(feature = "smallvec"))]
 pub type DBValue = Vec<u8>;
+
+/// The type of value stored in the database backend. Values of up to 32 bytes, which includes
+/// the hashes stored in proofs, are stored inline without a heap allocation.
+#[cfg(feature = "smallvec")]
+pub type DBValue = smallvec::SmallVec<[u8; 32]>;
@@ -0,0 +1,104 @@
+use super::{
+    proof::NoopKey, rstd::string::String, BatchHashDB, DBValue, HashDB, HashDBRef, TreeHasher,
+    WriteBatch,
+};
+use hash_db::{AsHashDB, Prefix};
+use memory_db::MemoryDB;
+
+// MemDb
+// ================================================================================================
+
+/// An in-memory database keyed by node hashes, ready to be used as the backend of any tree, e.g.
+/// `TreeDBMutBuilder::new(&mut MemDb::new(), &mut root)`. Records are reference counted as
+/// expected by the trees, see MemoryDB.
+pub struct MemDb<H: TreeHasher> {
+    db: MemoryDB<H, NoopKey<H>, DBValue>,
+}
+
+impl<H: TreeHasher> MemDb<H> {
+    /// Construct a new empty MemDb
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of records with a positive reference count
+    pub fn len(&self) -> usize {
+        self.db.keys().values().filter(|rc| **rc > 0).count()
+    }
+
+    /// Returns true if the database holds no records with a positive reference count
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the records whose reference count dropped to zero or below
+    pub fn purge(&mut self) {
+        self.db.purge()
+    }
+}
+
+impl<H: TreeHasher> Default for MemDb<H> {
+    fn default() -> Self {
+        Self {
+            db: MemoryDB::default(),
+        }
+    }
+}
+
+impl<H: TreeHasher> Clone for MemDb<H> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+        }
+    }
+}
+
+impl<H: TreeHasher> HashDB<H, DBValue> for MemDb<H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(&self.db, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(&self.db, key, prefix)
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        self.db.insert(prefix, value)
+    }
+
+    fn emplace(&mut self, key: H::Out, prefix: Prefix, value: DBValue) {
+        self.db.emplace(key, prefix, value)
+    }
+
+    fn remove(&mut self, key: &H::Out, prefix: Prefix) {
+        self.db.remove(key, prefix)
+    }
+}
+
+impl<H: TreeHasher> HashDBRef<H, DBValue> for MemDb<H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(self, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(self, key, prefix)
+    }
+}
+
+/// Batches are applied under the exclusive borrow of the database and as such atomically
+impl<H: TreeHasher> BatchHashDB<H> for MemDb<H> {
+    fn write_batch(&mut self, batch: &WriteBatch<H>) -> Result<(), String> {
+        batch.apply(self);
+        Ok(())
+    }
+}
+
+impl<H: TreeHasher> AsHashDB<H, DBValue> for MemDb<H> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<H, DBValue> + 'a) {
+        self
+    }
+}
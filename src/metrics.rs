@@ -0,0 +1,21 @@
+// TreeMetrics
+// ================================================================================================
+
+/// Hooks reporting the storage activity of a tree, attached with the with_metrics method of the
+/// tree builders, e.g. to increment Prometheus counters. The hooks take a shared reference such
+/// that one set of counters can be shared by many trees, every hook defaults to doing nothing.
+pub trait TreeMetrics: Sync {
+    /// Called for every node read from the database, including nodes that fail to decode
+    fn node_read(&self) {}
+
+    /// Called for every node read from the database that can not be decoded
+    fn decode_failure(&self) {}
+
+    /// Called for every node lookup served by a memo or cache of decoded nodes rather than the
+    /// database, see CachedDB
+    fn cache_hit(&self) {}
+
+    /// Called once per commit with the number of nodes written to the database and their total
+    /// encoded size in bytes, removes are not counted
+    fn nodes_written(&self, _nodes: usize, _bytes: usize) {}
+}
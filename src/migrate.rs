@@ -0,0 +1,159 @@
+use super::{
+    repair::root_stack,
+    rstd::{string::ToString, vec::Vec},
+    DBValue, DataError, HashDB, HashDBRef, Key, Monitor, Node, NodeError, NodeHash, TreeBuilder,
+    TreeError, TreeHasher,
+};
+use hash_db::EMPTY_PREFIX;
+
+// Leaves
+// ================================================================================================
+
+/// Walks the tree with the provided root depth first and yields the key and node of every
+/// non-default leaf ordered by key. Nodes are read from the database as the walk advances, such
+/// that only the path to the current leaf and the siblings along it are held in memory. The walk
+/// ends after the first error.
+struct Leaves<'db, const D: usize, H: TreeHasher> {
+    db: &'db dyn HashDBRef<H, DBValue>,
+    salt: &'db [u8],
+    stack: Vec<(H::Out, Key<D>, usize)>,
+}
+
+impl<'db, const D: usize, H: TreeHasher> Leaves<'db, D, H> {
+    /// Construct a walk of the leaves of the tree with the provided root
+    fn new(db: &'db dyn HashDBRef<H, DBValue>, root: &H::Out, salt: &'db [u8]) -> Self {
+        let stack = root_stack::<D, H>(root, salt)
+            .into_iter()
+            .map(|root| (root, Key::<D>::default(), 0))
+            .collect();
+        Self { db, salt, stack }
+    }
+
+    /// Returns the next non-default leaf of the walk
+    fn next_leaf(&mut self) -> Result<Option<(Key<D>, Node<H>)>, TreeError> {
+        while let Some((hash, key, depth)) = self.stack.pop() {
+            let data = self
+                .db
+                .get(&hash, EMPTY_PREFIX)
+                .ok_or(TreeError::DataError(DataError::DatabaseDataNotFound(
+                    hash.as_ref().to_vec(),
+                )))?;
+            let node = Node::<H>::decode(data, self.salt).map_err(TreeError::NodeError)?;
+            if depth == Key::<D>::BITS {
+                return Ok(Some((key, node)));
+            }
+
+            if let Node::Inner { left, right, .. } = &node {
+                let mut right_key = key;
+                right_key
+                    .set_bit(depth, true)
+                    .map_err(TreeError::KeyError)?;
+                for (child, child_key) in [(right, right_key), (left, key)] {
+                    if let NodeHash::Database(child) = child {
+                        self.stack.push((*child, child_key, depth + 1));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'db, const D: usize, H: TreeHasher> Iterator for Leaves<'db, D, H> {
+    type Item = Result<(Key<D>, Node<H>), TreeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let leaf = self.next_leaf();
+        if leaf.is_err() {
+            self.stack.clear();
+        }
+        leaf.transpose()
+    }
+}
+
+// MIGRATION
+// ================================================================================================
+
+/// Reconstructs the tree with the provided root, built with hasher H1 in the source database,
+/// under hasher H2 in the target database, e.g. to move a tree from Keccak to Poseidon. The leaves
+/// are streamed from the source in key order and the new tree is written bottom-up with a
+/// TreeBuilder, such that the tree is never held in memory. Leaves keep their value, expiry, bound
+/// key and key preimage, and the provided salt is used for both trees. Returns the root of the
+/// tree under H2.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a reachable node is missing from the source database
+/// - NodeError: if a reachable node can not be decoded
+pub fn migrate<const D: usize, H1: TreeHasher, H2: TreeHasher>(
+    root: &H1::Out,
+    source: &dyn HashDBRef<H1, DBValue>,
+    target: &mut dyn HashDB<H2, DBValue>,
+    salt: &[u8],
+) -> Result<H2::Out, TreeError> {
+    migrate_with_monitor::<D, H1, H2>(root, source, target, salt, &mut Monitor::new())
+}
+
+/// Migrates the tree to another hasher, see migrate, reporting progress to and checking for
+/// cancellation with the provided monitor. Each migrated leaf counts as processed. Nodes written
+/// before the operation is cancelled remain in the target database.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a reachable node is missing from the source database
+/// - NodeError: if a reachable node can not be decoded
+/// - Cancelled: if the cancellation token of the monitor is cancelled
+pub fn migrate_with_monitor<const D: usize, H1: TreeHasher, H2: TreeHasher>(
+    root: &H1::Out,
+    source: &dyn HashDBRef<H1, DBValue>,
+    target: &mut dyn HashDB<H2, DBValue>,
+    salt: &[u8],
+    monitor: &mut Monitor,
+) -> Result<H2::Out, TreeError> {
+    let leaves = Leaves::<D, H1>::new(source, root, salt).map(|leaf| {
+        monitor.tick()?;
+        let (key, node) = leaf?;
+        let node = rehash_leaf::<H1, H2>(node, salt).map_err(TreeError::NodeError)?;
+        Ok((key, node))
+    });
+    let root = TreeBuilder::<D, H2>::new(target)
+        .with_salt(salt)
+        .build_nodes(leaves)?;
+    monitor.finish();
+    Ok(root)
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Returns the leaf node hashed with H2 holding the value, expiry, bound key and key preimage of
+/// the provided leaf node hashed with H1
+///
+/// Errors:
+/// - InvalidNodeType: if the provided node is an inner node
+fn rehash_leaf<H1: TreeHasher, H2: TreeHasher>(
+    node: Node<H1>,
+    salt: &[u8],
+) -> Result<Node<H2>, NodeError> {
+    let (value, expiry, key, preimage) = match node {
+        Node::Value {
+            value,
+            expiry,
+            key,
+            preimage,
+            ..
+        } => (value, expiry, key, preimage),
+        Node::Inner { .. } => {
+            return Err(NodeError::InvalidNodeType(
+                "Inner".to_string(),
+                "Value".to_string(),
+            ))
+        }
+    };
+    Ok(match (preimage, key, expiry) {
+        (Some(preimage), key, _) => {
+            Node::new_preimage_value(key.as_deref(), salt, &preimage, &value)
+        }
+        (None, Some(key), expiry) => Node::new_bound_value(&key, salt, &value, expiry),
+        (None, None, Some(expiry)) => Node::new_expiring_value(salt, &value, expiry),
+        (None, None, None) => Node::new_salted_value(salt, &value),
+    })
+}
@@ -0,0 +1,20 @@
+use super::{rstd::vec::Vec, DBValue, HashDBRef, Hasher, MemDb, TreeHasher};
+use hash_db::Prefix;
+
+// MultiHashDBRef
+// ================================================================================================
+
+/// An extension of HashDBRef for backends which fetch many records in a single round trip, e.g.
+/// the multi_get of RocksDB or a batched network request. A tree built with
+/// TreeDBBuilder::new_multi fetches the nodes along the paths of a batch read or a multi-key proof
+/// level by level, issuing one get_many per level of the tree for all keys rather than one get per
+/// node.
+pub trait MultiHashDBRef<H: Hasher>: HashDBRef<H, DBValue> {
+    /// Returns the records with the provided keys in the order of the keys, None for a missing
+    /// record. Defaults to one get per key.
+    fn get_many(&self, keys: &[H::Out], prefix: Prefix) -> Vec<Option<DBValue>> {
+        keys.iter().map(|key| self.get(key, prefix)).collect()
+    }
+}
+
+impl<H: TreeHasher> MultiHashDBRef<H> for MemDb<H> {}
@@ -0,0 +1,101 @@
+use super::{verify_subtree, DBValue, Key, KeyError, TreeError, TreeHasher};
+
+// Namespace
+// ================================================================================================
+
+/// A namespace partitions the key space of a tree of depth D * 8 into independent subtrees. The
+/// namespace id occupies the first `bits` bits of every key in the namespace and the remaining bits
+/// are derived from an inner key, either by truncating the inner key or by hashing it. As such all
+/// keys in a namespace share a single subtree which can be iterated and proven independently of
+/// the rest of the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Namespace<const D: usize> {
+    id: u64,
+    bits: usize,
+}
+
+impl<const D: usize> Namespace<D> {
+    /// Construct a new namespace with the provided id occupying the first `bits` bits of the key.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if bits exceeds 64 or the depth of the tree
+    /// - NamespaceIdOutOfBounds: if the id does not fit in the provided number of bits
+    pub fn new(id: u64, bits: usize) -> Result<Self, KeyError> {
+        let max_bits = Key::<D>::BITS.min(u64::BITS as usize);
+        if bits > max_bits {
+            return Err(KeyError::BitIndexOutOfBounds(bits, max_bits));
+        }
+        if bits < u64::BITS as usize && id >> bits != 0 {
+            return Err(KeyError::NamespaceIdOutOfBounds(id, bits));
+        }
+        Ok(Self { id, bits })
+    }
+
+    /// Returns the id of the namespace
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the number of key bits occupied by the namespace id, which is the depth of the
+    /// namespace subtree root
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Returns the key of the namespace subtree root, the namespace id followed by zero bits
+    pub fn prefix(&self) -> [u8; D] {
+        self.prefix_key().into_bytes()
+    }
+
+    /// Returns the tree key for the provided inner key. The bits following the namespace id are
+    /// taken from the start of the inner key, the inner key is truncated if it is too long and
+    /// padded with zero bits if it is too short.
+    pub fn truncated_key(&self, inner: &[u8]) -> [u8; D] {
+        let mut key = self.prefix_key();
+        let inner_bits = inner.len() * 8;
+        for i in 0..(Key::<D>::BITS - self.bits).min(inner_bits) {
+            let bit = (inner[i / 8] >> (7 - i % 8)) & 1 == 1;
+            key.set_bit(self.bits + i, bit).expect("bit is in range");
+        }
+        key.into_bytes()
+    }
+
+    /// Returns the tree key for the provided inner key. The bits following the namespace id are
+    /// taken from the hash of the inner key.
+    pub fn hashed_key<H: TreeHasher>(&self, inner: &[u8]) -> [u8; D] {
+        self.truncated_key(H::hash(inner).as_ref())
+    }
+
+    /// Returns true if the provided tree key belongs to the namespace
+    pub fn contains(&self, key: &[u8]) -> bool {
+        let key = match Key::<D>::new(key) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let prefix = self.prefix_key();
+        (0..self.bits).all(|i| key.bit(i) == prefix.bit(i))
+    }
+
+    /// Verifies that the provided subtree root is the root of the namespace subtree in the tree
+    /// with the provided root. The proof contains the sibling hashes along the path from the
+    /// namespace subtree root to the root of the tree.
+    pub fn verify<H: TreeHasher>(
+        &self,
+        subtree_root: &H::Out,
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        let prefix = self.prefix_key();
+        verify_subtree::<H, D>(prefix.as_slice(), self.bits, subtree_root, proof, root)
+    }
+
+    /// Returns the key of the namespace subtree root
+    pub(crate) fn prefix_key(&self) -> Key<D> {
+        let mut key = Key::<D>::default();
+        for i in 0..self.bits {
+            let bit = (self.id >> (self.bits - 1 - i)) & 1 == 1;
+            key.set_bit(i, bit).expect("bit is in range");
+        }
+        key
+    }
+}
@@ -1,7 +1,4 @@
-use super::{
-    rstd::{string::ToString, vec, vec::Vec},
-    DBValue, Hasher, NodeError,
-};
+use super::{rstd::string::ToString, DBValue, NodeError, TreeHasher};
 use core::ops::Deref;
 
 #[cfg(feature = "std")]
@@ -15,7 +12,7 @@ use super::rstd::fmt;
 /// If the node is stored in database backend, the hash is stored in the Database variant
 /// If the node is a default node, the hash is stored in the Default variant
 #[derive(PartialEq, Eq, Hash)]
-pub enum NodeHash<H: Hasher> {
+pub enum NodeHash<H: TreeHasher> {
     /// Hash associated with a node stored in memory
     InMemory(H::Out),
     /// Hash associated with a node stored in database backend
@@ -25,7 +22,7 @@ pub enum NodeHash<H: Hasher> {
 }
 
 #[cfg(feature = "std")]
-impl<H: Hasher> fmt::Display for NodeHash<H> {
+impl<H: TreeHasher> fmt::Display for NodeHash<H> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             NodeHash::InMemory(hash) => write!(f, "InMemory({hash:?})"),
@@ -35,7 +32,7 @@ impl<H: Hasher> fmt::Display for NodeHash<H> {
     }
 }
 
-impl<H: Hasher> NodeHash<H> {
+impl<H: TreeHasher> NodeHash<H> {
     /// Returns the inner hash of a node
     pub fn hash(&self) -> &H::Out {
         match self {
@@ -52,7 +49,7 @@ impl<H: Hasher> NodeHash<H> {
 }
 
 /// Implementation of Clone for NodeHash
-impl<H: Hasher> Clone for NodeHash<H> {
+impl<H: TreeHasher> Clone for NodeHash<H> {
     fn clone(&self) -> Self {
         match self {
             NodeHash::Database(hash) => NodeHash::Database(*hash),
@@ -63,14 +60,14 @@ impl<H: Hasher> Clone for NodeHash<H> {
 }
 
 /// Implement default for NodeHash
-impl<H: Hasher> Default for NodeHash<H> {
+impl<H: TreeHasher> Default for NodeHash<H> {
     fn default() -> Self {
         NodeHash::Default(H::Out::default())
     }
 }
 
 /// Implement Deref for NodeHash
-impl<H: Hasher> Deref for NodeHash<H> {
+impl<H: TreeHasher> Deref for NodeHash<H> {
     type Target = H::Out;
 
     fn deref(&self) -> &Self::Target {
@@ -107,13 +104,18 @@ impl ChildSelector {
     }
 }
 
-/// Node is used to store the data of a node. A value node stores the value and leaf hash. An inner
-/// node stores the left child hash and right child hash.
+/// Node is used to store the data of a node. A value node stores the value, leaf hash, optional
+/// expiry timestamp, for key-bound leaves the key the leaf hash is bound to and, for leaves of
+/// hashed-key trees, the preimage of the key. An inner node stores the left child hash and right
+/// child hash.
 #[derive(PartialEq, Eq)]
-pub enum Node<H: Hasher> {
+pub enum Node<H: TreeHasher> {
     Value {
         hash: H::Out,
         value: DBValue,
+        expiry: Option<u64>,
+        key: Option<DBValue>,
+        preimage: Option<DBValue>,
     },
     Inner {
         hash: H::Out,
@@ -123,25 +125,115 @@ pub enum Node<H: Hasher> {
 }
 
 #[cfg(feature = "std")]
-impl<H: Hasher> fmt::Display for Node<H> {
+impl<H: TreeHasher> fmt::Display for Node<H> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Node::Value { hash, value } => write!(f, "Value({hash:?}, {value:?})"),
+            Node::Value {
+                hash,
+                value,
+                expiry: None,
+                ..
+            } => write!(f, "Value({hash:?}, {value:?})"),
+            Node::Value {
+                hash,
+                value,
+                expiry: Some(expiry),
+                ..
+            } => write!(f, "Value({hash:?}, {value:?}, {expiry})"),
             Node::Inner { hash, left, right } => write!(f, "Inner({hash:?}, {left}, {right})"),
         }
     }
 }
 
 /// Node implementation
-impl<H: Hasher> Node<H> {
+impl<H: TreeHasher> Node<H> {
     // CONSTRUCTORS
     // --------------------------------------------------------------------------------------------
 
     /// constructs a new value node
     pub fn new_value(value: &[u8]) -> Self {
+        Self::new_salted_value(&[], value)
+    }
+
+    /// Constructs a new value node where the leaf hash is computed over the concatenation of the
+    /// salt and the value. An empty salt is equivalent to an unsalted value node.
+    pub fn new_salted_value(salt: &[u8], value: &[u8]) -> Self {
+        Node::Value {
+            hash: salted_leaf_hash::<H>(salt, value),
+            value: DBValue::from(value),
+            expiry: None,
+            key: None,
+            preimage: None,
+        }
+    }
+
+    /// Constructs a new value node that expires at the provided timestamp. The leaf hash is
+    /// computed over the concatenation of the salt, the value and the big endian expiry such that
+    /// the expiry is committed to by the tree.
+    pub fn new_expiring_value(salt: &[u8], value: &[u8], expiry: u64) -> Self {
         Node::Value {
-            hash: H::hash(value),
-            value: value.to_vec(),
+            hash: salted_leaf_hash::<H>(salt, &expiring_value_preimage(value, expiry)),
+            value: DBValue::from(value),
+            expiry: Some(expiry),
+            key: None,
+            preimage: None,
+        }
+    }
+
+    /// Constructs a new value node where the leaf hash is bound to the provided key. The leaf hash
+    /// is computed over the concatenation of the key, the salt, the value and the big endian
+    /// expiry if provided, such that a proof for one key can not be replayed for another key
+    /// holding the same value. The key is stored in the node such that the encoded node is a self
+    /// authenticating leaf record.
+    pub fn new_bound_value(key: &[u8], salt: &[u8], value: &[u8], expiry: Option<u64>) -> Self {
+        let hash = match expiry {
+            Some(expiry) => {
+                bound_leaf_hash::<H>(key, salt, &expiring_value_preimage(value, expiry))
+            }
+            None => bound_leaf_hash::<H>(key, salt, value),
+        };
+        Node::Value {
+            hash,
+            value: DBValue::from(value),
+            expiry,
+            key: Some(DBValue::from(key)),
+            preimage: None,
+        }
+    }
+
+    /// Constructs a new value node that stores the preimage of its key, such that the original
+    /// key of a leaf in a hashed-key tree can be recovered from the leaf record. The leaf hash
+    /// commits to the preimage and is computed over the key preimage value, see
+    /// key_preimage_value, which is bound to the provided key if any.
+    pub fn new_preimage_value(
+        key: Option<&[u8]>,
+        salt: &[u8],
+        preimage: &[u8],
+        value: &[u8],
+    ) -> Self {
+        let leaf_preimage = key_preimage_value(preimage, value);
+        let hash = match key {
+            Some(key) => bound_leaf_hash::<H>(key, salt, &leaf_preimage),
+            None => salted_leaf_hash::<H>(salt, &leaf_preimage),
+        };
+        Node::Value {
+            hash,
+            value: DBValue::from(value),
+            expiry: None,
+            key: key.map(DBValue::from),
+            preimage: Some(DBValue::from(preimage)),
+        }
+    }
+
+    /// Constructs the null value node, the leaf of an empty tree. The null leaf is not bound to a
+    /// key, see null_leaf_hash.
+    pub fn new_null_value(salt: &[u8]) -> Self {
+        Node::Value {
+            hash: null_leaf_hash::<H>(salt),
+            value: DBValue::new(),
+            expiry: None,
+            key: None,
+            preimage: None,
         }
     }
 
@@ -156,7 +248,7 @@ impl<H: Hasher> Node<H> {
             return Err(NodeError::InconsistentDefaultHashes);
         }
 
-        let hash = H::hash(&[left.hash().as_ref(), right.hash().as_ref()].concat());
+        let hash = H::hash_inner(left.hash().as_ref(), right.hash().as_ref());
 
         Ok(Node::Inner { hash, left, right })
     }
@@ -170,7 +262,7 @@ impl<H: Hasher> Node<H> {
     /// - UnexpectedNodeType: if the node is a value node
     pub fn child_hash(&self, child: &ChildSelector) -> Result<&NodeHash<H>, NodeError> {
         match self {
-            Node::Value { hash: _, value: _ } => Err(NodeError::InvalidNodeType(
+            Node::Value { .. } => Err(NodeError::InvalidNodeType(
                 "Value".to_string(),
                 "Inner".to_string(),
             )),
@@ -191,7 +283,7 @@ impl<H: Hasher> Node<H> {
     /// - UnexpectedNodeType: if the node is an inner node
     pub fn value(&self) -> Result<&DBValue, NodeError> {
         match self {
-            Node::Value { hash: _, value } => Ok(value),
+            Node::Value { value, .. } => Ok(value),
             Node::Inner {
                 hash: _,
                 left: _,
@@ -203,11 +295,78 @@ impl<H: Hasher> Node<H> {
         }
     }
 
+    /// Returns the expiry timestamp of a value node, None if the node does not expire or is an
+    /// inner node.
+    pub fn expiry(&self) -> Option<u64> {
+        match self {
+            Node::Value { expiry, .. } => *expiry,
+            Node::Inner { .. } => None,
+        }
+    }
+
+    /// Returns the key a value node is bound to, None if the node is not key-bound or is an inner
+    /// node.
+    pub fn key(&self) -> Option<&DBValue> {
+        match self {
+            Node::Value { key, .. } => key.as_ref(),
+            Node::Inner { .. } => None,
+        }
+    }
+
+    /// Returns the key preimage stored in a value node, None if the node does not store a key
+    /// preimage or is an inner node.
+    pub fn preimage(&self) -> Option<&DBValue> {
+        match self {
+            Node::Value { preimage, .. } => preimage.as_ref(),
+            Node::Inner { .. } => None,
+        }
+    }
+
+    /// Returns the preimage of the leaf hash of a value node, i.e. the bytes hashed by
+    /// H::hash_leaf to compute the leaf hash of a tree with the provided salt. This accessor is
+    /// only valid for value nodes.
+    /// Errors:
+    /// - UnexpectedNodeType: if the node is an inner node
+    pub fn leaf_preimage(&self, salt: &[u8]) -> Result<DBValue, NodeError> {
+        let (value, expiry, key, preimage) = match self {
+            Node::Value {
+                value,
+                expiry,
+                key,
+                preimage,
+                ..
+            } => (value, expiry, key, preimage),
+            Node::Inner { .. } => {
+                return Err(NodeError::InvalidNodeType(
+                    "Inner".to_string(),
+                    "Value".to_string(),
+                ))
+            }
+        };
+        // the null leaf of a key-bound tree is not salted, see null_leaf_hash
+        if cfg!(feature = "key-bound") && value.is_empty() {
+            return Ok(DBValue::new());
+        }
+
+        let body = match (preimage, expiry) {
+            (Some(preimage), _) => key_preimage_value(preimage, value),
+            (None, Some(expiry)) => expiring_value_preimage(value, *expiry),
+            (None, None) => value.clone(),
+        };
+        let mut leaf_preimage = DBValue::new();
+        if let Some(key) = key {
+            leaf_preimage.extend_from_slice(key);
+        }
+        leaf_preimage.extend_from_slice(salt);
+        leaf_preimage.extend_from_slice(&body);
+        Ok(leaf_preimage)
+    }
+
     /// Returns a reference to the hash of a node. This accessor is valid for both value and inner
     /// nodes.
     pub fn hash(&self) -> &H::Out {
         match self {
-            Node::Value { hash, value: _ } => hash,
+            Node::Value { hash, .. } => hash,
             Node::Inner {
                 hash,
                 left: _,
@@ -221,7 +380,7 @@ impl<H: Hasher> Node<H> {
     /// - UnexpectedNodeType: if the node is a value node
     pub fn is_default(&self) -> bool {
         match self {
-            Node::Value { hash: _, value } => value.is_empty(),
+            Node::Value { value, .. } => value.is_empty(),
             Node::Inner {
                 hash: _,
                 left,
@@ -239,19 +398,19 @@ impl<H: Hasher> Node<H> {
         child_hash: NodeHash<H>,
     ) -> Result<(), NodeError> {
         match self {
-            Node::Value { hash: _, value: _ } => Err(NodeError::InvalidNodeType(
+            Node::Value { .. } => Err(NodeError::InvalidNodeType(
                 "Value".to_string(),
                 "Inner".to_string(),
             )),
             Node::Inner { hash, left, right } => match child {
                 ChildSelector::Left => {
                     *left = child_hash;
-                    *hash = H::hash(&[left.hash().as_ref(), right.hash().as_ref()].concat());
+                    *hash = H::hash_inner(left.hash().as_ref(), right.hash().as_ref());
                     Ok(())
                 }
                 ChildSelector::Right => {
                     *right = child_hash;
-                    *hash = H::hash(&[left.hash().as_ref(), right.hash().as_ref()].concat());
+                    *hash = H::hash_inner(left.hash().as_ref(), right.hash().as_ref());
                     Ok(())
                 }
             },
@@ -260,12 +419,21 @@ impl<H: Hasher> Node<H> {
 }
 
 /// Returns a clone of the node
-impl<H: Hasher> Clone for Node<H> {
+impl<H: TreeHasher> Clone for Node<H> {
     fn clone(&self) -> Self {
         match self {
-            Node::Value { hash, value } => Node::Value {
+            Node::Value {
+                hash,
+                value,
+                expiry,
+                key,
+                preimage,
+            } => Node::Value {
                 hash: *hash,
                 value: value.clone(),
+                expiry: *expiry,
+                key: key.clone(),
+                preimage: preimage.clone(),
             },
             Node::Inner { hash, left, right } => Node::Inner {
                 hash: *hash,
@@ -277,11 +445,14 @@ impl<H: Hasher> Clone for Node<H> {
 }
 
 /// Implements default for Node
-impl<H: Hasher> Default for Node<H> {
+impl<H: TreeHasher> Default for Node<H> {
     fn default() -> Self {
         Node::Value {
             hash: H::Out::default(),
             value: DBValue::default(),
+            expiry: None,
+            key: None,
+            preimage: None,
         }
     }
 }
@@ -295,11 +466,80 @@ impl<H: Hasher> Default for Node<H> {
 /// 1 - Inner node with both children
 /// 2 - Inner node with left child and default right child
 /// 3 - Inner node with right child and default left child
-impl<H: Hasher> From<Node<H>> for Vec<u8> {
+/// 4 - Value node with an expiry, followed by the big endian expiry and the value
+/// 5 - Key-bound value node, followed by the big endian key length, the key and the encoding of
+///     the value node without the key
+/// 6 - Value node with a key preimage, followed by the big endian preimage length, the preimage
+///     and the encoding of the value node without the preimage
+impl<H: TreeHasher> From<Node<H>> for DBValue {
     fn from(node: Node<H>) -> Self {
         match node {
-            Node::Value { hash: _, value } => {
-                let mut bytes = vec![0];
+            Node::Value {
+                hash,
+                value,
+                expiry,
+                key,
+                preimage: Some(preimage),
+            } => {
+                let inner = DBValue::from(Node::<H>::Value {
+                    hash,
+                    value,
+                    expiry,
+                    key,
+                    preimage: None,
+                });
+                let mut bytes =
+                    DBValue::with_capacity(inner.len() + preimage.len() + KEY_LENGTH_SIZE + 1);
+                bytes.push(6);
+                bytes.extend_from_slice(&(preimage.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(&preimage);
+                bytes.extend_from_slice(&inner);
+                bytes
+            }
+            Node::Value {
+                hash,
+                value,
+                expiry,
+                key: Some(key),
+                ..
+            } => {
+                let inner = DBValue::from(Node::<H>::Value {
+                    hash,
+                    value,
+                    expiry,
+                    key: None,
+                    preimage: None,
+                });
+                let mut bytes =
+                    DBValue::with_capacity(inner.len() + key.len() + KEY_LENGTH_SIZE + 1);
+                bytes.push(5);
+                bytes.extend_from_slice(&(key.len() as u32).to_be_bytes());
+                bytes.extend_from_slice(&key);
+                bytes.extend_from_slice(&inner);
+                bytes
+            }
+            Node::Value {
+                hash: _,
+                value,
+                expiry: None,
+                key: None,
+                ..
+            } => {
+                let mut bytes = DBValue::with_capacity(value.len() + 1);
+                bytes.push(0);
+                bytes.extend_from_slice(&value);
+                bytes
+            }
+            Node::Value {
+                hash: _,
+                value,
+                expiry: Some(expiry),
+                key: None,
+                ..
+            } => {
+                let mut bytes = DBValue::with_capacity(value.len() + EXPIRY_SIZE + 1);
+                bytes.push(4);
+                bytes.extend_from_slice(&expiry.to_be_bytes());
                 bytes.extend_from_slice(&value);
                 bytes
             }
@@ -308,7 +548,7 @@ impl<H: Hasher> From<Node<H>> for Vec<u8> {
                 left,
                 right,
             } => {
-                let mut bytes = vec![];
+                let mut bytes = DBValue::with_capacity(2 * H::LENGTH + 1);
                 match (&left, &right) {
                     // if the left child is default value then push 2
                     (_, NodeHash::Default(_)) => {
@@ -336,10 +576,21 @@ impl<H: Hasher> From<Node<H>> for Vec<u8> {
 /// 1 - Inner node with both children
 /// 2 - Inner node with left child and default right child
 /// 3 - Inner node with right child and default left child
-impl<H: Hasher> TryFrom<Vec<u8>> for Node<H> {
+/// 4 - Value node with an expiry
+/// 5 - Key-bound value node
+/// 6 - Value node with a key preimage
+impl<H: TreeHasher> TryFrom<DBValue> for Node<H> {
     type Error = NodeError;
 
-    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+    fn try_from(value: DBValue) -> Result<Self, Self::Error> {
+        Node::decode(value, &[])
+    }
+}
+
+impl<H: TreeHasher> Node<H> {
+    /// Deserialize a node from a vector of bytes using the provided salt to compute the leaf hash
+    /// of value nodes. See the TryFrom implementation for the encoding.
+    pub fn decode(value: DBValue, salt: &[u8]) -> Result<Self, NodeError> {
         match value.first() {
             // Construct Value node
             Some(0) => {
@@ -347,7 +598,47 @@ impl<H: Hasher> TryFrom<Vec<u8>> for Node<H> {
                     return Err(NodeError::DecodeNodeEmptyValue);
                 }
 
-                Ok(Node::new_value(&value[1..]))
+                Ok(Node::new_salted_value(salt, &value[1..]))
+            }
+            // Construct Value node with an expiry
+            Some(4) => {
+                let (expiry, value) = decode_expiring_value(&value)?;
+                Ok(Node::new_expiring_value(salt, value, expiry))
+            }
+            // Construct key-bound Value node
+            Some(5) => {
+                let (key, inner) = decode_bound_value(&value)?;
+                match inner.first() {
+                    Some(0) if inner.len() > 1 => {
+                        Ok(Node::new_bound_value(key, salt, &inner[1..], None))
+                    }
+                    Some(0) => Err(NodeError::DecodeNodeEmptyValue),
+                    Some(4) => {
+                        let (expiry, value) = decode_expiring_value(inner)?;
+                        Ok(Node::new_bound_value(key, salt, value, Some(expiry)))
+                    }
+                    Some(prefix) => Err(NodeError::DecodeNodeInvalidPrefix(*prefix)),
+                    None => Err(NodeError::DecodeNodeNoData),
+                }
+            }
+            // Construct Value node with a key preimage
+            Some(6) => {
+                let (preimage, inner) = decode_bound_value(&value)?;
+                match Node::<H>::decode(DBValue::from(inner), salt)? {
+                    Node::Value {
+                        value,
+                        expiry: None,
+                        key,
+                        preimage: None,
+                        ..
+                    } => Ok(Node::new_preimage_value(
+                        key.as_deref(),
+                        salt,
+                        preimage,
+                        &value,
+                    )),
+                    _ => Err(NodeError::DecodeNodeInvalidPrefix(6)),
+                }
             }
             // Construct Inner node when both children are not default
             Some(inner_node_type) => {
@@ -356,8 +647,8 @@ impl<H: Hasher> TryFrom<Vec<u8>> for Node<H> {
                 let actual_length = value.len();
                 if actual_length != expected_length {
                     return Err(NodeError::DecodeNodeInvalidLength(
-                        value.len(),
-                        2 * H::LENGTH + 1,
+                        expected_length,
+                        actual_length,
                     ));
                 }
 
@@ -388,15 +679,117 @@ impl<H: Hasher> TryFrom<Vec<u8>> for Node<H> {
 // HELPERS
 // ================================================================================================
 
+/// The number of bytes used to encode the expiry of a value node
+const EXPIRY_SIZE: usize = core::mem::size_of::<u64>();
+
+/// Returns the leaf preimage of an expiring value, the concatenation of the value and the big
+/// endian expiry. This is the value that is provided to verify an inclusion proof of an expiring
+/// leaf.
+pub fn expiring_value_preimage(value: &[u8], expiry: u64) -> DBValue {
+    let mut preimage = DBValue::with_capacity(value.len() + EXPIRY_SIZE);
+    preimage.extend_from_slice(value);
+    preimage.extend_from_slice(&expiry.to_be_bytes());
+    preimage
+}
+
+/// Decode the expiry and value of an encoded expiring value node.
+pub fn decode_expiring_value(data: &[u8]) -> Result<(u64, &[u8]), NodeError> {
+    if data.len() < EXPIRY_SIZE + 1 {
+        return Err(NodeError::DecodeNodeInvalidLength(
+            EXPIRY_SIZE + 1,
+            data.len(),
+        ));
+    }
+    if data.len() == EXPIRY_SIZE + 1 {
+        return Err(NodeError::DecodeNodeEmptyValue);
+    }
+    let mut expiry = [0u8; EXPIRY_SIZE];
+    expiry.copy_from_slice(&data[1..EXPIRY_SIZE + 1]);
+    Ok((u64::from_be_bytes(expiry), &data[EXPIRY_SIZE + 1..]))
+}
+
+/// The number of bytes used to encode the key length of a key-bound value node
+const KEY_LENGTH_SIZE: usize = core::mem::size_of::<u32>();
+
+/// Decode the key and the encoding of the value node without the key of an encoded key-bound
+/// value node. Also used to decode the preimage of an encoded value node with a key preimage.
+pub fn decode_bound_value(data: &[u8]) -> Result<(&[u8], &[u8]), NodeError> {
+    if data.len() < KEY_LENGTH_SIZE + 1 {
+        return Err(NodeError::DecodeNodeInvalidLength(
+            KEY_LENGTH_SIZE + 1,
+            data.len(),
+        ));
+    }
+    let mut key_length = [0u8; KEY_LENGTH_SIZE];
+    key_length.copy_from_slice(&data[1..KEY_LENGTH_SIZE + 1]);
+    let key_end = (u32::from_be_bytes(key_length) as usize)
+        .checked_add(KEY_LENGTH_SIZE + 1)
+        .ok_or(NodeError::DecodeNodeInvalidLength(usize::MAX, data.len()))?;
+    if data.len() < key_end {
+        return Err(NodeError::DecodeNodeInvalidLength(key_end, data.len()));
+    }
+    Ok((&data[KEY_LENGTH_SIZE + 1..key_end], &data[key_end..]))
+}
+
+/// Returns the leaf preimage of a value with a stored key preimage, the concatenation of the big
+/// endian preimage length, the preimage and the value. This is the value that is provided to
+/// verify an inclusion proof of a leaf that stores its key preimage.
+pub fn key_preimage_value(preimage: &[u8], value: &[u8]) -> DBValue {
+    let mut bytes = DBValue::with_capacity(KEY_LENGTH_SIZE + preimage.len() + value.len());
+    bytes.extend_from_slice(&(preimage.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(preimage);
+    bytes.extend_from_slice(value);
+    bytes
+}
+
+/// Returns the leaf hash of the concatenation of the key, the salt and the value.
+pub fn bound_leaf_hash<H: TreeHasher>(key: &[u8], salt: &[u8], value: &[u8]) -> H::Out {
+    H::hash_leaf(&[key, salt, value].concat())
+}
+
+/// Returns the hash of the null leaf. With the `key-bound` feature the null leaf is shared by all
+/// keys and is not salted, such that an exclusion proof is verified by providing an empty value,
+/// also for salted trees: providing the salt as the value does not verify, as a salted null leaf
+/// would be indistinguishable from a leaf that is not bound to its key. Otherwise the null leaf
+/// is the salted leaf hash of an empty value.
+pub fn null_leaf_hash<H: TreeHasher>(salt: &[u8]) -> H::Out {
+    if cfg!(feature = "key-bound") {
+        H::hash_leaf(&[])
+    } else {
+        salted_leaf_hash::<H>(salt, &[])
+    }
+}
+
+/// Returns the leaf hash that a proof of the provided value at the provided key is verified
+/// against. With the `key-bound` feature a non-empty value is bound to the key, an empty value
+/// is the null leaf.
+pub fn proof_leaf_hash<H: TreeHasher>(key: &[u8], value: &[u8]) -> H::Out {
+    if cfg!(feature = "key-bound") && !value.is_empty() {
+        bound_leaf_hash::<H>(key, &[], value)
+    } else {
+        H::hash_leaf(value)
+    }
+}
+
+/// Returns the leaf hash of the concatenation of the salt and the value.
+pub fn salted_leaf_hash<H: TreeHasher>(salt: &[u8], value: &[u8]) -> H::Out {
+    if salt.is_empty() {
+        H::hash_leaf(value)
+    } else {
+        H::hash_leaf(&[salt, value].concat())
+    }
+}
+
 /// Decode a hash from a byte vector. The byte vector must be exactly H::LENGTH bytes long.
 ///
 /// Errors:
-/// - DecodeHashFailed: if the byte vector is not exactly H::LENGTH bytes long
-fn decode_hash<H: Hasher>(data: &[u8]) -> Result<H::Out, NodeError> {
-    if data.len() != H::LENGTH {
+/// - DecodeHashFailed: if the byte vector is not exactly H::LENGTH bytes long or H::LENGTH does
+///   not match the length of H::Out
+pub fn decode_hash<H: TreeHasher>(data: &[u8]) -> Result<H::Out, NodeError> {
+    let mut hash = H::Out::default();
+    if data.len() != H::LENGTH || hash.as_ref().len() != H::LENGTH {
         return Err(NodeError::DecodeNodeHashFailed(data.to_vec()));
     }
-    let mut hash = H::Out::default();
     hash.as_mut().copy_from_slice(data);
     Ok(hash)
 }
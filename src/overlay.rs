@@ -0,0 +1,103 @@
+use super::{rstd::vec::Vec, DBValue, HashDB, HashDBRef, HashMap, Hasher, TreeHasher};
+use hash_db::{AsHashDB, Prefix};
+
+/// A batch of buffered writes of form: (hash, data, reference count delta)
+pub type OverlayBatch<H> = Vec<(<H as Hasher>::Out, DBValue, i32)>;
+
+// OverlayDB
+// ================================================================================================
+
+/// A database which buffers writes in memory on top of a read-only base, such that a TreeDBMut can
+/// operate against a read-only snapshot. Writes are buffered as reference count deltas, as such
+/// the commits of several trees can be staged in one overlay and extracted with consolidate to be
+/// written to the backend atomically. Reads return the buffered data of a record with a positive
+/// delta and fall back to the base otherwise.
+pub struct OverlayDB<'a, H: TreeHasher> {
+    base: &'a (dyn HashDBRef<H, DBValue> + Sync),
+    overlay: HashMap<H::Out, (DBValue, i32)>,
+}
+
+impl<'a, H: TreeHasher> OverlayDB<'a, H> {
+    /// Construct a new OverlayDB on top of the provided base
+    pub fn new(base: &'a (dyn HashDBRef<H, DBValue> + Sync)) -> Self {
+        Self {
+            base,
+            overlay: HashMap::default(),
+        }
+    }
+
+    /// Returns true if no writes are buffered
+    pub fn is_empty(&self) -> bool {
+        self.overlay.values().all(|(_, rc)| *rc == 0)
+    }
+
+    /// Extracts the buffered writes, ordered by hash and leaving the overlay empty. Each record
+    /// with a positive delta is emplaced into the backend delta times and each record with a
+    /// negative delta is removed from the backend -delta times, records whose writes cancel out
+    /// are omitted.
+    pub fn consolidate(&mut self) -> OverlayBatch<H> {
+        let mut batch: OverlayBatch<H> = self
+            .overlay
+            .drain()
+            .filter(|(_, (_, rc))| *rc != 0)
+            .map(|(hash, (data, rc))| (hash, data, rc))
+            .collect();
+        batch.sort_by(|(a, _, _), (b, _, _)| a.as_ref().cmp(b.as_ref()));
+        batch
+    }
+}
+
+impl<'a, H: TreeHasher> HashDB<H, DBValue> for OverlayDB<'a, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        match self.overlay.get(key) {
+            Some((data, rc)) if *rc > 0 => Some(data.clone()),
+            _ => self.base.get(key, prefix),
+        }
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        match self.overlay.get(key) {
+            Some((_, rc)) if *rc > 0 => true,
+            _ => self.base.contains(key, prefix),
+        }
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        let key = H::hash(value);
+        self.emplace(key, prefix, DBValue::from(value));
+        key
+    }
+
+    fn emplace(&mut self, key: H::Out, _prefix: Prefix, value: DBValue) {
+        let (data, rc) = self.overlay.entry(key).or_default();
+        if *rc <= 0 {
+            *data = value;
+        }
+        *rc += 1;
+    }
+
+    fn remove(&mut self, key: &H::Out, _prefix: Prefix) {
+        let (_, rc) = self.overlay.entry(*key).or_default();
+        *rc -= 1;
+    }
+}
+
+impl<'a, H: TreeHasher> HashDBRef<H, DBValue> for OverlayDB<'a, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(self, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(self, key, prefix)
+    }
+}
+
+impl<'a, H: TreeHasher> AsHashDB<H, DBValue> for OverlayDB<'a, H> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'b>(&'b mut self) -> &'b mut (dyn HashDB<H, DBValue> + 'b) {
+        self
+    }
+}
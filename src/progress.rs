@@ -0,0 +1,132 @@
+use super::{rstd::sync::Arc, TreeError};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// CONSTANTS
+// ================================================================================================
+
+/// The default number of processed nodes between progress reports
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+// Progress
+// ================================================================================================
+
+/// Receives periodic progress reports from long-running operations such as audits, repairs,
+/// sweeps, exports and migrations.
+pub trait Progress {
+    /// Called with the total number of nodes processed so far. Reports are made every interval
+    /// nodes, see Monitor::with_interval, and once more when the operation completes.
+    fn report(&mut self, processed: usize);
+}
+
+/// Implementation of Progress for closures.
+impl<F: FnMut(usize)> Progress for F {
+    fn report(&mut self, processed: usize) {
+        self(processed)
+    }
+}
+
+// CancellationToken
+// ================================================================================================
+
+/// A token used to cooperatively cancel long-running operations. Clones of a token share the
+/// cancellation state, such that a token can be cancelled from another thread, e.g. on shutdown.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the operations observing this token or any of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Monitor
+// ================================================================================================
+
+/// Observes a long-running operation, reporting progress to an optional Progress and checking an
+/// optional CancellationToken before each node is processed. An operation that observes a
+/// cancelled token stops and returns a Cancelled error with the number of nodes processed.
+pub struct Monitor<'a> {
+    progress: Option<&'a mut dyn Progress>,
+    cancellation: Option<&'a CancellationToken>,
+    interval: usize,
+    processed: usize,
+}
+
+/// Implement default for Monitor.
+impl<'a> Default for Monitor<'a> {
+    fn default() -> Self {
+        Self {
+            progress: None,
+            cancellation: None,
+            interval: PROGRESS_INTERVAL,
+            processed: 0,
+        }
+    }
+}
+
+impl<'a> Monitor<'a> {
+    /// Creates a new monitor without progress reporting or cancellation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reports progress of the operation to the provided progress.
+    pub fn with_progress(mut self, progress: &'a mut dyn Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Cancels the operation once the provided token is cancelled.
+    pub fn with_cancellation(mut self, cancellation: &'a CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sets the number of processed nodes between progress reports, at least one.
+    pub fn with_interval(mut self, interval: usize) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// Returns the number of nodes processed so far.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+
+    /// Called before a node is processed. Reports progress every interval nodes.
+    ///
+    /// Errors:
+    /// - Cancelled: if the cancellation token has been cancelled
+    pub(crate) fn tick(&mut self) -> Result<(), TreeError> {
+        if matches!(self.cancellation, Some(token) if token.is_cancelled()) {
+            return Err(TreeError::Cancelled(self.processed));
+        }
+        self.processed += 1;
+        if self.processed.is_multiple_of(self.interval) {
+            if let Some(progress) = self.progress.as_mut() {
+                progress.report(self.processed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Called once the operation completes, reports the final number of processed nodes.
+    pub(crate) fn finish(&mut self) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress.report(self.processed);
+        }
+    }
+}
@@ -1,22 +1,46 @@
 use super::{
-    rstd::{iter::IntoIterator, vec::Vec},
-    HashSet, Hasher,
+    envelope::Reader,
+    node::{
+        decode_expiring_value, decode_hash, expiring_value_preimage, proof_leaf_hash,
+        salted_leaf_hash,
+    },
+    repair::root_stack,
+    rstd::{iter::IntoIterator, vec, vec::Vec},
+    tree::null_hashes,
+    verify_at_depth, ChildSelector, DBValue, Hasher, Key, KeyError, Node, NodeError, NodeHash,
+    ProofError, TreeError, TreeHasher,
 };
 use core::marker::PhantomData;
 use hash_db::{AsHashDB, Prefix, EMPTY_PREFIX};
+use hashbrown::{HashMap, HashSet};
 use memory_db::{KeyFunction, MemoryDB};
 
+// CONSTANTS
+// ================================================================================================
+
+/// The format version of encoded storage proofs
+pub const STORAGE_PROOF_VERSION: u8 = 1;
+
+/// The format version of encoded compact storage proofs
+pub const COMPACT_STORAGE_PROOF_VERSION: u8 = 1;
+
 // StorageProof
 // ================================================================================================
 
 /// A proof that some set of key-value pairs are included in a sparse merkle tree.
+///
+/// The canonical encoding of a storage proof is as follows, all integers are big endian:
+/// - version (1 byte)
+/// - number of nodes (4 bytes)
+/// - for each node in ascending lexicographic order, the node length (4 bytes) and node
 pub struct StorageProof {
-    nodes: HashSet<Vec<u8>>,
+    // storage proofs are untrusted input and as such use the randomized default hasher
+    nodes: HashSet<DBValue>,
 }
 
 impl StorageProof {
     /// Creates a new storage proof from the provided set of nodes.
-    pub fn new(nodes: impl IntoIterator<Item = Vec<u8>>) -> Self {
+    pub fn new(nodes: impl IntoIterator<Item = DBValue>) -> Self {
         Self {
             nodes: HashSet::from_iter(nodes),
         }
@@ -34,15 +58,1404 @@ impl StorageProof {
         self.nodes.is_empty()
     }
 
+    /// Returns the number of nodes in this proof.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns an iterator over the encoded nodes of this proof in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &DBValue> {
+        self.nodes.iter()
+    }
+
+    /// Returns whether this proof contains the node with the provided hash, nodes are hashed as
+    /// nodes of an unsalted tree. The nodes are hashed on every call.
+    pub fn contains_node<H: TreeHasher>(&self, hash: &H::Out) -> bool {
+        self.nodes
+            .iter()
+            .any(|node| encoded_node_hash::<H>(node, &[]).as_ref() == Some(hash))
+    }
+
+    /// Returns the number of bytes of the encoding of this proof, see encode.
+    pub fn encoded_size(&self) -> usize {
+        5 + self.nodes.iter().map(|node| node.len() + 4).sum::<usize>()
+    }
+
     /// Consumes the storage proof and returns the set of nodes.
-    pub fn into_nodes(self) -> HashSet<Vec<u8>> {
+    pub fn into_nodes(self) -> HashSet<DBValue> {
         self.nodes
     }
 
+    /// Merges the provided proofs into a single proof holding each of their nodes once, e.g. the
+    /// proofs recorded from several trees sharing a database or several reads of one tree.
+    pub fn merge(proofs: impl IntoIterator<Item = StorageProof>) -> Self {
+        let mut nodes = HashSet::new();
+        for proof in proofs {
+            nodes.extend(proof.nodes);
+        }
+        Self { nodes }
+    }
+
+    /// Returns a proof holding the nodes of either this proof or the other proof.
+    pub fn union(&self, other: &StorageProof) -> Self {
+        Self::new(self.nodes.union(&other.nodes).cloned())
+    }
+
+    /// Returns a proof holding the nodes of this proof that are not in the other proof, e.g. the
+    /// nodes that remain to be sent to a client that holds the other proof.
+    pub fn difference(&self, other: &StorageProof) -> Self {
+        Self::new(self.nodes.difference(&other.nodes).cloned())
+    }
+
+    /// Returns a proof holding the nodes of both this proof and the other proof.
+    pub fn intersection(&self, other: &StorageProof) -> Self {
+        Self::new(self.nodes.intersection(&other.nodes).cloned())
+    }
+
+    /// Encodes the proof, see the struct documentation for the encoding. As the nodes are
+    /// sorted the encoding of a set of nodes is deterministic.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut nodes: Vec<&DBValue> = self.nodes.iter().collect();
+        nodes.sort();
+        let mut bytes = Vec::with_capacity(self.encoded_size());
+        bytes.push(STORAGE_PROOF_VERSION);
+        bytes.extend_from_slice(&(nodes.len() as u32).to_be_bytes());
+        for node in nodes {
+            bytes.extend_from_slice(&(node.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(node);
+        }
+        bytes
+    }
+
+    /// Decodes a proof produced by encode. Only the canonical encoding is accepted, i.e. the
+    /// nodes must be sorted and unique.
+    ///
+    /// Errors:
+    /// - UnsupportedVersion: if the proof was encoded with an unknown format version
+    /// - UnorderedNodes: if a node is not strictly greater than the preceding node
+    /// - UnexpectedEnd: if the proof is truncated
+    /// - TrailingBytes: if the proof is followed by unexpected bytes
+    pub fn decode(data: &[u8]) -> Result<Self, TreeError> {
+        let mut reader = Reader::new(data);
+        let version = reader.read(1)?[0];
+        if version != STORAGE_PROOF_VERSION {
+            return Err(TreeError::ProofError(ProofError::UnsupportedVersion(
+                version,
+            )));
+        }
+        let count = reader.read_u32()? as usize;
+        // every node is prefixed by its length, which bounds the number of nodes
+        let mut nodes = HashSet::with_capacity(count.min(reader.remaining() / 4));
+        let mut previous: Option<&[u8]> = None;
+        for index in 0..count {
+            let length = reader.read_u32()? as usize;
+            let node = reader.read(length)?;
+            if matches!(previous, Some(previous) if previous >= node) {
+                return Err(TreeError::ProofError(ProofError::UnorderedNodes(index)));
+            }
+            nodes.insert(DBValue::from(node));
+            previous = Some(node);
+        }
+        reader.finish()?;
+        Ok(Self { nodes })
+    }
+
+    /// Checks that the proof covers the paths of the provided keys in the tree of depth D * 8 with
+    /// the provided root, reporting exactly which node hashes are missing from the proof. See
+    /// verify_against_root for errors.
+    pub fn covers<H: TreeHasher, const D: usize>(
+        &self,
+        root: &H::Out,
+        keys: &[&[u8]],
+    ) -> Result<(), TreeError> {
+        self.verify_against_root::<H, D>(root, keys)
+    }
+
+    /// Verifies that the proof is sufficient to resolve the provided keys in the tree of depth
+    /// D * 8 with the provided root, without building a database. Every node of the proof must
+    /// decode and the path of every key must be resolved by the nodes of the proof, down to the
+    /// leaf or the first default node.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if a key is not D bytes long
+    /// - InvalidNode: if a node of the proof can not be decoded
+    /// - MissingNodes: if nodes required to resolve the keys are missing from the proof, the
+    ///   error lists the hashes of all missing nodes
+    pub fn verify_against_root<H: TreeHasher, const D: usize>(
+        &self,
+        root: &H::Out,
+        keys: &[&[u8]],
+    ) -> Result<(), TreeError> {
+        let mut nodes = HashMap::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            let hash = encoded_node_hash::<H>(node, &[]).ok_or(TreeError::ProofError(
+                ProofError::InvalidNode(node.to_vec()),
+            ))?;
+            nodes.insert(hash, node);
+        }
+
+        let mut missing = Vec::new();
+        for key in keys.iter() {
+            let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+            // the path of an empty tree is resolved without any nodes
+            let mut current_hash = match root_stack::<D, H>(root, &[]).pop() {
+                Some(root) => NodeHash::<H>::Database(root),
+                None => continue,
+            };
+            let mut bits = key.iter();
+            // descend until the leaf or the first default node is reached
+            while !current_hash.is_default() {
+                let data = match nodes.get(current_hash.hash()) {
+                    Some(data) => data,
+                    None => {
+                        missing.push(current_hash.hash().as_ref().to_vec());
+                        break;
+                    }
+                };
+                let node = Node::<H>::decode((*data).clone(), &[])
+                    .map_err(|_| TreeError::ProofError(ProofError::InvalidNode(data.to_vec())))?;
+                let bit = match bits.next() {
+                    Some(bit) => bit,
+                    None => break,
+                };
+                current_hash = node
+                    .child_hash(&ChildSelector::new(bit))
+                    .map_err(TreeError::NodeError)?
+                    .clone();
+            }
+        }
+
+        missing.sort();
+        missing.dedup();
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(TreeError::ProofError(ProofError::MissingNodes(missing))),
+        }
+    }
+
     /// Consumes the storage proof and returns a memory db containing the nodes.
-    pub fn into_memory_db<H: Hasher>(self) -> MemoryDB<H, NoopKey<H>, Vec<u8>> {
+    pub fn into_memory_db<H: TreeHasher>(self) -> MemoryDB<H, NoopKey<H>, DBValue> {
         self.into()
     }
+
+    /// Consumes the storage proof and returns a memory db containing the nodes of a tree that
+    /// uses the provided salt for leaf hashing.
+    pub fn into_salted_memory_db<H: TreeHasher>(
+        self,
+        salt: &[u8],
+    ) -> MemoryDB<H, NoopKey<H>, DBValue> {
+        self.into_keyed_memory_db::<H, NoopKey<H>>(salt, EMPTY_PREFIX)
+    }
+
+    /// Consumes the storage proof and returns a memory db containing the nodes of a tree that
+    /// uses the provided salt for leaf hashing. The nodes are keyed using the provided key
+    /// function and inserted under the provided prefix, such that the resulting database is
+    /// compatible with backends that use prefixed or hashed keys.
+    pub fn into_keyed_memory_db<H, KF>(
+        self,
+        salt: &[u8],
+        prefix: Prefix,
+    ) -> MemoryDB<H, KF, DBValue>
+    where
+        H: TreeHasher,
+        KF: KeyFunction<H> + Send + Sync,
+    {
+        let mut db = MemoryDB::<H, KF, DBValue>::default();
+        for node in self.into_nodes().into_iter() {
+            if let Some(hash) = encoded_node_hash::<H>(&node, salt) {
+                db.as_hash_db_mut().emplace(hash, prefix, node);
+            }
+        }
+        db
+    }
+
+    /// Consumes the storage proof and returns a memory db containing the nodes of the tree with
+    /// the provided root that uses the provided salt for leaf hashing. Unlike into_memory_db every
+    /// node must decode and be reachable from the root, nodes below the paths of the proof may be
+    /// missing. Empty value nodes are the default leaves and are skipped.
+    ///
+    /// Errors:
+    /// - InvalidNode: if a node of the proof can not be decoded or a reachable node marks a child
+    ///   as default whose hash is not the null hash of its height
+    /// - MissingNodes: if the root is not a default root and its node is missing from the proof
+    /// - UnreachableNode: if a node is not reachable from the root, the error holds the hash of
+    ///   the first such node in the order of the encoded proof
+    pub fn into_checked_memory_db<H: TreeHasher, const D: usize>(
+        self,
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<MemoryDB<H, NoopKey<H>, DBValue>, TreeError> {
+        let mut nodes = HashMap::with_capacity(self.nodes.len());
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut data = self.into_nodes().into_iter().collect::<Vec<_>>();
+        data.sort();
+        for data in data.into_iter() {
+            if data.as_slice() == [0] {
+                continue;
+            }
+            let node = Node::<H>::decode(data.clone(), salt)
+                .map_err(|_| TreeError::ProofError(ProofError::InvalidNode(data.to_vec())))?;
+            order.push(*node.hash());
+            nodes.insert(*node.hash(), (node, data));
+        }
+
+        // walk the nodes reachable from the root alongside their height, the root of an empty
+        // tree has no node
+        let null_hashes = null_hashes::<H>(Key::<D>::BITS, salt);
+        let mut stack = root_stack::<D, H>(root, salt)
+            .into_iter()
+            .map(|hash| (hash, Key::<D>::BITS))
+            .collect::<Vec<_>>();
+        if let Some((root, _)) = stack.first() {
+            if !nodes.contains_key(root) {
+                return Err(TreeError::ProofError(ProofError::MissingNodes(Vec::from(
+                    [root.as_ref().to_vec()],
+                ))));
+            }
+        }
+        let mut reachable = HashSet::with_capacity(nodes.len());
+        while let Some((hash, height)) = stack.pop() {
+            let Some((node, data)) = nodes.get(&hash) else {
+                continue;
+            };
+            if !reachable.insert(hash) {
+                continue;
+            }
+            if let Node::Inner { left, right, .. } = node {
+                let invalid = || TreeError::ProofError(ProofError::InvalidNode(data.to_vec()));
+                let height = height.checked_sub(1).ok_or_else(invalid)?;
+                for child in [right, left] {
+                    match child {
+                        NodeHash::Database(hash) => stack.push((*hash, height)),
+                        // the prefix of a node is not hashed, a default child must be an empty
+                        // subtree
+                        NodeHash::Default(hash) if *hash != null_hashes[height] => {
+                            return Err(invalid())
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        if let Some(hash) = order.iter().find(|hash| !reachable.contains(*hash)) {
+            return Err(TreeError::ProofError(ProofError::UnreachableNode(
+                hash.as_ref().to_vec(),
+            )));
+        }
+
+        let mut db = MemoryDB::<H, NoopKey<H>, DBValue>::default();
+        for (hash, (_, data)) in nodes.into_iter() {
+            db.as_hash_db_mut().emplace(hash, EMPTY_PREFIX, data);
+        }
+        Ok(db)
+    }
+}
+
+/// Verifies the provided (key, value) pairs against a storage proof recorded for their keys, such
+/// as the proof drained from a Recorder that read the keys, in the tree of depth D * 8 with the
+/// provided root. The nodes of the proof are decoded once and shared by the paths of all keys.
+/// Returns whether each value is the value at its key, an empty value claims non-inclusion.
+///
+/// Errors:
+/// - IncorrectKeySize: if a key is not D bytes long
+/// - InvalidNode: if a node of the proof can not be decoded or marks a child as default whose
+///   hash is not the null hash of its height
+/// - MissingNodes: if nodes required to resolve the keys are missing from the proof, the error
+///   lists the hashes of all missing nodes
+pub fn verify_multi<H: TreeHasher, const D: usize>(
+    items: &[(&[u8], &[u8])],
+    proof: &StorageProof,
+    root: &H::Out,
+) -> Result<Vec<bool>, TreeError> {
+    verify_salted_multi::<H, D>(items, proof, root, &[])
+}
+
+/// Verifies the provided (key, value) pairs against a storage proof of a tree with the provided
+/// salt, see verify_multi. The values are the leaf preimages as for verify.
+pub fn verify_salted_multi<H: TreeHasher, const D: usize>(
+    items: &[(&[u8], &[u8])],
+    proof: &StorageProof,
+    root: &H::Out,
+    salt: &[u8],
+) -> Result<Vec<bool>, TreeError> {
+    let mut nodes = HashMap::with_capacity(proof.nodes.len());
+    for data in proof.nodes.iter() {
+        // empty value nodes are the default leaves, which are never looked up
+        if data.as_slice() == [0] {
+            continue;
+        }
+        let node = Node::<H>::decode(data.clone(), salt)
+            .map_err(|_| TreeError::ProofError(ProofError::InvalidNode(data.to_vec())))?;
+        nodes.insert(*node.hash(), (node, data));
+    }
+
+    let null_hashes = null_hashes::<H>(Key::<D>::BITS, salt);
+    let mut results = Vec::with_capacity(items.len());
+    let mut missing = Vec::new();
+    'items: for (key, value) in items.iter() {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        // the path of an empty tree is resolved without any nodes
+        let mut current_hash = match root_stack::<D, H>(root, salt).pop() {
+            Some(root) => NodeHash::<H>::Database(root),
+            None => {
+                results.push(value.is_empty());
+                continue;
+            }
+        };
+        // descend until the leaf or the first default node is reached
+        for (depth, bit) in key.iter().enumerate() {
+            if current_hash.is_default() {
+                break;
+            }
+            let (node, data) = match nodes.get(current_hash.hash()) {
+                Some(node) => node,
+                None => {
+                    missing.push(current_hash.hash().as_ref().to_vec());
+                    continue 'items;
+                }
+            };
+            current_hash = node
+                .child_hash(&ChildSelector::new(bit))
+                .map_err(TreeError::NodeError)?
+                .clone();
+            // the prefix of a node is not hashed, a default child must be an empty subtree
+            if current_hash.is_default()
+                && *current_hash.hash() != null_hashes[Key::<D>::BITS - depth - 1]
+            {
+                return Err(TreeError::ProofError(ProofError::InvalidNode(
+                    data.to_vec(),
+                )));
+            }
+        }
+        results.push(match current_hash.is_default() {
+            true => value.is_empty(),
+            false => *current_hash.hash() == proof_leaf_hash::<H>(key.as_slice(), value),
+        });
+    }
+
+    missing.sort();
+    missing.dedup();
+    match missing.is_empty() {
+        true => Ok(results),
+        false => Err(TreeError::ProofError(ProofError::MissingNodes(missing))),
+    }
+}
+
+// MerkleProof
+// ================================================================================================
+
+/// An inclusion proof of a leaf at a key of a tree of depth D * 8. The proof carries the key and
+/// leaf hash it proves alongside exactly D * 8 sibling hashes ordered from the leaf to the root,
+/// such that it can not be verified against a different key or depth. A proof of non-inclusion
+/// is a proof of the null leaf.
+pub struct MerkleProof<H: TreeHasher, const D: usize> {
+    key: Key<D>,
+    leaf: H::Out,
+    siblings: Vec<DBValue>,
+}
+
+impl<H: TreeHasher, const D: usize> MerkleProof<H, D> {
+    /// Creates a new proof of the provided leaf hash at the provided key.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    /// - DepthMismatch: if the number of siblings is not D * 8
+    /// - DecodeNodeHashFailed: if a sibling is not H::LENGTH bytes long
+    pub fn new(key: &[u8], leaf: H::Out, siblings: Vec<DBValue>) -> Result<Self, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        if siblings.len() != Key::<D>::BITS {
+            return Err(TreeError::ProofError(ProofError::DepthMismatch(
+                Key::<D>::BITS,
+                siblings.len(),
+            )));
+        }
+        if let Some(sibling) = siblings.iter().find(|sibling| sibling.len() != H::LENGTH) {
+            return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                sibling.to_vec(),
+            )));
+        }
+        Ok(Self {
+            key,
+            leaf,
+            siblings,
+        })
+    }
+
+    /// Creates a new proof of the provided value at the provided key, an empty value is a proof
+    /// of non-inclusion. See new for errors.
+    pub fn from_value(key: &[u8], value: &[u8], siblings: Vec<DBValue>) -> Result<Self, TreeError> {
+        Self::new(key, proof_leaf_hash::<H>(key, value), siblings)
+    }
+
+    /// Returns the key being proven
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Returns the leaf hash being proven
+    pub fn leaf(&self) -> &H::Out {
+        &self.leaf
+    }
+
+    /// Returns the sibling hashes, ordered from the leaf to the root
+    pub fn siblings(&self) -> &[DBValue] {
+        &self.siblings
+    }
+
+    /// Returns the depth of the tree the proof belongs to
+    pub fn depth(&self) -> usize {
+        Key::<D>::BITS
+    }
+
+    /// Consumes the proof and returns the sibling hashes
+    pub fn into_siblings(self) -> Vec<DBValue> {
+        self.siblings
+    }
+
+    /// Returns the root computed from the leaf hash and the siblings.
+    pub fn compute_root(&self) -> H::Out {
+        let mut hash = self.leaf;
+        // iterate over the bits in the key in reverse order
+        for (bit, sibling) in self.key.iter().rev().zip(self.siblings.iter()) {
+            match ChildSelector::new(bit) {
+                ChildSelector::Left => hash = H::hash_inner(hash.as_ref(), sibling),
+                ChildSelector::Right => hash = H::hash_inner(sibling, hash.as_ref()),
+            }
+        }
+        hash
+    }
+
+    /// Verifies that the leaf is at the key in the tree with the provided root.
+    pub fn verify(&self, root: &H::Out) -> bool {
+        self.compute_root() == *root
+    }
+
+    /// Verifies that the provided value is at the key in the tree with the provided root, an
+    /// empty value verifies non-inclusion.
+    pub fn verify_value(&self, value: &[u8], root: &H::Out) -> bool {
+        self.leaf == proof_leaf_hash::<H>(self.key.as_slice(), value) && self.verify(root)
+    }
+}
+
+/// Implement clone for MerkleProof
+impl<H: TreeHasher, const D: usize> Clone for MerkleProof<H, D> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key,
+            leaf: self.leaf,
+            siblings: self.siblings.clone(),
+        }
+    }
+}
+
+// UpdateProof
+// ================================================================================================
+
+/// A proof that writing the new value at a key of a tree of depth D * 8 transforms the tree with
+/// the old root into the tree with the new root. The siblings along the path of the key are not
+/// changed by the write, such that the same siblings prove the old value under the old root and
+/// the new value under the new root. An absent value is the null leaf.
+pub struct UpdateProof<H: TreeHasher, const D: usize> {
+    key: Key<D>,
+    old_value: Option<DBValue>,
+    new_value: Option<DBValue>,
+    old_root: H::Out,
+    new_root: H::Out,
+    siblings: Vec<DBValue>,
+}
+
+impl<H: TreeHasher, const D: usize> UpdateProof<H, D> {
+    /// Creates a new proof of the transition of the value at the provided key from the old value
+    /// under the old root to the new value under the new root. Empty values are absent values.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn new(
+        key: &[u8],
+        (old_value, old_root): (Option<DBValue>, H::Out),
+        (new_value, new_root): (Option<DBValue>, H::Out),
+        siblings: Vec<DBValue>,
+    ) -> Result<Self, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        Ok(Self {
+            key,
+            old_value: old_value.filter(|value| !value.is_empty()),
+            new_value: new_value.filter(|value| !value.is_empty()),
+            old_root,
+            new_root,
+            siblings,
+        })
+    }
+
+    /// Returns the key being written
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Returns the value before the write, None if the key was absent
+    pub fn old_value(&self) -> Option<&DBValue> {
+        self.old_value.as_ref()
+    }
+
+    /// Returns the value after the write, None if the key was removed
+    pub fn new_value(&self) -> Option<&DBValue> {
+        self.new_value.as_ref()
+    }
+
+    /// Returns the root before the write
+    pub fn old_root(&self) -> &H::Out {
+        &self.old_root
+    }
+
+    /// Returns the root after the write
+    pub fn new_root(&self) -> &H::Out {
+        &self.new_root
+    }
+
+    /// Returns the sibling hashes along the path of the key, ordered from the leaf to the root
+    pub fn siblings(&self) -> &[DBValue] {
+        &self.siblings
+    }
+
+    /// Verifies the transition, see verify_update.
+    pub fn verify(&self) -> Result<bool, TreeError> {
+        verify_update(self)
+    }
+}
+
+/// Verifies that writing the new value of the proof at its key transforms the tree with the old
+/// root of the proof into the tree with the new root, without access to the database.
+///
+/// Errors:
+/// - DepthMismatch: if the number of siblings is not D * 8
+/// - DecodeNodeHashFailed: if a sibling is not H::LENGTH bytes long
+pub fn verify_update<H: TreeHasher, const D: usize>(
+    proof: &UpdateProof<H, D>,
+) -> Result<bool, TreeError> {
+    let key = proof.key.as_slice();
+    let old_value = proof.old_value.as_deref().unwrap_or_default();
+    let old = MerkleProof::<H, D>::from_value(key, old_value, proof.siblings.clone())?;
+    if !old.verify(&proof.old_root) {
+        return Ok(false);
+    }
+    let new_value = proof.new_value.as_deref().unwrap_or_default();
+    let new = MerkleProof::<H, D>::from_value(key, new_value, old.into_siblings())?;
+    Ok(new.verify(&proof.new_root))
+}
+
+/// Applies the write of the new value at the provided key to the tree with the provided root,
+/// using only the inclusion proof of the old value at the key, an empty old value being a proof
+/// of non-inclusion. Returns the root of the tree after the write, an empty new value removes
+/// the value at the key. As the write does not change the siblings along the path of the key,
+/// the new root is computed from the same proof.
+///
+/// Errors:
+/// - IncorrectKeySize: if the key is not D bytes long
+/// - DepthMismatch: if the number of siblings is not D * 8
+/// - RootMismatch: if the proof of the old value does not verify against the root
+pub fn insert_from_proof<H: TreeHasher, const D: usize>(
+    root: &H::Out,
+    key: &[u8],
+    old_value: &[u8],
+    proof: &[DBValue],
+    new_value: &[u8],
+) -> Result<H::Out, TreeError> {
+    let old = MerkleProof::<H, D>::from_value(key, old_value, proof.to_vec())?;
+    let old_root = old.compute_root();
+    if old_root != *root {
+        return Err(TreeError::ProofError(ProofError::RootMismatch(
+            root.as_ref().to_vec(),
+            old_root.as_ref().to_vec(),
+        )));
+    }
+    let new = MerkleProof::<H, D>::from_value(key, new_value, old.into_siblings())?;
+    Ok(new.compute_root())
+}
+
+// ConsistencyProof
+// ================================================================================================
+
+/// A proof that an append-only index tree of depth D * 8 with the new root extends the tree with
+/// the old root, i.e. the old tree holds only the leaves at indices below the old size and the
+/// new tree holds the same leaves at those indices. The proof is the inclusion proof of the leaf
+/// at index old size in the new tree. The left siblings along its path are the roots of the
+/// subtrees that partition the indices below the old size and are shared by both trees, the
+/// right siblings and the leaf are default hashes in the old tree.
+pub struct ConsistencyProof<H: TreeHasher, const D: usize> {
+    old_size: u64,
+    proof: MerkleProof<H, D>,
+}
+
+impl<H: TreeHasher, const D: usize> ConsistencyProof<H, D> {
+    /// Creates a new consistency proof from the inclusion proof of the leaf at index old size in
+    /// the new tree.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if the old size is out of bounds for the depth of the tree
+    /// - IncorrectKeySize: if the proof is not a proof of the leaf at index old size
+    pub fn new(old_size: u64, proof: MerkleProof<H, D>) -> Result<Self, TreeError> {
+        let key = Key::<D>::try_from(&old_size).map_err(TreeError::KeyError)?;
+        if key.as_slice() != proof.key() {
+            return Err(TreeError::KeyError(KeyError::IncorrectKeySize(
+                D,
+                proof.key().len(),
+            )));
+        }
+        Ok(Self { old_size, proof })
+    }
+
+    /// Returns the number of leaves of the old tree
+    pub fn old_size(&self) -> u64 {
+        self.old_size
+    }
+
+    /// Returns the inclusion proof of the leaf at index old size in the new tree
+    pub fn proof(&self) -> &MerkleProof<H, D> {
+        &self.proof
+    }
+
+    /// Returns the root of the old tree computed from the proof, i.e. the root of the tree with
+    /// the provided salt that holds the leaves of the new tree below the old size and no other
+    /// leaves.
+    pub fn compute_old_root(&self, salt: &[u8]) -> H::Out {
+        let null_hashes = null_hashes::<H>(Key::<D>::BITS, salt);
+        let mut hash = null_hashes[0];
+        let key = Key::<D>::try_from(&self.old_size).expect("old size is checked");
+        let path = key.iter().rev().zip(self.proof.siblings().iter());
+        for ((bit, sibling), null_hash) in path.zip(null_hashes.iter()) {
+            match ChildSelector::new(bit) {
+                ChildSelector::Left => hash = H::hash_inner(hash.as_ref(), null_hash.as_ref()),
+                ChildSelector::Right => hash = H::hash_inner(sibling, hash.as_ref()),
+            }
+        }
+        hash
+    }
+
+    /// Verifies that the tree with the new root extends the tree with the old root.
+    pub fn verify(&self, old_root: &H::Out, new_root: &H::Out) -> bool {
+        self.verify_salted(old_root, new_root, &[])
+    }
+
+    /// Verifies that the tree with the new root extends the tree with the old root, both trees
+    /// having the provided salt.
+    pub fn verify_salted(&self, old_root: &H::Out, new_root: &H::Out, salt: &[u8]) -> bool {
+        self.compute_old_root(salt) == *old_root && self.proof.verify(new_root)
+    }
+}
+
+// NeighborProof
+// ================================================================================================
+
+/// A proof of the value at an index of a tree of depth D * 8 together with the nearest occupied
+/// indices before and after it, as used by indexed merkle trees to prove non-membership. The
+/// proof holds the inclusion proofs of the index and of its neighbors. Along the path of a lower
+/// neighbor the right siblings below the bit at which it diverges from the next higher index are
+/// default hashes and vice versa, which proves that no index in between is occupied. A missing
+/// predecessor or successor proves that no index before or after the index is occupied.
+pub struct NeighborProof<H: TreeHasher, const D: usize> {
+    index: u64,
+    proof: MerkleProof<H, D>,
+    predecessor: Option<(u64, MerkleProof<H, D>)>,
+    successor: Option<(u64, MerkleProof<H, D>)>,
+}
+
+impl<H: TreeHasher, const D: usize> NeighborProof<H, D> {
+    /// Creates a new neighbor proof from the inclusion proofs of the index, its predecessor and
+    /// its successor.
+    ///
+    /// Errors:
+    /// - LeafIndexOutOfBounds: if an index is out of bounds for the depth of the tree
+    /// - IncorrectKeySize: if a proof is not a proof of the leaf at its index
+    pub fn new(
+        index: u64,
+        proof: MerkleProof<H, D>,
+        predecessor: Option<(u64, MerkleProof<H, D>)>,
+        successor: Option<(u64, MerkleProof<H, D>)>,
+    ) -> Result<Self, TreeError> {
+        for (index, proof) in [(index, &proof)]
+            .into_iter()
+            .chain(predecessor.iter().map(|(index, proof)| (*index, proof)))
+            .chain(successor.iter().map(|(index, proof)| (*index, proof)))
+        {
+            let key = Key::<D>::try_from(&index).map_err(TreeError::KeyError)?;
+            if key.as_slice() != proof.key() {
+                return Err(TreeError::KeyError(KeyError::IncorrectKeySize(
+                    D,
+                    proof.key().len(),
+                )));
+            }
+        }
+        Ok(Self {
+            index,
+            proof,
+            predecessor,
+            successor,
+        })
+    }
+
+    /// Returns the index being proven
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the inclusion proof of the leaf at the index, the proof of the null leaf if the
+    /// index is not occupied
+    pub fn proof(&self) -> &MerkleProof<H, D> {
+        &self.proof
+    }
+
+    /// Returns the nearest occupied index before the index and its inclusion proof
+    pub fn predecessor(&self) -> Option<(u64, &MerkleProof<H, D>)> {
+        self.predecessor
+            .as_ref()
+            .map(|(index, proof)| (*index, proof))
+    }
+
+    /// Returns the nearest occupied index after the index and its inclusion proof
+    pub fn successor(&self) -> Option<(u64, &MerkleProof<H, D>)> {
+        self.successor
+            .as_ref()
+            .map(|(index, proof)| (*index, proof))
+    }
+
+    /// Verifies that the proven leaf is at the index in the tree with the provided root and that
+    /// the predecessor and successor are the nearest occupied indices before and after it.
+    pub fn verify(&self, root: &H::Out) -> bool {
+        self.verify_salted(root, &[])
+    }
+
+    /// Verifies the neighbor proof against the root of a tree with the provided salt, whose empty
+    /// leaves and subtrees hash to the salted null hashes.
+    pub fn verify_salted(&self, root: &H::Out, salt: &[u8]) -> bool {
+        if !self.proof.verify(root) {
+            return false;
+        }
+        let null_hashes = null_hashes::<H>(Key::<D>::BITS, salt);
+        for (index, proof) in self.predecessor.iter().chain(self.successor.iter()) {
+            if proof.leaf == null_hashes[0] || !proof.verify(root) {
+                return false;
+            }
+            if index == &self.index {
+                return false;
+            }
+        }
+        if matches!(self.predecessor, Some((index, _)) if index > self.index)
+            || matches!(self.successor, Some((index, _)) if index < self.index)
+        {
+            return false;
+        }
+
+        let predecessor = self.predecessor.as_ref().map(|(_, proof)| proof);
+        let successor = self.successor.as_ref().map(|(_, proof)| proof);
+        empty_between(predecessor, Some(&self.proof), &null_hashes)
+            && empty_between(Some(&self.proof), successor, &null_hashes)
+    }
+}
+
+// DivergenceProof
+// ================================================================================================
+
+/// A proof that two trees of depth D * 8 hold different values at a key and identical values at
+/// every key before it, as used by fraud proofs. The proof holds the value of each tree at the
+/// key and its compact inclusion proof. The left siblings along the path of the key are equal in
+/// both proofs, which proves that no earlier key differs.
+pub struct DivergenceProof<H: TreeHasher, const D: usize> {
+    key: Key<D>,
+    first: (DBValue, CompactProof),
+    second: (DBValue, CompactProof),
+    _marker: PhantomData<H>,
+}
+
+impl<H: TreeHasher, const D: usize> DivergenceProof<H, D> {
+    /// Creates a new divergence proof from the values of both trees at the provided key and their
+    /// compact inclusion proofs, an empty value is the null leaf.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn new(
+        key: &[u8],
+        first: (DBValue, CompactProof),
+        second: (DBValue, CompactProof),
+    ) -> Result<Self, TreeError> {
+        Ok(Self {
+            key: Key::<D>::new(key).map_err(TreeError::KeyError)?,
+            first,
+            second,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the first key at which the trees differ
+    pub fn key(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    /// Returns the value of the first tree at the key and its compact inclusion proof
+    pub fn first(&self) -> (&[u8], &CompactProof) {
+        (&self.first.0, &self.first.1)
+    }
+
+    /// Returns the value of the second tree at the key and its compact inclusion proof
+    pub fn second(&self) -> (&[u8], &CompactProof) {
+        (&self.second.0, &self.second.1)
+    }
+
+    /// Verifies that the trees with the provided roots hold the proven values at the key, that
+    /// the values differ and that the trees hold identical values at every key before it.
+    ///
+    /// Errors:
+    /// - InvalidBitmask: if the bitmask of a compact proof is invalid
+    /// - SiblingCountMismatch: if a compact proof has the wrong number of siblings
+    pub fn verify(&self, first_root: &H::Out, second_root: &H::Out) -> Result<bool, TreeError> {
+        self.verify_salted(first_root, second_root, &[])
+    }
+
+    /// Verifies the proof of two trees with the provided salt, whose compact proofs omit the
+    /// salted null hashes, see verify.
+    pub fn verify_salted(
+        &self,
+        first_root: &H::Out,
+        second_root: &H::Out,
+        salt: &[u8],
+    ) -> Result<bool, TreeError> {
+        if self.first.0 == self.second.0 {
+            return Ok(false);
+        }
+        let mut siblings = Vec::with_capacity(2);
+        for ((value, proof), root) in [(&self.first, first_root), (&self.second, second_root)] {
+            let proof = proof.decompress::<H>(Key::<D>::BITS, salt)?;
+            let proof = MerkleProof::<H, D>::from_value(self.key.as_slice(), value, proof)?;
+            if !proof.verify(root) {
+                return Ok(false);
+            }
+            siblings.push(proof.into_siblings());
+        }
+        // the left siblings are the roots of the subtrees holding the keys before the key
+        Ok(self
+            .key
+            .iter()
+            .rev()
+            .enumerate()
+            .all(|(height, bit)| !bit || siblings[0][height] == siblings[1][height]))
+    }
+}
+
+// ChainedProof
+// ================================================================================================
+
+/// An inclusion proof through nested trees, where the leaf of a parent tree of depth P * 8 stores
+/// the root of a child tree of depth C * 8. The proof holds the inclusion proof of the child root
+/// at a key of the parent tree and the inclusion proof of a leaf at a key of the child tree, and
+/// is verified against the root of the parent tree.
+pub struct ChainedProof<H: TreeHasher, const P: usize, const C: usize> {
+    parent: MerkleProof<H, P>,
+    child: MerkleProof<H, C>,
+}
+
+impl<H: TreeHasher, const P: usize, const C: usize> ChainedProof<H, P, C> {
+    /// Creates a new chained proof from the proof of the child root in the parent tree and the
+    /// proof of the leaf in the child tree.
+    ///
+    /// Errors:
+    /// - RootMismatch: if the parent leaf is not the leaf of the root computed from the child
+    ///   proof
+    pub fn new(parent: MerkleProof<H, P>, child: MerkleProof<H, C>) -> Result<Self, TreeError> {
+        let child_leaf = proof_leaf_hash::<H>(parent.key(), child.compute_root().as_ref());
+        if child_leaf != *parent.leaf() {
+            return Err(TreeError::ProofError(ProofError::RootMismatch(
+                parent.leaf().as_ref().to_vec(),
+                child_leaf.as_ref().to_vec(),
+            )));
+        }
+        Ok(Self { parent, child })
+    }
+
+    /// Returns the inclusion proof of the child root in the parent tree
+    pub fn parent(&self) -> &MerkleProof<H, P> {
+        &self.parent
+    }
+
+    /// Returns the inclusion proof of the leaf in the child tree
+    pub fn child(&self) -> &MerkleProof<H, C> {
+        &self.child
+    }
+
+    /// Returns the root of the child tree computed from the child proof
+    pub fn child_root(&self) -> H::Out {
+        self.child.compute_root()
+    }
+
+    /// Verifies that the child leaf is in the child tree whose root is at the parent key in the
+    /// parent tree with the provided root.
+    pub fn verify(&self, root: &H::Out) -> bool {
+        self.parent.verify(root)
+    }
+
+    /// Verifies that the provided value is at the child key in the child tree whose root is at
+    /// the parent key in the parent tree with the provided root, an empty value verifies
+    /// non-inclusion in the child tree.
+    pub fn verify_value(&self, value: &[u8], root: &H::Out) -> bool {
+        self.child.verify_value(value, &self.child_root()) && self.verify(root)
+    }
+}
+
+// LevelCache
+// ================================================================================================
+
+/// The nodes at a level of a tree of depth D * 8 cached by a verifier, such that inclusion proofs
+/// truncated at that level are verified against the cached node along their key instead of the
+/// root. A proof truncated at depth K holds Key::<D>::BITS - K siblings regardless of the depth of
+/// the tree. The cache is checked against the root once when it is created.
+pub struct LevelCache<H: TreeHasher, const D: usize> {
+    depth: usize,
+    nodes: Vec<H::Out>,
+}
+
+impl<H: TreeHasher, const D: usize> LevelCache<H, D> {
+    /// Creates a new cache of the provided 2^depth node hashes at the provided depth, ordered by
+    /// key, of the tree with the provided root.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    /// - NodeCountMismatch: if the number of nodes is not 2^depth
+    /// - RootMismatch: if the nodes do not hash to the root
+    pub fn new(depth: usize, nodes: Vec<H::Out>, root: &H::Out) -> Result<Self, TreeError> {
+        if depth > Key::<D>::BITS || depth >= usize::BITS as usize {
+            return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+                depth,
+                Key::<D>::BITS.min(usize::BITS as usize - 1),
+            )));
+        }
+        if nodes.len() != 1 << depth {
+            return Err(TreeError::ProofError(ProofError::NodeCountMismatch(
+                1 << depth,
+                nodes.len(),
+            )));
+        }
+        let mut level = nodes.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| H::hash_inner(pair[0].as_ref(), pair[1].as_ref()))
+                .collect();
+        }
+        if level[0] != *root {
+            return Err(TreeError::ProofError(ProofError::RootMismatch(
+                root.as_ref().to_vec(),
+                level[0].as_ref().to_vec(),
+            )));
+        }
+        Ok(Self { depth, nodes })
+    }
+
+    /// Returns the depth of the cached level
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the cached node hashes, ordered by key
+    pub fn nodes(&self) -> &[H::Out] {
+        &self.nodes
+    }
+
+    /// Returns the cached node along the provided key.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn node(&self, key: &[u8]) -> Result<&H::Out, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let index = key
+            .iter()
+            .take(self.depth)
+            .fold(0, |index, bit| index << 1 | bit as usize);
+        Ok(&self.nodes[index])
+    }
+
+    /// Verifies an inclusion proof of a value at the provided key truncated at the depth of the
+    /// cache against the cached node along the key, see verify_at_depth.
+    pub fn verify(&self, key: &[u8], value: &[u8], proof: &[DBValue]) -> Result<bool, TreeError> {
+        verify_at_depth::<H, D>(key, value, proof, self.depth, self.node(key)?)
+    }
+}
+
+// CircuitWitness
+// ================================================================================================
+
+/// The witness of an inclusion proof laid out as consumed by a SNARK circuit. The path holds
+/// exactly D * 8 (direction, sibling) pairs ordered from the leaf to the root, where the direction
+/// is true if the node on the path is the right child of its parent. Default siblings are filled
+/// in. The leaf preimage is the input of H::hash_leaf, for a key that is not in the tree it is the
+/// preimage of the null leaf.
+pub struct CircuitWitness<H: TreeHasher, const D: usize> {
+    leaf_preimage: DBValue,
+    leaf: H::Out,
+    path: Vec<(bool, H::Out)>,
+    root: H::Out,
+}
+
+impl<H: TreeHasher, const D: usize> CircuitWitness<H, D> {
+    /// Creates a new witness of the provided leaf at the provided key from the leaf preimage and
+    /// the sibling hashes ordered from the leaf to the root.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not D * 8
+    /// - DecodeNodeHashFailed: if a sibling is not H::LENGTH bytes long
+    pub(crate) fn new(
+        key: &Key<D>,
+        leaf_preimage: DBValue,
+        leaf: H::Out,
+        siblings: &[DBValue],
+        root: H::Out,
+    ) -> Result<Self, TreeError> {
+        if siblings.len() != Key::<D>::BITS {
+            return Err(TreeError::ProofError(ProofError::DepthMismatch(
+                Key::<D>::BITS,
+                siblings.len(),
+            )));
+        }
+        let mut path = Vec::with_capacity(Key::<D>::BITS);
+        for (bit, sibling) in key.iter().rev().zip(siblings.iter()) {
+            path.push((
+                bit,
+                decode_hash::<H>(sibling).map_err(TreeError::NodeError)?,
+            ));
+        }
+        Ok(Self {
+            leaf_preimage,
+            leaf,
+            path,
+            root,
+        })
+    }
+
+    /// Returns the preimage of the leaf hash
+    pub fn leaf_preimage(&self) -> &[u8] {
+        &self.leaf_preimage
+    }
+
+    /// Returns the leaf hash
+    pub fn leaf(&self) -> &H::Out {
+        &self.leaf
+    }
+
+    /// Returns the (direction, sibling) pairs ordered from the leaf to the root
+    pub fn path(&self) -> &[(bool, H::Out)] {
+        &self.path
+    }
+
+    /// Returns the root of the tree
+    pub fn root(&self) -> &H::Out {
+        &self.root
+    }
+
+    /// Returns the root computed from the leaf preimage and the path, as computed by a circuit.
+    pub fn compute_root(&self) -> H::Out {
+        let mut hash = H::hash_leaf(&self.leaf_preimage);
+        for (direction, sibling) in self.path.iter() {
+            hash = match direction {
+                true => H::hash_inner(sibling.as_ref(), hash.as_ref()),
+                false => H::hash_inner(hash.as_ref(), sibling.as_ref()),
+            };
+        }
+        hash
+    }
+}
+
+// CompactProof
+// ================================================================================================
+
+/// An inclusion proof that omits the siblings that are default hashes, i.e. roots of empty
+/// subtrees, which the verifier computes itself. Bit i of the bitmask, most significant bit
+/// first, is set if the sibling at height i is included. Siblings are ordered from the leaf to
+/// the root of the tree, as in a full proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactProof {
+    bitmask: Vec<u8>,
+    siblings: Vec<DBValue>,
+}
+
+impl CompactProof {
+    /// Creates a new compact proof from the provided bitmask and included siblings.
+    pub fn new(bitmask: Vec<u8>, siblings: Vec<DBValue>) -> Self {
+        Self { bitmask, siblings }
+    }
+
+    /// Compresses the provided full proof of a tree with the provided salt, ordered from the leaf
+    /// to the root, by omitting the siblings that are default hashes.
+    pub fn compress<H: TreeHasher>(proof: &[DBValue], salt: &[u8]) -> Self {
+        let mut bitmask = vec![0u8; proof.len().div_ceil(8)];
+        let mut siblings = Vec::new();
+        let null_hashes = null_hashes::<H>(proof.len(), salt);
+        for (height, sibling) in proof.iter().enumerate() {
+            if sibling.as_slice() != null_hashes[height].as_ref() {
+                bitmask[height / 8] |= 1 << (7 - height % 8);
+                siblings.push(sibling.clone());
+            }
+        }
+        Self { bitmask, siblings }
+    }
+
+    /// Decompresses the proof into a full proof of the provided depth of a tree with the provided
+    /// salt, ordered from the leaf to the root.
+    ///
+    /// Errors:
+    /// - InvalidBitmask: if the bitmask is not depth bits long or has bits set beyond depth
+    /// - SiblingCountMismatch: if the number of siblings differs from the number of set bits
+    pub fn decompress<H: TreeHasher>(
+        &self,
+        depth: usize,
+        salt: &[u8],
+    ) -> Result<Vec<DBValue>, TreeError> {
+        let included = |height: usize| self.bitmask[height / 8] & (1 << (7 - height % 8)) != 0;
+        if self.bitmask.len() != depth.div_ceil(8) || (depth..self.bitmask.len() * 8).any(included)
+        {
+            return Err(TreeError::ProofError(ProofError::InvalidBitmask(
+                self.bitmask.clone(),
+            )));
+        }
+        let expected = (0..depth).filter(|height| included(*height)).count();
+        if expected != self.siblings.len() {
+            return Err(TreeError::ProofError(ProofError::SiblingCountMismatch(
+                expected,
+                self.siblings.len(),
+            )));
+        }
+
+        let mut proof = Vec::with_capacity(depth);
+        let mut siblings = self.siblings.iter();
+        let null_hashes = null_hashes::<H>(depth, salt);
+        for (height, null_hash) in null_hashes[..depth].iter().enumerate() {
+            match included(height) {
+                true => proof.extend(siblings.next().cloned()),
+                false => proof.push(DBValue::from(null_hash.as_ref())),
+            }
+        }
+        Ok(proof)
+    }
+
+    /// Returns the bitmask of included siblings
+    pub fn bitmask(&self) -> &[u8] {
+        &self.bitmask
+    }
+
+    /// Returns the included siblings, ordered from the leaf to the root
+    pub fn siblings(&self) -> &[DBValue] {
+        &self.siblings
+    }
+}
+
+// CompactStorageProof
+// ================================================================================================
+
+/// A storage proof encoded for transmission. Empty value nodes are stripped and the nodes are
+/// encoded as a traversal of the subtrees they form, such that a child hash that is the hash of
+/// a node of the proof is replaced by the node itself and the shared prefixes of the paths are
+/// encoded once. The hashes of the nested nodes are recomputed when decoding, nodes whose hash
+/// can not be recomputed without the salt of the tree are encoded alongside their hash.
+///
+/// The proof is encoded as follows, all integers are big endian:
+/// - version (1 byte)
+/// - number of subtrees (4 bytes), followed by the root node of each subtree in ascending order
+///   of hash
+///
+/// A node is encoded as either:
+/// - 0 (1 byte), the node length (4 bytes) and the encoded node, for value nodes
+/// - the prefix of the inner node (1 byte) followed by its left and right child
+///
+/// A child is encoded as either 0 (1 byte) followed by the child hash or 1 (1 byte) followed by
+/// the child node.
+pub struct CompactStorageProof {
+    data: Vec<u8>,
+}
+
+impl CompactStorageProof {
+    /// Compacts the provided storage proof.
+    ///
+    /// Errors:
+    /// - InvalidNode: if a node of the proof can not be decoded
+    pub fn from_storage_proof<H: TreeHasher>(proof: &StorageProof) -> Result<Self, TreeError> {
+        let mut nodes = HashMap::with_capacity(proof.nodes.len());
+        let mut children = HashSet::new();
+        for node in proof.nodes.iter() {
+            // empty value nodes are the default leaves, which are never looked up
+            if node.as_slice() == [0] {
+                continue;
+            }
+            let hash = encoded_node_hash::<H>(node, &[]).ok_or(TreeError::ProofError(
+                ProofError::InvalidNode(node.to_vec()),
+            ))?;
+            if is_inner_node::<H>(node) {
+                children.insert(&node[1..1 + H::LENGTH]);
+                children.insert(&node[1 + H::LENGTH..]);
+            }
+            nodes.insert(hash, node);
+        }
+
+        let mut roots: Vec<&H::Out> = nodes
+            .keys()
+            .filter(|hash| !children.contains(hash.as_ref()))
+            .collect();
+        roots.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+        let mut data = Vec::new();
+        data.push(COMPACT_STORAGE_PROOF_VERSION);
+        data.extend_from_slice(&(roots.len() as u32).to_be_bytes());
+        let mut emitted = HashSet::with_capacity(nodes.len());
+        // the traversal is iterative as the depth of the subtrees is controlled by the proof
+        let mut stack: Vec<CompactItem<'_>> = roots
+            .into_iter()
+            .rev()
+            .map(|root| CompactItem::Node(root.as_ref()))
+            .collect();
+        while let Some(item) = stack.pop() {
+            let hash = match item {
+                CompactItem::Node(hash) => hash,
+                CompactItem::Child(hash) => {
+                    let nested = !emitted.contains(hash)
+                        && decode_hash::<H>(hash)
+                            .map(|hash| nodes.contains_key(&hash))
+                            .unwrap_or(false);
+                    if !nested {
+                        data.push(0);
+                        data.extend_from_slice(hash);
+                        continue;
+                    }
+                    data.push(1);
+                    hash
+                }
+            };
+            emitted.insert(hash);
+            let node = decode_hash::<H>(hash)
+                .ok()
+                .and_then(|hash| nodes.get(&hash))
+                .expect("nodes are only emitted if they are in the proof");
+            if is_inner_node::<H>(node) {
+                data.push(node[0]);
+                stack.push(CompactItem::Child(&node[1 + H::LENGTH..]));
+                stack.push(CompactItem::Child(&node[1..1 + H::LENGTH]));
+            } else {
+                data.push(0);
+                data.extend_from_slice(&(node.len() as u32).to_be_bytes());
+                data.extend_from_slice(node);
+            }
+        }
+        Ok(Self { data })
+    }
+
+    /// Expands the compact proof into the storage proof it was created from, less the empty
+    /// value nodes.
+    ///
+    /// Errors:
+    /// - UnsupportedVersion: if the proof was encoded with an unknown format version
+    /// - InvalidNode: if a node of the proof can not be decoded
+    /// - UnexpectedEnd: if the proof is truncated
+    /// - TrailingBytes: if the proof is followed by unexpected bytes
+    pub fn to_storage_proof<H: TreeHasher>(&self) -> Result<StorageProof, TreeError> {
+        let mut reader = Reader::new(&self.data);
+        let version = reader.read(1)?[0];
+        if version != COMPACT_STORAGE_PROOF_VERSION {
+            return Err(TreeError::ProofError(ProofError::UnsupportedVersion(
+                version,
+            )));
+        }
+        let count = reader.read_u32()?;
+        let mut nodes = HashSet::new();
+        for _ in 0..count {
+            // the inner nodes along the current path, of form: (prefix, left child hash)
+            let mut stack: Vec<(u8, Option<H::Out>)> = Vec::new();
+            loop {
+                // read a node, inner nodes are completed once both children are read
+                let mut complete = match reader.read(1)?[0] {
+                    0 => {
+                        let length = reader.read_u32()? as usize;
+                        let node = DBValue::from(reader.read(length)?);
+                        let hash = encoded_node_hash::<H>(&node, &[]).ok_or(
+                            TreeError::ProofError(ProofError::InvalidNode(node.to_vec())),
+                        )?;
+                        nodes.insert(node);
+                        Some(hash)
+                    }
+                    prefix @ 1..=3 => {
+                        stack.push((prefix, None));
+                        None
+                    }
+                    prefix => {
+                        return Err(TreeError::ProofError(ProofError::InvalidNode(
+                            [prefix].to_vec(),
+                        )))
+                    }
+                };
+                loop {
+                    if let Some(hash) = complete.take() {
+                        match stack.last_mut() {
+                            None => break,
+                            Some((_, left @ None)) => *left = Some(hash),
+                            Some((prefix, Some(left))) => {
+                                let mut node = DBValue::with_capacity(2 * H::LENGTH + 1);
+                                node.push(*prefix);
+                                node.extend_from_slice(left.as_ref());
+                                node.extend_from_slice(hash.as_ref());
+                                nodes.insert(node);
+                                complete = Some(H::hash_inner(left.as_ref(), hash.as_ref()));
+                                stack.pop();
+                                continue;
+                            }
+                        }
+                    }
+                    // read the next child of the inner node at the top of the stack
+                    match reader.read(1)?[0] {
+                        0 => {
+                            let hash = decode_hash::<H>(reader.read(H::LENGTH)?)
+                                .map_err(TreeError::NodeError)?;
+                            complete = Some(hash);
+                        }
+                        1 => break,
+                        flag => {
+                            return Err(TreeError::ProofError(ProofError::InvalidNode(
+                                [flag].to_vec(),
+                            )))
+                        }
+                    }
+                }
+                if stack.is_empty() {
+                    break;
+                }
+            }
+        }
+        reader.finish()?;
+        Ok(StorageProof { nodes })
+    }
+
+    /// Returns the number of bytes of the encoded proof.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns whether the encoded proof is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the encoded proof, see the struct documentation for the encoding.
+    pub fn encode(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Decodes a proof produced by encode, see to_storage_proof for errors.
+    pub fn decode<H: TreeHasher>(data: &[u8]) -> Result<Self, TreeError> {
+        let proof = Self {
+            data: data.to_vec(),
+        };
+        proof.to_storage_proof::<H>()?;
+        Ok(proof)
+    }
+}
+
+/// An item of the traversal that encodes a compact storage proof, the hash of either a node or a
+/// child of an inner node.
+enum CompactItem<'a> {
+    Node(&'a [u8]),
+    Child(&'a [u8]),
 }
 
 // MemoryDB
@@ -59,13 +1472,76 @@ impl<H: Hasher> KeyFunction<H> for NoopKey<H> {
 }
 
 /// Implement from StorageProof for MemoryDB
-impl<H: Hasher> From<StorageProof> for MemoryDB<H, NoopKey<H>, Vec<u8>> {
+impl<H: TreeHasher> From<StorageProof> for MemoryDB<H, NoopKey<H>, DBValue> {
     fn from(proof: StorageProof) -> Self {
-        let mut db = MemoryDB::<H, NoopKey<H>, Vec<u8>>::default();
-        for node in proof.into_nodes().into_iter() {
-            db.as_hash_db_mut()
-                .emplace(H::hash(&node[1..]), EMPTY_PREFIX, node);
+        proof.into_salted_memory_db(&[])
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Returns whether the sibling hashes of the provided proofs show that no leaf between their keys
+/// is occupied, a missing lower or upper proof stands for the start or end of the tree. Below the
+/// bit at which the keys diverge the right siblings of the lower key and the left siblings of the
+/// upper key must be the provided null hashes, indexed by height.
+fn empty_between<H: TreeHasher, const D: usize>(
+    lower: Option<&MerkleProof<H, D>>,
+    upper: Option<&MerkleProof<H, D>>,
+    null_hashes: &[H::Out],
+) -> bool {
+    let start = match (lower, upper) {
+        (Some(lower), Some(upper)) => {
+            match lower
+                .key
+                .iter()
+                .zip(upper.key.iter())
+                .position(|(a, b)| a != b)
+            {
+                Some(depth) => depth + 1,
+                None => return false,
+            }
         }
-        db
+        _ => 0,
+    };
+    // the sibling at depth d + 1 is the root of a subtree of height Key::<D>::BITS - d - 1
+    let is_empty = |proof: &MerkleProof<H, D>, right: bool| {
+        (start..Key::<D>::BITS).all(|depth| {
+            let height = Key::<D>::BITS - depth - 1;
+            proof.key.bit(depth).expect("depth is checked") == right
+                || proof.siblings[height][..] == *null_hashes[height].as_ref()
+        })
+    };
+    lower.is_none_or(|lower| is_empty(lower, true))
+        && upper.is_none_or(|upper| is_empty(upper, false))
+}
+
+/// Returns whether the provided encoded node is an inner node.
+fn is_inner_node<H: TreeHasher>(node: &[u8]) -> bool {
+    matches!(node.first(), Some(1..=3)) && node.len() == 2 * H::LENGTH + 1
+}
+
+/// Returns the hash of an encoded node. The first byte of an encoded node is the node type prefix
+/// and is not included in the preimage of the node hash. Value nodes are hashed as leaves and
+/// inner nodes are hashed as the concatenation of their child hashes. Returns None if the node
+/// is empty or malformed.
+pub(crate) fn encoded_node_hash<H: TreeHasher>(node: &[u8], salt: &[u8]) -> Option<H::Out> {
+    match node.first()? {
+        0 => Some(salted_leaf_hash::<H>(salt, &node[1..])),
+        4 => {
+            let (expiry, value) = decode_expiring_value(node).ok()?;
+            Some(salted_leaf_hash::<H>(
+                salt,
+                &expiring_value_preimage(value, expiry),
+            ))
+        }
+        5 | 6 => Node::<H>::decode(DBValue::from(node), salt)
+            .ok()
+            .map(|node| *node.hash()),
+        _ if node.len() == 2 * H::LENGTH + 1 => Some(H::hash_inner(
+            &node[1..1 + H::LENGTH],
+            &node[1 + H::LENGTH..],
+        )),
+        _ => None,
     }
 }
@@ -1,31 +1,46 @@
-use super::{HashMap, Hasher, Node, StorageProof, TreeRecorder};
+use super::{
+    repair::{push_children, root_stack},
+    rstd::vec::Vec,
+    DBValue, DataError, FxBuildHasher, HashDBRef, HashMap, HashSet, Node, StorageProof, TreeError,
+    TreeHasher, TreeRecorder,
+};
+use core::hash::BuildHasher;
+use hash_db::EMPTY_PREFIX;
 
 // Recorder
 // ================================================================================================
 
-/// Recorder to record database reads.
-pub struct Recorder<H: Hasher> {
-    nodes: HashMap<H::Out, Node<H>>,
+/// Recorder to record database reads. The recorded nodes are kept in a map hashed with S, which
+/// defaults to FxBuildHasher.
+pub struct Recorder<H: TreeHasher, S = FxBuildHasher> {
+    nodes: HashMap<H::Out, Node<H>, S>,
 }
 
 /// Implement default for Recorder.
-impl<H: Hasher> Default for Recorder<H> {
+impl<H: TreeHasher, S: BuildHasher + Default> Default for Recorder<H, S> {
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(S::default())
     }
 }
 
-/// Implementation of Recorder.
-impl<H: Hasher> Recorder<H> {
+impl<H: TreeHasher> Recorder<H> {
     /// Creates a new empty recorder.
     pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Implementation of Recorder.
+impl<H: TreeHasher, S: BuildHasher> Recorder<H, S> {
+    /// Creates a new empty recorder that hashes the recorded nodes with the provided hasher.
+    pub fn with_hasher(hasher: S) -> Self {
         Self {
-            nodes: HashMap::default(),
+            nodes: HashMap::with_hasher(hasher),
         }
     }
 
     /// Drain the recorder and return the recorded nodes.
-    pub fn drain(&mut self) -> hashbrown::hash_map::Drain<H::Out, Node<H>> {
+    pub fn drain(&mut self) -> hashbrown::hash_map::Drain<'_, H::Out, Node<H>> {
         self.nodes.drain()
     }
 
@@ -38,10 +53,81 @@ impl<H: Hasher> Recorder<H> {
     pub fn to_storage_proof(&self) -> StorageProof {
         StorageProof::new(self.nodes.values().cloned().map(|node| node.into()))
     }
+
+    /// Returns statistics of the recorded nodes.
+    pub fn stats(&self) -> RecorderStats {
+        let mut stats = RecorderStats::default();
+        for node in self.nodes.values() {
+            stats.nodes += 1;
+            if matches!(node, Node::Value { .. }) {
+                stats.leaves += 1;
+            }
+            stats.encoded_size += DBValue::from(node.clone()).len();
+        }
+        stats
+    }
+
+    /// Returns statistics of the recorded nodes including the number of nodes in the tree with
+    /// the provided root, which is used to compute the fraction of the tree that was recorded.
+    /// The tree is traversed in full to count its nodes.
+    ///
+    /// Errors:
+    /// - DatabaseDataNotFound: if a node reachable from the root is missing from the database
+    pub fn coverage<const D: usize>(
+        &self,
+        root: &H::Out,
+        db: &dyn HashDBRef<H, DBValue>,
+        salt: &[u8],
+    ) -> Result<RecorderStats, TreeError> {
+        let mut visited = HashSet::<H::Out>::default();
+        let mut stack: Vec<H::Out> = root_stack::<D, H>(root, salt);
+        while let Some(hash) = stack.pop() {
+            if !visited.insert(hash) {
+                continue;
+            }
+            let data = db.get(&hash, EMPTY_PREFIX).ok_or(TreeError::DataError(
+                DataError::DatabaseDataNotFound(hash.as_ref().to_vec()),
+            ))?;
+            let node = Node::<H>::decode(data, salt).map_err(TreeError::NodeError)?;
+            push_children(&node, &mut stack);
+        }
+
+        Ok(RecorderStats {
+            tree_nodes: Some(visited.len()),
+            ..self.stats()
+        })
+    }
+}
+
+// RecorderStats
+// ================================================================================================
+
+/// Statistics of the nodes recorded by a Recorder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecorderStats {
+    /// The number of distinct nodes recorded
+    pub nodes: usize,
+    /// The number of distinct leaves recorded
+    pub leaves: usize,
+    /// The total size of the encoded nodes, i.e. the size of the resulting storage proof
+    pub encoded_size: usize,
+    /// The number of nodes in the tree, only available if computed via Recorder::coverage
+    pub tree_nodes: Option<usize>,
+}
+
+impl RecorderStats {
+    /// Returns the fraction of the nodes of the tree that were recorded, None if the number of
+    /// nodes in the tree is unknown or the tree is empty.
+    pub fn coverage(&self) -> Option<f64> {
+        match self.tree_nodes {
+            Some(0) | None => None,
+            Some(tree_nodes) => Some(self.nodes as f64 / tree_nodes as f64),
+        }
+    }
 }
 
 /// Implementation of TreeRecorder for Recorder.
-impl<H: Hasher> TreeRecorder<H> for Recorder<H> {
+impl<H: TreeHasher, S: BuildHasher> TreeRecorder<H> for Recorder<H, S> {
     fn record(&mut self, node: &Node<H>) {
         self.nodes.insert(*node.hash(), node.clone());
     }
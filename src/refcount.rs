@@ -0,0 +1,125 @@
+use super::{DBValue, HashDB, HashDBRef, TreeHasher};
+use hash_db::{AsHashDB, Prefix};
+
+/// The number of bytes used to encode the reference count of a record
+const REFCOUNT_SIZE: usize = core::mem::size_of::<u32>();
+
+// RefCountedDB
+// ================================================================================================
+
+/// A database wrapper that stores every record exactly once in the underlying database together
+/// with a reference count. Each `emplace` of an existing record increments its count and each
+/// `remove` decrements it, the record is only removed from the underlying database once the count
+/// reaches zero.
+///
+/// Identical leaf values share a node hash, as such a single record may be referenced by many
+/// leaves across many trees and versions. Backends that overwrite on insert and delete on remove
+/// (e.g. a plain key-value store) can not express this, removing one of many leaves sharing a value
+/// would delete the shared record. Wrapping such a backend in a RefCountedDB makes the reference
+/// counting explicit and persistent.
+///
+/// Records are stored in the underlying database as the node encoding followed by a 4 byte little
+/// endian reference count. The count is stripped on read such that trees built on a RefCountedDB
+/// see the plain node encoding.
+pub struct RefCountedDB<'db, H: TreeHasher> {
+    db: &'db mut dyn HashDB<H, DBValue>,
+}
+
+impl<'db, H: TreeHasher> RefCountedDB<'db, H> {
+    /// Construct a new RefCountedDB wrapping the provided database
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>) -> Self {
+        Self { db }
+    }
+
+    /// Returns the number of references to the record with the provided key, zero if the record
+    /// does not exist
+    pub fn refcount(&self, key: &H::Out, prefix: Prefix) -> u32 {
+        self.db
+            .get(key, prefix)
+            .and_then(|record| split_record(&record).map(|(_, count)| count))
+            .unwrap_or(0)
+    }
+
+    /// Replace the record with the provided key in the underlying database
+    fn write(&mut self, key: H::Out, prefix: Prefix, value: &[u8], count: u32) {
+        if self.db.contains(&key, prefix) {
+            self.db.remove(&key, prefix);
+        }
+        if count > 0 {
+            let mut record = DBValue::with_capacity(value.len() + REFCOUNT_SIZE);
+            record.extend_from_slice(value);
+            record.extend_from_slice(&count.to_le_bytes());
+            self.db.emplace(key, prefix, record);
+        }
+    }
+}
+
+impl<'db, H: TreeHasher> HashDB<H, DBValue> for RefCountedDB<'db, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        let record = self.db.get(key, prefix)?;
+        split_record(&record).map(|(value, _)| DBValue::from(value))
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        self.refcount(key, prefix) > 0
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        let key = H::hash(value);
+        self.emplace(key, prefix, DBValue::from(value));
+        key
+    }
+
+    fn emplace(&mut self, key: H::Out, prefix: Prefix, value: DBValue) {
+        let count = self.refcount(&key, prefix).saturating_add(1);
+        self.write(key, prefix, &value, count);
+    }
+
+    fn remove(&mut self, key: &H::Out, prefix: Prefix) {
+        let record = match self.db.get(key, prefix) {
+            Some(record) => record,
+            None => return,
+        };
+        if let Some((value, count)) = split_record(&record) {
+            self.write(*key, prefix, value, count - 1);
+        }
+    }
+}
+
+impl<'db, H: TreeHasher> HashDBRef<H, DBValue> for RefCountedDB<'db, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(self, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(self, key, prefix)
+    }
+}
+
+impl<'db, H: TreeHasher> AsHashDB<H, DBValue> for RefCountedDB<'db, H> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<H, DBValue> + 'a) {
+        self
+    }
+}
+
+// HELPERS
+// ================================================================================================
+
+/// Split a stored record into the value and its reference count. Returns None if the record is
+/// too short to contain a reference count or the count is zero.
+fn split_record(record: &[u8]) -> Option<(&[u8], u32)> {
+    if record.len() < REFCOUNT_SIZE {
+        return None;
+    }
+    let (value, count) = record.split_at(record.len() - REFCOUNT_SIZE);
+    let mut bytes = [0u8; REFCOUNT_SIZE];
+    bytes.copy_from_slice(count);
+    match u32::from_le_bytes(bytes) {
+        0 => None,
+        count => Some((value, count)),
+    }
+}
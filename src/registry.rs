@@ -0,0 +1,95 @@
+use super::{
+    DBValue, HashDB, KeyedTree, KeyedTreeMut, NodeError, TreeDB, TreeDBBuilder, TreeDBMut,
+    TreeDBMutBuilder, TreeError, TreeHasher,
+};
+
+// Registry
+// ================================================================================================
+
+/// A registry maintains a parent tree of depth D * 8 that commits to the roots of many child trees
+/// stored in the same database. Each child tree is identified by a key in the parent tree and the
+/// leaf at that key holds the committed root of the child. Child trees are only modified through
+/// the registry, which commits the child and writes its new root into the parent tree in the same
+/// operation, such that the root of the registry is a single commitment over all child trees that
+/// can not drift out of sync.
+pub struct Registry<'db, const D: usize, H: TreeHasher> {
+    db: &'db mut dyn HashDB<H, DBValue>,
+    root: &'db mut H::Out,
+}
+
+impl<'db, const D: usize, H: TreeHasher> Registry<'db, D, H> {
+    /// Construct a new Registry over the provided database and parent tree root
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>, root: &'db mut H::Out) -> Self {
+        Self { db, root }
+    }
+
+    /// Returns the root of the parent tree
+    pub fn root(&self) -> &H::Out {
+        self.root
+    }
+
+    /// Returns the committed root of the child tree with the provided id, None if the child tree
+    /// has not been registered.
+    pub fn child_root(&self, id: &[u8]) -> Result<Option<H::Out>, TreeError> {
+        let db = self.db.as_hash_db();
+        let parent = TreeDBBuilder::<D, H>::new(&db, self.root).build();
+        let root = match parent.value(id)? {
+            Some(root) => root,
+            None => return Ok(None),
+        };
+        let mut child_root = H::Out::default();
+        if child_root.as_ref().len() != root.len() {
+            return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                root.to_vec(),
+            )));
+        }
+        child_root.as_mut().copy_from_slice(&root);
+        Ok(Some(child_root))
+    }
+
+    /// Provides read access to the child tree of depth C * 8 with the provided id. An unregistered
+    /// child tree is empty.
+    pub fn read<const C: usize, R>(
+        &self,
+        id: &[u8],
+        f: impl FnOnce(&TreeDB<'_, C, H>) -> R,
+    ) -> Result<R, TreeError> {
+        let child_root = self.child_root(id)?.unwrap_or_default();
+        let db = self.db.as_hash_db();
+        let child = TreeDBBuilder::<C, H>::new(&db, &child_root).build();
+        Ok(f(&child))
+    }
+
+    /// Provides write access to the child tree of depth C * 8 with the provided id. Once the
+    /// provided function returns the child tree is committed and its new root is written into the
+    /// parent tree, which is then committed. If the provided function returns an error the changes
+    /// to the child tree are discarded and the parent tree is left unchanged.
+    pub fn update<const C: usize, R>(
+        &mut self,
+        id: &[u8],
+        f: impl FnOnce(&mut TreeDBMut<'_, C, H>) -> Result<R, TreeError>,
+    ) -> Result<R, TreeError> {
+        let mut child_root = self.child_root(id)?.unwrap_or_default();
+        let result = {
+            let mut child = TreeDBMutBuilder::<C, H>::new(&mut *self.db, &mut child_root).build();
+            let result = f(&mut child)?;
+            child.commit();
+            result
+        };
+
+        let mut parent = TreeDBMutBuilder::<D, H>::new(&mut *self.db, &mut *self.root).build();
+        parent.insert(id, DBValue::from(child_root.as_ref()))?;
+        parent.commit();
+        Ok(result)
+    }
+
+    /// Removes the child tree with the provided id from the parent tree. The nodes of the child
+    /// tree remain in the database.
+    pub fn remove(&mut self, id: &[u8]) -> Result<Option<H::Out>, TreeError> {
+        let child_root = self.child_root(id)?;
+        let mut parent = TreeDBMutBuilder::<D, H>::new(&mut *self.db, &mut *self.root).build();
+        parent.remove(id)?;
+        parent.commit();
+        Ok(child_root)
+    }
+}
@@ -0,0 +1,250 @@
+use super::{
+    null_nodes, proof::encoded_node_hash, rstd::vec::Vec, DBValue, DataError, FxBuildHasher,
+    HashDB, HashDBRef, HashSet, Key, Monitor, Node, NodeHash, TreeError, TreeHasher,
+};
+use hash_db::EMPTY_PREFIX;
+
+// Divergence
+// ================================================================================================
+
+/// Identifies one of the two backends being compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    First,
+    Second,
+}
+
+/// A node reachable from the root that is not intact in one or both of the backends being
+/// compared. A node is corrupt if its data is present but does not hash to the node hash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence<O> {
+    /// The node is missing from the backend
+    Missing(O, Backend),
+    /// The node is present but corrupt in the backend
+    Corrupt(O, Backend),
+    /// The node is not intact in either backend, the subtree below it can not be traversed
+    Lost(O),
+}
+
+// ANTI-ENTROPY
+// ================================================================================================
+
+/// Walks the tree with the provided root across two backends that claim to hold it and returns
+/// every reachable node that is missing or corrupt in either backend. A node that is intact in one
+/// backend is used to continue the traversal, such that all nodes reachable from the root in the
+/// union of both backends are checked. Each node is reported once.
+pub fn compare_backends<const D: usize, H: TreeHasher>(
+    root: &H::Out,
+    first: &dyn HashDBRef<H, DBValue>,
+    second: &dyn HashDBRef<H, DBValue>,
+    salt: &[u8],
+) -> Vec<Divergence<H::Out>> {
+    compare_backends_with_monitor::<D, H>(root, first, second, salt, &mut Monitor::new())
+        .expect("an unmonitored comparison is never cancelled")
+}
+
+/// Compares two backends, see compare_backends, reporting progress to and checking for
+/// cancellation with the provided monitor. Each checked node counts as processed.
+///
+/// Errors:
+/// - Cancelled: if the cancellation token of the monitor is cancelled
+pub fn compare_backends_with_monitor<const D: usize, H: TreeHasher>(
+    root: &H::Out,
+    first: &dyn HashDBRef<H, DBValue>,
+    second: &dyn HashDBRef<H, DBValue>,
+    salt: &[u8],
+    monitor: &mut Monitor,
+) -> Result<Vec<Divergence<H::Out>>, TreeError> {
+    let mut divergences = Vec::new();
+    let mut stack = root_stack::<D, H>(root, salt);
+    let mut visited = HashSet::<H::Out>::default();
+    while let Some(hash) = stack.pop() {
+        // identical subtrees share nodes, each node is only checked once
+        if !visited.insert(hash) {
+            continue;
+        }
+        monitor.tick()?;
+        let states = [
+            (intact_node(first, &hash, salt), Backend::First),
+            (intact_node(second, &hash, salt), Backend::Second),
+        ];
+        let node = states.iter().find_map(|(state, _)| match state {
+            NodeState::Intact(node) => Some(node),
+            _ => None,
+        });
+        let node = match node {
+            Some(node) => node,
+            None => {
+                divergences.push(Divergence::Lost(hash));
+                continue;
+            }
+        };
+
+        for (state, backend) in states.iter() {
+            match state {
+                NodeState::Intact(_) => {}
+                NodeState::Missing => divergences.push(Divergence::Missing(hash, *backend)),
+                NodeState::Corrupt => divergences.push(Divergence::Corrupt(hash, *backend)),
+            }
+        }
+        push_children(node, &mut stack);
+    }
+    monitor.finish();
+    Ok(divergences)
+}
+
+/// Walks the tree with the provided root in the source backend and copies every reachable node
+/// that is missing or corrupt in the target backend from the source to the target. Returns the
+/// number of nodes copied.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a reachable node is missing or corrupt in the source backend
+pub fn repair_backend<const D: usize, H: TreeHasher>(
+    root: &H::Out,
+    source: &dyn HashDBRef<H, DBValue>,
+    target: &mut dyn HashDB<H, DBValue>,
+    salt: &[u8],
+) -> Result<usize, TreeError> {
+    repair_backend_with_monitor::<D, H>(root, source, target, salt, &mut Monitor::new())
+}
+
+/// Repairs the target backend, see repair_backend, reporting progress to and checking for
+/// cancellation with the provided monitor. Each checked node counts as processed. Nodes copied
+/// before the operation is cancelled remain in the target backend.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a reachable node is missing or corrupt in the source backend
+/// - Cancelled: if the cancellation token of the monitor is cancelled
+pub fn repair_backend_with_monitor<const D: usize, H: TreeHasher>(
+    root: &H::Out,
+    source: &dyn HashDBRef<H, DBValue>,
+    target: &mut dyn HashDB<H, DBValue>,
+    salt: &[u8],
+    monitor: &mut Monitor,
+) -> Result<usize, TreeError> {
+    let mut copied = 0;
+    let mut stack = root_stack::<D, H>(root, salt);
+    let mut visited = HashSet::<H::Out>::default();
+    while let Some(hash) = stack.pop() {
+        // identical subtrees share nodes, each node is only checked once
+        if !visited.insert(hash) {
+            continue;
+        }
+        monitor.tick()?;
+        let node = match intact_node(source, &hash, salt) {
+            NodeState::Intact(node) => node,
+            _ => {
+                return Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+                    hash.as_ref().to_vec(),
+                )))
+            }
+        };
+
+        match intact_node(&target.as_hash_db(), &hash, salt) {
+            NodeState::Intact(_) => {}
+            state => {
+                if matches!(state, NodeState::Corrupt) {
+                    target.remove(&hash, EMPTY_PREFIX);
+                }
+                target.emplace(hash, EMPTY_PREFIX, node.clone().into());
+                copied += 1;
+            }
+        }
+
+        push_children(&node, &mut stack);
+    }
+    monitor.finish();
+    Ok(copied)
+}
+
+// TEARDOWN
+// ================================================================================================
+
+/// Removes the tree with the provided root from the database, e.g. to tear down the tree of a
+/// tenant. Every reachable node is removed once per reference the tree holds to it: once per
+/// occurrence in the tree and once more for the root, as written by TreeDBMut::commit. Nodes that
+/// other trees share with the tree keep the references of those trees. The trees of a reference
+/// counted TreeDBMut are removed with TreeDBMut::release_root instead. Returns the number of
+/// nodes deleted from the database. The database is not modified if a node can not be read.
+///
+/// Errors:
+/// - DatabaseDataNotFound: if a reachable node is missing from the database
+/// - NodeError: if a reachable node can not be decoded
+pub fn destroy<const D: usize, H: TreeHasher>(
+    root: &H::Out,
+    db: &mut dyn HashDB<H, DBValue>,
+    salt: &[u8],
+) -> Result<usize, TreeError> {
+    let mut stack = root_stack::<D, H>(root, salt);
+    // the root holds an additional reference, see TreeDBMut::commit
+    let mut references = stack.clone();
+    while let Some(hash) = stack.pop() {
+        let data = db.get(&hash, EMPTY_PREFIX).ok_or(TreeError::DataError(
+            DataError::DatabaseDataNotFound(hash.as_ref().to_vec()),
+        ))?;
+        let node = Node::<H>::decode(data, salt).map_err(TreeError::NodeError)?;
+        push_children(&node, &mut stack);
+        references.push(hash);
+    }
+
+    for hash in references.iter() {
+        db.remove(hash, EMPTY_PREFIX);
+    }
+    let removed = references.into_iter().collect::<HashSet<H::Out>>();
+    Ok(removed
+        .iter()
+        .filter(|hash| !db.contains(hash, EMPTY_PREFIX))
+        .count())
+}
+
+// HELPERS
+// ================================================================================================
+
+/// The state of a node in a backend
+enum NodeState<H: TreeHasher> {
+    Intact(Node<H>),
+    Missing,
+    Corrupt,
+}
+
+/// Fetches the node with the provided hash from the backend and checks that its data hashes to
+/// the node hash.
+fn intact_node<H: TreeHasher>(
+    db: &dyn HashDBRef<H, DBValue>,
+    hash: &H::Out,
+    salt: &[u8],
+) -> NodeState<H> {
+    let data = match db.get(hash, EMPTY_PREFIX) {
+        Some(data) => data,
+        None => return NodeState::Missing,
+    };
+    if encoded_node_hash::<H>(&data, salt).as_ref() != Some(hash) {
+        return NodeState::Corrupt;
+    }
+    match Node::decode(data, salt) {
+        Ok(node) => NodeState::Intact(node),
+        Err(_) => NodeState::Corrupt,
+    }
+}
+
+/// Returns the traversal stack for the provided root, which is empty if the root is the root of
+/// an empty tree as default nodes are not stored in the backend.
+pub(crate) fn root_stack<const D: usize, H: TreeHasher>(root: &H::Out, salt: &[u8]) -> Vec<H::Out> {
+    let (_, default_root) = null_nodes::<H, FxBuildHasher>(Key::<D>::BITS, salt);
+    if root == &H::Out::default() || root == &default_root {
+        Vec::new()
+    } else {
+        Vec::from([*root])
+    }
+}
+
+/// Pushes the hashes of the non-default children of an inner node onto the stack.
+pub(crate) fn push_children<H: TreeHasher>(node: &Node<H>, stack: &mut Vec<H::Out>) {
+    if let Node::Inner { left, right, .. } = node {
+        for child in [right, left] {
+            if let NodeHash::Database(hash) = child {
+                stack.push(*hash);
+            }
+        }
+    }
+}
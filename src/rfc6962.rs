@@ -0,0 +1,100 @@
+use super::{rstd::vec::Vec, Hasher};
+
+// CONSTANTS
+// ================================================================================================
+
+/// The domain separation prefix of leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+
+/// The domain separation prefix of inner node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+// RFC 6962
+// ================================================================================================
+
+/// Returns the RFC 6962 hash of a leaf with the provided value, i.e. HASH(0x00 || value).
+pub fn rfc6962_leaf_hash<H: Hasher>(value: &[u8]) -> H::Out {
+    H::hash(&[&[LEAF_PREFIX][..], value].concat())
+}
+
+/// Returns the RFC 6962 hash of an inner node with the provided children, i.e.
+/// HASH(0x01 || left || right).
+pub fn rfc6962_node_hash<H: Hasher>(left: &H::Out, right: &H::Out) -> H::Out {
+    H::hash(&[&[NODE_PREFIX][..], left.as_ref(), right.as_ref()].concat())
+}
+
+/// Returns the RFC 6962 Merkle Tree Hash of the provided leaf hashes. The hash of an empty list
+/// is the hash of the empty string.
+pub(crate) fn rfc6962_root<H: Hasher>(leaves: &[H::Out]) -> H::Out {
+    match leaves.len() {
+        0 => H::hash(&[]),
+        1 => leaves[0],
+        n => {
+            let k = split(n);
+            rfc6962_node_hash::<H>(
+                &rfc6962_root::<H>(&leaves[..k]),
+                &rfc6962_root::<H>(&leaves[k..]),
+            )
+        }
+    }
+}
+
+/// Returns the RFC 6962 audit path of the leaf at the provided index of the provided leaf
+/// hashes, ordered from the leaf to the root. The index must be less than the number of leaves.
+pub(crate) fn rfc6962_audit_path<H: Hasher>(index: usize, leaves: &[H::Out]) -> Vec<H::Out> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+    let k = split(leaves.len());
+    let (mut path, sibling) = match index < k {
+        true => (
+            rfc6962_audit_path::<H>(index, &leaves[..k]),
+            rfc6962_root::<H>(&leaves[k..]),
+        ),
+        false => (
+            rfc6962_audit_path::<H>(index - k, &leaves[k..]),
+            rfc6962_root::<H>(&leaves[..k]),
+        ),
+    };
+    path.push(sibling);
+    path
+}
+
+/// Verifies an RFC 6962 audit path of the provided leaf hash at the provided index of a tree of
+/// the provided size against the provided root, following the algorithm of RFC 9162 section
+/// 2.1.3.2.
+pub fn verify_rfc6962_audit_path<H: Hasher>(
+    index: u64,
+    size: u64,
+    leaf: &H::Out,
+    path: &[H::Out],
+    root: &H::Out,
+) -> bool {
+    if index >= size {
+        return false;
+    }
+    let (mut f, mut s) = (index, size - 1);
+    let mut hash = *leaf;
+    for sibling in path.iter() {
+        if s == 0 {
+            return false;
+        }
+        if f & 1 == 1 || f == s {
+            hash = rfc6962_node_hash::<H>(sibling, &hash);
+            while f & 1 == 0 && f != 0 {
+                f >>= 1;
+                s >>= 1;
+            }
+        } else {
+            hash = rfc6962_node_hash::<H>(&hash, sibling);
+        }
+        f >>= 1;
+        s >>= 1;
+    }
+    s == 0 && hash == *root
+}
+
+/// Returns the largest power of two less than n, n must be greater than one.
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
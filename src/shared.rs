@@ -0,0 +1,82 @@
+use super::{DBValue, HashDB, HashDBRef, TreeHasher};
+use hash_db::{AsHashDB, Prefix};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// SharedDB
+// ================================================================================================
+
+/// A database wrapper that shares ownership of the underlying database behind an `Arc<Mutex<_>>`.
+/// A SharedDB is cheap to clone and every clone accesses the same underlying database, as such it
+/// can be moved into a worker task together with the root of a tree and used as the backend of a
+/// TreeDBMut that is built inside the task. With the `send` feature the mutable trees are Send and
+/// can be held across await points.
+pub struct SharedDB<DB> {
+    db: Arc<Mutex<DB>>,
+}
+
+impl<DB> SharedDB<DB> {
+    /// Construct a new SharedDB taking ownership of the provided database
+    pub fn new(db: DB) -> Self {
+        Self {
+            db: Arc::new(Mutex::new(db)),
+        }
+    }
+
+    /// Locks and returns the underlying database. A lock poisoned by a panicking holder is
+    /// recovered as the database is only mutated through complete operations.
+    pub fn lock(&self) -> MutexGuard<'_, DB> {
+        self.db
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<DB> Clone for SharedDB<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            db: Arc::clone(&self.db),
+        }
+    }
+}
+
+impl<H: TreeHasher, DB: HashDB<H, DBValue>> HashDB<H, DBValue> for SharedDB<DB> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        self.lock().get(key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        self.lock().contains(key, prefix)
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> H::Out {
+        self.lock().insert(prefix, value)
+    }
+
+    fn emplace(&mut self, key: H::Out, prefix: Prefix, value: DBValue) {
+        self.lock().emplace(key, prefix, value)
+    }
+
+    fn remove(&mut self, key: &H::Out, prefix: Prefix) {
+        self.lock().remove(key, prefix)
+    }
+}
+
+impl<H: TreeHasher, DB: HashDB<H, DBValue>> HashDBRef<H, DBValue> for SharedDB<DB> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        HashDB::get(self, key, prefix)
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        HashDB::contains(self, key, prefix)
+    }
+}
+
+impl<H: TreeHasher, DB: HashDB<H, DBValue>> AsHashDB<H, DBValue> for SharedDB<DB> {
+    fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<H, DBValue> + 'a) {
+        self
+    }
+}
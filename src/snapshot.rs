@@ -0,0 +1,115 @@
+use super::{
+    rstd::sync::Arc, tree::Proof, DBValue, HashDBRef, HashMap, KeyedTree, TreeDBBuilder, TreeError,
+    TreeHasher,
+};
+use hash_db::Prefix;
+
+// TreeSnapshot
+// ================================================================================================
+
+/// An immutable view of a mutable tree including its uncommitted changes, constructed with
+/// TreeDBMut::snapshot. The view holds the root of the tree and the encoded uncommitted nodes,
+/// the committed nodes are read from the database the tree reads from, e.g. a clone of a
+/// SharedDB. Clones share the uncommitted nodes, such that a snapshot is cheap to hand to
+/// concurrent readers while the tree continues to be mutated. A commit of the tree that removes
+/// nodes of the snapshot from the database invalidates it, unless the tree is reference counted.
+pub struct TreeSnapshot<const D: usize, H: TreeHasher> {
+    root: H::Out,
+    salt: DBValue,
+    prefix: DBValue,
+    overlay: Arc<HashMap<H::Out, DBValue>>,
+}
+
+impl<const D: usize, H: TreeHasher> TreeSnapshot<D, H> {
+    /// Construct a snapshot of the tree with the provided root, salt, storage prefix and
+    /// uncommitted nodes
+    pub(crate) fn new(
+        root: H::Out,
+        salt: DBValue,
+        prefix: DBValue,
+        overlay: HashMap<H::Out, DBValue>,
+    ) -> Self {
+        Self {
+            root,
+            salt,
+            prefix,
+            overlay: Arc::new(overlay),
+        }
+    }
+
+    /// Returns the root of the tree at the time of the snapshot
+    pub fn root(&self) -> &H::Out {
+        &self.root
+    }
+
+    /// Returns the value at the provided key, see KeyedTree::value
+    pub fn value(
+        &self,
+        db: &dyn HashDBRef<H, DBValue>,
+        key: &[u8],
+    ) -> Result<Option<DBValue>, TreeError> {
+        let db = SnapshotDB { db, snapshot: self };
+        TreeDBBuilder::<D, H>::new(&db, &self.root)
+            .with_salt(&self.salt)
+            .with_prefix(&self.prefix)
+            .build()
+            .value(key)
+    }
+
+    /// Returns the hash of the leaf at the provided key, see KeyedTree::leaf
+    pub fn leaf(
+        &self,
+        db: &dyn HashDBRef<H, DBValue>,
+        key: &[u8],
+    ) -> Result<Option<H::Out>, TreeError> {
+        let db = SnapshotDB { db, snapshot: self };
+        TreeDBBuilder::<D, H>::new(&db, &self.root)
+            .with_salt(&self.salt)
+            .with_prefix(&self.prefix)
+            .build()
+            .leaf(key)
+    }
+
+    /// Returns an inclusion proof of the value at the provided key, see KeyedTree::proof
+    pub fn proof(&self, db: &dyn HashDBRef<H, DBValue>, key: &[u8]) -> Result<Proof<H>, TreeError> {
+        let db = SnapshotDB { db, snapshot: self };
+        TreeDBBuilder::<D, H>::new(&db, &self.root)
+            .with_salt(&self.salt)
+            .with_prefix(&self.prefix)
+            .build()
+            .proof(key)
+    }
+}
+
+impl<const D: usize, H: TreeHasher> Clone for TreeSnapshot<D, H> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root,
+            salt: self.salt.clone(),
+            prefix: self.prefix.clone(),
+            overlay: Arc::clone(&self.overlay),
+        }
+    }
+}
+
+// SnapshotDB
+// ================================================================================================
+
+/// A database which reads the uncommitted nodes of a snapshot before the provided database
+struct SnapshotDB<'a, const D: usize, H: TreeHasher> {
+    db: &'a dyn HashDBRef<H, DBValue>,
+    snapshot: &'a TreeSnapshot<D, H>,
+}
+
+impl<'a, const D: usize, H: TreeHasher> HashDBRef<H, DBValue> for SnapshotDB<'a, D, H> {
+    fn get(&self, key: &H::Out, prefix: Prefix) -> Option<DBValue> {
+        match self.snapshot.overlay.get(key) {
+            Some(data) => Some(data.clone()),
+            None => self.db.get(key, prefix),
+        }
+    }
+
+    fn contains(&self, key: &H::Out, prefix: Prefix) -> bool {
+        self.snapshot.overlay.contains_key(key) || self.db.contains(key, prefix)
+    }
+}
@@ -1,36 +1,88 @@
-use super::{HashMap, Hasher, Node};
+use super::{FxBuildHasher, HashMap, Node, TreeHasher};
+use core::{
+    hash::{BuildHasher, Hash},
+    mem::size_of,
+};
+use hash_db::Hasher;
 
 // NodeStorage
 // ================================================================================================
 
-/// NodeStorage used to store in memory nodes
-pub struct NodeStorage<H: Hasher> {
-    nodes: HashMap<H::Out, (Node<H>, usize)>,
+/// NodeStorage used to store in memory nodes, the nodes are kept in a map hashed with S. The heap
+/// bytes held by the stored nodes are tracked as nodes are inserted and removed, see approx_bytes.
+pub struct NodeStorage<H: TreeHasher, S = FxBuildHasher> {
+    nodes: HashMap<H::Out, (Node<H>, usize), S>,
+    heap_bytes: usize,
 }
 
-impl<H: Hasher> NodeStorage<H> {
+impl<H: TreeHasher, S: BuildHasher + Default> NodeStorage<H, S> {
     /// create a new empty storage
     pub fn empty() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// create a new empty storage with room for the provided number of nodes
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            nodes: HashMap::new(),
+            nodes: HashMap::with_capacity_and_hasher(capacity, S::default()),
+            heap_bytes: 0,
         }
     }
 
+    /// returns the number of nodes the storage can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// returns the approximate memory used by the storage in bytes: the size of an entry for every
+    /// slot of the map, including unused capacity, plus the heap bytes held by the stored nodes
+    pub fn approx_bytes(&self) -> usize {
+        self.nodes.capacity() * size_of::<(H::Out, (Node<H>, usize))>() + self.heap_bytes
+    }
+
+    /// shrinks the capacity of the storage as much as possible
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
     /// get a node from the storage
     pub fn get(&self, hash: &H::Out) -> Option<&Node<H>> {
         self.nodes.get(hash).map(|(node, _)| node)
     }
 
+    /// returns the number of distinct nodes in the storage
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
     /// insert a node into the storage
     pub fn insert(&mut self, node: Node<H>) {
         let hash = node.hash();
+        let heap_bytes = &mut self.heap_bytes;
         self.nodes
             .entry(*hash)
             .and_modify(|(node, count)| {
                 *node = node.clone();
                 *count += 1;
             })
-            .or_insert((node, 1));
+            .or_insert_with(|| {
+                *heap_bytes += heap_size(&node);
+                (node, 1)
+            });
+    }
+
+    /// insert a node into the storage, a node with the same hash is replaced and keeps its count
+    pub fn insert_or_replace(&mut self, node: Node<H>) {
+        self.heap_bytes += heap_size(&node);
+        match self.nodes.get_mut(node.hash()) {
+            Some((current, _)) => {
+                self.heap_bytes -= heap_size(current);
+                *current = node;
+            }
+            None => {
+                self.nodes.insert(*node.hash(), (node, 1));
+            }
+        }
     }
 
     /// remove a node from the storage
@@ -46,10 +98,117 @@ impl<H: Hasher> NodeStorage<H> {
                 }
             })
             .and_then(|node| self.nodes.remove(hash).map(|_| node))
+            .inspect(|node| self.heap_bytes -= heap_size(node))
+    }
+
+    /// iterate over the nodes in the storage along with their counts
+    pub fn iter(&self) -> impl Iterator<Item = (&H::Out, &(Node<H>, usize))> {
+        self.nodes.iter()
     }
 
     /// drain the storage
-    pub fn drain(&mut self) -> hashbrown::hash_map::Drain<H::Out, (Node<H>, usize)> {
+    pub fn drain(&mut self) -> hashbrown::hash_map::Drain<'_, H::Out, (Node<H>, usize)> {
+        self.heap_bytes = 0;
         self.nodes.drain()
     }
 }
+
+/// Implement clone for NodeStorage, the clone is hashed with a new instance of S
+impl<H: TreeHasher, S: BuildHasher + Default> Clone for NodeStorage<H, S> {
+    fn clone(&self) -> Self {
+        let mut nodes = HashMap::with_capacity_and_hasher(self.nodes.len(), S::default());
+        nodes.extend(
+            self.nodes
+                .iter()
+                .map(|(hash, entry)| (*hash, entry.clone())),
+        );
+        Self {
+            nodes,
+            heap_bytes: self.heap_bytes,
+        }
+    }
+}
+
+/// Returns the number of bytes the provided node holds on the heap, i.e. the bytes of the value,
+/// key and preimage of a value node
+fn heap_size<H: TreeHasher>(node: &Node<H>) -> usize {
+    match node {
+        Node::Value {
+            value,
+            key,
+            preimage,
+            ..
+        } => {
+            value.len()
+                + key.as_ref().map_or(0, |key| key.len())
+                + preimage.as_ref().map_or(0, |preimage| preimage.len())
+        }
+        Node::Inner { .. } => 0,
+    }
+}
+
+// StagedStats
+// ================================================================================================
+
+/// Statistics of the changes staged in memory by a TreeDBMut, see TreeDBMut::staged_stats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StagedStats {
+    /// The number of distinct nodes staged
+    pub nodes: usize,
+    /// The number of nodes that can be staged without reallocating
+    pub capacity: usize,
+    /// The number of committed nodes staged for removal
+    pub removals: usize,
+    /// The approximate memory used by the staged nodes and removals in bytes, including unused
+    /// capacity
+    pub approx_bytes: usize,
+}
+
+// NodeMemo
+// ================================================================================================
+
+/// The maximum number of nodes retained in a NodeMemo before it is cleared
+const NODE_MEMO_CAPACITY: usize = 256;
+
+/// NodeMemo used to memoize decoded database nodes within a single tree handle, such that
+/// repeated lookups of the same nodes do not fetch and decode them from the database again. The
+/// nodes are keyed by K, their hash by default. The memo is bounded and is cleared once it
+/// reaches NODE_MEMO_CAPACITY entries.
+pub struct NodeMemo<H: TreeHasher, K = <H as Hasher>::Out> {
+    nodes: core::cell::RefCell<HashMap<K, Node<H>>>,
+}
+
+impl<H: TreeHasher, K: Eq + Hash> NodeMemo<H, K> {
+    /// create a new empty memo
+    pub fn empty() -> Self {
+        Self {
+            nodes: core::cell::RefCell::new(HashMap::default()),
+        }
+    }
+
+    /// get a node from the memo
+    pub fn get(&self, key: &K) -> Option<Node<H>> {
+        self.nodes.borrow().get(key).cloned()
+    }
+
+    /// insert a node into the memo with the provided key
+    pub fn insert_with_key(&self, key: K, node: &Node<H>) {
+        let mut nodes = self.nodes.borrow_mut();
+        if nodes.len() >= NODE_MEMO_CAPACITY {
+            nodes.clear();
+        }
+        nodes.insert(key, node.clone());
+    }
+
+    /// clear the memo
+    pub fn clear(&mut self) {
+        self.nodes.get_mut().clear();
+    }
+}
+
+impl<H: TreeHasher> NodeMemo<H> {
+    /// insert a node into the memo keyed by its hash
+    pub fn insert(&self, node: &Node<H>) {
+        self.insert_with_key(*node.hash(), node);
+    }
+}
@@ -0,0 +1,52 @@
+use super::{proof::encoded_node_hash, rstd::vec::Vec, StorageProof, TreeHasher};
+use hash_db::{HashDB, EMPTY_PREFIX};
+use sp_trie::GenericMemoryDB;
+
+// Substrate Interop
+// ================================================================================================
+
+/// Convert a StorageProof into a substrate sp_trie::StorageProof. The encoded nodes are carried
+/// over as is.
+impl From<StorageProof> for sp_trie::StorageProof {
+    fn from(proof: StorageProof) -> Self {
+        sp_trie::StorageProof::new(
+            proof
+                .into_nodes()
+                .into_iter()
+                .map(|node| node.into_iter().collect::<Vec<u8>>()),
+        )
+    }
+}
+
+/// Convert a substrate sp_trie::StorageProof into a StorageProof. The proof must have been
+/// produced by this crate, i.e. the nodes must be encoded sparse merkle tree nodes.
+impl From<sp_trie::StorageProof> for StorageProof {
+    fn from(proof: sp_trie::StorageProof) -> Self {
+        StorageProof::new(
+            proof
+                .into_iter_nodes()
+                .map(|node| node.into_iter().collect()),
+        )
+    }
+}
+
+impl StorageProof {
+    /// Consumes the storage proof and returns a substrate memory db containing the nodes. Nodes
+    /// are keyed by their sparse merkle tree node hash rather than the hash of their encoding,
+    /// so the resulting database can be used directly as the backend of a TreeDB. The key
+    /// function is generic such that both `sp_trie::MemoryDB` and `sp_trie::PrefixedMemoryDB`
+    /// are supported.
+    pub fn into_substrate_memory_db<H, KF>(self) -> GenericMemoryDB<H, KF>
+    where
+        H: TreeHasher,
+        KF: memory_db::KeyFunction<H> + Send + Sync,
+    {
+        let mut db = GenericMemoryDB::<H, KF>::default();
+        for node in self.into_nodes().into_iter() {
+            if let Some(hash) = encoded_node_hash::<H>(&node, &[]) {
+                db.emplace(hash, EMPTY_PREFIX, node.into_iter().collect());
+            }
+        }
+        db
+    }
+}
@@ -0,0 +1,72 @@
+//! Ready-made hashers, backends and pre-populated trees for tests and examples, available with the
+//! `test-utils` feature.
+
+use super::{
+    DBValue, DigestHasher, Hasher, IndexTreeDBMutBuilder, IndexTreeMut, KeyedTreeMut,
+    TreeDBMutBuilder,
+};
+use core::marker::PhantomData;
+use hash_db::Prefix;
+use memory_db::KeyFunction;
+use std::vec::Vec;
+
+// HASHERS AND BACKENDS
+// ================================================================================================
+
+/// Sha3 hasher provided by the digest adapter.
+pub type Sha3 = DigestHasher<sha3::Sha3_256, 32>;
+
+/// Unit struct for NoopKey
+pub struct NoopKey<H: Hasher>(PhantomData<H>);
+
+/// implementation of the KeyFunction trait for the NoopKey hasher, the key is the hash provided.
+/// The prefix is ignored.
+impl<H: Hasher> KeyFunction<H> for NoopKey<H> {
+    type Key = Vec<u8>;
+
+    fn key(hash: &H::Out, _prefix: Prefix) -> Vec<u8> {
+        hash.as_ref().to_vec()
+    }
+}
+
+/// An in memory database keyed by node hashes, as expected by the trees
+pub type MemoryDB<H = Sha3> = memory_db::MemoryDB<H, NoopKey<H>, DBValue>;
+
+// PRE-POPULATED TREES
+// ================================================================================================
+
+/// Returns a database and root of a keyed tree of depth D * 8 holding the provided key-value
+/// pairs, the tree is committed to the database.
+///
+/// Panics if a key is invalid for the depth of the tree.
+pub fn populated_keyed_db<const D: usize>(
+    entries: &[(&[u8], &[u8])],
+) -> (MemoryDB, <Sha3 as Hasher>::Out) {
+    let mut db = MemoryDB::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<D, Sha3>::new(&mut db, &mut root).build();
+    for (key, value) in entries.iter() {
+        tree.insert(key, DBValue::from(*value))
+            .expect("failed to insert entry");
+    }
+    tree.commit();
+    (db, root)
+}
+
+/// Returns a database and root of an index tree of depth D * 8 holding the provided index-value
+/// pairs, the tree is committed to the database.
+///
+/// Panics if an index is out of bounds for the depth of the tree.
+pub fn populated_index_db<const D: usize>(
+    entries: &[(u64, &[u8])],
+) -> (MemoryDB, <Sha3 as Hasher>::Out) {
+    let mut db = MemoryDB::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<D, Sha3>::new(&mut db, &mut root).build();
+    for (index, value) in entries.iter() {
+        tree.insert(index, DBValue::from(*value))
+            .expect("failed to insert entry");
+    }
+    tree.commit();
+    (db, root)
+}
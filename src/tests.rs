@@ -1,14 +1,31 @@
 use super::{
-    rstd::{vec, vec::Vec},
-    DBValue, Hasher, IndexTree, IndexTreeDB, IndexTreeDBBuilder, IndexTreeDBMut,
-    IndexTreeDBMutBuilder, IndexTreeMut, KeyedTree, KeyedTreeMut, Recorder, TreeDB, TreeDBBuilder,
-    TreeDBMut, TreeDBMutBuilder,
+    celestia_leaf_hash, celestia_node_hash, compare_backends, compare_backends_with_monitor,
+    destroy, insert_from_proof, migrate, migrate_with_monitor,
+    node::proof_leaf_hash,
+    nodes, repair_backend, repair_backend_with_monitor, replay_journal, rfc6962_leaf_hash,
+    rollback_journal,
+    rstd::{string::String, sync::Arc, vec::Vec},
+    verify_at_depth, verify_celestia_proof, verify_empty_subtree, verify_multi,
+    verify_rfc6962_audit_path, verify_subtree, verify_update, AsyncHashDB, AsyncHashDBRef,
+    AsyncTreeDBBuilder, AsyncTreeDBMutBuilder, Backend, BatchHashDB, CachedDB, CancellationToken,
+    CelestiaProof, ChainedProof, ChangeSet, CompactProof, CompactStorageProof, DBValue, DataError,
+    Divergence, DivergenceProof, DualHasher, Entry, HashDB, HashDBRef, HashMap, Hasher, IndexTree,
+    IndexTreeDB, IndexTreeDBBuilder, IndexTreeDBMut, IndexTreeDBMutBuilder, IndexTreeMut, KeyError,
+    KeyedTree, KeyedTreeMut, LevelCache, MemDb, MerkleProof, Monitor, MultiHashDBRef, Namespace,
+    NeighborProof, NodeError, OverlayDB, ProofEnvelope, ProofError, Recorder, RecorderStats,
+    Registry, StagedStats, StorageProof, TreeBuilder, TreeDB, TreeDBBuilder, TreeDBMut,
+    TreeDBMutBuilder, TreeError, TreeHasher, TreeMetrics, TryHashDB, UpdateProof,
+    VersionedTreeDBMutBuilder, WriteBatch, COMPACT_STORAGE_PROOF_VERSION, MAX_LEVEL_DEPTH,
+    STORAGE_PROOF_VERSION,
 };
 
-use core::marker::PhantomData;
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use hash256_std_hasher::Hash256StdHasher;
-use hash_db::Prefix;
-use memory_db::{KeyFunction, MemoryDB};
+use hash_db::{AsHashDB, Prefix, EMPTY_PREFIX};
+use memory_db::{HashKey, KeyFunction, MemoryDB, PrefixedKey};
 use sha3::{Digest, Sha3_256};
 
 // MOCK
@@ -32,6 +49,37 @@ impl Hasher for Sha3 {
     }
 }
 
+/// Sha3 uses the default leaf and inner node hashing.
+impl TreeHasher for Sha3 {}
+
+/// Unit struct for Sha256, the hash function of certificate transparency logs and of the
+/// reference implementations of the Celestia sparse merkle tree.
+#[derive(Debug)]
+struct Sha256;
+
+impl Hasher for Sha256 {
+    type Out = [u8; 32];
+
+    type StdHasher = Hash256StdHasher;
+
+    const LENGTH: usize = 32;
+
+    fn hash(data: &[u8]) -> Self::Out {
+        sha2::Sha256::digest(data).into()
+    }
+}
+
+impl TreeHasher for Sha256 {}
+
+/// Decodes a hex string of 32 bytes, as used by test vectors.
+fn decode_hex(hex: &str) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes
+}
+
 /// Unit struct for NoopKey
 pub struct NoopKey<H: Hasher>(PhantomData<H>);
 
@@ -66,12 +114,10 @@ fn mock_data() -> (
 ) {
     let mut root = Default::default();
     let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
-    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-        .expect("failed to construct tree buidler")
-        .build();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
     for (_index, path, value) in TEST_DATA.iter() {
-        tree.insert(path, value.to_vec()).unwrap();
+        tree.insert(path, DBValue::from(&value[..])).unwrap();
     }
 
     tree.commit();
@@ -79,6 +125,29 @@ fn mock_data() -> (
     (db, root)
 }
 
+/// Returns the preimage of the leaf hash of an unsalted value at the provided key, the value is
+/// prefixed with the key if the leaf is key-bound.
+fn leaf_preimage(key: &[u8], value: &[u8]) -> Vec<u8> {
+    match cfg!(feature = "key-bound") && !value.is_empty() {
+        true => [key, value].concat(),
+        false => value.to_vec(),
+    }
+}
+
+/// Returns the encoded value node of an unsalted value at the provided key as stored in the
+/// database.
+fn encoded_leaf(key: &[u8], value: &[u8]) -> DBValue {
+    let mut bytes = DBValue::new();
+    if cfg!(feature = "key-bound") {
+        bytes.push(5);
+        bytes.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(key);
+    }
+    bytes.push(0);
+    bytes.extend_from_slice(value);
+    bytes
+}
+
 // TEST ROOT
 // ================================================================================================
 
@@ -87,9 +156,7 @@ macro_rules! test_root {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             let actual_root = tree.root().clone();
 
@@ -100,7 +167,7 @@ macro_rules! test_root {
         #[test]
         fn $name() {
             let (db, root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).unwrap().build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).build();
 
             let actual_root = tree.root();
 
@@ -122,9 +189,7 @@ macro_rules! test_depth {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             let depth = tree.depth();
 
@@ -135,7 +200,7 @@ macro_rules! test_depth {
         #[test]
         fn $name() {
             let (db, root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).unwrap().build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).build();
 
             let depth = tree.depth();
 
@@ -156,14 +221,12 @@ macro_rules! test_value {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_value = tree.value(&data.$selector).unwrap();
 
-                assert_eq!(actual_value, Some(data.2.to_vec()));
+                assert_eq!(actual_value, Some(DBValue::from(data.2)));
             }
         }
     };
@@ -171,12 +234,12 @@ macro_rules! test_value {
         #[test]
         fn $name() {
             let (db, root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).unwrap().build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_value = tree.value(&data.$selector).unwrap();
 
-                assert_eq!(actual_value, Some(data.2.to_vec()));
+                assert_eq!(actual_value, Some(DBValue::from(data.2)));
             }
         }
     };
@@ -194,14 +257,12 @@ macro_rules! test_leaf {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_leaf = tree.leaf(&data.$selector).unwrap();
 
-                assert_eq!(actual_leaf, Sha3::hash(&data.2).into());
+                assert_eq!(actual_leaf, Some(proof_leaf_hash::<Sha3>(data.1, data.2)));
             }
         }
     };
@@ -209,12 +270,12 @@ macro_rules! test_leaf {
         #[test]
         fn $name() {
             let (db, root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).unwrap().build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_leaf = tree.leaf(&data.$selector).unwrap();
 
-                assert_eq!(actual_leaf, Sha3::hash(&data.2).into());
+                assert_eq!(actual_leaf, Some(proof_leaf_hash::<Sha3>(data.1, data.2)));
             }
         }
     };
@@ -232,9 +293,7 @@ macro_rules! test_proof {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             for data in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
                 let (value, root, proof) = tree.proof(&data.$selector).unwrap();
@@ -242,7 +301,7 @@ macro_rules! test_proof {
                 assert_eq!(
                     $tree_interface::<TREE_DEPTH, Sha3>::verify(
                         &data.$selector,
-                        &value.unwrap_or_else(|| vec![]),
+                        &value.unwrap_or_default(),
                         &proof,
                         &root
                     ),
@@ -255,7 +314,7 @@ macro_rules! test_proof {
         #[test]
         fn $name() {
             let (db, root) = mock_data();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).unwrap().build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root).build();
 
             for data in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
                 let (value, root, proof) = tree.proof(&data.$selector).unwrap();
@@ -263,7 +322,7 @@ macro_rules! test_proof {
                 assert_eq!(
                     $tree_interface::<TREE_DEPTH, Sha3>::verify(
                         &data.$selector,
-                        &value.unwrap_or_else(|| vec![]),
+                        &value.unwrap_or_default(),
                         &proof,
                         &root
                     ),
@@ -291,20 +350,18 @@ macro_rules! test_insert {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
             let new_value = b"new value";
-            let new_leaf = Sha3::hash(new_value).into();
+            let new_leaf = Some(proof_leaf_hash::<Sha3>(TEST_DATA[0].1, new_value));
 
             let old_value = tree
-                .insert(&TEST_DATA[0].$selector, new_value.to_vec())
+                .insert(&TEST_DATA[0].$selector, DBValue::from(&new_value[..]))
                 .unwrap();
             let actual_value = tree.value(&TEST_DATA[0].$selector).unwrap();
             let actual_leaf = tree.leaf(&TEST_DATA[0].$selector).unwrap();
 
-            assert_eq!(old_value, Some(TEST_DATA[0].2.to_vec()));
-            assert_eq!(actual_value, Some(new_value.to_vec()));
+            assert_eq!(old_value, Some(DBValue::from(TEST_DATA[0].2)));
+            assert_eq!(actual_value, Some(DBValue::from(&new_value[..])));
             assert_eq!(actual_leaf, new_leaf);
         }
     };
@@ -320,15 +377,13 @@ macro_rules! test_remove {
         #[test]
         fn $name() {
             let (mut db, mut root) = mock_data();
-            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
-                .build();
+            let mut tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
 
             let old_value = tree.remove(&TEST_DATA[0].$selector).unwrap();
             let actual_value = tree.value(&TEST_DATA[0].$selector).unwrap();
             let actual_leaf = tree.leaf(&TEST_DATA[0].$selector).unwrap();
 
-            assert_eq!(old_value, Some(TEST_DATA[0].2.to_vec()));
+            assert_eq!(old_value, Some(DBValue::from(TEST_DATA[0].2)));
             assert_eq!(actual_value, None);
             assert_eq!(actual_leaf, None);
         }
@@ -347,7 +402,6 @@ macro_rules! test_recorder_and_storage_proof {
             let mut recorder = Recorder::new();
             let (mut db, mut root) = mock_data();
             let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
-                .unwrap()
                 .with_recorder(&mut recorder)
                 .build();
 
@@ -356,14 +410,12 @@ macro_rules! test_recorder_and_storage_proof {
             }
             let storage_proof = recorder.drain_storage_proof();
             let mut memory_db = storage_proof.into_memory_db::<Sha3>();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut memory_db, &mut root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&mut memory_db, &mut root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_value = tree.value(&data.$selector).unwrap();
 
-                assert_eq!(actual_value, Some(data.2.to_vec()));
+                assert_eq!(actual_value, Some(DBValue::from(data.2)));
             }
         }
     };
@@ -373,7 +425,6 @@ macro_rules! test_recorder_and_storage_proof {
             let mut recorder = Recorder::new();
             let (db, root) = mock_data();
             let tree = $tree::<TREE_DEPTH, Sha3>::new(&db, &root)
-                .unwrap()
                 .with_recorder(&mut recorder)
                 .build();
 
@@ -382,14 +433,12 @@ macro_rules! test_recorder_and_storage_proof {
             }
             let storage_proof = recorder.drain_storage_proof();
             let memory_db = storage_proof.into_memory_db::<Sha3>();
-            let tree = $tree::<TREE_DEPTH, Sha3>::new(&memory_db, &root)
-                .unwrap()
-                .build();
+            let tree = $tree::<TREE_DEPTH, Sha3>::new(&memory_db, &root).build();
 
             for data in TEST_DATA.iter() {
                 let actual_value = tree.value(&data.$selector).unwrap();
 
-                assert_eq!(actual_value, Some(data.2.to_vec()));
+                assert_eq!(actual_value, Some(DBValue::from(data.2)));
             }
         }
     };
@@ -411,3 +460,5538 @@ test_recorder_and_storage_proof!(
     mut IndexTreeDBMutBuilder,
     0
 );
+
+// TEST SUBSTRATE STORAGE PROOF
+// ================================================================================================
+#[cfg(all(feature = "substrate", not(feature = "smallvec")))]
+#[test]
+fn test_substrate_storage_proof_round_trip() {
+    let mut recorder = Recorder::new();
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+
+    for data in TEST_DATA.iter() {
+        let _ = tree.value(data.1).unwrap();
+    }
+    let storage_proof: sp_trie::StorageProof = recorder.drain_storage_proof().into();
+    let storage_proof: crate::StorageProof = storage_proof.into();
+    let memory_db = storage_proof.into_substrate_memory_db::<Sha3, memory_db::HashKey<_>>();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&memory_db, &root).build();
+
+    for data in TEST_DATA.iter() {
+        let actual_value = tree.value(data.1).unwrap();
+
+        assert_eq!(actual_value, Some(DBValue::from(data.2)));
+    }
+}
+
+// TEST DIGEST HASHER
+// ================================================================================================
+#[cfg(feature = "digest")]
+#[test]
+fn test_digest_hasher_matches_sha3() {
+    type DigestSha3 = crate::DigestHasher<Sha3_256, 32>;
+
+    for (_index, _key, value) in TEST_DATA.iter() {
+        assert_eq!(DigestSha3::hash(value), Sha3::hash(value));
+    }
+}
+
+// TEST IMPL HASHER
+// ================================================================================================
+crate::impl_hasher!(
+    /// Sha3 hasher generated by the impl_hasher macro.
+    MacroSha3, 32, |data: &[u8]| Sha3_256::digest(data).into()
+);
+
+#[test]
+fn test_impl_hasher_matches_sha3() {
+    for (_index, _key, value) in TEST_DATA.iter() {
+        assert_eq!(MacroSha3::hash(value), Sha3::hash(value));
+    }
+
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let mut macro_db = MemoryDB::<MacroSha3, NoopKey<MacroSha3>, DBValue>::default();
+    let mut macro_root = Default::default();
+    let mut macro_tree =
+        TreeDBMutBuilder::<TREE_DEPTH, MacroSha3>::new(&mut macro_db, &mut macro_root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        macro_tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+
+    assert_eq!(macro_tree.root(), tree.root());
+}
+
+// TEST NON 32 BYTE HASHERS
+// ================================================================================================
+crate::impl_hasher!(
+    /// 20 byte hasher constructed by truncating the Sha3 output.
+    Sha3Truncated20, 20, |data: &[u8]| {
+        let mut out = [0u8; 20];
+        out.copy_from_slice(&Sha3_256::digest(data)[..20]);
+        out
+    }
+);
+
+/// 64 byte hash output. Arrays larger than 32 bytes do not implement Default and as such a
+/// wrapper type is required.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hash64([u8; 64]);
+
+impl Default for Hash64 {
+    fn default() -> Self {
+        Hash64([0u8; 64])
+    }
+}
+
+impl AsRef<[u8]> for Hash64 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsMut<[u8]> for Hash64 {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+/// 64 byte Sha3 hasher.
+#[derive(Debug)]
+pub struct Sha3_64;
+
+impl Hasher for Sha3_64 {
+    type Out = Hash64;
+
+    type StdHasher = crate::OutputStdHasher;
+
+    const LENGTH: usize = 64;
+
+    fn hash(data: &[u8]) -> Self::Out {
+        Hash64(sha3::Sha3_512::digest(data).into())
+    }
+}
+
+impl TreeHasher for Sha3_64 {}
+
+/// Inserts the test data into a tree using hasher H and checks values, proofs and storage proofs.
+fn check_hasher<H: TreeHasher>() {
+    let mut db = MemoryDB::<H, NoopKey<H>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, H>::new(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, H>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let (actual_value, proof_root, proof) = tree.proof(key).unwrap();
+        assert!(proof.iter().all(|sibling| sibling.len() == H::LENGTH));
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, H>::verify(key, value, &proof, &proof_root),
+            Ok(true)
+        );
+        if !value.is_empty() {
+            assert_eq!(actual_value, Some(DBValue::from(&value[..])));
+        }
+    }
+
+    let memory_db = recorder.drain_storage_proof().into_memory_db::<H>();
+    let tree = TreeDBBuilder::<TREE_DEPTH, H>::new(&memory_db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+    }
+}
+
+#[test]
+fn test_20_byte_hasher() {
+    check_hasher::<Sha3Truncated20>();
+}
+
+#[test]
+fn test_64_byte_hasher() {
+    check_hasher::<Sha3_64>();
+}
+
+#[test]
+fn test_verify_rejects_sibling_of_wrong_length() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (value, root, mut proof) = tree.proof(TEST_DATA[0].1).unwrap();
+    proof[0].truncate(20);
+
+    assert!(matches!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(TEST_DATA[0].1, &value.unwrap(), &proof, &root),
+        Err(crate::TreeError::NodeError(_))
+    ));
+}
+
+#[test]
+fn test_index_key_conversion_bounds() {
+    use crate::key::Key;
+
+    assert!(Key::<2>::try_from(&u64::from(u16::MAX)).is_ok());
+    assert!(Key::<2>::try_from(&(1u64 << 16)).is_err());
+    assert_eq!(
+        Key::<16>::try_from(&u64::MAX).unwrap().as_slice(),
+        [[0u8; 8], [0xff; 8]].concat()
+    );
+}
+
+// TEST DUAL HASHER
+// ================================================================================================
+crate::impl_hasher!(
+    /// Keccak hasher used to hash leaves in the dual hasher tests.
+    Keccak, 32, |data: &[u8]| sha3::Keccak256::digest(data).into()
+);
+
+#[test]
+fn test_dual_hasher() {
+    type Dual = DualHasher<Keccak, Sha3>;
+
+    check_hasher::<Dual>();
+
+    let (_, sha3_root) = mock_data();
+    let mut db = MemoryDB::<Dual, NoopKey<Dual>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Dual>::new(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(
+            tree.leaf(key).unwrap(),
+            Some(proof_leaf_hash::<Dual>(key, value))
+        );
+    }
+    assert_ne!(tree.root(), &sha3_root);
+}
+
+// TEST SALTED TREES
+// ================================================================================================
+#[test]
+fn test_salted_tree() {
+    const SALT: &[u8] = b"salt";
+    let (_, unsalted_root) = mock_data();
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    assert_ne!(root, unsalted_root);
+
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .with_recorder(&mut recorder)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        let (_, proof_root, proof) = tree.proof(key).unwrap();
+        let preimage = [SALT, value].concat();
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify(key, &preimage, &proof, &proof_root),
+            Ok(true)
+        );
+    }
+
+    // with key-bound leaves the null leaf is not salted, see null_leaf_hash
+    let null_preimage: &[u8] = if cfg!(feature = "key-bound") {
+        &[]
+    } else {
+        SALT
+    };
+    for (_index, key, _value) in NON_INCLUSION_DATA.iter() {
+        let (_, proof_root, proof) = tree.proof(key).unwrap();
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify(key, null_preimage, &proof, &proof_root),
+            Ok(true)
+        );
+
+        // proofs of absence are verified against the salted null leaf
+        let (proof_root, proof) = tree.proof_of_absence(key).unwrap().unwrap();
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_salted_absence(key, &proof, &proof_root, SALT),
+            Ok(true)
+        );
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_absence(key, &proof, &proof_root),
+            Ok(cfg!(feature = "key-bound"))
+        );
+    }
+
+    let memory_db = recorder
+        .drain_storage_proof()
+        .into_salted_memory_db::<Sha3>(SALT);
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&memory_db, &root)
+        .with_salt(SALT)
+        .build();
+    for (index, _key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(index).unwrap(), Some(DBValue::from(&value[..])));
+    }
+}
+
+// TEST DEEP TREE
+// ================================================================================================
+#[cfg(feature = "std")]
+#[test]
+fn test_deep_tree_insert_and_remove() {
+    const DEEP_TREE_DEPTH: usize = 64;
+
+    // run on a thread with a small stack to ensure the insertion does not recurse per level
+    std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(|| {
+            let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+            let mut root = Default::default();
+            let mut tree =
+                TreeDBMutBuilder::<DEEP_TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+            let keys = [
+                [0u8; DEEP_TREE_DEPTH],
+                [1u8; DEEP_TREE_DEPTH],
+                [255u8; DEEP_TREE_DEPTH],
+            ];
+
+            for key in keys.iter() {
+                tree.insert(key, DBValue::from(&key[..8])).unwrap();
+            }
+            tree.commit();
+            assert_eq!(
+                tree.remove(&keys[1]).unwrap(),
+                Some(DBValue::from(&keys[1][..8]))
+            );
+            tree.commit();
+
+            assert_eq!(
+                tree.value(&keys[0]).unwrap(),
+                Some(DBValue::from(&keys[0][..8]))
+            );
+            assert_eq!(tree.value(&keys[1]).unwrap(), None);
+            assert_eq!(
+                tree.value(&keys[2]).unwrap(),
+                Some(DBValue::from(&keys[2][..8]))
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+// TEST LEAF MEMO
+// ================================================================================================
+#[test]
+fn test_leaf_memo_with_repeated_values() {
+    const SALT: &[u8] = b"salt";
+    let values: Vec<DBValue> = (0..100u8)
+        .map(|i| DBValue::from(&[i % 3, 1, 2, 3][..]))
+        .chain((0..300u16).map(|i| DBValue::from(&i.to_be_bytes()[..])))
+        .collect();
+
+    // insert all values through a single tree such that the leaf memo is reused and cleared
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for (index, value) in values.iter().enumerate() {
+        tree.insert(&(index as u64), value.clone()).unwrap();
+    }
+    tree.commit();
+
+    // insert each value through a fresh tree such that no leaf is memoized
+    let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut expected_root = Default::default();
+    for (index, value) in values.iter().enumerate() {
+        let mut tree =
+            IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+                .with_salt(SALT)
+                .build();
+        tree.insert(&(index as u64), value.clone()).unwrap();
+        tree.commit();
+    }
+    assert_eq!(root, expected_root);
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .build();
+    for (index, value) in values.iter().enumerate() {
+        let preimage = [SALT, value].concat();
+        let key = (index as u16).to_be_bytes();
+        assert_eq!(
+            tree.leaf(&(index as u64)).unwrap(),
+            Some(proof_leaf_hash::<Sha3>(&key, &preimage))
+        );
+    }
+}
+
+// TEST REFCOUNTED DB
+// ================================================================================================
+
+/// A database that overwrites records on emplace and deletes them on remove, as a plain
+/// key-value store would.
+#[cfg(not(feature = "key-bound"))]
+#[derive(Default)]
+struct OverwriteDB(HashMap<[u8; 32], DBValue>);
+
+#[cfg(not(feature = "key-bound"))]
+impl HashDB<Sha3, DBValue> for OverwriteDB {
+    fn get(&self, key: &[u8; 32], _prefix: Prefix) -> Option<DBValue> {
+        self.0.get(key).cloned()
+    }
+
+    fn contains(&self, key: &[u8; 32], _prefix: Prefix) -> bool {
+        self.0.contains_key(key)
+    }
+
+    fn insert(&mut self, prefix: Prefix, value: &[u8]) -> [u8; 32] {
+        let key = Sha3::hash(value);
+        self.emplace(key, prefix, DBValue::from(value));
+        key
+    }
+
+    fn emplace(&mut self, key: [u8; 32], _prefix: Prefix, value: DBValue) {
+        self.0.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8; 32], _prefix: Prefix) {
+        self.0.remove(key);
+    }
+}
+
+#[cfg(not(feature = "key-bound"))]
+impl AsHashDB<Sha3, DBValue> for OverwriteDB {
+    fn as_hash_db(&self) -> &dyn HashDB<Sha3, DBValue> {
+        self
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<Sha3, DBValue> + 'a) {
+        self
+    }
+}
+
+// with key-bound leaves identical values at different keys do not share a leaf
+#[cfg(not(feature = "key-bound"))]
+#[test]
+fn test_refcounted_db_shared_leaves() {
+    use super::RefCountedDB;
+
+    const SHARED_VALUE: &[u8] = b"shared";
+    let leaf = Sha3::hash(SHARED_VALUE);
+
+    let mut backend = OverwriteDB::default();
+    let mut db = RefCountedDB::new(&mut backend);
+
+    // two trees and two keys within the first tree share the same leaf
+    let mut root_a = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root_a).build();
+    tree.insert(&0, DBValue::from(SHARED_VALUE)).unwrap();
+    tree.insert(&1, DBValue::from(SHARED_VALUE)).unwrap();
+    tree.commit();
+
+    let mut root_b = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root_b).build();
+    tree.insert(&2, DBValue::from(SHARED_VALUE)).unwrap();
+    tree.commit();
+    assert_eq!(db.refcount(&leaf, EMPTY_PREFIX), 3);
+
+    // removing one of the shared leaves must not delete the shared record
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root_a).build();
+    tree.remove(&0).unwrap();
+    tree.commit();
+    assert_eq!(db.refcount(&leaf, EMPTY_PREFIX), 2);
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root_a).build();
+    assert_eq!(tree.value(&0).unwrap(), None);
+    assert_eq!(tree.value(&1).unwrap(), Some(DBValue::from(SHARED_VALUE)));
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root_b).build();
+    assert_eq!(tree.value(&2).unwrap(), Some(DBValue::from(SHARED_VALUE)));
+
+    // the record is deleted once the last reference is removed
+    for (index, root) in [(1, &mut root_a), (2, &mut root_b)] {
+        let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, root).build();
+        tree.remove(&index).unwrap();
+        tree.commit();
+    }
+    assert_eq!(db.refcount(&leaf, EMPTY_PREFIX), 0);
+    assert!(!HashDB::contains(&db, &leaf, EMPTY_PREFIX));
+    assert!(backend.0.is_empty());
+}
+
+// TEST MAX VALUE SIZE
+// ================================================================================================
+#[test]
+fn test_max_value_size() {
+    const MAX_VALUE_SIZE: usize = 6;
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_max_value_size(MAX_VALUE_SIZE)
+        .build();
+
+    assert_eq!(tree.insert(&0, DBValue::from(&b"value1"[..])), Ok(None));
+    assert_eq!(
+        tree.insert(&0, DBValue::from(&b"value10"[..])),
+        Err(TreeError::DataError(DataError::ValueTooLarge(
+            7,
+            MAX_VALUE_SIZE
+        )))
+    );
+    assert_eq!(tree.value(&0).unwrap(), Some(DBValue::from(&b"value1"[..])));
+    assert_eq!(
+        tree.remove(&0).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+}
+
+// TEST EXPIRY
+// ================================================================================================
+#[test]
+fn test_expiry_and_sweep() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let expiries = [Some(10), None, Some(20), Some(30)];
+    for ((index, _key, value), expiry) in TEST_DATA.iter().zip(expiries.iter()) {
+        match expiry {
+            Some(expiry) => tree.insert_with_expiry(index, DBValue::from(&value[..]), *expiry),
+            None => tree.insert(index, DBValue::from(&value[..])),
+        }
+        .unwrap();
+    }
+    tree.commit();
+
+    // the expiry is persisted and committed to by the leaf hash
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for ((index, _key, value), expiry) in TEST_DATA.iter().zip(expiries.iter()) {
+        let (proof_value, proof_root, proof) = tree.proof(index).unwrap();
+        assert_eq!(proof_value, Some(DBValue::from(&value[..])));
+        let preimage = match expiry {
+            Some(expiry) => [value, &expiry.to_be_bytes()[..]].concat(),
+            None => value.to_vec(),
+        };
+        assert_eq!(
+            IndexTreeDB::<TREE_DEPTH, Sha3>::verify(index, &preimage, &proof, &proof_root),
+            Ok(true)
+        );
+    }
+
+    // sweep removes the leaves that have expired
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(tree.expiry(&0).unwrap(), Some(10));
+    assert_eq!(tree.expiry(&100).unwrap(), None);
+    assert_eq!(tree.sweep(20).unwrap(), 2);
+    assert_eq!(tree.sweep(20).unwrap(), 0);
+    tree.commit();
+
+    let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut expected_root = Default::default();
+    let mut tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+            .build();
+    let (index, _key, value) = TEST_DATA[1];
+    tree.insert(&index, DBValue::from(value)).unwrap();
+    let (index, _key, value) = TEST_DATA[3];
+    tree.insert_with_expiry(&index, DBValue::from(value), 30)
+        .unwrap();
+    tree.commit();
+    assert_eq!(root, expected_root);
+}
+
+// TEST NAMESPACES
+// ================================================================================================
+#[test]
+fn test_namespaces() {
+    assert_eq!(
+        Namespace::<TREE_DEPTH>::new(16, 4),
+        Err(KeyError::NamespaceIdOutOfBounds(16, 4))
+    );
+    assert_eq!(
+        Namespace::<TREE_DEPTH>::new(0, 17),
+        Err(KeyError::BitIndexOutOfBounds(17, 16))
+    );
+
+    let alice = Namespace::<TREE_DEPTH>::new(1, 4).unwrap();
+    let bob = Namespace::<TREE_DEPTH>::new(2, 4).unwrap();
+    assert_eq!(alice.truncated_key(&[0xab, 0xcd]), [0x1a, 0xbc]);
+    assert_eq!(alice.truncated_key(&[0xab]), [0x1a, 0xb0]);
+    assert_eq!(bob.prefix(), [0x20, 0x00]);
+    assert!(alice.contains(&alice.hashed_key::<Sha3>(b"key")));
+    assert!(!bob.contains(&alice.hashed_key::<Sha3>(b"key")));
+
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let mut expected = Vec::new();
+    for (namespace, inner, value) in [
+        (alice, &b"key1"[..], &b"value1"[..]),
+        (alice, b"key2", b"value2"),
+        (bob, b"key1", b"value3"),
+    ] {
+        let key = namespace.hashed_key::<Sha3>(inner);
+        tree.insert(&key, DBValue::from(value)).unwrap();
+        expected.push((namespace, DBValue::from(&key[..]), DBValue::from(value)));
+    }
+    tree.commit();
+
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for namespace in [alice, bob] {
+        let mut namespace_expected: Vec<(DBValue, DBValue)> = expected
+            .iter()
+            .filter(|(ns, _, _)| ns == &namespace)
+            .map(|(_, key, value)| (key.clone(), value.clone()))
+            .collect();
+        namespace_expected.sort();
+        assert_eq!(
+            tree.namespace_entries(&namespace).unwrap(),
+            namespace_expected
+        );
+
+        let (subtree_root, proof_root, proof) = tree.namespace_proof(&namespace).unwrap();
+        assert_eq!(proof_root, root);
+        assert_eq!(
+            namespace.verify::<Sha3>(&subtree_root, &proof, &proof_root),
+            Ok(true)
+        );
+    }
+
+    let (alice_root, _, _) = tree.namespace_proof(&alice).unwrap();
+    let (_, _, bob_proof) = tree.namespace_proof(&bob).unwrap();
+    assert_eq!(
+        bob.verify::<Sha3>(&alice_root, &bob_proof, &root),
+        Ok(false)
+    );
+
+    let empty = Namespace::<TREE_DEPTH>::new(3, 4).unwrap();
+    assert_eq!(tree.namespace_entries(&empty).unwrap(), Vec::new());
+}
+
+// TEST JSON MAP
+// ================================================================================================
+#[cfg(feature = "json")]
+#[test]
+fn test_json_map_round_trip() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let mut json = Vec::new();
+    tree.to_json_map(&mut json).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&json).unwrap(),
+        r#"{"0000":"76616c756531","0064":"76616c756532","00c8":"76616c756533","012c":"76616c756534"}"#
+    );
+
+    let mut new_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut new_root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut new_db, &mut new_root).build();
+    assert_eq!(tree.from_json_map(&json[..]), Ok(TEST_DATA.len()));
+    assert_eq!(tree.root(), &root);
+
+    assert!(matches!(
+        tree.from_json_map(&br#"{"0x0001": 1}"#[..]),
+        Err(TreeError::DataError(DataError::JsonError(_)))
+    ));
+    assert_eq!(
+        tree.from_json_map(&br#"{"000102": "00"}"#[..]),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 3)))
+    );
+}
+
+// TEST KEYED VIEW
+// ================================================================================================
+#[test]
+fn test_keyed_view_of_index_tree() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&1, DBValue::from(&b"value5"[..])).unwrap();
+    let key = IndexTreeDBMut::<TREE_DEPTH, Sha3>::index_to_key(&1).unwrap();
+    assert_eq!(key.as_slice(), &[0, 1]);
+    assert_eq!(
+        tree.as_keyed().value(key.as_slice()).unwrap(),
+        Some(DBValue::from(&b"value5"[..]))
+    );
+    assert_eq!(
+        tree.as_keyed().entries().unwrap().len(),
+        TEST_DATA.len() + 1
+    );
+    tree.commit();
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (index, key, value) in TEST_DATA.iter() {
+        let index_key = IndexTreeDB::<TREE_DEPTH, Sha3>::index_to_key(index).unwrap();
+        assert_eq!(index_key.as_slice(), *key);
+        assert_eq!(
+            tree.as_keyed().value(key).unwrap(),
+            Some(DBValue::from(&value[..]))
+        );
+    }
+    assert_eq!(
+        IndexTreeDB::<TREE_DEPTH, Sha3>::index_to_key(&(1 << 16)).err(),
+        Some(TreeError::KeyError(KeyError::LeafIndexOutOfBounds(
+            1 << 16,
+            1 << 16
+        )))
+    );
+}
+
+// TEST KEYED MEMORY DB
+// ================================================================================================
+#[test]
+fn test_storage_proof_into_keyed_memory_db() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    for (index, _key, _value) in TEST_DATA.iter() {
+        tree.value(index).unwrap();
+    }
+    let storage_proof = recorder.drain_storage_proof();
+
+    // nodes keyed by a hashed key function are readable by a tree
+    let hash_key_db = storage_proof.into_keyed_memory_db::<Sha3, HashKey<Sha3>>(&[], EMPTY_PREFIX);
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&hash_key_db, &root).build();
+    for (index, _key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(index).unwrap(), Some(DBValue::from(&value[..])));
+    }
+
+    // nodes keyed by a prefixed key function are stored under the provided prefix
+    let mut recorder = Recorder::new();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    tree.value(&0).unwrap();
+    let prefix: Prefix = (b"tree", None);
+    let prefixed_db = recorder
+        .drain_storage_proof()
+        .into_keyed_memory_db::<Sha3, PrefixedKey<Sha3>>(&[], prefix);
+    assert!(HashDB::contains(&prefixed_db, &root, prefix));
+    assert!(!HashDB::contains(&prefixed_db, &root, EMPTY_PREFIX));
+}
+
+// TEST ANTI-ENTROPY
+// ================================================================================================
+#[test]
+fn test_compare_and_repair_backends() {
+    let (db, root) = mock_data();
+    let (mut replica, _) = mock_data();
+    assert_eq!(
+        compare_backends::<TREE_DEPTH, Sha3>(&root, &db, &replica, &[]),
+        Vec::new()
+    );
+
+    // remove a leaf and corrupt an inner node in the replica
+    let (_, key, value) = TEST_DATA[0];
+    let leaf = proof_leaf_hash::<Sha3>(key, value);
+    HashDB::remove(&mut replica, &leaf, EMPTY_PREFIX);
+    while HashDB::contains(&replica, &root, EMPTY_PREFIX) {
+        HashDB::remove(&mut replica, &root, EMPTY_PREFIX);
+    }
+    HashDB::emplace(
+        &mut replica,
+        root,
+        EMPTY_PREFIX,
+        DBValue::from(&b"corrupt"[..]),
+    );
+    assert_eq!(
+        compare_backends::<TREE_DEPTH, Sha3>(&root, &db, &replica, &[]),
+        Vec::from([
+            Divergence::Corrupt(root, Backend::Second),
+            Divergence::Missing(leaf, Backend::Second),
+        ])
+    );
+    assert_eq!(
+        compare_backends::<TREE_DEPTH, Sha3>(&root, &replica, &db, &[]),
+        Vec::from([
+            Divergence::Corrupt(root, Backend::First),
+            Divergence::Missing(leaf, Backend::First),
+        ])
+    );
+
+    // a node that is not intact in either backend is lost
+    let mut broken = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    assert_eq!(
+        compare_backends::<TREE_DEPTH, Sha3>(&root, &replica, &broken, &[]),
+        Vec::from([Divergence::Lost(root)])
+    );
+    assert_eq!(
+        repair_backend::<TREE_DEPTH, Sha3>(&root, &replica, &mut broken, &[]),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+            root.to_vec()
+        )))
+    );
+
+    // repair copies the missing and corrupt nodes across
+    assert_eq!(
+        repair_backend::<TREE_DEPTH, Sha3>(&root, &db, &mut replica, &[]),
+        Ok(2)
+    );
+    assert_eq!(
+        compare_backends::<TREE_DEPTH, Sha3>(&root, &db, &replica, &[]),
+        Vec::new()
+    );
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&replica, &root).build();
+    for (index, _key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(index).unwrap(), Some(DBValue::from(&value[..])));
+    }
+}
+
+// TEST RECORDER STATS
+// ================================================================================================
+#[test]
+fn test_recorder_stats() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    assert_eq!(recorder.stats(), RecorderStats::default());
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    tree.value(&0).unwrap();
+
+    let stats = recorder.coverage::<TREE_DEPTH>(&root, &db, &[]).unwrap();
+    assert_eq!(stats.nodes, TREE_DEPTH * 8 + 1);
+    assert_eq!(stats.leaves, 1);
+    assert_eq!(stats.tree_nodes, Some(db.keys().len()));
+    assert_eq!(
+        stats.coverage(),
+        Some((TREE_DEPTH * 8 + 1) as f64 / db.keys().len() as f64)
+    );
+    assert_eq!(
+        stats.encoded_size,
+        recorder
+            .drain_storage_proof()
+            .into_nodes()
+            .iter()
+            .map(|node| node.len())
+            .sum::<usize>()
+    );
+}
+
+// TEST NODE MEMO
+// ================================================================================================
+
+/// A database that counts the number of reads from the wrapped database.
+struct CountingDB<'a> {
+    db: &'a MemoryDB<Sha3, NoopKey<Sha3>, DBValue>,
+    reads: core::cell::Cell<usize>,
+}
+
+impl<'a> HashDBRef<Sha3, DBValue> for CountingDB<'a> {
+    fn get(&self, key: &[u8; 32], prefix: Prefix) -> Option<DBValue> {
+        self.reads.set(self.reads.get() + 1);
+        HashDB::get(self.db, key, prefix)
+    }
+
+    fn contains(&self, key: &[u8; 32], prefix: Prefix) -> bool {
+        HashDB::contains(self.db, key, prefix)
+    }
+}
+
+#[test]
+fn test_node_memo_skips_repeated_reads() {
+    let (db, root) = mock_data();
+    let counting_db = CountingDB {
+        db: &db,
+        reads: core::cell::Cell::new(0),
+    };
+    let mut recorder = Recorder::new();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&counting_db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+
+    assert_eq!(tree.value(&0).unwrap(), Some(DBValue::from(&b"value1"[..])));
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+    assert_eq!(tree.value(&0).unwrap(), Some(DBValue::from(&b"value1"[..])));
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+
+    // the recorder captures the nodes read through the memo
+    let memory_db = recorder.drain_storage_proof().into_memory_db::<Sha3>();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&memory_db, &root).build();
+    assert_eq!(tree.value(&0).unwrap(), Some(DBValue::from(&b"value1"[..])));
+}
+
+// TEST REGISTRY
+// ================================================================================================
+
+#[test]
+fn test_registry_propagates_child_roots() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = <Sha3 as Hasher>::Out::default();
+    let mut registry = Registry::<1, Sha3>::new(&mut db, &mut root);
+    assert_eq!(registry.child_root(&[1]).unwrap(), None);
+
+    registry
+        .update::<TREE_DEPTH, _>(&[1], |tree| {
+            tree.insert(&[0, 1], DBValue::from(&b"value1"[..]))
+        })
+        .unwrap();
+    registry
+        .update::<TREE_DEPTH, _>(&[2], |tree| {
+            tree.insert(&[0, 2], DBValue::from(&b"value2"[..]))
+        })
+        .unwrap();
+
+    // the parent tree commits to the root of each child tree
+    let child_root = registry.child_root(&[1]).unwrap().unwrap();
+    let value = registry
+        .read::<TREE_DEPTH, _>(&[1], |tree| {
+            assert_eq!(tree.root(), &child_root);
+            tree.value(&[0, 1]).unwrap()
+        })
+        .unwrap();
+    assert_eq!(value, Some(DBValue::from(&b"value1"[..])));
+
+    // a failed update leaves the registry unchanged
+    let parent_root = *registry.root();
+    let result = registry.update::<TREE_DEPTH, ()>(&[1], |tree| {
+        tree.insert(&[0, 3], DBValue::from(&b"value3"[..]))?;
+        Err(TreeError::DataError(DataError::InMemoryNotSupported))
+    });
+    assert!(result.is_err());
+    assert_eq!(registry.root(), &parent_root);
+    assert_eq!(registry.child_root(&[1]).unwrap(), Some(child_root));
+
+    assert!(registry.remove(&[2]).unwrap().is_some());
+    assert_eq!(registry.child_root(&[2]).unwrap(), None);
+
+    let tree = TreeDBBuilder::<1, Sha3>::new(&db, &root).build();
+    assert_eq!(
+        tree.value(&[1]).unwrap(),
+        Some(DBValue::from(child_root.as_ref()))
+    );
+}
+
+// TEST NODE DUMP
+// ================================================================================================
+#[test]
+fn test_nodes_dump() {
+    let (db, root) = mock_data();
+    let dump = nodes::<TREE_DEPTH, Sha3>(&db, &root, &[])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(dump.first().map(|(hash, _)| *hash), Some(root));
+
+    // the order only depends on the content of the tree
+    let again = nodes::<TREE_DEPTH, Sha3>(&db, &root, &[])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(dump, again);
+
+    // the dump restores the tree into an empty database
+    let mut restored = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    for (hash, node) in dump.iter() {
+        restored.emplace(*hash, EMPTY_PREFIX, DBValue::from(&node[..]));
+    }
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&restored, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+    }
+
+    // a missing node ends the iterator with an error
+    let (child, _) = &dump[1];
+    HashDB::remove(&mut restored, child, EMPTY_PREFIX);
+    let mut iter = nodes::<TREE_DEPTH, Sha3>(&restored, &root, &[]);
+    assert!(iter.next().unwrap().is_ok());
+    assert_eq!(
+        iter.next().unwrap(),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+            child.to_vec()
+        )))
+    );
+    assert!(iter.next().is_none());
+}
+
+// TEST BUILD HASHER
+// ================================================================================================
+#[test]
+fn test_custom_build_hasher() {
+    type RandomState = hashbrown::hash_map::DefaultHashBuilder;
+    let (_, expected_root) = mock_data();
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3, RandomState>::new(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    assert_eq!(root, expected_root);
+
+    let mut recorder = Recorder::<Sha3, RandomState>::with_hasher(RandomState::default());
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    tree.value(TEST_DATA[0].1).unwrap();
+    assert_eq!(recorder.stats().nodes, TREE_DEPTH * 8 + 1);
+}
+
+// TEST TEST UTILS
+// ================================================================================================
+#[cfg(feature = "test-utils")]
+#[test]
+fn test_populated_test_utils_dbs() {
+    use crate::test_utils::{populated_index_db, populated_keyed_db};
+    let (_, expected_root) = mock_data();
+
+    let entries = TEST_DATA.map(|(_index, key, value)| (key, value));
+    let (db, root) = populated_keyed_db::<TREE_DEPTH>(&entries);
+    assert_eq!(root, expected_root);
+    let tree = TreeDBBuilder::<TREE_DEPTH, crate::test_utils::Sha3>::new(&db, &root).build();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+
+    let entries = TEST_DATA.map(|(index, _key, value)| (index, value));
+    let (_, root) = populated_index_db::<TREE_DEPTH>(&entries);
+    assert_eq!(root, expected_root);
+}
+
+// TEST PROOF ENVELOPE
+// ================================================================================================
+#[test]
+fn test_proof_envelope() {
+    let (db, root) = mock_data();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (index, _key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let envelope =
+            ProofEnvelope::<TREE_DEPTH, Sha3>::from_index(*index, tree.proof(index).unwrap())
+                .unwrap();
+        let encoded = envelope.encode();
+        let decoded = ProofEnvelope::<TREE_DEPTH, Sha3>::decode(&encoded).unwrap();
+        assert_eq!(decoded.encode(), encoded);
+        assert_eq!(decoded.root(), &root);
+        assert_eq!(decoded.value().is_some(), !value.is_empty());
+        assert_eq!(decoded.verify(), Ok(true));
+    }
+
+    let envelope = ProofEnvelope::<TREE_DEPTH, Sha3>::new(
+        TEST_DATA[0].1,
+        tree.as_keyed().proof(TEST_DATA[0].1).unwrap(),
+    )
+    .unwrap();
+    let encoded = envelope.encode();
+
+    // mismatched parameters are rejected with a diagnostic
+    assert_eq!(
+        ProofEnvelope::<3, Sha3>::decode(&encoded).err(),
+        Some(TreeError::ProofError(ProofError::DepthMismatch(
+            24,
+            TREE_DEPTH * 8
+        )))
+    );
+    let mut tampered = encoded.clone();
+    tampered[5] ^= 1;
+    assert!(matches!(
+        ProofEnvelope::<TREE_DEPTH, Sha3>::decode(&tampered),
+        Err(TreeError::ProofError(ProofError::HasherMismatch(_, _)))
+    ));
+    tampered[0] = 2;
+    assert!(matches!(
+        ProofEnvelope::<TREE_DEPTH, Sha3>::decode(&tampered),
+        Err(TreeError::ProofError(ProofError::UnsupportedVersion(2)))
+    ));
+    assert!(matches!(
+        ProofEnvelope::<TREE_DEPTH, Sha3>::decode(&encoded[..encoded.len() - 1]),
+        Err(TreeError::ProofError(ProofError::UnexpectedEnd(_)))
+    ));
+}
+
+// TEST PROGRESS AND CANCELLATION
+// ================================================================================================
+#[test]
+fn test_progress_and_cancellation() {
+    let (db, root) = mock_data();
+    let node_count = nodes::<TREE_DEPTH, Sha3>(&db, &root, &[]).count();
+
+    // progress is reported every interval nodes and once on completion
+    let mut reports = Vec::new();
+    let mut progress = |processed: usize| reports.push(processed);
+    let mut monitor = Monitor::new().with_progress(&mut progress).with_interval(4);
+    let divergences =
+        compare_backends_with_monitor::<TREE_DEPTH, Sha3>(&root, &db, &db, &[], &mut monitor)
+            .unwrap();
+    assert!(divergences.is_empty());
+    assert_eq!(monitor.processed(), node_count);
+    let mut expected = (4..=node_count).step_by(4).collect::<Vec<_>>();
+    expected.push(node_count);
+    assert_eq!(reports, expected);
+
+    // a cancelled token stops the operation before the next node is processed
+    let token = CancellationToken::new();
+    let mut target = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut cancel = |processed: usize| {
+        if processed == 3 {
+            token.cancel();
+        }
+    };
+    let mut monitor = Monitor::new()
+        .with_progress(&mut cancel)
+        .with_cancellation(&token)
+        .with_interval(1);
+    assert_eq!(
+        repair_backend_with_monitor::<TREE_DEPTH, Sha3>(&root, &db, &mut target, &[], &mut monitor),
+        Err(TreeError::Cancelled(3))
+    );
+
+    // the export ends with an error once cancelled
+    let iter = nodes::<TREE_DEPTH, Sha3>(&db, &root, &[])
+        .with_monitor(Monitor::new().with_cancellation(&token));
+    let exported = iter.collect::<Vec<_>>();
+    assert_eq!(exported, Vec::from([Err(TreeError::Cancelled(0))]));
+
+    // an uncancelled export yields every node
+    let fresh = CancellationToken::new();
+    let iter = nodes::<TREE_DEPTH, Sha3>(&db, &root, &[])
+        .with_monitor(Monitor::new().with_cancellation(&fresh));
+    assert_eq!(iter.filter(Result::is_ok).count(), node_count);
+}
+
+// TEST PROOF OF ABSENCE
+// ================================================================================================
+#[test]
+fn test_proof_of_absence() {
+    let (mut db, mut root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, _value) in NON_INCLUSION_DATA.iter() {
+        let (proof_root, proof) = tree.proof_of_absence(key).unwrap().unwrap();
+        assert_eq!(proof_root, root);
+        // the proof ends at the first default node along the path
+        assert!(proof.len() < TREE_DEPTH * 8);
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_absence(key, &proof, &root),
+            Ok(true)
+        );
+        // the proof does not prove the absence of a key in a different subtree
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_absence(&[1, 44], &proof, &root),
+            Ok(false)
+        );
+    }
+
+    // keys in the tree have no proof of absence
+    for (_index, key, _value) in TEST_DATA.iter() {
+        assert_eq!(tree.proof_of_absence(key).unwrap(), None);
+    }
+
+    // the proof of absence of a key is invalidated once the key is inserted
+    let (_, proof) = tree.proof_of_absence(&[0, 50]).unwrap().unwrap();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 50], DBValue::from(&b"value5"[..]))
+        .unwrap();
+    assert_eq!(tree.proof_of_absence(&[0, 50]).unwrap(), None);
+    let new_root = *KeyedTreeMut::root(&mut tree);
+    assert_eq!(
+        TreeDBMut::<TREE_DEPTH, Sha3>::verify_absence(&[0, 50], &proof, &new_root),
+        Ok(false)
+    );
+
+    // the proof of absence in an empty tree is empty
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let (empty_root, proof) = tree.proof_of_absence(&[0, 50]).unwrap().unwrap();
+    assert!(proof.is_empty());
+    assert_eq!(
+        TreeDBMut::<TREE_DEPTH, Sha3>::verify_absence(&[0, 50], &proof, &empty_root),
+        Ok(true)
+    );
+}
+
+// TEST COMPACT PROOF
+// ================================================================================================
+#[test]
+fn test_compact_proof() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, _value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let (value, proof_root, compact) = tree.proof_compact(key).unwrap();
+        let value = value.unwrap_or_default();
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_compact(key, &value, &compact, &proof_root),
+            Ok(true)
+        );
+
+        // default siblings are omitted and recomputed on decompression
+        let (_, _, proof) = tree.proof(key).unwrap();
+        assert!(compact.siblings().len() < proof.len());
+        assert_eq!(compact.bitmask().len(), TREE_DEPTH);
+        assert_eq!(
+            compact.decompress::<Sha3>(TREE_DEPTH * 8, &[]).unwrap(),
+            proof
+        );
+    }
+
+    // malformed compact proofs are rejected
+    let (value, proof_root, compact) = tree.proof_compact(&[0, 0]).unwrap();
+    let value = value.unwrap();
+    let truncated = CompactProof::new(compact.bitmask()[1..].to_vec(), compact.siblings().to_vec());
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify_compact(&[0, 0], &value, &truncated, &proof_root),
+        Err(TreeError::ProofError(ProofError::InvalidBitmask(
+            compact.bitmask()[1..].to_vec()
+        )))
+    );
+    let missing = CompactProof::new(compact.bitmask().to_vec(), compact.siblings()[1..].to_vec());
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify_compact(&[0, 0], &value, &missing, &proof_root),
+        Err(TreeError::ProofError(ProofError::SiblingCountMismatch(
+            compact.siblings().len(),
+            compact.siblings().len() - 1
+        )))
+    );
+
+    // the default siblings of a salted tree are the salted null hashes
+    const SALT: &[u8] = b"salt";
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        let (_, proof_root, compact) = tree.proof_compact(key).unwrap();
+        let (_, _, proof) = tree.proof(key).unwrap();
+        assert!(compact.siblings().len() < proof.len());
+        assert_eq!(
+            compact.decompress::<Sha3>(TREE_DEPTH * 8, SALT).unwrap(),
+            proof
+        );
+        let preimage = [SALT, value].concat();
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_salted_compact(
+                key,
+                &preimage,
+                &compact,
+                &proof_root,
+                SALT
+            ),
+            Ok(true)
+        );
+    }
+}
+
+// TEST MERKLE PROOF
+// ================================================================================================
+#[test]
+fn test_merkle_proof() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let (_, proof_root, proof) = tree.merkle_proof(key).unwrap();
+        assert_eq!(proof.key(), *key);
+        assert_eq!(proof.depth(), TREE_DEPTH * 8);
+        assert!(proof.verify(&proof_root));
+        assert!(proof.verify_value(value, &proof_root));
+        assert!(!proof.verify_value(b"other", &proof_root));
+    }
+
+    // the proof is bound to its key
+    let (value, _, proof) = tree.merkle_proof(&[0, 0]).unwrap();
+    let moved = MerkleProof::<Sha3, TREE_DEPTH>::from_value(
+        &[0, 100],
+        &value.unwrap(),
+        proof.siblings().to_vec(),
+    )
+    .unwrap();
+    assert!(!moved.verify(&root));
+
+    // a proof can not be constructed for a different key size or depth
+    assert!(matches!(
+        MerkleProof::<Sha3, 3>::new(&[0, 0, 1], *proof.leaf(), proof.siblings().to_vec()),
+        Err(TreeError::ProofError(ProofError::DepthMismatch(24, 16)))
+    ));
+    assert_eq!(
+        MerkleProof::<Sha3, TREE_DEPTH>::new(&[0, 1, 2], *proof.leaf(), Vec::new()).err(),
+        Some(TreeError::KeyError(KeyError::IncorrectKeySize(2, 3)))
+    );
+
+    // index trees produce proofs bound to the key of the index
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (_, proof_root, proof) = tree.merkle_proof(&300).unwrap();
+    assert_eq!(proof.key(), &[1, 44]);
+    assert!(proof.verify_value(b"value4", &proof_root));
+}
+
+// TEST STORAGE PROOF VERIFICATION
+// ================================================================================================
+#[test]
+fn test_storage_proof_verify_against_root() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    let keys: [&[u8]; 2] = [&[0, 0], &[0, 100]];
+    for key in keys.iter() {
+        tree.value(key).unwrap();
+    }
+    let leaf = tree.leaf(&[0, 0]).unwrap().unwrap();
+    let nodes = recorder.drain_storage_proof().into_nodes();
+    let proof = StorageProof::new(nodes.iter().cloned());
+    assert_eq!(
+        proof.verify_against_root::<Sha3, TREE_DEPTH>(&root, &keys),
+        Ok(())
+    );
+
+    // the path of a key that was not read is not resolved by the proof
+    let result = proof.verify_against_root::<Sha3, TREE_DEPTH>(&root, &[&[1, 44]]);
+    assert!(matches!(
+        result,
+        Err(TreeError::ProofError(ProofError::MissingNodes(missing))) if missing.len() == 1
+    ));
+
+    // a proof missing the leaf of a key lists the hash of the leaf
+    let incomplete = StorageProof::new(
+        nodes
+            .iter()
+            .filter(|node| node[..] != encoded_leaf(&[0, 0], b"value1")[..])
+            .cloned(),
+    );
+    assert_eq!(
+        incomplete.verify_against_root::<Sha3, TREE_DEPTH>(&root, &keys),
+        Err(TreeError::ProofError(ProofError::MissingNodes(Vec::from(
+            [leaf.to_vec()]
+        ))))
+    );
+    assert_eq!(
+        incomplete.covers::<Sha3, TREE_DEPTH>(&root, &keys[1..]),
+        Ok(())
+    );
+    assert_eq!(
+        incomplete.covers::<Sha3, TREE_DEPTH>(&root, &keys),
+        incomplete.verify_against_root::<Sha3, TREE_DEPTH>(&root, &keys)
+    );
+
+    // nodes that can not be decoded are rejected
+    let invalid = StorageProof::new(nodes.iter().cloned().chain([DBValue::from(&[9, 9][..])]));
+    assert_eq!(
+        invalid.verify_against_root::<Sha3, TREE_DEPTH>(&root, &keys),
+        Err(TreeError::ProofError(ProofError::InvalidNode(Vec::from([
+            9, 9
+        ]))))
+    );
+
+    // the empty tree is resolved without any nodes
+    assert_eq!(
+        StorageProof::empty().verify_against_root::<Sha3, TREE_DEPTH>(&Default::default(), &keys),
+        Ok(())
+    );
+}
+
+// TEST UPDATE PROOF
+// ================================================================================================
+#[test]
+fn test_update_proof() {
+    let (mut db, mut root) = mock_data();
+    let old_root = root;
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    // insertion of an absent key
+    let insert = tree
+        .insert_and_prove(&[0, 50], DBValue::from(&b"value5"[..]))
+        .unwrap();
+    assert_eq!(insert.old_root(), &old_root);
+    assert_eq!(insert.old_value(), None);
+    assert_eq!(verify_update(&insert), Ok(true));
+
+    // update of an existing key chains from the previous new root
+    let update = tree
+        .insert_and_prove(&[0, 100], DBValue::from(&b"value6"[..]))
+        .unwrap();
+    assert_eq!(update.old_root(), insert.new_root());
+    assert_eq!(update.old_value(), Some(&DBValue::from(&b"value2"[..])));
+    assert_eq!(update.verify(), Ok(true));
+
+    // removal of a key
+    let removal = tree.insert_and_prove(&[1, 44], DBValue::new()).unwrap();
+    assert_eq!(removal.new_value(), None);
+    assert_eq!(verify_update(&removal), Ok(true));
+    tree.commit();
+    assert_eq!(removal.new_root(), &root);
+
+    // a transition to a different value is rejected
+    let forged = UpdateProof::<Sha3, TREE_DEPTH>::new(
+        update.key(),
+        (update.old_value().cloned(), *update.old_root()),
+        (Some(DBValue::from(&b"forged"[..])), *update.new_root()),
+        update.siblings().to_vec(),
+    )
+    .unwrap();
+    assert_eq!(verify_update(&forged), Ok(false));
+}
+
+// TEST CONSISTENCY PROOF
+// ================================================================================================
+#[test]
+fn test_consistency_proof() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let value = |index: u64| DBValue::from(&index.to_be_bytes()[..]);
+    for index in 0..3 {
+        tree.insert(&index, value(index)).unwrap();
+    }
+    let old_root = *tree.root();
+
+    // the tree extended by appending leaves is consistent with the old tree
+    assert!(tree
+        .consistency_proof(3)
+        .unwrap()
+        .verify(&old_root, &old_root));
+    for index in 3..7 {
+        tree.insert(&index, value(index)).unwrap();
+    }
+    let new_root = *tree.root();
+    let proof = tree.consistency_proof(3).unwrap();
+    assert_eq!(proof.compute_old_root(&[]), old_root);
+    assert!(proof.verify(&old_root, &new_root));
+
+    // the proof is bound to the size of the old tree
+    assert!(!tree
+        .consistency_proof(2)
+        .unwrap()
+        .verify(&old_root, &new_root));
+    assert!(!tree
+        .consistency_proof(4)
+        .unwrap()
+        .verify(&old_root, &new_root));
+
+    // a tree in which a previously written index changed is not consistent
+    tree.insert(&1, value(100)).unwrap();
+    let changed_root = *tree.root();
+    let proof = tree.consistency_proof(3).unwrap();
+    assert!(!proof.verify(&old_root, &changed_root));
+    tree.commit();
+
+    // the immutable tree proves that it holds no leaves beyond its size
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let proof = tree.consistency_proof(7).unwrap();
+    assert!(proof.verify(&root, &root));
+    assert!(!tree.consistency_proof(6).unwrap().verify(&root, &root));
+
+    // the old root of a salted tree is computed from the salted null hashes
+    const SALT: &[u8] = b"salt";
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for index in 0..3 {
+        tree.insert(&index, value(index)).unwrap();
+    }
+    let old_root = *tree.root();
+    assert!(tree
+        .consistency_proof(3)
+        .unwrap()
+        .verify_salted(&old_root, &old_root, SALT));
+    for index in 3..7 {
+        tree.insert(&index, value(index)).unwrap();
+    }
+    let new_root = *tree.root();
+    let proof = tree.consistency_proof(3).unwrap();
+    assert_eq!(proof.compute_old_root(SALT), old_root);
+    assert!(proof.verify_salted(&old_root, &new_root, SALT));
+    // with key-bound leaves the null leaf is not salted, see null_leaf_hash
+    assert_eq!(
+        proof.verify(&old_root, &new_root),
+        cfg!(feature = "key-bound")
+    );
+}
+
+// TEST CIRCUIT WITNESS
+// ================================================================================================
+#[test]
+fn test_circuit_witness() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let witness = tree.circuit_witness(key).unwrap();
+        assert_eq!(witness.leaf_preimage(), leaf_preimage(key, value));
+        assert_eq!(witness.path().len(), TREE_DEPTH * 8);
+        assert_eq!(witness.root(), &root);
+        assert_eq!(witness.compute_root(), root);
+
+        // the directions are the bits of the key from the leaf to the root
+        let path = u16::from_be_bytes([key[0], key[1]]);
+        let directions = witness.path().iter().map(|(direction, _)| *direction);
+        assert!(directions.eq((0..16).map(|bit| (path >> bit) & 1 == 1)));
+    }
+
+    // the leaf preimage includes the salt and the expiry committed to by the leaf hash
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(b"salt")
+        .build();
+    tree.insert_with_expiry(&[0, 1], DBValue::from(&b"value"[..]), 7)
+        .unwrap();
+    let witness = tree.circuit_witness(&[0, 1]).unwrap();
+    assert_eq!(
+        witness.leaf_preimage(),
+        leaf_preimage(
+            &[0, 1],
+            &[&b"salt"[..], b"value", &7u64.to_be_bytes()].concat()
+        )
+    );
+    assert_eq!(&Sha3::hash(witness.leaf_preimage()), witness.leaf());
+    assert_eq!(witness.compute_root(), *KeyedTreeMut::root(&mut tree));
+}
+
+// TEST SUBTREE PROOF
+// ================================================================================================
+#[test]
+fn test_subtree_proof() {
+    let (mut db, mut root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+
+    let (subtree_root, proof_root, proof) = tree.subtree_proof(&[0, 0], 0).unwrap();
+    assert_eq!((subtree_root, proof_root, proof.len()), (root, root, 0));
+
+    for depth in [1, 8, 12, 16] {
+        let (subtree_root, proof_root, proof) = tree.subtree_proof(&[0, 200], depth).unwrap();
+        assert_eq!(proof_root, root);
+        assert_eq!(proof.len(), depth);
+        assert_eq!(
+            verify_subtree::<Sha3, TREE_DEPTH>(&[0, 200], depth, &subtree_root, &proof, &root),
+            Ok(true)
+        );
+        assert_eq!(
+            verify_subtree::<Sha3, TREE_DEPTH>(&[128, 200], depth, &subtree_root, &proof, &root),
+            Ok(false)
+        );
+    }
+
+    // bits below the subtree root are ignored
+    assert_eq!(
+        tree.subtree_proof(&[0, 0], 8).unwrap(),
+        tree.subtree_proof(&[0, 255], 8).unwrap()
+    );
+    assert_eq!(
+        tree.subtree_proof(&[0, 0], 17),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(17, 16)))
+    );
+    assert_eq!(
+        verify_subtree::<Sha3, TREE_DEPTH>(&[0, 0], 17, &root, &[], &root),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(17, 16)))
+    );
+
+    // the subtree root of an untouched subtree is unchanged by updates in another subtree
+    let (account_root, _, _) = tree.subtree_proof(&[1, 0], 8).unwrap();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 7], DBValue::from(&b"value"[..])).unwrap();
+    let (updated_root, new_root, proof) = tree.subtree_proof(&[1, 0], 8).unwrap();
+    assert_eq!(updated_root, account_root);
+    assert_eq!(
+        verify_subtree::<Sha3, TREE_DEPTH>(&[1, 0], 8, &account_root, &proof, &new_root),
+        Ok(true)
+    );
+}
+
+// TEST LEAF PROOF
+// ================================================================================================
+#[test]
+fn test_leaf_proof() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, _value) in TEST_DATA.iter() {
+        let (leaf, proof_root, proof) = tree.proof_for_leaf(key).unwrap();
+        let leaf = leaf.unwrap();
+        assert_eq!(proof_root, root);
+        assert_eq!(Some(leaf), tree.leaf(key).unwrap());
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_leaf(key, &leaf, &proof, &root),
+            Ok(true)
+        );
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_leaf(&[1, 1], &leaf, &proof, &root),
+            Ok(false)
+        );
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_leaf(key, &Sha3::hash(b"other"), &proof, &root),
+            Ok(false)
+        );
+    }
+
+    let (leaf, _, proof) = tree.proof_for_leaf(&[0, 0]).unwrap();
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify_leaf(&[0, 0], &leaf.unwrap(), &proof[1..], &root),
+        Err(TreeError::ProofError(ProofError::DepthMismatch(16, 15)))
+    );
+
+    // leaf proofs verify against salted trees without the salt or the value
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(b"salt")
+        .build();
+    tree.insert(&[0, 7], DBValue::from(&b"large blob"[..]))
+        .unwrap();
+    let (leaf, proof_root, proof) = tree.proof_for_leaf(&[0, 7]).unwrap();
+    assert_eq!(
+        TreeDBMut::<TREE_DEPTH, Sha3>::verify_leaf(&[0, 7], &leaf.unwrap(), &proof, &proof_root),
+        Ok(true)
+    );
+
+    // index trees verify leaf hashes at an index
+    let (db, root) = mock_data();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (_, _, proof) = tree.proof(&300).unwrap();
+    let leaf = tree.leaf(&300).unwrap().unwrap();
+    assert_eq!(
+        IndexTreeDB::<TREE_DEPTH, Sha3>::verify_leaf(&300, &leaf, &proof, &root),
+        Ok(true)
+    );
+    assert_eq!(
+        IndexTreeDBMut::<TREE_DEPTH, Sha3>::verify_leaf(&200, &leaf, &proof, &root),
+        Ok(false)
+    );
+    assert!(IndexTreeDB::<TREE_DEPTH, Sha3>::verify_leaf(&300, &leaf, &proof[1..], &root).is_err());
+}
+
+// TEST INSERT FROM PROOF
+// ================================================================================================
+#[test]
+fn test_insert_from_proof() {
+    let (mut db, mut root) = mock_data();
+    for (_index, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let old_root = root;
+        let (_, _, proof) = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+            .build()
+            .proof(key)
+            .unwrap();
+        let new_root =
+            insert_from_proof::<Sha3, TREE_DEPTH>(&old_root, key, value, &proof, b"updated")
+                .unwrap();
+
+        let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+        tree.insert(key, DBValue::from(&b"updated"[..])).unwrap();
+        tree.commit();
+        assert_eq!(new_root, root);
+
+        // an empty new value removes the value at the key
+        assert_eq!(
+            insert_from_proof::<Sha3, TREE_DEPTH>(&new_root, key, b"updated", &proof, value),
+            Ok(old_root)
+        );
+    }
+
+    let (_, _, proof) = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .build()
+        .proof(&[0, 0])
+        .unwrap();
+    assert!(matches!(
+        insert_from_proof::<Sha3, TREE_DEPTH>(&root, &[0, 0], b"stale", &proof, b"value"),
+        Err(TreeError::ProofError(ProofError::RootMismatch(..)))
+    ));
+    assert_eq!(
+        insert_from_proof::<Sha3, TREE_DEPTH>(&root, &[0, 0], b"updated", &proof[1..], b"value"),
+        Err(TreeError::ProofError(ProofError::DepthMismatch(16, 15)))
+    );
+}
+
+// TEST STORAGE PROOF ENCODING
+// ================================================================================================
+#[test]
+fn test_storage_proof_encoding() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    for (_index, key, _value) in TEST_DATA.iter() {
+        tree.value(key).unwrap();
+    }
+    let mut nodes: Vec<DBValue> = recorder
+        .drain_storage_proof()
+        .into_nodes()
+        .into_iter()
+        .collect();
+    let encoded = StorageProof::new(nodes.iter().cloned()).encode();
+
+    // the encoding is independent of the order of the nodes
+    nodes.reverse();
+    assert_eq!(StorageProof::new(nodes.iter().cloned()).encode(), encoded);
+    assert_eq!(encoded[0], STORAGE_PROOF_VERSION);
+    assert_eq!(
+        u32::from_be_bytes(encoded[1..5].try_into().unwrap()) as usize,
+        nodes.len()
+    );
+
+    let decoded = StorageProof::decode(&encoded).unwrap();
+    assert_eq!(decoded.encode(), encoded);
+    let keys: Vec<&[u8]> = TEST_DATA.iter().map(|(_, key, _)| &key[..]).collect();
+    assert_eq!(decoded.covers::<Sha3, TREE_DEPTH>(&root, &keys), Ok(()));
+    assert_eq!(
+        StorageProof::decode(&StorageProof::empty().encode())
+            .unwrap()
+            .encode(),
+        [STORAGE_PROOF_VERSION, 0, 0, 0, 0]
+    );
+
+    // only the canonical encoding is accepted
+    let unordered = [
+        &[STORAGE_PROOF_VERSION, 0, 0, 0, 2, 0, 0, 0, 1, 2][..],
+        &[0, 0, 0, 1, 1],
+    ]
+    .concat();
+    assert_eq!(
+        StorageProof::decode(&unordered).err(),
+        Some(TreeError::ProofError(ProofError::UnorderedNodes(1)))
+    );
+    let duplicated = [
+        &[STORAGE_PROOF_VERSION, 0, 0, 0, 2, 0, 0, 0, 1, 1][..],
+        &[0, 0, 0, 1, 1],
+    ]
+    .concat();
+    assert_eq!(
+        StorageProof::decode(&duplicated).err(),
+        Some(TreeError::ProofError(ProofError::UnorderedNodes(1)))
+    );
+    assert_eq!(
+        StorageProof::decode(&[2, 0, 0, 0, 0]).err(),
+        Some(TreeError::ProofError(ProofError::UnsupportedVersion(2)))
+    );
+    assert_eq!(
+        StorageProof::decode(&encoded[..encoded.len() - 1]).err(),
+        Some(TreeError::ProofError(ProofError::UnexpectedEnd(
+            encoded.len() - 1
+        )))
+    );
+    assert_eq!(
+        StorageProof::decode(&[&encoded[..], &[0]].concat()).err(),
+        Some(TreeError::ProofError(ProofError::TrailingBytes(1)))
+    );
+}
+
+// TEST PROOF SIZE HINT
+// ================================================================================================
+#[test]
+fn test_proof_size_hint() {
+    let (mut db, mut root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let keys: Vec<&[u8]> = TEST_DATA
+        .iter()
+        .chain(NON_INCLUSION_DATA.iter())
+        .map(|(_, key, _)| &key[..])
+        .collect();
+    let mut total = 0;
+    for key in keys.iter() {
+        let (_, _, proof) = tree.proof_compact(key).unwrap();
+        assert_eq!(tree.proof_size_hint(key).unwrap(), proof.siblings().len());
+        total += proof.siblings().len();
+    }
+    assert_eq!(tree.proofs_size_hint(&keys).unwrap(), total);
+    assert_eq!(
+        tree.proof_size_hint(&[0]),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 1)))
+    );
+
+    // the hint reflects uncommitted changes of a mutable tree
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[1, 45], DBValue::from(&b"value"[..])).unwrap();
+    let (_, _, proof) = tree.proof_compact(&[1, 44]).unwrap();
+    assert_eq!(
+        tree.proof_size_hint(&[1, 44]).unwrap(),
+        proof.siblings().len()
+    );
+    assert_eq!(
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(
+            &mut MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default(),
+            &mut Default::default()
+        )
+        .build()
+        .proofs_size_hint(&keys),
+        Ok(0)
+    );
+}
+
+// TEST EMPTY SUBTREE PROOF
+// ================================================================================================
+#[test]
+fn test_empty_subtree_proof() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (key, depth) in [([128, 0], 1), ([2, 0], 8), ([1, 128], 9), ([0, 50], 16)] {
+        let (proof_root, proof) = tree.empty_subtree_proof(&key, depth).unwrap().unwrap();
+        assert_eq!(proof_root, root);
+        assert_eq!(proof.len(), depth);
+        assert_eq!(
+            verify_empty_subtree::<Sha3, TREE_DEPTH>(&key, depth, &proof, &root, &[]),
+            Ok(true)
+        );
+    }
+
+    // subtrees holding a key can not be proven empty
+    for (key, depth) in [([0, 0], 0), ([0, 255], 8), ([1, 0], 9), ([0, 100], 16)] {
+        assert_eq!(tree.empty_subtree_proof(&key, depth).unwrap(), None);
+        let (_, _, proof) = tree.subtree_proof(&key, depth).unwrap();
+        assert_eq!(
+            verify_empty_subtree::<Sha3, TREE_DEPTH>(&key, depth, &proof, &root, &[]),
+            Ok(false)
+        );
+    }
+    assert_eq!(
+        tree.empty_subtree_proof(&[0, 0], 17),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(17, 16)))
+    );
+
+    // the empty tree is proven empty without siblings
+    let db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = Default::default();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (proof_root, proof) = tree.empty_subtree_proof(&[0, 0], 0).unwrap().unwrap();
+    assert!(proof.is_empty());
+    assert_eq!(
+        verify_empty_subtree::<Sha3, TREE_DEPTH>(&[0, 0], 0, &proof, &proof_root, &[]),
+        Ok(true)
+    );
+
+    // the empty subtrees of a salted tree are the salted null hashes
+    const SALT: &[u8] = b"salt";
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .build();
+    for (key, depth) in [([128, 0], 1), ([2, 0], 8), ([1, 128], 9), ([0, 50], 16)] {
+        let (proof_root, proof) = tree.empty_subtree_proof(&key, depth).unwrap().unwrap();
+        assert_eq!(
+            verify_empty_subtree::<Sha3, TREE_DEPTH>(&key, depth, &proof, &proof_root, SALT),
+            Ok(true)
+        );
+        // with key-bound leaves the null leaf is not salted, see null_leaf_hash
+        assert_eq!(
+            verify_empty_subtree::<Sha3, TREE_DEPTH>(&key, depth, &proof, &proof_root, &[]),
+            Ok(cfg!(feature = "key-bound"))
+        );
+    }
+}
+
+// TEST VERIFY AT DEPTH
+// ================================================================================================
+#[test]
+fn test_verify_at_depth() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let (_, _, proof) = tree.proof(key).unwrap();
+        for depth in 0..=TREE_DEPTH * 8 {
+            let (subtree_root, _, _) = tree.subtree_proof(key, depth).unwrap();
+            let partial = &proof[..TREE_DEPTH * 8 - depth];
+            assert_eq!(
+                verify_at_depth::<Sha3, TREE_DEPTH>(key, value, partial, depth, &subtree_root),
+                Ok(true)
+            );
+            assert_eq!(
+                verify_at_depth::<Sha3, TREE_DEPTH>(key, b"other", partial, depth, &subtree_root),
+                Ok(false)
+            );
+        }
+        // a subtree root at depth zero is the root of the tree
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_at_depth(key, value, &proof, 0, &root),
+            Ok(true)
+        );
+        // the proof must end at the subtree root
+        assert_eq!(
+            TreeDB::<TREE_DEPTH, Sha3>::verify_at_depth(key, value, &proof, 1, &root),
+            Ok(false)
+        );
+    }
+    assert_eq!(
+        verify_at_depth::<Sha3, TREE_DEPTH>(&[0, 0], b"value", &[], 17, &root),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(17, 16)))
+    );
+}
+
+// TEST STORAGE PROOF SET OPERATIONS
+// ================================================================================================
+#[test]
+fn test_storage_proof_set_operations() {
+    let (db, root) = mock_data();
+    let record = |keys: &[&[u8]]| {
+        let mut recorder = Recorder::new();
+        let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+            .with_recorder(&mut recorder)
+            .build();
+        for key in keys {
+            tree.value(key).unwrap();
+        }
+        recorder.drain_storage_proof()
+    };
+    let left: [&[u8]; 2] = [&[0, 0], &[0, 100]];
+    let right: [&[u8]; 2] = [&[0, 200], &[1, 44]];
+    let all = [left, right].concat();
+
+    let merged = StorageProof::merge([record(&left), record(&right), record(&left)]);
+    assert_eq!(merged.encode(), record(&all).encode());
+    assert_eq!(merged.covers::<Sha3, TREE_DEPTH>(&root, &all), Ok(()));
+    assert_eq!(
+        record(&left).union(&record(&right)).encode(),
+        merged.encode()
+    );
+
+    // the shared nodes along the paths are held once
+    let shared = record(&left).intersection(&record(&right));
+    assert!(!shared.is_empty());
+    assert_eq!(
+        merged.len(),
+        record(&left).len() + record(&right).len() - shared.len()
+    );
+
+    // the difference holds the nodes a client holding the left proof is missing
+    let missing = merged.difference(&record(&left));
+    assert_eq!(missing.len(), merged.len() - record(&left).len());
+    assert!(missing.intersection(&record(&left)).is_empty());
+    assert_eq!(record(&left).union(&missing).encode(), merged.encode());
+    assert!(StorageProof::merge([]).is_empty());
+}
+
+// TEST COMPACT STORAGE PROOF
+// ================================================================================================
+#[test]
+fn test_compact_storage_proof() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    for (_index, key, _value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        tree.value(key).unwrap();
+    }
+    let proof = recorder.drain_storage_proof();
+    // empty value nodes are stripped
+    let expected = StorageProof::new(
+        StorageProof::decode(&proof.encode())
+            .unwrap()
+            .into_nodes()
+            .into_iter()
+            .filter(|node| node.as_slice() != [0]),
+    );
+
+    let compact = CompactStorageProof::from_storage_proof::<Sha3>(&proof).unwrap();
+    assert!(compact.len() < proof.encode().len());
+    assert_eq!(
+        compact.to_storage_proof::<Sha3>().unwrap().encode(),
+        expected.encode()
+    );
+    let decoded = CompactStorageProof::decode::<Sha3>(&compact.encode()).unwrap();
+    assert_eq!(decoded.encode(), compact.encode());
+    let keys: Vec<&[u8]> = TEST_DATA.iter().map(|(_, key, _)| &key[..]).collect();
+    assert_eq!(
+        decoded
+            .to_storage_proof::<Sha3>()
+            .unwrap()
+            .covers::<Sha3, TREE_DEPTH>(&root, &keys),
+        Ok(())
+    );
+
+    // leaves of salted trees are encoded alongside their hash
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut recorder = Recorder::new();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(b"salt")
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(b"salt")
+        .with_recorder(&mut recorder)
+        .build();
+    for key in keys.iter() {
+        tree.value(key).unwrap();
+    }
+    let proof = recorder.drain_storage_proof();
+    let compact = CompactStorageProof::from_storage_proof::<Sha3>(&proof).unwrap();
+    assert_eq!(
+        compact.to_storage_proof::<Sha3>().unwrap().encode(),
+        proof.encode()
+    );
+
+    // the empty proof and malformed proofs
+    let empty = CompactStorageProof::from_storage_proof::<Sha3>(&StorageProof::empty()).unwrap();
+    assert_eq!(empty.encode(), [COMPACT_STORAGE_PROOF_VERSION, 0, 0, 0, 0]);
+    assert!(empty.to_storage_proof::<Sha3>().unwrap().is_empty());
+    let encoded = compact.encode();
+    assert_eq!(
+        CompactStorageProof::decode::<Sha3>(&[2, 0, 0, 0, 0]).err(),
+        Some(TreeError::ProofError(ProofError::UnsupportedVersion(2)))
+    );
+    assert_eq!(
+        CompactStorageProof::decode::<Sha3>(&encoded[..encoded.len() - 1]).err(),
+        Some(TreeError::ProofError(ProofError::UnexpectedEnd(
+            encoded.len() - 1
+        )))
+    );
+    assert_eq!(
+        CompactStorageProof::decode::<Sha3>(&[&encoded[..], &[0]].concat()).err(),
+        Some(TreeError::ProofError(ProofError::TrailingBytes(1)))
+    );
+    assert_eq!(
+        CompactStorageProof::decode::<Sha3>(&[COMPACT_STORAGE_PROOF_VERSION, 0, 0, 0, 1, 7]).err(),
+        Some(TreeError::ProofError(ProofError::InvalidNode(Vec::from([
+            7
+        ]))))
+    );
+}
+
+// TEST VERIFY MULTI
+// ================================================================================================
+#[test]
+fn test_verify_multi() {
+    let (db, root) = mock_data();
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    for (_index, key, _value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        tree.value(key).unwrap();
+    }
+    let proof = recorder.drain_storage_proof();
+
+    let items: Vec<(&[u8], &[u8])> = TEST_DATA
+        .iter()
+        .chain(NON_INCLUSION_DATA.iter())
+        .map(|(_, key, value)| (&key[..], &value[..]))
+        .collect();
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(&items, &proof, &root),
+        Ok(Vec::from_iter(items.iter().map(|_| true)))
+    );
+
+    // each claim is checked independently
+    let items: [(&[u8], &[u8]); 4] = [
+        (&[0, 0], b"value1"),
+        (&[0, 100], b"other"),
+        (&[0, 50], b"value"),
+        (&[0, 200], b""),
+    ];
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(&items, &proof, &root),
+        Ok(Vec::from([true, false, false, false]))
+    );
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(&items[..1], &proof, &Sha3::hash(b"root")),
+        Err(TreeError::ProofError(ProofError::MissingNodes(Vec::from(
+            [Sha3::hash(b"root").to_vec()]
+        ))))
+    );
+
+    // keys whose paths are not in the proof are reported as missing
+    let mut recorder = Recorder::new();
+    TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build()
+        .value(&[0, 0])
+        .unwrap();
+    let partial = recorder.drain_storage_proof();
+    assert!(matches!(
+        verify_multi::<Sha3, TREE_DEPTH>(&[(&[1, 44], b"value4")], &partial, &root),
+        Err(TreeError::ProofError(ProofError::MissingNodes(_)))
+    ));
+
+    // the empty tree proves the absence of every key without any nodes
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(
+            &[(&[0, 0], b""), (&[1, 44], b"value4")],
+            &StorageProof::empty(),
+            &Default::default()
+        ),
+        Ok(Vec::from([true, false]))
+    );
+
+    // a node re-encoded to mark a non-empty child as default keeps its hash but is rejected
+    let tampered = StorageProof::new(proof.into_nodes().into_iter().map(|mut node| {
+        if node[0] == 1 {
+            node[0] = 2;
+        }
+        node
+    }));
+    assert!(matches!(
+        verify_multi::<Sha3, TREE_DEPTH>(&[(&[1, 44], b"")], &tampered, &root),
+        Err(TreeError::ProofError(ProofError::InvalidNode(_)))
+    ));
+}
+
+// TEST AUDIT SAMPLE
+// ================================================================================================
+#[test]
+fn test_audit_sample() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (sample, proof) = tree.audit_sample(b"challenge", 3).unwrap();
+    assert_eq!(sample.len(), 3);
+    let items: Vec<(&[u8], &[u8])> = sample
+        .iter()
+        .map(|(key, value)| (&key[..], &value[..]))
+        .collect();
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(&items, &proof, &root),
+        Ok(Vec::from([true, true, true]))
+    );
+    for (key, value) in sample.iter() {
+        assert_eq!(tree.value(key).unwrap().as_ref(), Some(value));
+    }
+
+    // the sample is determined by the seed
+    let (same, same_proof) = tree.audit_sample(b"challenge", 3).unwrap();
+    assert_eq!(same, sample);
+    assert_eq!(same_proof.encode(), proof.encode());
+    let (prefix, _) = tree.audit_sample(b"challenge", 2).unwrap();
+    assert_eq!(prefix[..], sample[..2]);
+
+    // all leaves are selected if the count exceeds the number of leaves
+    let (mut all, _) = tree.audit_sample(b"other", 10).unwrap();
+    all.sort();
+    assert_eq!(all, tree.entries().unwrap());
+    assert_eq!(tree.audit_sample(b"challenge", 0).unwrap().0, Vec::new());
+
+    let db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = Default::default();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (sample, proof) = tree.audit_sample(b"challenge", 3).unwrap();
+    assert!(sample.is_empty() && proof.is_empty());
+}
+
+// TEST UNCOMMITTED STORAGE PROOF
+// ================================================================================================
+#[test]
+fn test_uncommitted_storage_proof() {
+    let (mut db, mut root) = mock_data();
+    let keys: [&[u8]; 3] = [&[0, 0], &[0, 7], &[1, 44]];
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.insert(&[0, 7], DBValue::from(&b"inserted"[..]))
+        .unwrap();
+
+    // the proof holds the uncommitted nodes along the paths of the keys
+    let (proof_root, proof) = tree.storage_proof(&keys).unwrap();
+    let items: [(&[u8], &[u8]); 3] = [
+        (&[0, 0], b"updated"),
+        (&[0, 7], b"inserted"),
+        (&[1, 44], b"value4"),
+    ];
+    assert_eq!(
+        verify_multi::<Sha3, TREE_DEPTH>(&items, &proof, &proof_root),
+        Ok(Vec::from([true, true, true]))
+    );
+    let proof_db = StorageProof::decode(&proof.encode())
+        .unwrap()
+        .into_memory_db::<Sha3>();
+    let proof_tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&proof_db, &proof_root).build();
+    for (key, value) in items.iter() {
+        assert_eq!(proof_tree.value(key).unwrap(), Some(DBValue::from(*value)));
+    }
+
+    // the uncommitted root is the root of the committed tree
+    tree.commit();
+    assert_eq!(root, proof_root);
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(
+        tree.storage_proof(&keys).unwrap().1.encode(),
+        proof.encode()
+    );
+}
+
+// TEST RFC 6962
+// ================================================================================================
+#[test]
+fn test_rfc6962() {
+    // the test vectors of the certificate transparency reference implementation
+    let leaves: [&[u8]; 8] = [
+        b"",
+        b"\x00",
+        b"\x10",
+        b"\x20\x21",
+        b"\x30\x31",
+        b"\x40\x41\x42\x43",
+        b"\x50\x51\x52\x53\x54\x55\x56\x57",
+        b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+    ];
+    let roots = [
+        "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+        "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+        "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+        "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+        "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+        "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+        "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+        "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+    ];
+
+    // the default null node of the memory db is the leaf hash of the value 0x00 of the vectors
+    let mut db = MemoryDB::<Sha256, NoopKey<Sha256>, DBValue>::from_null_node(
+        b"null",
+        DBValue::from(&b"null"[..]),
+    );
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha256>::new(&mut db, &mut root).build();
+    // an index without a value is hashed as an empty value
+    for (index, leaf) in leaves.iter().enumerate().skip(1) {
+        tree.insert(&(index as u64), DBValue::from(*leaf)).unwrap();
+    }
+    tree.commit();
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha256>::new(&db, &root).build();
+    assert_eq!(tree.rfc6962_root(0).unwrap(), Sha256::hash(&[]));
+    for (size, expected) in (1..=leaves.len() as u64).zip(roots.iter()) {
+        let ct_root = tree.rfc6962_root(size).unwrap();
+        assert_eq!(ct_root, decode_hex(expected));
+        for index in 0..size {
+            let leaf = rfc6962_leaf_hash::<Sha256>(leaves[index as usize]);
+            let path = tree.rfc6962_audit_path(index, size).unwrap();
+            assert!(verify_rfc6962_audit_path::<Sha256>(
+                index, size, &leaf, &path, &ct_root
+            ));
+            assert!(!verify_rfc6962_audit_path::<Sha256>(
+                (index + 1) % size.max(2),
+                size.max(2),
+                &leaf,
+                &path,
+                &ct_root
+            ));
+        }
+    }
+    assert_eq!(
+        tree.rfc6962_audit_path(8, 8),
+        Err(TreeError::KeyError(KeyError::LeafIndexOutOfBounds(8, 8)))
+    );
+    assert_eq!(
+        tree.rfc6962_root(1 << 17),
+        Err(TreeError::KeyError(KeyError::LeafIndexOutOfBounds(
+            (1 << 17) - 1,
+            1 << 16
+        )))
+    );
+}
+
+// TEST STORAGE PROOF INTROSPECTION
+// ================================================================================================
+#[test]
+fn test_storage_proof_introspection() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (_, proof) = tree.storage_proof(&[&[0, 0], &[1, 44]]).unwrap();
+
+    assert_eq!(proof.iter().count(), proof.len());
+    assert_eq!(proof.encoded_size(), proof.encode().len());
+    assert!(proof.contains_node::<Sha3>(&root));
+    assert!(proof.contains_node::<Sha3>(&tree.leaf(&[1, 44]).unwrap().unwrap()));
+    assert!(!proof.contains_node::<Sha3>(&tree.leaf(&[0, 100]).unwrap().unwrap()));
+    assert!(proof
+        .iter()
+        .any(|node| node[..] == encoded_leaf(&[0, 0], b"value1")[..]));
+
+    let empty = StorageProof::empty();
+    assert_eq!((empty.len(), empty.iter().count()), (0, 0));
+    assert_eq!(empty.encoded_size(), empty.encode().len());
+    assert!(!empty.contains_node::<Sha3>(&root));
+}
+
+// TEST PROOF INDICES
+// ================================================================================================
+
+#[test]
+fn test_proof_indices() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    for index in [3u64, 17, 200, 4000, 65535] {
+        tree.insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+            .unwrap();
+    }
+
+    // the uncommitted tree proves the same indices as the committed tree
+    let indices = [65535u64, 3, 9, 4000];
+    let (values, root_mut, proof_mut) = tree.proof_indices(&indices).unwrap();
+    tree.commit();
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (_, expected_root, proof) = tree.proof_indices(&indices).unwrap();
+    assert_eq!((root_mut, expected_root), (root, root));
+    assert_eq!(proof_mut.encode(), proof.encode());
+    assert_eq!(values[2], None);
+    assert_eq!(values[3].as_deref(), Some(&4000u64.to_be_bytes()[..]));
+
+    let values: Vec<&[u8]> = values
+        .iter()
+        .map(|v| v.as_deref().unwrap_or_default())
+        .collect();
+    assert!(
+        IndexTreeDB::<TREE_DEPTH, Sha3>::verify_indices(&indices, &values, &proof, &root).unwrap()
+    );
+
+    // a wrong value, a missing value and a wrong root fail verification
+    let mut wrong = values.clone();
+    wrong[2] = b"value";
+    assert!(
+        !IndexTreeDB::<TREE_DEPTH, Sha3>::verify_indices(&indices, &wrong, &proof, &root).unwrap()
+    );
+    assert!(!IndexTreeDB::<TREE_DEPTH, Sha3>::verify_indices(
+        &indices,
+        &values[1..],
+        &proof,
+        &root
+    )
+    .unwrap());
+    assert!(IndexTreeDB::<TREE_DEPTH, Sha3>::verify_indices(
+        &indices,
+        &values,
+        &proof,
+        &Sha3::hash(b"root")
+    )
+    .is_err());
+
+    // the proof does not cover indices outside of the list
+    assert!(IndexTreeDB::<TREE_DEPTH, Sha3>::verify_indices(
+        &[17],
+        &[&17u64.to_be_bytes()],
+        &proof,
+        &root
+    )
+    .is_err());
+}
+
+// TEST NEIGHBOR PROOF
+// ================================================================================================
+
+#[test]
+fn test_neighbor_proof() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    // the neighbors of any index of an empty tree are missing
+    let (value, empty_root, proof) = tree.neighbor_proof(5).unwrap();
+    assert_eq!(value, Some(DBValue::default()));
+    assert!(proof.predecessor().is_none() && proof.successor().is_none());
+    assert!(proof.verify(&empty_root));
+
+    for index in [3u64, 17, 200, 4000] {
+        tree.insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+            .unwrap();
+    }
+    let (_, root_mut, proof_mut) = tree.neighbor_proof(100).unwrap();
+    tree.commit();
+    assert_eq!(root_mut, root);
+    assert!(proof_mut.verify(&root));
+
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (index, occupied, predecessor, successor) in [
+        (100, false, Some(17), Some(200)),
+        (17, true, Some(3), Some(200)),
+        (0, false, None, Some(3)),
+        (3, true, None, Some(17)),
+        (4000, true, Some(200), None),
+        (65535, false, Some(4000), None),
+    ] {
+        let (value, _, proof) = tree.neighbor_proof(index).unwrap();
+        assert_eq!(!value.unwrap_or_default().is_empty(), occupied);
+        assert_eq!(proof.index(), index);
+        assert_eq!(proof.predecessor().map(|(index, _)| index), predecessor);
+        assert_eq!(proof.successor().map(|(index, _)| index), successor);
+        assert!(proof.verify(&root));
+        assert!(!proof.verify(&empty_root));
+    }
+
+    // neighbors that skip an occupied index fail verification
+    let (_, _, proof) = tree.merkle_proof(&100).unwrap();
+    let (_, _, predecessor) = tree.merkle_proof(&3).unwrap();
+    let (_, _, successor) = tree.merkle_proof(&200).unwrap();
+    let skipping = NeighborProof::new(
+        100,
+        proof.clone(),
+        Some((3, predecessor)),
+        Some((200, successor)),
+    )
+    .unwrap();
+    assert!(!skipping.verify(&root));
+
+    // an unoccupied index is not a neighbor and a proof must match its index
+    let (_, _, unoccupied) = tree.merkle_proof(&50).unwrap();
+    let (_, _, successor) = tree.merkle_proof(&200).unwrap();
+    let unoccupied = NeighborProof::new(
+        100,
+        proof.clone(),
+        Some((50, unoccupied)),
+        Some((200, successor)),
+    )
+    .unwrap();
+    assert!(!unoccupied.verify(&root));
+    assert!(NeighborProof::new(101, proof, None, None).is_err());
+
+    // the empty leaves and subtrees of a salted tree are the salted null hashes
+    const SALT: &[u8] = b"salt";
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    for index in [3u64, 17, 200, 4000] {
+        tree.insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+            .unwrap();
+    }
+    tree.commit();
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .build();
+    for index in [0, 3, 100, 4000, 65535] {
+        let (_, _, proof) = tree.neighbor_proof(index).unwrap();
+        assert!(proof.verify_salted(&root, SALT));
+        assert!(!proof.verify_salted(&root, b"pepper") || cfg!(feature = "key-bound"));
+        // with key-bound leaves the null leaf is not salted, see null_leaf_hash
+        assert_eq!(proof.verify(&root), cfg!(feature = "key-bound"));
+    }
+}
+
+// TEST TREE FROM PROOF
+// ================================================================================================
+
+#[test]
+fn test_tree_from_proof() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (_, proof) = tree.storage_proof(&[&[0, 0], &[1, 44]]).unwrap();
+    let copy = || StorageProof::new(proof.iter().cloned());
+    let with_node =
+        |node: &[u8]| StorageProof::new(proof.iter().cloned().chain([DBValue::from(node)]));
+
+    let proof_tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(copy(), &root)
+        .unwrap()
+        .build();
+    assert_eq!(proof_tree.root(), &root);
+    for key in [&[0u8, 0][..], &[1, 44]] {
+        assert_eq!(proof_tree.value(key).unwrap(), tree.value(key).unwrap());
+    }
+    assert!(proof_tree.value(&[0, 100]).is_err());
+
+    // undecodable, unreachable and missing nodes are rejected
+    let garbage = [7u8, 1, 2, 3];
+    assert_eq!(
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(with_node(&garbage), &root).err(),
+        Some(TreeError::ProofError(ProofError::InvalidNode(Vec::from(
+            garbage
+        ))))
+    );
+    let stray = [&[0u8][..], b"stray"].concat();
+    assert_eq!(
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(with_node(&stray), &root).err(),
+        Some(TreeError::ProofError(ProofError::UnreachableNode(
+            Sha3::hash(b"stray").as_ref().to_vec()
+        )))
+    );
+    let other_root = Sha3::hash(b"root");
+    assert_eq!(
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(copy(), &other_root).err(),
+        Some(TreeError::ProofError(ProofError::MissingNodes(Vec::from(
+            [other_root.as_ref().to_vec()]
+        ))))
+    );
+    assert!(matches!(
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(copy(), &Default::default()),
+        Err(TreeError::ProofError(ProofError::UnreachableNode(_)))
+    ));
+
+    // a node re-encoded to mark a non-empty child as default keeps its hash but is rejected, as
+    // the keys below that child would read as absent
+    let tampered = StorageProof::new(proof.iter().cloned().map(|mut node| {
+        if node[0] == 1 {
+            node[0] = 2;
+        }
+        node
+    }));
+    assert!(matches!(
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(tampered, &root),
+        Err(TreeError::ProofError(ProofError::InvalidNode(_)))
+    ));
+
+    // an empty proof is the proof of an empty tree
+    let empty_tree =
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::from_proof(StorageProof::empty(), &Default::default())
+            .unwrap()
+            .build();
+    assert_eq!(empty_tree.value(&[0, 0]).unwrap(), None);
+}
+
+// TEST DIVERGENCE PROOF
+// ================================================================================================
+
+#[test]
+fn test_divergence_proof() {
+    let (mut db, root) = mock_data();
+
+    // build the divergent trees from scratch such that the nodes of the first tree are kept
+    let mut build = |changes: &[(&[u8], &[u8])]| {
+        let mut root = Default::default();
+        let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+        for (_, key, value) in TEST_DATA.iter() {
+            let value = changes
+                .iter()
+                .find(|(changed, _)| changed == key)
+                .map_or(*value, |(_, value)| *value);
+            if !value.is_empty() {
+                tree.insert(key, DBValue::from(value)).unwrap();
+            }
+        }
+        tree.commit();
+        root
+    };
+    let second_root = build(&[(&[1, 44], b"fraud"), (&[0, 200], b"fraud")]);
+    let third_root = build(&[(&[0, 100], b"")]);
+
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert!(tree.divergence_proof(&root).unwrap().is_none());
+
+    // the first differing key is proven even if later keys differ as well
+    let proof = tree.divergence_proof(&second_root).unwrap().unwrap();
+    assert_eq!(proof.key(), &[0, 200]);
+    assert_eq!(proof.first().0, b"value3");
+    assert_eq!(proof.second().0, b"fraud");
+    assert!(proof.verify(&root, &second_root).unwrap());
+    assert!(!proof.verify(&second_root, &root).unwrap());
+
+    // a removed leaf is proven by the null leaf
+    let proof = tree.divergence_proof(&third_root).unwrap().unwrap();
+    assert_eq!(proof.key(), &[0, 100]);
+    assert_eq!(proof.second().0, b"");
+    assert!(proof.verify(&root, &third_root).unwrap());
+
+    // a later differing key is not the first divergence
+    let second = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &second_root).build();
+    let (first_value, _, first_proof) = tree.proof_compact(&[1, 44]).unwrap();
+    let (second_value, _, second_proof) = second.proof_compact(&[1, 44]).unwrap();
+    let later = DivergenceProof::<Sha3, TREE_DEPTH>::new(
+        &[1, 44],
+        (first_value.unwrap(), first_proof),
+        (second_value.unwrap(), second_proof),
+    )
+    .unwrap();
+    assert!(!later.verify(&root, &second_root).unwrap());
+}
+
+// TEST CHAINED PROOF
+// ================================================================================================
+
+#[test]
+fn test_chained_proof() {
+    const CHILD_DEPTH: usize = 1;
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut child_root = Default::default();
+    let mut child = TreeDBMutBuilder::<CHILD_DEPTH, Sha3>::new(&mut db, &mut child_root).build();
+    child.insert(&[1], DBValue::from(&b"child1"[..])).unwrap();
+    child.insert(&[200], DBValue::from(&b"child2"[..])).unwrap();
+    child.commit();
+
+    let mut root = Default::default();
+    let mut parent = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    parent
+        .insert(&[0, 5], DBValue::from(child_root.as_ref()))
+        .unwrap();
+    parent
+        .insert(&[1, 5], DBValue::from(&b"value"[..]))
+        .unwrap();
+    parent.commit();
+
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let (value, proof_root, proof) = tree.chained_proof::<CHILD_DEPTH>(&[0, 5], &[200]).unwrap();
+    assert_eq!((value.as_deref(), proof_root), (Some(&b"child2"[..]), root));
+    assert_eq!(proof.child_root(), child_root);
+    assert!(proof.verify(&root));
+    assert!(proof.verify_value(b"child2", &root));
+    assert!(!proof.verify_value(b"child1", &root));
+    assert!(!proof.verify_value(b"child2", &child_root));
+
+    // non-inclusion in the child tree
+    let (_, _, proof) = tree.chained_proof::<CHILD_DEPTH>(&[0, 5], &[3]).unwrap();
+    assert!(proof.verify_value(b"", &root));
+
+    // the parent leaf must commit to the root of the child proof
+    let (_, _, parent_proof) = tree.merkle_proof(&[1, 5]).unwrap();
+    let (_, _, child_proof) = tree.chained_proof::<CHILD_DEPTH>(&[0, 5], &[1]).unwrap();
+    assert!(matches!(
+        ChainedProof::new(parent_proof, child_proof.child().clone()),
+        Err(TreeError::ProofError(ProofError::RootMismatch(_, _)))
+    ));
+
+    // a value that is not a hash is not a child root
+    assert!(matches!(
+        tree.chained_proof::<CHILD_DEPTH>(&[1, 5], &[1]),
+        Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(_)))
+    ));
+}
+
+// TEST LEVEL CACHE
+// ================================================================================================
+
+#[test]
+fn test_level_cache() {
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+
+    let nodes = tree.level_nodes(4).unwrap();
+    assert_eq!(nodes.len(), 16);
+    let cache = LevelCache::<Sha3, TREE_DEPTH>::new(4, nodes.clone(), &root).unwrap();
+    for (_, key, value) in TEST_DATA.iter().chain(NON_INCLUSION_DATA.iter()) {
+        let (_, proof_root, proof) = tree.truncated_proof(key, 4).unwrap();
+        assert_eq!((proof_root, proof.len()), (root, 12));
+        assert_eq!(cache.verify(key, value, &proof), Ok(true));
+        assert_eq!(cache.verify(key, b"other", &proof), Ok(false));
+    }
+    let (_, _, proof) = tree.proof(&[0, 0]).unwrap();
+    assert_eq!(cache.verify(&[0, 0], b"value1", &proof), Ok(false));
+
+    // the uncommitted level of a mutable tree matches the committed level
+    let mut mut_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut mut_root = Default::default();
+    let mut tree_mut =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut mut_db, &mut mut_root).build();
+    for (_, key, value) in TEST_DATA.iter() {
+        tree_mut.insert(key, DBValue::from(*value)).unwrap();
+    }
+    assert_eq!(tree_mut.level_nodes(4).unwrap(), nodes);
+    assert_eq!(tree.level_nodes(0).unwrap(), Vec::from([root]));
+
+    // the cache must hash to the root
+    let mut tampered = nodes.clone();
+    tampered.swap(0, 1);
+    assert!(matches!(
+        LevelCache::<Sha3, TREE_DEPTH>::new(4, tampered, &root),
+        Err(TreeError::ProofError(ProofError::RootMismatch(_, _)))
+    ));
+    assert!(matches!(
+        LevelCache::<Sha3, TREE_DEPTH>::new(3, nodes, &root),
+        Err(TreeError::ProofError(ProofError::NodeCountMismatch(8, 16)))
+    ));
+    assert!(tree.level_nodes(17).is_err());
+    assert!(tree.truncated_proof(&[0, 0], 17).is_err());
+
+    // levels of deep trees are capped at MAX_LEVEL_DEPTH rather than the depth of the tree
+    let empty_root = Default::default();
+    let deep_tree = TreeDBBuilder::<8, Sha3>::new(&db, &empty_root).build();
+    assert_eq!(
+        deep_tree.level_nodes(MAX_LEVEL_DEPTH + 1),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+            MAX_LEVEL_DEPTH + 1,
+            MAX_LEVEL_DEPTH
+        )))
+    );
+    assert_eq!(
+        deep_tree.level_nodes(64),
+        Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+            64,
+            MAX_LEVEL_DEPTH
+        )))
+    );
+}
+
+// TEST TRANSACTIONS
+// ================================================================================================
+
+#[test]
+fn test_transactions() {
+    let (mut db, mut root) = mock_data();
+    let (expected_db, expected_root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(
+        tree.rollback(),
+        Err(TreeError::DataError(DataError::NoTransaction))
+    );
+
+    // a rolled back transaction discards inserts and removals of committed leaves
+    tree.begin_transaction();
+    tree.insert(&[5, 5], DBValue::from(&b"discarded"[..]))
+        .unwrap();
+    tree.remove(&[0, 0]).unwrap();
+    assert_ne!(&tree.root_hint(), &expected_root);
+    tree.rollback().unwrap();
+    assert_eq!(&tree.root_hint(), &expected_root);
+    assert_eq!(tree.value(&[5, 5]).unwrap(), None);
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap().as_deref(),
+        Some(&b"value1"[..])
+    );
+
+    // an inner transaction committed within an outer transaction is rolled back with it
+    tree.begin_transaction();
+    tree.insert(&[6, 6], DBValue::from(&b"outer"[..])).unwrap();
+    tree.begin_transaction();
+    tree.insert(&[7, 7], DBValue::from(&b"inner"[..])).unwrap();
+    assert_eq!(tree.transaction_depth(), 2);
+    tree.commit_transaction().unwrap();
+    tree.rollback().unwrap();
+    assert_eq!(tree.transaction_depth(), 0);
+    assert_eq!(&tree.root_hint(), &expected_root);
+    assert_eq!(tree.value(&[7, 7]).unwrap(), None);
+
+    // committing the tree commits open transactions
+    tree.begin_transaction();
+    tree.insert(&[8, 8], DBValue::from(&b"kept"[..])).unwrap();
+    tree.commit();
+    assert_eq!(tree.transaction_depth(), 0);
+    assert_eq!(
+        tree.commit_transaction(),
+        Err(TreeError::DataError(DataError::NoTransaction))
+    );
+    drop(tree);
+
+    // the database holds the nodes of the original tree and the kept leaf only
+    let mut expected_db = expected_db;
+    let mut expected_root = expected_root;
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[8, 8], DBValue::from(&b"kept"[..]))
+        .unwrap();
+    expected.commit();
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST CHANGE SET
+// ================================================================================================
+
+#[test]
+fn test_change_set() {
+    let (mut db, mut root) = mock_data();
+    let old_root = root;
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert!(tree.commit().is_empty());
+
+    // a leaf restored to its old value is omitted from the leaf changes
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.insert(&[0, 0], DBValue::from(&b"value1"[..])).unwrap();
+    tree.remove(&[1, 44]).unwrap();
+    let change_set = tree.commit();
+    drop(tree);
+
+    assert_eq!(change_set.root, root);
+    assert_eq!(
+        change_set.leaves,
+        Vec::from([
+            (
+                DBValue::from(&[1, 44][..]),
+                Some(DBValue::from(&b"value4"[..])),
+                None
+            ),
+            (
+                DBValue::from(&[5, 5][..]),
+                None,
+                Some(DBValue::from(&b"added"[..]))
+            ),
+        ])
+    );
+    assert!(change_set.inserted.contains(&root));
+    assert!(change_set.removed.contains(&old_root));
+    for hash in change_set.inserted.iter() {
+        assert!(HashDB::contains(&db, hash, EMPTY_PREFIX));
+    }
+    for hash in change_set.removed.iter() {
+        assert!(!HashDB::contains(&db, hash, EMPTY_PREFIX));
+    }
+}
+
+// TEST ROOT HINT
+// ================================================================================================
+
+#[test]
+fn test_root_hint() {
+    let (mut db, mut root) = mock_data();
+    let (mut expected_db, mut expected_root) = mock_data();
+    let keys = db.keys();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(tree.root_hint(), expected_root);
+
+    // the hint reflects pending changes without writing them to the database
+    tree.insert(&5, DBValue::from(&b"pending"[..])).unwrap();
+    let hint = tree.root_hint();
+    assert_ne!(hint, expected_root);
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), keys);
+
+    let mut expected =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+            .build();
+    expected.insert(&5, DBValue::from(&b"pending"[..])).unwrap();
+    assert_eq!(expected.root_hint(), hint);
+    assert_eq!(expected.root(), &hint);
+}
+
+// TEST CLEAR
+// ================================================================================================
+
+#[test]
+fn test_clear() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    // pending changes are discarded along with the committed leaves
+    tree.insert(&5, DBValue::from(&b"pending"[..])).unwrap();
+    tree.clear().unwrap();
+    assert_eq!(tree.value(&0).unwrap(), None);
+    assert_eq!(tree.value(&5).unwrap(), None);
+
+    let change_set = tree.commit();
+    assert_eq!(change_set.leaves.len(), TEST_DATA.len());
+    assert!(change_set.leaves.iter().all(|(_, _, new)| new.is_none()));
+    drop(tree);
+
+    let mut empty_root = Default::default();
+    let mut empty_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut empty_db, &mut empty_root)
+        .build()
+        .commit();
+    assert_eq!(root, empty_root);
+    assert_eq!(change_set.root, empty_root);
+    assert!(db.keys().is_empty());
+
+    // the cleared tree can be written to again
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&7, DBValue::from(&b"value"[..])).unwrap();
+    tree.commit();
+    assert_eq!(tree.value(&7).unwrap().as_deref(), Some(&b"value"[..]));
+}
+
+// TEST COLLAPSE
+// ================================================================================================
+
+#[test]
+fn test_remove_collapses_subtrees() {
+    let (mut db, mut root) = mock_data();
+    let (expected_db, expected_root) = mock_data();
+
+    // removing a leaf restores the default nodes of the emptied subtrees
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[9, 9], DBValue::from(&b"value"[..])).unwrap();
+    tree.commit();
+    tree.remove(&[9, 9]).unwrap();
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // removing every leaf restores the empty tree
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    for (_, key, _) in TEST_DATA.iter() {
+        tree.remove(key).unwrap();
+    }
+    tree.commit();
+    drop(tree);
+    assert!(db.keys().is_empty());
+}
+
+// TEST TAKE
+// ================================================================================================
+
+#[test]
+fn test_take() {
+    let (mut db, mut root) = mock_data();
+    let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut expected_root = Default::default();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    for (_, key, value) in TEST_DATA.iter().filter(|(_, key, _)| key != &[0, 100]) {
+        expected.insert(key, DBValue::from(*value)).unwrap();
+    }
+    expected.commit();
+    drop(expected);
+
+    // taking a value returns it and leaves the tree of a key that was never written
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(
+        tree.take(&[0, 100]).unwrap(),
+        Some(DBValue::from(&b"value2"[..]))
+    );
+    assert_eq!(tree.take(&[0, 100]).unwrap(), None);
+    assert_eq!(tree.root(), &expected_root);
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // the indexed tree takes the value at an index
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let empty_root = *tree.root();
+    tree.insert(&7, DBValue::from(&b"value"[..])).unwrap();
+    assert_eq!(tree.take(&7).unwrap(), Some(DBValue::from(&b"value"[..])));
+    assert_eq!(tree.root(), &empty_root);
+}
+
+// TEST INSERT WITNESSED
+// ================================================================================================
+
+#[test]
+fn test_insert_witnessed() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    for (key, value, old_value) in [
+        (&[0, 100][..], &b"updated"[..], Some(&b"value2"[..])),
+        (&[0, 50][..], &b"inserted"[..], None),
+        (&[1, 44][..], &b""[..], Some(&b"value4"[..])),
+    ] {
+        let (_, _, expected) = tree.proof(key).unwrap();
+        let (actual_old_value, siblings) =
+            tree.insert_witnessed(key, DBValue::from(value)).unwrap();
+        assert_eq!(actual_old_value.as_deref(), old_value);
+        assert_eq!(siblings, expected);
+        assert_eq!(siblings.len(), TREE_DEPTH * 8);
+
+        // the write does not change the siblings along the path of the key
+        let (_, new_root, after) = tree.proof(key).unwrap();
+        assert_eq!(siblings, after);
+        assert!(TreeDBMut::<TREE_DEPTH, Sha3>::verify(key, value, &siblings, &new_root).unwrap());
+    }
+}
+
+// TEST FORK
+// ================================================================================================
+
+#[test]
+fn test_fork() {
+    let (mut db, mut root) = mock_data();
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[5, 5], DBValue::from(&b"shared"[..])).unwrap();
+    let base = tree.fork();
+
+    // explore two divergent sets of changes on top of the shared change
+    tree.insert(&[0, 0], DBValue::from(&b"first"[..])).unwrap();
+    let first_root = tree.root_hint();
+    let first = tree.switch(base).unwrap();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap().as_deref(),
+        Some(&b"value1"[..])
+    );
+    tree.remove(&[0, 100]).unwrap();
+    tree.insert(&[0, 0], DBValue::from(&b"second"[..])).unwrap();
+    let second = tree.fork();
+
+    // choose the first set of changes for commit
+    tree.switch(first).unwrap();
+    assert_eq!(tree.root_hint(), first_root);
+    tree.commit();
+    assert!(matches!(
+        tree.switch(second),
+        Err(TreeError::DataError(DataError::StaleFork))
+    ));
+    drop(tree);
+
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[5, 5], DBValue::from(&b"shared"[..]))
+        .unwrap();
+    expected
+        .insert(&[0, 0], DBValue::from(&b"first"[..]))
+        .unwrap();
+    expected.commit();
+    drop(expected);
+    assert_eq!(root, first_root);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST RESET TO
+// ================================================================================================
+
+#[test]
+fn test_reset_to() {
+    let (mut db, mut root) = mock_data();
+    let first_root = root;
+
+    // store a second version of the tree alongside the first one
+    let mut second_root = Default::default();
+    let mut tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut second_root).build();
+    tree.insert(&7, DBValue::from(&b"second"[..])).unwrap();
+    tree.commit();
+    drop(tree);
+
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.reset_to(second_root).unwrap();
+    assert_eq!(tree.value(&7).unwrap().as_deref(), Some(&b"second"[..]));
+
+    // pending changes and open transactions are discarded
+    tree.begin_transaction();
+    tree.insert(&8, DBValue::from(&b"pending"[..])).unwrap();
+    tree.reset_to(first_root).unwrap();
+    assert_eq!(tree.root_hint(), first_root);
+    assert!(tree.rollback().is_err());
+    assert_eq!(tree.value(&8).unwrap(), None);
+    assert_eq!(tree.value(&0).unwrap().as_deref(), Some(&b"value1"[..]));
+
+    // unknown roots are rejected and leave the tree unchanged
+    let unknown = [7u8; 32];
+    assert_eq!(
+        tree.reset_to(unknown),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+            unknown.to_vec()
+        )))
+    );
+    assert_eq!(tree.root(), &first_root);
+
+    // a default root resets the tree to the empty tree
+    tree.reset_to(Default::default()).unwrap();
+    assert_eq!(tree.value(&0).unwrap(), None);
+}
+
+// TEST TREE BUILDER
+// ================================================================================================
+
+#[test]
+fn test_tree_builder() {
+    let (expected_db, expected_root) = mock_data();
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = TreeBuilder::<TREE_DEPTH, Sha3>::new(&mut db)
+        .build(
+            TEST_DATA
+                .iter()
+                .map(|(_, key, value)| (key, DBValue::from(*value))),
+        )
+        .unwrap();
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // shared values, empty values and a salt match a tree built by insertion
+    let leaves: Vec<(u64, DBValue)> = (0..600u64)
+        .step_by(3)
+        .map(|index| match index % 5 {
+            0 => (index, DBValue::new()),
+            _ => (index, DBValue::from(&[(index % 7) as u8][..])),
+        })
+        .collect();
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = TreeBuilder::<TREE_DEPTH, Sha3>::new(&mut db)
+        .with_salt(b"salt")
+        .build_indexed(leaves.iter().cloned())
+        .unwrap();
+    let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut expected_root = Default::default();
+    let mut tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+            .with_salt(b"salt")
+            .build();
+    for (index, value) in leaves.iter() {
+        tree.insert(index, value.clone()).unwrap();
+    }
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // the resulting tree can be updated and pruned like any other tree
+    let mut root = root;
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(b"salt")
+        .build();
+    tree.clear().unwrap();
+    tree.commit();
+    drop(tree);
+    assert!(db.keys().is_empty());
+
+    // no leaves yield the empty tree
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = TreeBuilder::<TREE_DEPTH, Sha3>::new(&mut db)
+        .build_indexed([(3, DBValue::new())])
+        .unwrap();
+    let mut empty_root = Default::default();
+    IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut empty_root)
+        .build()
+        .commit();
+    assert_eq!(root, empty_root);
+    assert!(db.keys().is_empty());
+
+    assert_eq!(
+        TreeBuilder::<TREE_DEPTH, Sha3>::new(&mut db)
+            .build_indexed([(5, DBValue::from(&b"a"[..])), (5, DBValue::from(&b"b"[..]))]),
+        Err(TreeError::KeyError(KeyError::UnsortedKey(Vec::from([
+            0, 5
+        ]))))
+    );
+}
+
+// TEST EXTEND
+// ================================================================================================
+
+#[test]
+fn test_extend() {
+    let (expected_db, expected_root) = mock_data();
+
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.extend(
+        TEST_DATA
+            .iter()
+            .map(|(_, key, value)| (Vec::from(*key), DBValue::from(*value))),
+    );
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let entries: HashMap<u64, DBValue> = TEST_DATA
+        .iter()
+        .map(|(index, _, value)| (*index, DBValue::from(*value)))
+        .collect();
+    tree.extend(entries);
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+}
+
+// TEST COMMIT TO
+// ================================================================================================
+
+#[test]
+fn test_commit_to() {
+    let (mut db, mut root) = mock_data();
+    let snapshot_keys = db.keys();
+    let mut column = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[5, 5], DBValue::from(&b"value5"[..])).unwrap();
+    let expected_root = tree.root_hint();
+    let change_set = tree.commit_to(&mut column);
+    drop(tree);
+
+    // the new nodes are written to the column, the snapshot is not modified
+    assert_eq!(root, expected_root);
+    assert_eq!(change_set.root, expected_root);
+    assert_eq!(db.keys(), snapshot_keys);
+    for hash in change_set.inserted.iter() {
+        assert!(HashDB::contains(&column, hash, EMPTY_PREFIX));
+        assert!(!HashDB::contains(&db, hash, EMPTY_PREFIX));
+    }
+
+    // the tree reads from the snapshot overlaid with the column
+    for hash in change_set.inserted.iter() {
+        let node = HashDB::get(&column, hash, EMPTY_PREFIX).unwrap();
+        db.emplace(*hash, EMPTY_PREFIX, node);
+    }
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(
+        tree.value(&[5, 5]).unwrap().as_deref(),
+        Some(&b"value5"[..])
+    );
+}
+
+// TEST REFERENCE COUNTING
+// ================================================================================================
+
+#[test]
+fn test_reference_counting() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_reference_counting()
+        .build();
+    for (_, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(*value)).unwrap();
+    }
+    // identical leaves are shared by both keys
+    tree.insert(&[5, 5], DBValue::from(&b"value1"[..])).unwrap();
+    let first_root = tree.commit().root;
+    let first_entries = tree.entries().unwrap();
+
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.remove(&[1, 44]).unwrap();
+    let change_set = tree.commit();
+    let second_root = change_set.root;
+    let second_entries = tree.entries().unwrap();
+    assert!(change_set.removed.is_empty());
+
+    // both roots are readable until released
+    let entries = |db: &MemoryDB<Sha3, NoopKey<Sha3>, DBValue>, root| {
+        TreeDBBuilder::<TREE_DEPTH, Sha3>::new(db, root)
+            .build()
+            .entries()
+            .unwrap()
+    };
+    drop(tree);
+    assert_eq!(entries(&db, &first_root), first_entries);
+    assert_eq!(entries(&db, &second_root), second_entries);
+
+    // releasing a root only removes the nodes no other root references
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_reference_counting()
+        .build();
+    let removed = tree.release_root(first_root).unwrap();
+    assert!(removed.contains(&first_root));
+    drop(tree);
+    assert_eq!(entries(&db, &second_root), second_entries);
+
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_reference_counting()
+        .build();
+    tree.release_root(second_root).unwrap();
+    drop(tree);
+    assert!(db.keys().is_empty());
+}
+
+// TEST VERSIONED TREE
+// ================================================================================================
+
+#[test]
+fn test_versioned_tree() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = VersionedTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    for (_, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(*value)).unwrap();
+    }
+    tree.commit();
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.commit();
+    tree.remove(&[0, 0]).unwrap();
+    let latest = *tree.root();
+    assert_eq!(tree.versions()[3], latest);
+    assert_eq!(tree.version(), 3);
+
+    // every version serves reads and proofs
+    for (version, value) in [
+        (0, None),
+        (1, Some(&b"value1"[..])),
+        (2, Some(&b"updated"[..])),
+        (3, None),
+    ] {
+        assert_eq!(tree.value_at(version, &[0, 0]).unwrap().as_deref(), value);
+        let (_, version_root, proof) = tree.proof_at(version, &[0, 0]).unwrap();
+        assert_eq!(version_root, tree.root_at(version).unwrap());
+        assert!(TreeDBMut::<TREE_DEPTH, Sha3>::verify(
+            &[0, 0],
+            value.unwrap_or_default(),
+            &proof,
+            &version_root
+        )
+        .unwrap());
+    }
+    assert_eq!(
+        tree.value_at(4, &[0, 0]),
+        Err(TreeError::DataError(DataError::UnknownVersion(4)))
+    );
+
+    // pruning removes the nodes only referenced by earlier versions
+    assert!(tree.prune(2).unwrap() > 0);
+    assert_eq!(
+        tree.value_at(1, &[0, 0]),
+        Err(TreeError::DataError(DataError::PrunedVersion(1)))
+    );
+    assert_eq!(
+        tree.value_at(2, &[0, 100]).unwrap().as_deref(),
+        Some(&b"value2"[..])
+    );
+    tree.prune(3).unwrap();
+    assert_eq!(
+        tree.value_at(3, &[0, 100]).unwrap().as_deref(),
+        Some(&b"value2"[..])
+    );
+    drop(tree);
+
+    // only the nodes of the latest version remain
+    let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut expected_root = Default::default();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+            .with_reference_counting()
+            .build();
+    for (_, key, value) in TEST_DATA.iter().skip(1) {
+        expected.insert(key, DBValue::from(*value)).unwrap();
+    }
+    expected.commit();
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST MAX STAGED NODES
+// ================================================================================================
+
+#[test]
+fn test_max_staged_nodes() {
+    let (expected_db, expected_root) = mock_data();
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+
+    // a write stages the TREE_DEPTH * 8 + 1 nodes of its path and exceeds the limit
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_max_staged_nodes(TREE_DEPTH * 8)
+        .build();
+    for (index, _, value) in TEST_DATA.iter() {
+        tree.insert(index, DBValue::from(*value)).unwrap();
+        assert!(tree.commit().is_empty());
+    }
+
+    // writes within a transaction are not committed automatically
+    tree.begin_transaction();
+    tree.insert(&7, DBValue::from(&b"discarded"[..])).unwrap();
+    tree.rollback().unwrap();
+    assert_eq!(tree.value(&7).unwrap(), None);
+    drop(tree);
+
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // writes of many leaves are committed once they complete rather than between their leaves,
+    // such that the nodes written are those of a single commit
+    let written = |batch: bool, max_staged_nodes: usize| {
+        let entries = TEST_DATA
+            .iter()
+            .map(|(index, _, value)| (*index, DBValue::from(*value)));
+        let metrics = CountingMetrics::default();
+        let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+        let mut root = Default::default();
+        let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+            .with_max_staged_nodes(max_staged_nodes)
+            .with_metrics(&metrics)
+            .build();
+        match batch {
+            true => tree.insert_batch(entries).unwrap(),
+            false => assert!(tree.insert_many(entries).iter().all(Result::is_ok)),
+        }
+        // the write is committed automatically once it exceeds the limit
+        assert_eq!(tree.commit().is_empty(), max_staged_nodes < usize::MAX);
+        drop(tree);
+        assert_eq!(root, expected_root);
+        metrics.nodes.load(Ordering::Relaxed)
+    };
+    for batch in [false, true] {
+        assert_eq!(written(batch, TREE_DEPTH * 8), written(batch, usize::MAX));
+    }
+}
+
+// TEST INSERT WITH PROOF
+// ================================================================================================
+
+#[test]
+fn test_insert_with_proof() {
+    let (mut full_db, mut full_root) = mock_data();
+    let mut full = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut full_db, &mut full_root).build();
+
+    // a partial-state tree holds the root only
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = full.root_hint();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    let writes = [
+        (&[0, 0][..], &b"updated"[..]),
+        (&[0, 100][..], &b"value2"[..]),
+        (&[0, 200][..], &b"updated"[..]),
+        (&[1, 44][..], &b""[..]),
+        (&[5, 5][..], &b"inserted"[..]),
+        (&[0, 0][..], &b"again"[..]),
+    ];
+    for (key, value) in writes {
+        let (old_value, _, proof) = full.proof(key).unwrap();
+        let old_value = old_value.unwrap_or_default();
+        assert_eq!(
+            tree.insert_with_proof(key, DBValue::from(value), &old_value, &proof[1..]),
+            Err(TreeError::ProofError(ProofError::DepthMismatch(
+                TREE_DEPTH * 8,
+                TREE_DEPTH * 8 - 1
+            )))
+        );
+        assert!(matches!(
+            tree.insert_with_proof(key, DBValue::from(value), b"wrong", &proof),
+            Err(TreeError::ProofError(ProofError::RootMismatch(_, _)))
+        ));
+
+        let expected = full.insert(key, DBValue::from(value)).unwrap();
+        let actual = tree
+            .insert_with_proof(key, DBValue::from(value), &old_value, &proof)
+            .unwrap();
+        assert_eq!(actual, expected.filter(|value| !value.is_empty()));
+        assert_eq!(tree.root_hint(), full.root_hint());
+    }
+
+    // the written paths are persisted and can be read from the partial-state tree
+    tree.commit();
+    assert_eq!(tree.value(&[0, 0]).unwrap().as_deref(), Some(&b"again"[..]));
+    assert_eq!(
+        tree.value(&[5, 5]).unwrap().as_deref(),
+        Some(&b"inserted"[..])
+    );
+    assert_eq!(tree.value(&[1, 44]).unwrap(), None);
+    assert_eq!(tree.root(), &full.root().clone());
+}
+
+// TEST APPLY CHANGESET
+// ================================================================================================
+
+#[test]
+fn test_apply_changeset() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.remove(&[1, 44]).unwrap();
+    let change_set = tree.commit();
+    drop(tree);
+
+    let (mut replica_db, mut replica_root) = mock_data();
+    let old_root = replica_root;
+    let mut replica =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut replica_db, &mut replica_root).build();
+
+    // a ChangeSet declaring a different root is not applied
+    let tampered = ChangeSet::<Sha3> {
+        root: old_root,
+        inserted: Vec::new(),
+        removed: Vec::new(),
+        leaves: change_set.leaves.clone(),
+    };
+    assert!(matches!(
+        replica.apply_changeset(&tampered),
+        Err(TreeError::ProofError(ProofError::RootMismatch(_, _)))
+    ));
+    assert_eq!(replica.root_hint(), old_root);
+    assert_eq!(
+        replica.value(&[0, 0]).unwrap().as_deref(),
+        Some(&b"value1"[..])
+    );
+
+    replica.apply_changeset(&change_set).unwrap();
+    assert_eq!(replica.root_hint(), change_set.root);
+
+    // the old values no longer match once the ChangeSet is applied
+    assert_eq!(
+        replica.apply_changeset(&change_set),
+        Err(TreeError::DataError(DataError::ValueMismatch(Vec::from([
+            0, 0
+        ]))))
+    );
+    assert_eq!(replica.root_hint(), change_set.root);
+
+    replica.commit();
+    drop(replica);
+    assert_eq!(replica_root, root);
+    assert_eq!(replica_db.keys(), db.keys());
+
+    // a ChangeSet that fails to apply is not committed automatically, even if its leaves exceed
+    // the maximum number of staged nodes
+    let (mut replica_db, mut replica_root) = mock_data();
+    let expected_keys = replica_db.keys();
+    let mut replica = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut replica_db, &mut replica_root)
+        .with_max_staged_nodes(1)
+        .build();
+    assert!(matches!(
+        replica.apply_changeset(&tampered),
+        Err(TreeError::ProofError(ProofError::RootMismatch(_, _)))
+    ));
+    assert_eq!(replica.root_hint(), old_root);
+    assert_eq!(replica.transaction_depth(), 0);
+    drop(replica);
+    assert_eq!(replica_root, old_root);
+    assert_eq!(replica_db.keys(), expected_keys);
+}
+
+// TEST COMPARE AND SWAP
+// ================================================================================================
+
+#[test]
+fn test_compare_and_swap() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let old_root = tree.root_hint();
+
+    // a mismatched value fails without modifying the tree
+    let mismatch = Err(TreeError::DataError(DataError::ValueMismatch(Vec::from([
+        0, 0,
+    ]))));
+    assert_eq!(
+        tree.compare_and_swap(&[0, 0], Some(b"wrong"), DBValue::from(&b"swapped"[..])),
+        mismatch
+    );
+    assert_eq!(
+        tree.compare_and_swap(&[0, 0], None, DBValue::from(&b"swapped"[..])),
+        mismatch
+    );
+    assert_eq!(tree.root_hint(), old_root);
+
+    tree.compare_and_swap(&[0, 0], Some(b"value1"), DBValue::from(&b"swapped"[..]))
+        .unwrap();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap().as_deref(),
+        Some(&b"swapped"[..])
+    );
+
+    // an absent key is expected as None and an empty value removes the key
+    tree.compare_and_swap(&[5, 5], None, DBValue::from(&b"added"[..]))
+        .unwrap();
+    assert_eq!(tree.value(&[5, 5]).unwrap().as_deref(), Some(&b"added"[..]));
+    tree.compare_and_swap(&[5, 5], Some(b"added"), DBValue::new())
+        .unwrap();
+    assert_eq!(tree.value(&[5, 5]).unwrap(), None);
+
+    let mut index_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut index_root = Default::default();
+    let mut index_tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut index_db, &mut index_root).build();
+    index_tree
+        .compare_and_swap(&7, None, DBValue::from(&b"value"[..]))
+        .unwrap();
+    assert!(index_tree
+        .compare_and_swap(&7, None, DBValue::from(&b"value"[..]))
+        .is_err());
+    assert_eq!(
+        index_tree.value(&7).unwrap().as_deref(),
+        Some(&b"value"[..])
+    );
+}
+
+// TEST REMOVE RANGE
+// ================================================================================================
+
+#[test]
+fn test_remove_range() {
+    let mut base_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut base_root = Default::default();
+    let mut tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut base_db, &mut base_root).build();
+    for index in (0..600u64).step_by(3) {
+        tree.insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+            .unwrap();
+    }
+    tree.commit();
+    drop(tree);
+
+    for (start, end) in [
+        (0, 0),
+        (10, 5),
+        (7, 8),
+        (10, 200),
+        (256, 512),
+        (1, 65536),
+        (0, 65536),
+    ] {
+        let (mut db, mut root) = (base_db.clone(), base_root);
+        let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+        tree.remove_range(&start, &end).unwrap();
+        let change_set = tree.commit();
+        drop(tree);
+
+        // the range is removed as by removing each index
+        let (mut expected_db, mut expected_root) = (base_db.clone(), base_root);
+        let mut expected =
+            IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+                .build();
+        for index in (0..600)
+            .step_by(3)
+            .filter(|index| (start..end).contains(index))
+        {
+            expected.remove(&index).unwrap();
+        }
+        let expected_change_set = expected.commit();
+        drop(expected);
+
+        assert_eq!(root, expected_root);
+        assert_eq!(change_set.leaves, expected_change_set.leaves);
+        assert_eq!(db.keys(), expected_db.keys());
+    }
+
+    let (mut db, mut root) = mock_data();
+    let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(
+        tree.remove_range(&0, &65537),
+        Err(TreeError::KeyError(KeyError::LeafIndexOutOfBounds(
+            65536, 65536
+        )))
+    );
+}
+
+// TEST ENTRY
+// ================================================================================================
+
+#[test]
+fn test_entry() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    // a counter is incremented, or initialised if it is absent
+    let increment = |value: &mut DBValue| value[0] += 1;
+    for _ in 0..3 {
+        tree.entry(&[5, 5])
+            .unwrap()
+            .and_modify(increment)
+            .unwrap()
+            .or_insert_with(|| DBValue::from(&[1][..]))
+            .unwrap();
+    }
+    assert_eq!(tree.value(&[5, 5]).unwrap().as_deref(), Some(&[3][..]));
+
+    // an occupied entry holds the value and is not overwritten by or_insert
+    match tree.entry(&[0, 0]).unwrap() {
+        Entry::Occupied(entry) => {
+            assert_eq!(entry.key(), &[0, 0]);
+            assert_eq!(entry.get().as_slice(), b"value1");
+        }
+        Entry::Vacant(_) => panic!("expected an occupied entry"),
+    }
+    assert_eq!(
+        tree.entry(&[0, 0])
+            .unwrap()
+            .or_insert(DBValue::from(&b"default"[..]))
+            .unwrap()
+            .as_slice(),
+        b"value1"
+    );
+
+    // an occupied entry is replaced and removed in place
+    if let Entry::Occupied(mut entry) = tree.entry(&[0, 100]).unwrap() {
+        let old_value = entry.insert(DBValue::from(&b"replaced"[..])).unwrap();
+        assert_eq!(old_value.as_slice(), b"value2");
+        assert_eq!(entry.remove().unwrap().as_slice(), b"replaced");
+    }
+    assert_eq!(tree.value(&[0, 100]).unwrap(), None);
+    assert!(matches!(tree.entry(&[0, 100]).unwrap(), Entry::Vacant(_)));
+
+    let mut index_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut index_root = Default::default();
+    let mut index_tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut index_db, &mut index_root).build();
+    index_tree
+        .entry(&7)
+        .unwrap()
+        .or_insert(DBValue::from(&b"value"[..]))
+        .unwrap();
+    assert_eq!(
+        index_tree.value(&7).unwrap().as_deref(),
+        Some(&b"value"[..])
+    );
+}
+
+// TEST TRY COMMIT
+// ================================================================================================
+
+/// A database that fails every write once the shared number of writes left is exhausted, as a
+/// full disk would. The contents of the database at the first failed write are kept along with the
+/// key of the write, as a crash at that write would leave them.
+struct FailingDB {
+    db: TestDB,
+    writes_left: Arc<AtomicUsize>,
+    crashed: Option<(TestDB, [u8; 32])>,
+}
+
+type TestDB = MemoryDB<Sha3, NoopKey<Sha3>, DBValue>;
+
+impl FailingDB {
+    fn write(&mut self, key: &[u8; 32]) -> Result<(), String> {
+        self.writes_left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .map(|_| ())
+            .map_err(|_| {
+                self.crashed.get_or_insert_with(|| (self.db.clone(), *key));
+                String::from("disk full")
+            })
+    }
+}
+
+impl AsHashDB<Sha3, DBValue> for FailingDB {
+    fn as_hash_db(&self) -> &dyn HashDB<Sha3, DBValue> {
+        &self.db
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<Sha3, DBValue> + 'a) {
+        &mut self.db
+    }
+}
+
+impl TryHashDB<Sha3, DBValue> for FailingDB {
+    fn try_emplace(&mut self, key: [u8; 32], prefix: Prefix, value: DBValue) -> Result<(), String> {
+        self.write(&key)?;
+        self.db.emplace(key, prefix, value);
+        Ok(())
+    }
+
+    fn try_remove(&mut self, key: &[u8; 32], prefix: Prefix) -> Result<(), String> {
+        self.write(key)?;
+        self.db.remove(key, prefix);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_try_commit() {
+    let (db, mut root) = mock_data();
+    let old_root = root;
+    let writes_left = Arc::new(AtomicUsize::new(3));
+    let mut failing_db = FailingDB {
+        db: db.clone(),
+        writes_left: writes_left.clone(),
+        crashed: None,
+    };
+    let mut tree =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_fallible(&mut failing_db, &mut root).build();
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.remove(&[0, 0]).unwrap();
+    let new_root = tree.root_hint();
+
+    // a failed commit is reverted and the changes remain staged
+    assert!(matches!(
+        tree.try_commit(),
+        Err(TreeError::DataError(DataError::WriteFailed(err))) if err == "disk full"
+    ));
+    assert_eq!(tree.root_hint(), new_root);
+    assert_eq!(tree.value(&[5, 5]).unwrap().as_deref(), Some(&b"added"[..]));
+    assert!(!HashDB::contains(tree.db(), &new_root, EMPTY_PREFIX));
+
+    // the commit is retried once the database accepts writes
+    writes_left.store(usize::MAX, Ordering::SeqCst);
+    let change_set = tree.try_commit().unwrap();
+    drop(tree);
+    assert_ne!(root, old_root);
+
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[5, 5], DBValue::from(&b"added"[..]))
+        .unwrap();
+    expected.remove(&[0, 0]).unwrap();
+    assert_eq!(change_set.leaves, expected.commit().leaves);
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(failing_db.db.keys(), expected_db.keys());
+}
+
+// TEST JOURNAL
+// ================================================================================================
+
+#[test]
+fn test_journal() {
+    let (db, root) = mock_data();
+    let (mut expected_db, mut expected_root) = (db.clone(), root);
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[5, 5], DBValue::from(&b"added"[..]))
+        .unwrap();
+    expected.remove(&[0, 0]).unwrap();
+    expected.commit();
+    drop(expected);
+
+    // crash the commit at every write until it completes
+    for writes in 0.. {
+        let mut failing_db = FailingDB {
+            db: db.clone(),
+            writes_left: Arc::new(AtomicUsize::new(writes)),
+            crashed: None,
+        };
+        let mut new_root = root;
+        let mut tree =
+            TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_fallible(&mut failing_db, &mut new_root)
+                .with_journal()
+                .build();
+        tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+        tree.remove(&[0, 0]).unwrap();
+        let committed = tree.try_commit().is_ok();
+        drop(tree);
+        let Some((crashed, key)) = failing_db.crashed else {
+            // the journal is removed once the commit completes
+            assert!(committed);
+            assert_eq!(new_root, expected_root);
+            assert_eq!(failing_db.db.keys(), expected_db.keys());
+            break;
+        };
+        // a crash at a cursor record following a node write leaves the node write unrecorded
+        let journal_key = Sha3::hash(b"merkle-tree-db/journal");
+        if key != journal_key
+            && !HashDB::contains(&db, &key, EMPTY_PREFIX)
+            && !HashDB::contains(&expected_db, &key, EMPTY_PREFIX)
+        {
+            continue;
+        }
+
+        let mut replayed = crashed.clone();
+        match replay_journal::<Sha3>(&mut replayed).unwrap() {
+            Some(replayed_root) => {
+                assert_eq!(replayed_root, expected_root);
+                assert_eq!(replayed.keys(), expected_db.keys());
+            }
+            // the crash precedes the journal
+            None => assert_eq!(replayed.keys(), db.keys()),
+        }
+
+        let mut rolled_back = crashed.clone();
+        let rolled_back_root = rollback_journal::<Sha3>(&mut rolled_back).unwrap();
+        assert!(rolled_back_root.is_none() || rolled_back_root == Some(root));
+        assert_eq!(rolled_back.keys(), db.keys());
+
+        // a pending journal must be recovered before the next commit
+        let mut crashed = crashed;
+        if HashDB::contains(&crashed, &journal_key, EMPTY_PREFIX) {
+            let mut pending_root = Default::default();
+            let mut tree =
+                TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut crashed, &mut pending_root)
+                    .with_journal()
+                    .build();
+            tree.insert(&[7, 7], DBValue::from(&b"next"[..])).unwrap();
+            assert!(matches!(
+                tree.try_commit(),
+                Err(TreeError::DataError(DataError::PendingJournal))
+            ));
+        }
+    }
+}
+
+// TEST INSERT BATCH
+// ================================================================================================
+
+#[test]
+fn test_insert_batch() {
+    let entries = [
+        (&[5, 5][..], &b"added"[..]),
+        (&[0, 0][..], &b"updated"[..]),
+        (&[1, 44][..], &b""[..]),
+        (&[0, 100][..], &b"value2"[..]),
+        (&[200, 1][..], &b"first"[..]),
+        (&[200, 1][..], &b"last"[..]),
+        (&[0, 0][..], &b"again"[..]),
+    ];
+
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert_batch(
+        entries
+            .iter()
+            .map(|(key, value)| (key, DBValue::from(*value))),
+    )
+    .unwrap();
+    assert_eq!(
+        tree.value(&[200, 1]).unwrap().as_deref(),
+        Some(&b"last"[..])
+    );
+    let change_set = tree.commit();
+    drop(tree);
+
+    // the batch is equivalent to inserting the entries in order
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    for (key, value) in entries.iter() {
+        expected.insert(key, DBValue::from(*value)).unwrap();
+    }
+    assert_eq!(change_set.leaves, expected.commit().leaves);
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // a batch removing every leaf restores the empty tree
+    let mut index_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut index_root = Default::default();
+    let mut index_tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut index_db, &mut index_root).build();
+    let empty_root = index_tree.root_hint();
+    index_tree
+        .insert_batch((0..100).map(|index| (index, DBValue::from(&b"value"[..]))))
+        .unwrap();
+    assert_eq!(
+        index_tree.value(&42).unwrap().as_deref(),
+        Some(&b"value"[..])
+    );
+    index_tree
+        .insert_batch((0..100).map(|index| (index, DBValue::new())))
+        .unwrap();
+    assert_eq!(index_tree.root_hint(), empty_root);
+    index_tree.commit();
+    drop(index_tree);
+    assert!(index_db.keys().is_empty());
+}
+
+// TEST DEFERRED HASHING
+// ================================================================================================
+
+#[test]
+fn test_deferred_hashing() {
+    let entries = [
+        (&[5, 5][..], &b"added"[..]),
+        (&[0, 0][..], &b"updated"[..]),
+        (&[1, 44][..], &b""[..]),
+        (&[0, 100][..], &b"value2"[..]),
+        (&[200, 1][..], &b"first"[..]),
+        (&[200, 1][..], &b"last"[..]),
+        (&[0, 0][..], &b"again"[..]),
+    ];
+
+    // the dirty leaves are read before their paths are rehashed, proofs wait for the rehash
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_deferred_hashing()
+        .build();
+    let committed_root = tree.root_hint();
+    for (key, value) in entries.iter() {
+        tree.insert(key, DBValue::from(*value)).unwrap();
+    }
+    assert_eq!(tree.root_hint(), committed_root);
+    assert_eq!(
+        tree.remove(&[200, 1]).unwrap().as_deref(),
+        Some(&b"last"[..])
+    );
+    assert_eq!(
+        tree.insert(&[200, 1], DBValue::from(&b"last"[..])).unwrap(),
+        None
+    );
+    assert_eq!(tree.value(&[0, 0]).unwrap().as_deref(), Some(&b"again"[..]));
+    assert_eq!(tree.value(&[1, 44]).unwrap(), None);
+    assert_eq!(
+        tree.value(&[0, 200]).unwrap().as_deref(),
+        Some(&b"value3"[..])
+    );
+    assert_eq!(
+        tree.proof(&[0, 0]).err(),
+        Some(TreeError::DataError(DataError::UnhashedLeaves))
+    );
+
+    // a rolled back transaction discards its dirty leaves
+    tree.begin_transaction();
+    tree.insert(&[9, 9], DBValue::from(&b"discarded"[..]))
+        .unwrap();
+    tree.rollback().unwrap();
+    assert_eq!(tree.value(&[9, 9]).unwrap(), None);
+    let change_set = tree.commit();
+    drop(tree);
+
+    // the rehashed tree is the tree of the same inserts hashed one by one
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    for (key, value) in entries.iter() {
+        expected.insert(key, DBValue::from(*value)).unwrap();
+    }
+    assert_eq!(change_set.leaves, expected.commit().leaves);
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    /// Sha3 counting the inner nodes hashed, only this test hashes with it
+    #[derive(Debug)]
+    struct CountingSha3;
+
+    static INNER_HASHES: AtomicUsize = AtomicUsize::new(0);
+
+    impl Hasher for CountingSha3 {
+        type Out = [u8; 32];
+
+        type StdHasher = Hash256StdHasher;
+
+        const LENGTH: usize = 32;
+
+        fn hash(data: &[u8]) -> Self::Out {
+            Sha3::hash(data)
+        }
+    }
+
+    impl TreeHasher for CountingSha3 {
+        fn hash_inner(left: &[u8], right: &[u8]) -> Self::Out {
+            INNER_HASHES.fetch_add(1, Ordering::Relaxed);
+            Sha3::hash_inner(left, right)
+        }
+    }
+
+    // the inner nodes shared by the paths of the dirty leaves are hashed once, as with a batch
+    let inner_hashes = |deferred: bool, batch: bool| {
+        let mut db = MemoryDB::<CountingSha3, NoopKey<CountingSha3>, DBValue>::default();
+        let mut root = Default::default();
+        let mut builder =
+            IndexTreeDBMutBuilder::<TREE_DEPTH, CountingSha3>::new(&mut db, &mut root);
+        if deferred {
+            builder = builder.with_deferred_hashing();
+        }
+        let mut tree = builder.build();
+        let entries = (0..64u64).map(|index| (index, DBValue::from(&b"value"[..])));
+        INNER_HASHES.store(0, Ordering::Relaxed);
+        match batch {
+            true => tree.insert_batch(entries).unwrap(),
+            false => {
+                for (index, value) in entries {
+                    tree.insert(&index, value).unwrap();
+                }
+                tree.rehash().unwrap();
+            }
+        }
+        let inner_hashes = INNER_HASHES.load(Ordering::Relaxed);
+        (inner_hashes, *tree.root())
+    };
+    let (immediate, root) = inner_hashes(false, false);
+    let (deferred, deferred_root) = inner_hashes(true, false);
+    let (batched, batched_root) = inner_hashes(false, true);
+    assert_eq!(deferred_root, root);
+    assert_eq!(batched_root, root);
+    assert_eq!(immediate, 64 * 16);
+    assert_eq!(deferred, batched);
+    assert_eq!(deferred, 63 + 10);
+}
+
+// TEST TWO-PHASE COMMIT
+// ================================================================================================
+
+#[test]
+fn test_two_phase_commit() {
+    let (mut db, mut root) = mock_data();
+    let old_root = root;
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    let old_value = tree.value(&[0, 0]).unwrap();
+    assert!(matches!(
+        tree.finalize(),
+        Err(TreeError::DataError(DataError::NoPreparedCommit))
+    ));
+
+    // a prepared commit writes nothing and freezes the tree
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.remove(&[0, 0]).unwrap();
+    let prepared = tree.prepare().unwrap();
+    assert_eq!(prepared.root, tree.root_hint());
+    assert!(!prepared.inserted.is_empty());
+    assert!(prepared
+        .inserted
+        .iter()
+        .all(|hash| !HashDB::contains(tree.db(), hash, EMPTY_PREFIX)));
+    assert!(matches!(
+        tree.insert(&[6, 6], DBValue::from(&b"frozen"[..])),
+        Err(TreeError::DataError(DataError::CommitPrepared))
+    ));
+    assert!(matches!(
+        tree.try_commit(),
+        Err(TreeError::DataError(DataError::CommitPrepared))
+    ));
+    assert!(matches!(
+        tree.prepare(),
+        Err(TreeError::DataError(DataError::CommitPrepared))
+    ));
+
+    // an aborted commit discards the changes
+    tree.abort().unwrap();
+    assert_eq!(tree.root_hint(), old_root);
+    assert_eq!(tree.value(&[0, 0]).unwrap(), old_value);
+
+    // a finalized commit writes the prepared changes
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.remove(&[0, 0]).unwrap();
+    let prepared = tree.prepare().unwrap();
+    tree.finalize().unwrap();
+    assert!(matches!(
+        tree.abort(),
+        Err(TreeError::DataError(DataError::NoPreparedCommit))
+    ));
+    tree.insert(&[6, 6], DBValue::from(&b"next"[..])).unwrap();
+    drop(tree);
+    assert_eq!(root, prepared.root);
+
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[5, 5], DBValue::from(&b"added"[..]))
+        .unwrap();
+    expected.remove(&[0, 0]).unwrap();
+    let change_set = expected.commit();
+    drop(expected);
+    assert_eq!(db.keys(), expected_db.keys());
+    assert_eq!(prepared.inserted, change_set.inserted);
+    assert_eq!(prepared.removed, change_set.removed);
+    assert_eq!(prepared.leaves, change_set.leaves);
+}
+
+// TEST TRUNCATE
+// ================================================================================================
+
+#[test]
+fn test_truncate() {
+    let mut base_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut base_root = Default::default();
+    let mut tree =
+        IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut base_db, &mut base_root).build();
+    for index in 0..300u64 {
+        tree.insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+            .unwrap();
+    }
+    tree.commit();
+    drop(tree);
+
+    for len in [300, 299, 128, 37, 1, 0, 65536, u64::MAX] {
+        let (mut db, mut root) = (base_db.clone(), base_root);
+        let mut tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+        tree.truncate(&len).unwrap();
+        tree.commit();
+        drop(tree);
+
+        // the log truncated to len is the log of its first len entries
+        let mut expected_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+        let mut expected_root = Default::default();
+        let mut expected =
+            IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root)
+                .build();
+        for index in 0..len.min(300) {
+            expected
+                .insert(&index, DBValue::from(&index.to_be_bytes()[..]))
+                .unwrap();
+        }
+        expected.commit();
+        drop(expected);
+
+        assert_eq!(root, expected_root);
+        assert_eq!(db.keys(), expected_db.keys());
+    }
+}
+
+// TEST REVERT KEY
+// ================================================================================================
+
+#[test]
+fn test_revert_key() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.insert(&[9, 9], DBValue::from(&b"added"[..])).unwrap();
+    tree.remove(&[0, 100]).unwrap();
+    tree.remove(&[1, 44]).unwrap();
+
+    // updates, inserts and removes are reverted, unchanged keys are left alone
+    assert!(tree.revert_key(&[0, 0]).unwrap());
+    assert!(tree.revert_key(&[5, 5]).unwrap());
+    assert!(tree.revert_key(&[0, 100]).unwrap());
+    assert!(!tree.revert_key(&[0, 100]).unwrap());
+    assert!(!tree.revert_key(&[0, 200]).unwrap());
+    assert!(!tree.revert_key(&[7, 7]).unwrap());
+    assert_eq!(
+        tree.revert_key(&[0]),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 1)))
+    );
+    let change_set = tree.commit();
+    drop(tree);
+
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[9, 9], DBValue::from(&b"added"[..]))
+        .unwrap();
+    expected.remove(&[1, 44]).unwrap();
+    assert_eq!(change_set.leaves, expected.commit().leaves);
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST DESTROY
+// ================================================================================================
+
+#[test]
+fn test_destroy() {
+    let (mut db, root) = mock_data();
+    let mut tenant_root = Default::default();
+    let mut tenant = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut tenant_root).build();
+    // the tenant shares a leaf and a subtree with the tree
+    for (key, value) in [
+        (&[0, 0][..], &b"value1"[..]),
+        (&[1, 44][..], &b"value4"[..]),
+        (&[7, 7][..], &b"value4"[..]),
+        (&[9, 9][..], &b"tenant"[..]),
+    ] {
+        tenant.insert(key, DBValue::from(value)).unwrap();
+    }
+    tenant.commit();
+    drop(tenant);
+
+    // the nodes only reachable from the tenant root are deleted, the shared nodes are kept
+    let tenant_nodes = nodes::<TREE_DEPTH, Sha3>(&db, &tenant_root, &[])
+        .map(|node| node.unwrap().0)
+        .collect::<Vec<_>>();
+    let deleted = destroy::<TREE_DEPTH, Sha3>(&tenant_root, &mut db, &[]).unwrap();
+    assert!(deleted > 0 && deleted < tenant_nodes.len());
+    let (expected_db, expected_root) = mock_data();
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // destroying the last tree empties the database
+    assert_eq!(
+        destroy::<TREE_DEPTH, Sha3>(&root, &mut db, &[]).unwrap(),
+        nodes::<TREE_DEPTH, Sha3>(&expected_db, &root, &[]).count()
+    );
+    assert!(db.keys().is_empty());
+    assert_eq!(
+        destroy::<TREE_DEPTH, Sha3>(&Default::default(), &mut db, &[]),
+        Ok(0)
+    );
+    assert_eq!(
+        destroy::<TREE_DEPTH, Sha3>(&root, &mut db, &[]),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+            root.to_vec()
+        )))
+    );
+}
+
+// TEST INSERT MANY
+// ================================================================================================
+
+#[test]
+fn test_insert_many() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_max_value_size(8)
+        .build();
+    let results = tree.insert_many([
+        (&[0, 0][..], DBValue::from(&b"updated"[..])),
+        (&[5][..], DBValue::from(&b"short"[..])),
+        (&[5, 5][..], DBValue::from(&b"added"[..])),
+        (&[6, 6][..], DBValue::from(&b"too large"[..])),
+        (&[0, 100][..], DBValue::new()),
+    ]);
+    assert_eq!(
+        results,
+        Vec::from([
+            Ok(Some(DBValue::from(&b"value1"[..]))),
+            Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 1))),
+            Ok(None),
+            Err(TreeError::DataError(DataError::ValueTooLarge(9, 8))),
+            Ok(Some(DBValue::from(&b"value2"[..]))),
+        ])
+    );
+    tree.commit();
+    drop(tree);
+
+    // the valid entries are applied
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    expected
+        .insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    expected
+        .insert(&[5, 5], DBValue::from(&b"added"[..]))
+        .unwrap();
+    expected.remove(&[0, 100]).unwrap();
+    expected.commit();
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST REMOVE BATCH
+// ================================================================================================
+
+#[test]
+fn test_remove_batch() {
+    let keys = [
+        &[1, 44][..],
+        &[7, 7][..],
+        &[0, 0][..],
+        &[1, 44][..],
+        &[0, 200][..],
+    ];
+
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    assert_eq!(
+        tree.remove_batch([&[0, 0][..], &[0][..]]),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 1)))
+    );
+    assert_eq!(
+        tree.remove_batch(keys).unwrap(),
+        Vec::from([
+            Some(DBValue::from(&b"value4"[..])),
+            None,
+            Some(DBValue::from(&b"value1"[..])),
+            None,
+            Some(DBValue::from(&b"value3"[..])),
+        ])
+    );
+    let change_set = tree.commit();
+    drop(tree);
+
+    // the batch is removed as by removing each key
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected_db, &mut expected_root).build();
+    for key in keys {
+        expected.remove(key).unwrap();
+    }
+    assert_eq!(change_set.leaves, expected.commit().leaves);
+    drop(expected);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+
+    // a batch exceeding the maximum number of staged nodes is committed once it completes
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_max_staged_nodes(1)
+        .build();
+    assert_eq!(tree.remove_batch(keys).unwrap().iter().flatten().count(), 3);
+    assert!(tree.commit().is_empty());
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST SNAPSHOT
+// ================================================================================================
+
+#[test]
+fn test_snapshot() {
+    let (mut db, mut root) = mock_data();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[5, 5], DBValue::from(&b"added"[..])).unwrap();
+    tree.remove(&[0, 0]).unwrap();
+    let snapshot = tree.snapshot();
+    assert_eq!(snapshot.root(), &tree.root_hint());
+    let snapshot_root = tree.root_hint();
+
+    // the snapshot is unaffected by later writes
+    tree.insert(&[5, 5], DBValue::from(&b"changed"[..]))
+        .unwrap();
+    tree.insert(&[0, 0], DBValue::from(&b"restored"[..]))
+        .unwrap();
+    drop(tree);
+
+    let snapshot = snapshot.clone();
+    assert_eq!(
+        snapshot.value(&db, &[5, 5]).unwrap().as_deref(),
+        Some(&b"added"[..])
+    );
+    assert_eq!(snapshot.value(&db, &[0, 0]).unwrap(), None);
+    assert_eq!(
+        snapshot.value(&db, &[0, 100]).unwrap().as_deref(),
+        Some(&b"value2"[..])
+    );
+    assert!(snapshot.leaf(&db, &[5, 5]).unwrap().is_some());
+    let (value, proof_root, proof) = snapshot.proof(&db, &[5, 5]).unwrap();
+    assert_eq!(proof_root, snapshot_root);
+    assert!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[5, 5], &value.unwrap(), &proof, &snapshot_root)
+            .unwrap()
+    );
+}
+
+// TEST MIGRATE
+// ================================================================================================
+
+/// Inserts the test data and an expiring leaf into a salted tree using hasher H and commits
+fn salted_tree<H: TreeHasher>() -> (MemoryDB<H, NoopKey<H>, DBValue>, H::Out) {
+    let mut db = MemoryDB::<H, NoopKey<H>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, H>::new(&mut db, &mut root)
+        .with_salt(b"salt")
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.insert_with_expiry(&[3, 3], DBValue::from(&b"expiring"[..]), 42)
+        .unwrap();
+    tree.commit();
+    drop(tree);
+    (db, root)
+}
+
+#[test]
+fn test_migrate() {
+    let (source, root) = salted_tree::<Sha3>();
+    let (expected_db, expected_root) = salted_tree::<Keccak>();
+    let leaf_count = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&source, &root)
+        .with_salt(b"salt")
+        .build()
+        .entries()
+        .unwrap()
+        .len();
+
+    // the migrated tree is identical to the tree built with the new hasher
+    let mut reports = Vec::new();
+    let mut progress = |processed: usize| reports.push(processed);
+    let mut monitor = Monitor::new().with_progress(&mut progress).with_interval(2);
+    let mut target = MemoryDB::<Keccak, NoopKey<Keccak>, DBValue>::default();
+    let migrated = migrate_with_monitor::<TREE_DEPTH, Sha3, Keccak>(
+        &root,
+        &source,
+        &mut target,
+        b"salt",
+        &mut monitor,
+    )
+    .unwrap();
+    assert_eq!(migrated, expected_root);
+    assert_eq!(monitor.processed(), leaf_count);
+    assert_eq!(reports.last(), Some(&leaf_count));
+    assert_eq!(target.keys(), expected_db.keys());
+    let tree = TreeDBBuilder::<TREE_DEPTH, Keccak>::new(&target, &migrated)
+        .with_salt(b"salt")
+        .build();
+    assert_eq!(
+        tree.value(&[3, 3]).unwrap(),
+        Some(DBValue::from(&b"expiring"[..]))
+    );
+
+    // an empty tree migrates to the empty root of the new hasher
+    let mut target = MemoryDB::<Keccak, NoopKey<Keccak>, DBValue>::default();
+    let empty = migrate::<TREE_DEPTH, Sha3, Keccak>(&Default::default(), &source, &mut target, &[])
+        .unwrap();
+    let empty_root = TreeBuilder::<TREE_DEPTH, Keccak>::new(&mut target)
+        .build::<&[u8]>([])
+        .unwrap();
+    assert_eq!(empty, empty_root);
+    assert!(target.keys().is_empty());
+
+    // a cancelled migration stops before the first leaf
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut monitor = Monitor::new().with_cancellation(&token);
+    assert_eq!(
+        migrate_with_monitor::<TREE_DEPTH, Sha3, Keccak>(
+            &root,
+            &source,
+            &mut target,
+            b"salt",
+            &mut monitor
+        ),
+        Err(TreeError::Cancelled(0))
+    );
+
+    // a missing node fails the migration
+    let empty_db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    assert_eq!(
+        migrate::<TREE_DEPTH, Sha3, Keccak>(&root, &empty_db, &mut target, b"salt"),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+            root.to_vec()
+        )))
+    );
+}
+
+// TEST MEMDB
+// ================================================================================================
+
+#[test]
+fn test_memdb() {
+    let (expected_db, expected_root) = mock_data();
+    let mut db = MemDb::<Sha3>::new();
+    assert!(db.is_empty());
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    drop(tree);
+
+    // the database holds the same records as a MemoryDB keyed by node hash
+    assert_eq!(root, expected_root);
+    assert_eq!(db.len(), expected_db.keys().len());
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+    }
+
+    // clones are independent and removed records are purged
+    let mut copy = db.clone();
+    destroy::<TREE_DEPTH, Sha3>(&root, &mut copy, &[]).unwrap();
+    copy.purge();
+    assert!(copy.is_empty());
+    assert_eq!(db.len(), expected_db.keys().len());
+}
+
+// TEST ASYNC
+// ================================================================================================
+
+/// Polls the provided future to completion, the futures of the async test backend are always
+/// ready.
+fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    let mut future = core::pin::pin!(future);
+    let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+    loop {
+        if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+/// An async backend over a MemDb whose writes fail once the shared number of writes left is used
+struct AsyncMemDb {
+    db: MemDb<Sha3>,
+    writes_left: Arc<AtomicUsize>,
+}
+
+impl AsyncHashDBRef<Sha3> for AsyncMemDb {
+    async fn get(&self, key: &[u8; 32], prefix: Prefix<'_>) -> Option<DBValue> {
+        HashDB::get(&self.db, key, prefix)
+    }
+}
+
+impl AsyncHashDB<Sha3> for AsyncMemDb {
+    async fn emplace(
+        &mut self,
+        key: [u8; 32],
+        prefix: Prefix<'_>,
+        value: DBValue,
+    ) -> Result<(), String> {
+        self.write()?;
+        self.db.emplace(key, prefix, value);
+        Ok(())
+    }
+
+    async fn remove(&mut self, key: &[u8; 32], prefix: Prefix<'_>) -> Result<(), String> {
+        self.write()?;
+        self.db.remove(key, prefix);
+        Ok(())
+    }
+}
+
+impl AsyncMemDb {
+    fn new() -> Self {
+        Self {
+            db: MemDb::new(),
+            writes_left: Arc::new(AtomicUsize::new(usize::MAX)),
+        }
+    }
+
+    fn write(&self) -> Result<(), String> {
+        self.writes_left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .map(|_| ())
+            .map_err(|_| String::from("unavailable"))
+    }
+}
+
+#[test]
+fn test_async_tree() {
+    let (expected_db, expected_root) = mock_data();
+    let mut db = AsyncMemDb::new();
+    let writes_left = Arc::clone(&db.writes_left);
+    let mut root = Default::default();
+    let mut tree = AsyncTreeDBMutBuilder::<TREE_DEPTH, Sha3, _>::new(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(
+            block_on(tree.insert(key, DBValue::from(&value[..]))).unwrap(),
+            None
+        );
+    }
+    // staged values are read before the commit
+    assert_eq!(
+        block_on(tree.value(&[0, 0])).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+    assert_eq!(tree.root(), &<[u8; 32]>::default());
+    assert_eq!(block_on(tree.commit()).unwrap(), expected_root);
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert_eq!(db.db.len(), expected_db.keys().len());
+
+    // reads and proofs match the synchronous tree
+    let tree = AsyncTreeDBBuilder::<TREE_DEPTH, Sha3, _>::new(&db, &root).build();
+    let sync_tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&expected_db, &expected_root).build();
+    for key in [&[0, 0][..], &[1, 44][..], &[9, 9][..]] {
+        assert_eq!(block_on(tree.value(key)), sync_tree.value(key));
+        assert_eq!(block_on(tree.leaf(key)), sync_tree.leaf(key));
+        assert_eq!(block_on(tree.proof(key)), sync_tree.proof(key));
+    }
+    assert!(matches!(
+        block_on(tree.value(&[0])),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(_, _)))
+    ));
+
+    // updates and removals match the synchronous tree
+    let (mut sync_db, mut sync_root) = mock_data();
+    let mut sync_tree =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut sync_db, &mut sync_root).build();
+    sync_tree
+        .insert(&[0, 0], DBValue::from(&b"updated"[..]))
+        .unwrap();
+    sync_tree.remove(&[1, 44]).unwrap();
+    sync_tree.commit();
+    drop(sync_tree);
+    let mut tree = AsyncTreeDBMutBuilder::<TREE_DEPTH, Sha3, _>::new(&mut db, &mut root).build();
+    block_on(tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))).unwrap();
+    assert_eq!(
+        block_on(tree.remove(&[1, 44])).unwrap(),
+        Some(DBValue::from(&b"value4"[..]))
+    );
+    assert_eq!(block_on(tree.value(&[1, 44])).unwrap(), None);
+
+    // a failed commit reverts its writes and keeps the changes staged
+    writes_left.store(3, Ordering::SeqCst);
+    assert!(matches!(
+        block_on(tree.commit()),
+        Err(TreeError::DataError(DataError::WriteFailed(_)))
+    ));
+    assert_eq!(tree.root(), &expected_root);
+    writes_left.store(usize::MAX, Ordering::SeqCst);
+    assert_eq!(block_on(tree.commit()).unwrap(), sync_root);
+    drop(tree);
+    assert_eq!(db.db.len(), sync_db.keys().len());
+}
+
+// TEST OVERLAY
+// ================================================================================================
+
+#[test]
+fn test_overlay_db() {
+    let (base, base_root) = mock_data();
+    let (mut expected_db, mut expected_root) = mock_data();
+    let mut expected_tenant_root = Default::default();
+    let mut overlay = OverlayDB::new(&base);
+    let mut root = base_root;
+    let mut tenant_root = Default::default();
+
+    // the commits of two trees are staged in the overlay
+    for (db, root, tenant_root) in [
+        (
+            &mut overlay as &mut dyn HashDB<Sha3, DBValue>,
+            &mut root,
+            &mut tenant_root,
+        ),
+        (
+            &mut expected_db,
+            &mut expected_root,
+            &mut expected_tenant_root,
+        ),
+    ] {
+        let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(db, root).build();
+        tree.insert(&[0, 0], DBValue::from(&b"updated"[..]))
+            .unwrap();
+        tree.remove(&[1, 44]).unwrap();
+        tree.commit();
+        drop(tree);
+        let mut tenant = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(db, tenant_root).build();
+        tenant
+            .insert(&[9, 9], DBValue::from(&b"tenant"[..]))
+            .unwrap();
+        tenant.commit();
+    }
+    assert_eq!(root, expected_root);
+    assert_eq!(tenant_root, expected_tenant_root);
+    assert!(!overlay.is_empty());
+
+    // the base is untouched and the overlay reads the staged trees
+    assert_eq!(base.keys(), mock_data().0.keys());
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&overlay, &root).build();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap(),
+        Some(DBValue::from(&b"updated"[..]))
+    );
+    assert_eq!(tree.value(&[1, 44]).unwrap(), None);
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&base, &base_root).build();
+    assert_eq!(
+        tree.value(&[1, 44]).unwrap(),
+        Some(DBValue::from(&b"value4"[..]))
+    );
+
+    // the consolidated batch applied to the base yields the database of the direct commits
+    let batch = overlay.consolidate();
+    assert!(overlay.is_empty());
+    let mut db = base.clone();
+    for (hash, data, rc) in batch {
+        for _ in 0..rc {
+            db.emplace(hash, EMPTY_PREFIX, data.clone());
+        }
+        for _ in rc..0 {
+            db.remove(&hash, EMPTY_PREFIX);
+        }
+    }
+    assert_eq!(db.keys(), expected_db.keys());
+}
+
+// TEST CACHED DB
+// ================================================================================================
+
+#[test]
+fn test_cached_db() {
+    let (db, root) = mock_data();
+    let counting_db = CountingDB {
+        db: &db,
+        reads: core::cell::Cell::new(0),
+    };
+    let cache = CachedDB::new(&counting_db, 64);
+
+    // the decoded nodes are shared across trees built on the cache
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_cached(&cache, &root).build();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+    assert_eq!(cache.len(), TREE_DEPTH * 8 + 1);
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_cached(&cache, &root).build();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+
+    // plain readers of the cache share the nodes in their encoded form
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&cache, &root).build();
+    let sync_tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(tree.proof(&[0, 0]), sync_tree.proof(&[0, 0]));
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+
+    // the least recently used nodes are evicted beyond the capacity
+    let cache = CachedDB::new(&counting_db, 4);
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_cached(&cache, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+        assert!(cache.len() <= cache.capacity());
+    }
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+// TEST PREFIX
+// ================================================================================================
+
+#[test]
+fn test_prefixed_trees() {
+    type PrefixedDB = MemoryDB<Sha3, PrefixedKey<Sha3>, DBValue>;
+    let (expected_db, expected_root) = mock_data();
+    let node_count = expected_db.keys().len();
+
+    // identical trees under different prefixes are stored apart
+    let mut db = PrefixedDB::default();
+    let mut roots = [<Sha3 as Hasher>::Out::default(); 2];
+    for (prefix, root) in [&b"a"[..], &b"b"[..]].into_iter().zip(roots.iter_mut()) {
+        let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, root)
+            .with_prefix(prefix)
+            .with_journal()
+            .build();
+        for (_index, key, value) in TEST_DATA.iter() {
+            tree.insert(key, DBValue::from(&value[..])).unwrap();
+        }
+        tree.try_commit().unwrap();
+    }
+    assert_eq!(roots, [expected_root; 2]);
+    assert_eq!(db.keys().len(), 2 * node_count);
+
+    // a tree is read under its prefix only
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &roots[0])
+        .with_prefix(b"a")
+        .build();
+    assert_eq!(
+        tree.value(&[0, 0]).unwrap(),
+        Some(DBValue::from(&b"value1"[..]))
+    );
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &roots[0]).build();
+    assert!(matches!(
+        tree.value(&[0, 0]),
+        Err(TreeError::DataError(DataError::DatabaseDataNotFound(_)))
+    ));
+    let tree = IndexTreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &roots[1])
+        .with_prefix(b"b")
+        .build();
+    assert_eq!(tree.value(&0).unwrap(), Some(DBValue::from(&b"value1"[..])));
+
+    // clearing one tree leaves the nodes of the other tree untouched
+    let [mut root_a, root_b] = roots;
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root_a)
+        .with_prefix(b"a")
+        .build();
+    let snapshot = tree.snapshot();
+    tree.clear().unwrap();
+    tree.commit();
+    drop(tree);
+    assert_eq!(db.keys().len(), node_count);
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root_b)
+        .with_prefix(b"b")
+        .build();
+    assert_eq!(tree.entries().unwrap().len(), TEST_DATA.len());
+    assert!(snapshot.value(&db, &[0, 0]).is_err());
+
+    // the bulk builder writes the same nodes under the prefix
+    let mut built = PrefixedDB::default();
+    let mut entries = TEST_DATA
+        .iter()
+        .map(|(_index, key, value)| (key.to_vec(), DBValue::from(&value[..])))
+        .collect::<Vec<_>>();
+    entries.sort();
+    let root = TreeBuilder::<TREE_DEPTH, Sha3>::new(&mut built)
+        .with_prefix(b"b")
+        .build(entries)
+        .unwrap();
+    assert_eq!(root, root_b);
+    assert_eq!(built.keys(), db.keys());
+}
+
+// TEST FILEDB
+// ================================================================================================
+
+#[cfg(feature = "std")]
+#[test]
+fn test_file_db() {
+    use super::FileDB;
+    use std::{fs, io::Write as _};
+
+    let path = std::env::temp_dir().join(format!("merkle-tree-db-{}.log", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let (expected_db, expected_root) = mock_data();
+
+    let mut db = FileDB::<Sha3>::open(&path).unwrap();
+    assert!(db.is_empty());
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_fallible(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.try_commit().unwrap();
+    drop(tree);
+    db.sync().unwrap();
+    drop(db);
+    assert_eq!(root, expected_root);
+
+    // the index is rebuilt from the log on open
+    let db = FileDB::<Sha3>::open(&path).unwrap();
+    assert_eq!(db.len(), expected_db.keys().len());
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+    }
+    drop(db);
+
+    // a torn tail record is truncated on open
+    let length = fs::metadata(&path).unwrap().len();
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[0, 1, 2]).unwrap();
+    drop(file);
+    let mut db = FileDB::<Sha3>::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), length);
+
+    // removed records are dropped from the replayed index
+    destroy::<TREE_DEPTH, Sha3>(&root, &mut db, &[]).unwrap();
+    assert!(db.is_empty());
+    drop(db);
+    assert!(FileDB::<Sha3>::open(&path).unwrap().is_empty());
+
+    // a tail record whose checksum does not match is truncated
+    let length = fs::metadata(&path).unwrap().len();
+    let mut data = fs::read(&path).unwrap();
+    *data.last_mut().unwrap() ^= 1;
+    fs::write(&path, &data).unwrap();
+    drop(FileDB::<Sha3>::open(&path).unwrap());
+    assert!(fs::metadata(&path).unwrap().len() < length);
+
+    // a corrupt record other than the tail record is an error
+    let mut data = fs::read(&path).unwrap();
+    data[1] ^= 1;
+    fs::write(&path, &data).unwrap();
+    let err = FileDB::<Sha3>::open(&path).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    fs::remove_file(&path).unwrap();
+}
+
+// TEST WRITE BATCH
+// ================================================================================================
+
+/// A batched backend which counts the batches written and rejects batches once the number of
+/// batches left is exhausted, leaving the database untouched.
+struct BatchedDB {
+    db: MemDb<Sha3>,
+    batches: usize,
+    batches_left: usize,
+}
+
+impl BatchHashDB<Sha3> for BatchedDB {
+    fn write_batch(&mut self, batch: &WriteBatch<Sha3>) -> Result<(), String> {
+        if self.batches_left == 0 {
+            return Err(String::from("batch rejected"));
+        }
+        self.batches_left -= 1;
+        self.batches += 1;
+        batch.apply(&mut self.db);
+        Ok(())
+    }
+}
+
+impl AsHashDB<Sha3, DBValue> for BatchedDB {
+    fn as_hash_db(&self) -> &dyn HashDB<Sha3, DBValue> {
+        &self.db
+    }
+
+    fn as_hash_db_mut<'a>(&'a mut self) -> &'a mut (dyn HashDB<Sha3, DBValue> + 'a) {
+        &mut self.db
+    }
+}
+
+#[test]
+fn test_write_batch() {
+    let (expected_db, expected_root) = mock_data();
+    let mut db = BatchedDB {
+        db: MemDb::new(),
+        batches: 0,
+        batches_left: 1,
+    };
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_batched(&mut db, &mut root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.try_commit().unwrap();
+    drop(tree);
+
+    // the commit is written as a single batch holding the same records as a per node commit
+    assert_eq!(root, expected_root);
+    assert_eq!(db.batches, 1);
+    assert_eq!(db.db.len(), expected_db.keys().len());
+
+    // a rejected batch leaves the database untouched and the changes staged
+    let snapshot = db.db.clone();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_batched(&mut db, &mut root).build();
+    tree.remove(TEST_DATA[0].1).unwrap();
+    tree.insert(&[0, 1], DBValue::from(&[7u8; 32][..])).unwrap();
+    assert!(matches!(
+        tree.try_commit(),
+        Err(TreeError::DataError(DataError::WriteFailed(_)))
+    ));
+    drop(tree);
+    assert_eq!(root, expected_root);
+    let mut current = db.db.clone();
+    current.purge();
+    assert_eq!(current.len(), expected_db.keys().len());
+
+    // the retried commit matches a commit to a MemDb, with removes and emplaces consolidated
+    db.batches_left = 1;
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new_batched(&mut db, &mut root).build();
+    tree.remove(TEST_DATA[0].1).unwrap();
+    tree.insert(&[0, 1], DBValue::from(&[7u8; 32][..])).unwrap();
+    tree.try_commit().unwrap();
+    drop(tree);
+    let mut expected = snapshot;
+    let mut expected_root = expected_root;
+    let mut tree =
+        TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut expected, &mut expected_root).build();
+    tree.remove(TEST_DATA[0].1).unwrap();
+    tree.insert(&[0, 1], DBValue::from(&[7u8; 32][..])).unwrap();
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    db.db.purge();
+    expected.purge();
+    assert_eq!(db.db.len(), expected.len());
+    assert_eq!(db.batches, 2);
+}
+
+// TEST MULTI GET
+// ================================================================================================
+
+/// Counts a multi-get as a single read, i.e. a single round trip
+impl<'a> MultiHashDBRef<Sha3> for CountingDB<'a> {
+    fn get_many(&self, keys: &[[u8; 32]], prefix: Prefix) -> Vec<Option<DBValue>> {
+        self.reads.set(self.reads.get() + 1);
+        keys.iter()
+            .map(|key| HashDB::get(self.db, key, prefix))
+            .collect()
+    }
+}
+
+#[test]
+fn test_multi_get() {
+    let (db, root) = mock_data();
+    let mut keys: Vec<&[u8]> = TEST_DATA.iter().map(|(_, key, _)| *key).collect();
+    keys.push(&[1, 0]);
+    let expected_tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let expected_values = keys
+        .iter()
+        .map(|key| expected_tree.value(key).unwrap())
+        .collect::<Vec<_>>();
+
+    // the nodes along the paths are fetched with one round trip per level for all keys
+    let counting_db = CountingDB {
+        db: &db,
+        reads: core::cell::Cell::new(0),
+    };
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_multi(&counting_db, &root).build();
+    assert_eq!(tree.values(&keys).unwrap(), expected_values);
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+    assert_eq!(tree.values(&[]).unwrap(), Vec::<Option<DBValue>>::new());
+
+    // multi-key proofs fetch their paths the same way and match the proofs of a plain tree
+    let counting_db = CountingDB {
+        db: &db,
+        reads: core::cell::Cell::new(0),
+    };
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_multi(&counting_db, &root).build();
+    let (proof_root, proof) = tree.storage_proof(&keys).unwrap();
+    assert_eq!(counting_db.reads.get(), TREE_DEPTH * 8 + 1);
+    let (expected_root, expected_proof) = expected_tree.storage_proof(&keys).unwrap();
+    assert_eq!(proof_root, expected_root);
+    assert!(proof.iter().eq(expected_proof.iter()));
+
+    // a plain database is read once per node
+    let counting_db = CountingDB {
+        db: &db,
+        reads: core::cell::Cell::new(0),
+    };
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&counting_db, &root).build();
+    assert_eq!(tree.values(&keys).unwrap(), expected_values);
+    assert!(counting_db.reads.get() > TREE_DEPTH * 8 + 1);
+}
+
+// TEST METRICS
+// ================================================================================================
+
+/// Metrics counting every hook
+#[derive(Default)]
+struct CountingMetrics {
+    reads: AtomicUsize,
+    decode_failures: AtomicUsize,
+    cache_hits: AtomicUsize,
+    nodes: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl TreeMetrics for CountingMetrics {
+    fn node_read(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn decode_failure(&self) {
+        self.decode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn nodes_written(&self, nodes: usize, bytes: usize) {
+        self.nodes.fetch_add(nodes, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_tree_metrics() {
+    let (expected_db, expected_root) = mock_data();
+
+    // commits report the nodes written
+    let metrics = CountingMetrics::default();
+    let mut db = MemDb::<Sha3>::new();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_metrics(&metrics)
+        .build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    tree.commit();
+    drop(tree);
+    assert_eq!(root, expected_root);
+    assert!(metrics.nodes.load(Ordering::Relaxed) >= expected_db.keys().len());
+    assert!(metrics.bytes.load(Ordering::Relaxed) > metrics.nodes.load(Ordering::Relaxed));
+    assert_eq!(metrics.reads.load(Ordering::Relaxed), 0);
+
+    // reads are reported once per node, repeated lookups are served by the memo
+    let metrics = CountingMetrics::default();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&expected_db, &expected_root)
+        .with_metrics(&metrics)
+        .build();
+    tree.value(&[0, 0]).unwrap();
+    assert_eq!(metrics.reads.load(Ordering::Relaxed), TREE_DEPTH * 8 + 1);
+    tree.value(&[0, 0]).unwrap();
+    assert_eq!(metrics.reads.load(Ordering::Relaxed), TREE_DEPTH * 8 + 1);
+    assert_eq!(
+        metrics.cache_hits.load(Ordering::Relaxed),
+        TREE_DEPTH * 8 + 1
+    );
+
+    // nodes served by a shared cache are reported as cache hits
+    let metrics = CountingMetrics::default();
+    let cache = CachedDB::new(&expected_db, 64);
+    for _ in 0..2 {
+        let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new_cached(&cache, &expected_root)
+            .with_metrics(&metrics)
+            .build();
+        tree.value(&[0, 0]).unwrap();
+    }
+    assert_eq!(metrics.reads.load(Ordering::Relaxed), TREE_DEPTH * 8 + 1);
+    assert_eq!(
+        metrics.cache_hits.load(Ordering::Relaxed),
+        TREE_DEPTH * 8 + 1
+    );
+
+    // mutable trees report reads and decode failures
+    let metrics = CountingMetrics::default();
+    let mut db = MemDb::<Sha3>::new();
+    let mut root = db.insert(EMPTY_PREFIX, &[0xff; 3]);
+    let tree = IndexTreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_metrics(&metrics)
+        .build();
+    assert!(matches!(tree.value(&0), Err(TreeError::NodeError(_))));
+    assert_eq!(metrics.reads.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.decode_failures.load(Ordering::Relaxed), 1);
+}
+
+// TEST STAGED STATS
+// ================================================================================================
+
+#[test]
+fn test_staged_stats() {
+    let mut db = MemDb::<Sha3>::new();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_staged_capacity(64)
+        .build();
+    let stats = tree.staged_stats();
+    assert_eq!(stats.nodes, 0);
+    assert!(stats.capacity >= 64);
+    assert!(stats.approx_bytes > 0);
+
+    // the heap bytes of the staged values are accounted for
+    for (_index, key, value) in TEST_DATA.iter() {
+        tree.insert(key, DBValue::from(&value[..])).unwrap();
+    }
+    let before = tree.staged_stats();
+    assert!(before.nodes > TEST_DATA.len());
+    assert_eq!(before.capacity, stats.capacity);
+    tree.insert(&[1, 0], DBValue::from(&[7u8; 1024][..]))
+        .unwrap();
+    let after = tree.staged_stats();
+    assert!(after.approx_bytes >= before.approx_bytes + 1024);
+    tree.remove(&[1, 0]).unwrap();
+    assert_eq!(tree.staged_stats(), before);
+
+    // a commit drains the staged nodes but keeps their capacity until shrunk
+    tree.commit();
+    let stats = tree.staged_stats();
+    assert_eq!((stats.nodes, stats.removals), (0, 0));
+    assert!(stats.capacity >= 64);
+    tree.shrink_to_fit();
+    assert_eq!(tree.staged_stats(), StagedStats::default());
+
+    // removals of committed nodes are staged as well
+    tree.remove(TEST_DATA[0].1).unwrap();
+    assert!(tree.staged_stats().removals > 0);
+}
+
+// TEST CELESTIA SMT
+// ================================================================================================
+#[test]
+fn test_celestia_proof() {
+    let leaf =
+        |key: &[u8], value: &[u8]| celestia_leaf_hash::<Sha3>(&Sha3::hash(key), &Sha3::hash(value));
+    let bit = |path: &[u8; 32], depth: usize| (path[depth / 8] >> (7 - depth % 8)) & 1 == 1;
+    let placeholder = [0u8; 32];
+
+    // the root of an empty tree is the placeholder
+    let db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let root = Default::default();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(tree.celestia_root().unwrap(), placeholder);
+
+    // a single leaf is the root, two leaves meet below the first bit at which their paths differ
+    // and the sides of the nodes above are placeholders
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    // the paths of the two keys share their first two bits
+    let [_, (_, first_key, first_value), (_, second_key, second_value), _] = TEST_DATA;
+    tree.insert(first_key, DBValue::from(first_value)).unwrap();
+    tree.commit();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(
+        tree.celestia_root().unwrap(),
+        Sha3::hash(&[&[0u8][..], &Sha3::hash(first_key), &Sha3::hash(first_value)].concat())
+    );
+
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(second_key, DBValue::from(second_value))
+        .unwrap();
+    tree.commit();
+    let (first_path, second_path) = (Sha3::hash(first_key), Sha3::hash(second_key));
+    let depth = (0..256)
+        .find(|depth| bit(&first_path, *depth) != bit(&second_path, *depth))
+        .unwrap();
+    let (first_leaf, second_leaf) = (leaf(first_key, first_value), leaf(second_key, second_value));
+    let mut expected = match bit(&first_path, depth) {
+        true => celestia_node_hash::<Sha3>(&second_leaf, &first_leaf),
+        false => celestia_node_hash::<Sha3>(&first_leaf, &second_leaf),
+    };
+    for depth in (0..depth).rev() {
+        expected = match bit(&first_path, depth) {
+            true => Sha3::hash(&[&[1u8][..], &placeholder, &expected].concat()),
+            false => Sha3::hash(&[&[1u8][..], &expected, &placeholder].concat()),
+        };
+    }
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(tree.celestia_root().unwrap(), expected);
+
+    // proofs of non-membership end at a placeholder or at the leaf of another key
+    let (mut placeholders, mut unrelated) = (0, 0);
+    for key in (0..1024u16).map(u16::to_be_bytes) {
+        if TEST_DATA
+            .iter()
+            .any(|(_, data_key, _)| data_key[..] == key[..])
+        {
+            continue;
+        }
+        let (value, proof_root, proof) = tree.celestia_proof(&key).unwrap();
+        assert_eq!((value, proof_root), (None, expected));
+        match proof.non_membership_leaf_data() {
+            Some(data) => {
+                assert_eq!((data.len(), data[0]), (65, 0));
+                unrelated += 1;
+            }
+            None => placeholders += 1,
+        }
+        assert!(verify_celestia_proof(&key, &[], &proof, &expected));
+        assert!(!verify_celestia_proof(&key, b"value1", &proof, &expected));
+    }
+    assert!(placeholders > 0 && unrelated > 0);
+
+    // proofs of membership verify with the value only
+    let (db, root) = mock_data();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    let celestia_root = tree.celestia_root().unwrap();
+    for (_index, key, value) in TEST_DATA.iter() {
+        let (actual_value, proof_root, proof) = tree.celestia_proof(key).unwrap();
+        assert_eq!(actual_value, Some(DBValue::from(*value)));
+        assert_eq!(proof_root, celestia_root);
+        assert!(proof.non_membership_leaf().is_none());
+        assert!(verify_celestia_proof(key, value, &proof, &celestia_root));
+        assert!(!verify_celestia_proof(
+            key,
+            b"other",
+            &proof,
+            &celestia_root
+        ));
+        assert!(!verify_celestia_proof(key, &[], &proof, &celestia_root));
+
+        // the leaf of a key does not prove its non-membership
+        let forged = CelestiaProof::<Sha3>::new(
+            proof.side_nodes().to_vec(),
+            Some((Sha3::hash(key), Sha3::hash(value))),
+        );
+        assert!(!verify_celestia_proof(key, &[], &forged, &celestia_root));
+    }
+
+    assert!(matches!(
+        tree.celestia_proof(&[0]),
+        Err(TreeError::KeyError(KeyError::IncorrectKeySize(2, 1)))
+    ));
+}
+
+#[test]
+fn test_celestia_known_answers() {
+    /// A proof vector of form: (key, side nodes from the leaf to the root, non-membership leaf)
+    type Vector<'a> = (&'a [u8], &'a [&'a str], Option<(&'a str, &'a str)>);
+
+    // the vectors are derived with SHA-256 by a port of the update and prove algorithms of the Go
+    // implementation (celestiaorg/smt), which inserts leaves one by one rather than partitioning
+    // the leaves by path as this crate does
+    let check = |tree: &TreeDB<TREE_DEPTH, Sha256>, root: &str, vectors: &[Vector]| {
+        let root = decode_hex(root);
+        assert_eq!(tree.celestia_root().unwrap(), root);
+        for (key, side_nodes, non_membership_leaf) in vectors.iter() {
+            let (value, proof_root, proof) = tree.celestia_proof(key).unwrap();
+            assert_eq!(proof_root, root);
+            let side_nodes: Vec<[u8; 32]> = side_nodes.iter().map(|hex| decode_hex(hex)).collect();
+            assert_eq!(proof.side_nodes(), &side_nodes[..]);
+            let non_membership_leaf = non_membership_leaf
+                .map(|(path, value_hash)| (decode_hex(path), decode_hex(value_hash)));
+            assert_eq!(proof.non_membership_leaf(), non_membership_leaf.as_ref());
+            let value = value.unwrap_or_default();
+            assert!(verify_celestia_proof(key, &value, &proof, &root));
+        }
+    };
+
+    // the roots after each insert, including the uncommitted inserts of a mutable tree
+    let roots = [
+        "0430af2b4898d63ef70b6e67002d54659154fc7875d71546721890c979423419",
+        "596c4452d8da7eab98a74c0ea1f1b5be10b5a0a151e648a202d76ac655fc35c6",
+        "97f0a78740d6ab127f11be90b6d69b8ab584ce3e1b42897167909a57a37f3276",
+        "0234ded5cb2fdcfcf5bea92f189008e386670f67e8dfbad370e1d8b24bf26d80",
+    ];
+    let mut db = MemoryDB::<Sha256, NoopKey<Sha256>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha256>::new(&mut db, &mut root).build();
+    assert_eq!(tree.celestia_root().unwrap(), [0u8; 32]);
+    for ((_index, key, value), expected) in TEST_DATA.iter().zip(roots) {
+        tree.insert(key, DBValue::from(*value)).unwrap();
+        assert_eq!(tree.celestia_root().unwrap(), decode_hex(expected));
+    }
+    let (_, mut_root, mut_proof) = tree.celestia_proof(&[0, 100]).unwrap();
+    tree.commit();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha256>::new(&db, &root).build();
+    assert_eq!(tree.celestia_proof(&[0, 100]).unwrap().1, mut_root);
+    assert_eq!(
+        tree.celestia_proof(&[0, 100]).unwrap().2.side_nodes(),
+        mut_proof.side_nodes()
+    );
+    check(
+        &tree,
+        roots[3],
+        &[
+            (
+                &[0, 100],
+                &[
+                    "0430af2b4898d63ef70b6e67002d54659154fc7875d71546721890c979423419",
+                    "eb86495424a9829be1c4dbe97c71f2518194dfba6a7682203eef4776aa785986",
+                ],
+                None,
+            ),
+            (
+                &[0, 50],
+                &[
+                    "0430af2b4898d63ef70b6e67002d54659154fc7875d71546721890c979423419",
+                    "eb86495424a9829be1c4dbe97c71f2518194dfba6a7682203eef4776aa785986",
+                ],
+                Some((
+                    "d070dc5b8da9aea7dc0f5ad4c29d89965200059c9a0ceca3abd5da2492dcb71d",
+                    "0537d481f73a757334328052da3af9626ced97028e20b849f6115c22cd765197",
+                )),
+            ),
+            (
+                &[1, 45],
+                &[
+                    "d7b6cc9bbf44f155b5657552714644270c386f585f3232b828da33103ac2cd85",
+                    "66522c494e38c84b98c19e947630e1fe44f93b8914a32e26dcd83229fee08209",
+                ],
+                Some((
+                    "08037e79bb41c0f1eda6751f0dabb5293ca2d5bf949cee256a7eab6e52cdf64d",
+                    "31cd97ebe10a80abe1b3f401824fc2040fb8b03aafd0d37acf6504777eddee11",
+                )),
+            ),
+        ],
+    );
+
+    // the paths of [0, 0] and [0, 57] share their first three bits, such that the side nodes
+    // above their node and below the side node of a proof are placeholders
+    let mut db = MemoryDB::<Sha256, NoopKey<Sha256>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha256>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 0], DBValue::from(&b"value1"[..])).unwrap();
+    tree.insert(&[0, 57], DBValue::from(&b"value2"[..]))
+        .unwrap();
+    tree.commit();
+    let placeholder = "0000000000000000000000000000000000000000000000000000000000000000";
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha256>::new(&db, &root).build();
+    check(
+        &tree,
+        "137154bdf3c7a4bd8cc1962c8592c2c063145a4bc92ee15251eddedd318781dd",
+        &[
+            (
+                &[0, 57],
+                &[
+                    "0430af2b4898d63ef70b6e67002d54659154fc7875d71546721890c979423419",
+                    placeholder,
+                    placeholder,
+                    placeholder,
+                ],
+                None,
+            ),
+            (
+                &[0, 3],
+                &["073d636d6fc459de60837566457510a716e1fac56fafedd4755e68c539332235"],
+                None,
+            ),
+            (
+                &[0, 1],
+                &[
+                    "02148e034f17f20f7c1a6c2a0133d9c44be5dbef805ac9eb8d9548c775a950bf",
+                    placeholder,
+                    placeholder,
+                ],
+                None,
+            ),
+        ],
+    );
+}
+
+// TEST SEND
+// ================================================================================================
+#[cfg(feature = "send")]
+#[test]
+fn test_send_tree_db_mut() {
+    use crate::SharedDB;
+    let (_, expected_root) = mock_data();
+    let shared = SharedDB::new(MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default());
+
+    // a mutable tree with a recorder is moved into and updated on a worker thread
+    let mut db = shared.clone();
+    let mut root = Default::default();
+    let mut recorder = Recorder::new();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_recorder(&mut recorder)
+        .build();
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            for (_index, key, value) in TEST_DATA.iter() {
+                tree.insert(key, DBValue::from(&value[..])).unwrap();
+            }
+            tree.commit();
+        });
+    });
+    assert_eq!(root, expected_root);
+
+    // the changes are visible through every clone of the shared database
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&shared, &root).build();
+    for (_index, key, value) in TEST_DATA.iter() {
+        assert_eq!(tree.value(key).unwrap(), Some(DBValue::from(&value[..])));
+    }
+}
+
+// TEST LEAF KEYS
+// ================================================================================================
+#[cfg(feature = "leaf-keys")]
+#[test]
+fn test_leaf_keys() {
+    use crate::key_preimage_value;
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+
+    // keys are hashed to their path, the original key is stored in the leaf record
+    let entries: [(&[u8], &[u8]); 2] = [(b"alice", b"apple"), (b"bob", b"banana")];
+    for (preimage, value) in entries.iter() {
+        let key = &Sha3::hash(preimage)[..TREE_DEPTH];
+        tree.insert_with_preimage(key, preimage, DBValue::from(*value))
+            .unwrap();
+    }
+    tree.insert(&[0, 1], DBValue::from(&b"plain"[..])).unwrap();
+    let mut expected = Vec::from([(DBValue::from(&[0, 1][..]), DBValue::from(&b"plain"[..]))]);
+    expected.extend(
+        entries
+            .iter()
+            .map(|(preimage, value)| (DBValue::from(*preimage), DBValue::from(*value))),
+    );
+    expected.sort_by_key(|(key, _)| match key.len() {
+        TREE_DEPTH => key.clone(),
+        _ => DBValue::from(&Sha3::hash(key)[..TREE_DEPTH]),
+    });
+    assert_eq!(tree.preimage_entries().unwrap(), expected);
+    tree.commit();
+
+    // the preimages survive persistence and are committed to by the leaf hash
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root).build();
+    assert_eq!(tree.preimage_entries().unwrap(), expected);
+    let key = &Sha3::hash(b"alice")[..TREE_DEPTH];
+    let (value, proof_root, proof) = tree.proof(key).unwrap();
+    assert_eq!(value, Some(DBValue::from(&b"apple"[..])));
+    let leaf = key_preimage_value(b"alice", b"apple");
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(key, &leaf, &proof, &proof_root),
+        Ok(true)
+    );
+    let forged = key_preimage_value(b"mallory", b"apple");
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(key, &forged, &proof, &proof_root),
+        Ok(false)
+    );
+}
+
+// TEST KEY-BOUND LEAVES
+// ================================================================================================
+#[cfg(feature = "key-bound")]
+#[test]
+fn test_key_bound_leaves() {
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root).build();
+    tree.insert(&[0, 1], DBValue::from(&b"value"[..])).unwrap();
+    tree.insert(&[0, 2], DBValue::from(&b"value"[..])).unwrap();
+
+    // identical values at different keys have distinct leaves
+    assert_ne!(tree.leaf(&[0, 1]).unwrap(), tree.leaf(&[0, 2]).unwrap());
+    tree.commit();
+
+    let mut recorder = Recorder::new();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_recorder(&mut recorder)
+        .build();
+    let (value, proof_root, proof) = tree.proof(&[0, 1]).unwrap();
+    let value = value.unwrap();
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[0, 1], &value, &proof, &proof_root),
+        Ok(true)
+    );
+
+    // a proof for one key can not be replayed for another key holding the same value
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[0, 2], &value, &proof, &proof_root),
+        Ok(false)
+    );
+
+    // exclusion proofs are verified with an empty value
+    let (_, proof_root, proof) = tree.proof(&[0, 3]).unwrap();
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[0, 3], &[], &proof, &proof_root),
+        Ok(true)
+    );
+
+    // the encoded leaf is a self authenticating record of the key and value
+    let memory_db = recorder.drain_storage_proof().into_memory_db::<Sha3>();
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&memory_db, &root).build();
+    assert_eq!(tree.value(&[0, 1]).unwrap(), Some(value));
+}
+
+#[cfg(feature = "key-bound")]
+#[test]
+fn test_key_bound_salted_non_inclusion() {
+    const SALT: &[u8] = b"salt";
+    let mut db = MemoryDB::<Sha3, NoopKey<Sha3>, DBValue>::default();
+    let mut root = Default::default();
+    let mut tree = TreeDBMutBuilder::<TREE_DEPTH, Sha3>::new(&mut db, &mut root)
+        .with_salt(SALT)
+        .build();
+    tree.insert(&[0, 1], DBValue::from(&b"value"[..])).unwrap();
+    tree.commit();
+
+    let tree = TreeDBBuilder::<TREE_DEPTH, Sha3>::new(&db, &root)
+        .with_salt(SALT)
+        .build();
+    let (_, proof_root, proof) = tree.proof(&[0, 1]).unwrap();
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(
+            &[0, 1],
+            &[SALT, b"value"].concat(),
+            &proof,
+            &proof_root
+        ),
+        Ok(true)
+    );
+
+    // the null leaf is not salted, exclusion proofs of salted trees are verified with an empty
+    // value rather than the salt
+    let (_, proof_root, proof) = tree.proof(&[0, 2]).unwrap();
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[0, 2], &[], &proof, &proof_root),
+        Ok(true)
+    );
+    assert_eq!(
+        TreeDB::<TREE_DEPTH, Sha3>::verify(&[0, 2], SALT, &proof, &proof_root),
+        Ok(false)
+    );
+}
@@ -1,21 +1,65 @@
 use super::{
-    rstd::{vec, vec::Vec},
-    DBValue, HashMap, Hasher, Node, NodeHash, TreeError,
+    node::{null_leaf_hash, proof_leaf_hash},
+    rstd::vec::Vec,
+    CelestiaProof, ChainedProof, ChildSelector, CompactProof, DBValue, HashMap, Hasher, Key,
+    KeyError, MerkleProof, NeighborProof, Node, NodeError, NodeHash, StorageProof, TreeError,
+    TreeHasher,
 };
+use crate::verify_multi;
+use core::hash::BuildHasher;
 
 // TRAITS
 // ================================================================================================
 
-type Proof<H> = (Option<DBValue>, <H as Hasher>::Out, Vec<DBValue>);
+/// An inclusion proof of form: (value, root, proof)
+pub(crate) type Proof<H> = (Option<DBValue>, <H as Hasher>::Out, Vec<DBValue>);
+
+/// A proof of a subtree root of form: (subtree root, root, proof)
+pub(crate) type SubtreeProof<H> = (<H as Hasher>::Out, <H as Hasher>::Out, Vec<DBValue>);
+
+/// A sample of leaves of form: (key-value pairs, storage proof of the leaves)
+pub(crate) type AuditSample = (Vec<(DBValue, DBValue)>, StorageProof);
+
+/// A non-inclusion proof of form: (root, proof)
+pub(crate) type AbsenceProof<H> = (<H as Hasher>::Out, Vec<DBValue>);
+
+/// A proof of the values at a list of indices of form: (values, root, storage proof)
+type BatchProof<H> = (Vec<Option<DBValue>>, <H as Hasher>::Out, StorageProof);
+
+/// A typed inclusion proof of form: (value, root, proof)
+pub(crate) type TypedProof<H, const D: usize> =
+    (Option<DBValue>, <H as Hasher>::Out, MerkleProof<H, D>);
+
+/// A compact inclusion proof of form: (value, root, compact proof)
+type CompactProofOf<H> = (Option<DBValue>, <H as Hasher>::Out, CompactProof);
+
+/// A proof through nested trees of form: (child value, parent root, proof)
+pub(crate) type ChainedProofOf<H, const P: usize, const C: usize> =
+    (Option<DBValue>, <H as Hasher>::Out, ChainedProof<H, P, C>);
+
+/// A neighbor proof of form: (value, root, proof)
+pub(crate) type NeighborProofOf<H, const D: usize> =
+    (Option<DBValue>, <H as Hasher>::Out, NeighborProof<H, D>);
+
+/// A Celestia proof of form: (value, Celestia root, proof)
+pub(crate) type CelestiaProofOf<H> = (Option<DBValue>, <H as Hasher>::Out, CelestiaProof<H>);
+
+/// An inclusion proof of a leaf hash of form: (leaf hash, root, proof)
+type LeafProof<H> = (Option<<H as Hasher>::Out>, <H as Hasher>::Out, Vec<DBValue>);
 
 /// A immutable key-value datastore implemented as a database-backed sparse merkle tree.
-pub trait KeyedTree<H: Hasher, const D: usize> {
+pub trait KeyedTree<H: TreeHasher, const D: usize> {
     /// Returns the root of the tree.
     fn root(&self) -> &H::Out;
 
     /// Returns the depth of the tree.
     fn depth(&self) -> usize {
-        D * 8
+        Key::<D>::BITS
+    }
+
+    /// Returns the salt used for leaf hashing, empty if the tree is unsalted.
+    fn salt(&self) -> &[u8] {
+        &[]
     }
 
     /// Returns the value at the provided key.
@@ -27,6 +71,55 @@ pub trait KeyedTree<H: Hasher, const D: usize> {
     /// Returns an inclusion proof of a value a the specified key.
     fn proof(&self, key: &[u8]) -> Result<Proof<H>, TreeError>;
 
+    /// Returns a typed inclusion proof of a value at the specified key, which is bound to the key
+    /// and depth of the tree. Returns a tuple of form: (value, root, proof).
+    fn merkle_proof(&self, key: &[u8]) -> Result<TypedProof<H, D>, TreeError> {
+        let (value, root, proof) = self.proof(key)?;
+        let proof = MerkleProof::from_value(key, value.as_deref().unwrap_or_default(), proof)?;
+        Ok((value, root, proof))
+    }
+
+    /// Returns a non-inclusion proof of the specified key, None if the key is in the tree.
+    fn proof_of_absence(&self, key: &[u8]) -> Result<Option<AbsenceProof<H>>, TreeError>;
+
+    /// Returns a compact inclusion proof of a value at the specified key, which omits the
+    /// siblings that are default hashes. Returns a tuple of form: (value, root, compact proof).
+    fn proof_compact(&self, key: &[u8]) -> Result<CompactProofOf<H>, TreeError> {
+        let (value, root, proof) = self.proof(key)?;
+        Ok((
+            value,
+            root,
+            CompactProof::compress::<H>(&proof, self.salt()),
+        ))
+    }
+
+    /// Returns an inclusion proof of a value at the specified key truncated at the provided
+    /// depth, i.e. the Key::<D>::BITS - depth siblings from the leaf to the node at that depth,
+    /// which is verified against a cached node of the level with LevelCache. Returns a tuple of
+    /// form: (value, root, proof).
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    fn truncated_proof(&self, key: &[u8], depth: usize) -> Result<Proof<H>, TreeError> {
+        if depth > Key::<D>::BITS {
+            return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+                depth,
+                Key::<D>::BITS,
+            )));
+        }
+        let (value, root, mut proof) = self.proof(key)?;
+        proof.truncate(Key::<D>::BITS - depth);
+        Ok((value, root, proof))
+    }
+
+    /// Returns an inclusion proof of the leaf hash at the specified key, which allows inclusion to
+    /// be proven without the value. Returns a tuple of form: (leaf hash, root, proof).
+    fn proof_for_leaf(&self, key: &[u8]) -> Result<LeafProof<H>, TreeError> {
+        let leaf = self.leaf(key)?;
+        let (_, root, proof) = self.proof(key)?;
+        Ok((leaf, root, proof))
+    }
+
     /// Verifies an inclusion proof of a value at the specified key.
     fn verify(
         key: &[u8],
@@ -34,16 +127,89 @@ pub trait KeyedTree<H: Hasher, const D: usize> {
         proof: &[DBValue],
         root: &H::Out,
     ) -> Result<bool, TreeError>;
+
+    /// Verifies a non-inclusion proof of the specified key.
+    fn verify_absence(key: &[u8], proof: &[DBValue], root: &H::Out) -> Result<bool, TreeError> {
+        verify_absence::<H, D>(key, proof, root, &[])
+    }
+
+    /// Verifies a non-inclusion proof of the specified key in a tree with the specified salt.
+    fn verify_salted_absence(
+        key: &[u8],
+        proof: &[DBValue],
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<bool, TreeError> {
+        verify_absence::<H, D>(key, proof, root, salt)
+    }
+
+    /// Verifies a compact inclusion proof of a value at the specified key.
+    fn verify_compact(
+        key: &[u8],
+        value: &[u8],
+        proof: &CompactProof,
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        Self::verify_salted_compact(key, value, proof, root, &[])
+    }
+
+    /// Verifies a compact inclusion proof of a value at the specified key in a tree with the
+    /// specified salt, the value is the leaf preimage as for verify.
+    fn verify_salted_compact(
+        key: &[u8],
+        value: &[u8],
+        proof: &CompactProof,
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<bool, TreeError> {
+        let proof = proof.decompress::<H>(Key::<D>::BITS, salt)?;
+        Self::verify(key, value, &proof, root)
+    }
+
+    /// Verifies an inclusion proof of a value at the specified key against the root of the
+    /// subtree at the specified depth along the key, the proof holds the siblings from the leaf
+    /// to the subtree root.
+    fn verify_at_depth(
+        key: &[u8],
+        value: &[u8],
+        proof: &[DBValue],
+        depth: usize,
+        subtree_root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        verify_at_depth::<H, D>(key, value, proof, depth, subtree_root)
+    }
+
+    /// Verifies an inclusion proof of a leaf hash at the specified key.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not the depth of the tree
+    fn verify_leaf(
+        key: &[u8],
+        leaf: &H::Out,
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        Ok(MerkleProof::<H, D>::new(key, *leaf, proof.to_vec())?.verify(root))
+    }
 }
 
 /// A mutable key-value datastore implemented as a database-backed sparse merkle tree.
-pub trait KeyedTreeMut<H: Hasher, const D: usize> {
-    /// Returns the root of the tree.
+pub trait KeyedTreeMut<H: TreeHasher, const D: usize> {
+    /// Returns the root of the tree. Pending changes are committed to the database first.
     fn root(&mut self) -> &H::Out;
 
+    /// Returns the root the tree would have if the pending changes were committed, without
+    /// committing them, e.g. for speculative evaluation of writes.
+    fn root_hint(&self) -> H::Out;
+
     /// Returns the depth of the tree.
     fn depth(&self) -> usize {
-        D * 8
+        Key::<D>::BITS
+    }
+
+    /// Returns the salt used for leaf hashing, empty if the tree is unsalted.
+    fn salt(&self) -> &[u8] {
+        &[]
     }
 
     /// Returns the value at the provided key.
@@ -58,9 +224,64 @@ pub trait KeyedTreeMut<H: Hasher, const D: usize> {
     /// Inserts a value at the provided key.
     fn insert(&mut self, key: &[u8], value: DBValue) -> Result<Option<DBValue>, TreeError>;
 
-    /// Removes a value at the provided key.
+    /// Removes a value at the provided key and returns the old value, pruning the path of the key.
     fn remove(&mut self, key: &[u8]) -> Result<Option<DBValue>, TreeError>;
 
+    /// Takes the value out of the provided key, returning the owned old value and pruning the path
+    /// of the key within the same descent, see remove.
+    fn take(&mut self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        self.remove(key)
+    }
+
+    /// Returns a typed inclusion proof of a value at the specified key, which is bound to the key
+    /// and depth of the tree. Returns a tuple of form: (value, root, proof).
+    fn merkle_proof(&self, key: &[u8]) -> Result<TypedProof<H, D>, TreeError> {
+        let (value, root, proof) = self.proof(key)?;
+        let proof = MerkleProof::from_value(key, value.as_deref().unwrap_or_default(), proof)?;
+        Ok((value, root, proof))
+    }
+
+    /// Returns a non-inclusion proof of the specified key, None if the key is in the tree.
+    fn proof_of_absence(&self, key: &[u8]) -> Result<Option<AbsenceProof<H>>, TreeError>;
+
+    /// Returns a compact inclusion proof of a value at the specified key, which omits the
+    /// siblings that are default hashes. Returns a tuple of form: (value, root, compact proof).
+    fn proof_compact(&self, key: &[u8]) -> Result<CompactProofOf<H>, TreeError> {
+        let (value, root, proof) = self.proof(key)?;
+        Ok((
+            value,
+            root,
+            CompactProof::compress::<H>(&proof, self.salt()),
+        ))
+    }
+
+    /// Returns an inclusion proof of a value at the specified key truncated at the provided
+    /// depth, i.e. the Key::<D>::BITS - depth siblings from the leaf to the node at that depth,
+    /// which is verified against a cached node of the level with LevelCache. Returns a tuple of
+    /// form: (value, root, proof).
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    fn truncated_proof(&self, key: &[u8], depth: usize) -> Result<Proof<H>, TreeError> {
+        if depth > Key::<D>::BITS {
+            return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+                depth,
+                Key::<D>::BITS,
+            )));
+        }
+        let (value, root, mut proof) = self.proof(key)?;
+        proof.truncate(Key::<D>::BITS - depth);
+        Ok((value, root, proof))
+    }
+
+    /// Returns an inclusion proof of the leaf hash at the specified key, which allows inclusion to
+    /// be proven without the value. Returns a tuple of form: (leaf hash, root, proof).
+    fn proof_for_leaf(&self, key: &[u8]) -> Result<LeafProof<H>, TreeError> {
+        let leaf = self.leaf(key)?;
+        let (_, root, proof) = self.proof(key)?;
+        Ok((leaf, root, proof))
+    }
+
     /// Verifies an inclusion proof of a value at the specified key.
     fn verify(
         key: &[u8],
@@ -68,16 +289,80 @@ pub trait KeyedTreeMut<H: Hasher, const D: usize> {
         proof: &[DBValue],
         root: &H::Out,
     ) -> Result<bool, TreeError>;
+
+    /// Verifies a non-inclusion proof of the specified key.
+    fn verify_absence(key: &[u8], proof: &[DBValue], root: &H::Out) -> Result<bool, TreeError> {
+        verify_absence::<H, D>(key, proof, root, &[])
+    }
+
+    /// Verifies a non-inclusion proof of the specified key in a tree with the specified salt.
+    fn verify_salted_absence(
+        key: &[u8],
+        proof: &[DBValue],
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<bool, TreeError> {
+        verify_absence::<H, D>(key, proof, root, salt)
+    }
+
+    /// Verifies a compact inclusion proof of a value at the specified key.
+    fn verify_compact(
+        key: &[u8],
+        value: &[u8],
+        proof: &CompactProof,
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        Self::verify_salted_compact(key, value, proof, root, &[])
+    }
+
+    /// Verifies a compact inclusion proof of a value at the specified key in a tree with the
+    /// specified salt, the value is the leaf preimage as for verify.
+    fn verify_salted_compact(
+        key: &[u8],
+        value: &[u8],
+        proof: &CompactProof,
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<bool, TreeError> {
+        let proof = proof.decompress::<H>(Key::<D>::BITS, salt)?;
+        Self::verify(key, value, &proof, root)
+    }
+
+    /// Verifies an inclusion proof of a value at the specified key against the root of the
+    /// subtree at the specified depth along the key, the proof holds the siblings from the leaf
+    /// to the subtree root.
+    fn verify_at_depth(
+        key: &[u8],
+        value: &[u8],
+        proof: &[DBValue],
+        depth: usize,
+        subtree_root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        verify_at_depth::<H, D>(key, value, proof, depth, subtree_root)
+    }
+
+    /// Verifies an inclusion proof of a leaf hash at the specified key.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not the depth of the tree
+    fn verify_leaf(
+        key: &[u8],
+        leaf: &H::Out,
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        Ok(MerkleProof::<H, D>::new(key, *leaf, proof.to_vec())?.verify(root))
+    }
 }
 
 /// A immutable index-value datastore implemented as a database-backed sparse merkle tree.
-pub trait IndexTree<H: Hasher, const D: usize> {
+pub trait IndexTree<H: TreeHasher, const D: usize> {
     /// Returns the root of the tree.
     fn root(&self) -> &H::Out;
 
     /// Returns the depth of the tree.
     fn depth(&self) -> usize {
-        D * 8
+        Key::<D>::BITS
     }
 
     /// Returns the value at the provided index.
@@ -89,6 +374,50 @@ pub trait IndexTree<H: Hasher, const D: usize> {
     /// Returns an inclusion proof of a value a the specified index.
     fn proof(&self, index: &u64) -> Result<Proof<H>, TreeError>;
 
+    /// Returns a single witness of the values at the provided indices, which need not be
+    /// contiguous or ordered. The storage proof holds the nodes of the paths of all indices once.
+    /// Returns a tuple of form: (values, root, proof), the values in the order of the indices.
+    fn proof_indices(&self, indices: &[u64]) -> Result<BatchProof<H>, TreeError>;
+
+    /// Returns a typed inclusion proof of a value at the specified index, which is bound to the
+    /// key of the index and depth of the tree. Returns a tuple of form: (value, root, proof).
+    fn merkle_proof(&self, index: &u64) -> Result<TypedProof<H, D>, TreeError> {
+        let (value, root, proof) = self.proof(index)?;
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        let value_slice = value.as_deref().unwrap_or_default();
+        let proof = MerkleProof::from_value(key.as_slice(), value_slice, proof)?;
+        Ok((value, root, proof))
+    }
+
+    /// Verifies a witness returned by proof_indices, i.e. that each value is the value at its
+    /// index in the tree with the provided root. An empty value claims non-inclusion.
+    ///
+    /// Errors:
+    /// - MissingNodes: if the proof lacks nodes on the path of an index
+    fn verify_indices(
+        indices: &[u64],
+        values: &[&[u8]],
+        proof: &StorageProof,
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        verify_indices::<H, D>(indices, values, proof, root)
+    }
+
+    /// Verifies an inclusion proof of a leaf hash at the specified index, such that a verifier
+    /// tracking leaf commitments does not require the value.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not the depth of the tree
+    fn verify_leaf(
+        index: &u64,
+        leaf: &H::Out,
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        Ok(MerkleProof::<H, D>::new(key.as_slice(), *leaf, proof.to_vec())?.verify(root))
+    }
+
     /// Verifies an inclusion proof of a value at the specified index.
     fn verify(
         index: &u64,
@@ -99,13 +428,17 @@ pub trait IndexTree<H: Hasher, const D: usize> {
 }
 
 /// A mutable index-value datastore implemented as a database-backed sparse merkle tree.
-pub trait IndexTreeMut<H: Hasher, const D: usize> {
-    /// Returns the root of the tree.
+pub trait IndexTreeMut<H: TreeHasher, const D: usize> {
+    /// Returns the root of the tree. Pending changes are committed to the database first.
     fn root(&mut self) -> &H::Out;
 
+    /// Returns the root the tree would have if the pending changes were committed, without
+    /// committing them, e.g. for speculative evaluation of writes.
+    fn root_hint(&self) -> H::Out;
+
     /// Returns the depth of the tree.
     fn depth(&self) -> usize {
-        D * 8
+        Key::<D>::BITS
     }
 
     /// Returns the value at the provided index.
@@ -117,12 +450,62 @@ pub trait IndexTreeMut<H: Hasher, const D: usize> {
     /// Returns an inclusion proof of a value a the specified index.
     fn proof(&self, index: &u64) -> Result<Proof<H>, TreeError>;
 
+    /// Returns a single witness of the values at the provided indices, which need not be
+    /// contiguous or ordered. The storage proof holds the nodes of the paths of all indices once.
+    /// Returns a tuple of form: (values, root, proof), the values in the order of the indices.
+    fn proof_indices(&self, indices: &[u64]) -> Result<BatchProof<H>, TreeError>;
+
+    /// Returns a typed inclusion proof of a value at the specified index, which is bound to the
+    /// key of the index and depth of the tree. Returns a tuple of form: (value, root, proof).
+    fn merkle_proof(&self, index: &u64) -> Result<TypedProof<H, D>, TreeError> {
+        let (value, root, proof) = self.proof(index)?;
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        let value_slice = value.as_deref().unwrap_or_default();
+        let proof = MerkleProof::from_value(key.as_slice(), value_slice, proof)?;
+        Ok((value, root, proof))
+    }
+
+    /// Verifies a witness returned by proof_indices, i.e. that each value is the value at its
+    /// index in the tree with the provided root. An empty value claims non-inclusion.
+    ///
+    /// Errors:
+    /// - MissingNodes: if the proof lacks nodes on the path of an index
+    fn verify_indices(
+        indices: &[u64],
+        values: &[&[u8]],
+        proof: &StorageProof,
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        verify_indices::<H, D>(indices, values, proof, root)
+    }
+
+    /// Verifies an inclusion proof of a leaf hash at the specified index, such that a verifier
+    /// tracking leaf commitments does not require the value.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not the depth of the tree
+    fn verify_leaf(
+        index: &u64,
+        leaf: &H::Out,
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        let key = Key::<D>::try_from(index).map_err(TreeError::KeyError)?;
+        Ok(MerkleProof::<H, D>::new(key.as_slice(), *leaf, proof.to_vec())?.verify(root))
+    }
+
     /// Inserts a value at the provided index.
     fn insert(&mut self, index: &u64, value: DBValue) -> Result<Option<DBValue>, TreeError>;
 
     /// Removes a value at the provided index.
     fn remove(&mut self, index: &u64) -> Result<Option<DBValue>, TreeError>;
 
+    /// Takes the value out of the provided index, returning the owned old value and pruning the
+    /// path of the index within the same descent, see remove.
+    fn take(&mut self, index: &u64) -> Result<Option<DBValue>, TreeError> {
+        self.remove(index)
+    }
+
     /// Verifies an inclusion proof of a value at the specified index.
     fn verify(
         index: &u64,
@@ -133,28 +516,26 @@ pub trait IndexTreeMut<H: Hasher, const D: usize> {
 }
 
 /// A trait that allows recording of tree nodes.
-pub trait TreeRecorder<H: Hasher> {
+pub trait TreeRecorder<H: TreeHasher> {
     fn record(&mut self, node: &Node<H>);
 }
 
 // Helpers
 // ================================================================================================
 
-/// Return the HashMap hashing node hash to Node for null nodes of a tree of depth D
-pub fn null_nodes<H: Hasher>(depth: usize) -> (HashMap<H::Out, Node<H>>, H::Out) {
-    let mut hashes = HashMap::with_capacity(depth);
-    let mut current_hash = H::hash(&[]);
-
-    hashes.insert(
-        current_hash,
-        Node::Value {
-            hash: current_hash,
-            value: vec![],
-        },
-    );
+/// Return the HashMap hashing node hash to Node for null nodes of a tree of depth D, the map is
+/// hashed with S. The null leaf is the null leaf hash of the salt, see null_leaf_hash.
+pub fn null_nodes<H: TreeHasher, S: BuildHasher + Default>(
+    depth: usize,
+    salt: &[u8],
+) -> (HashMap<H::Out, Node<H>, S>, H::Out) {
+    let mut hashes = HashMap::with_capacity_and_hasher(depth + 1, S::default());
+    let null_leaf = Node::<H>::new_null_value(salt);
+    let mut current_hash = *null_leaf.hash();
+    hashes.insert(current_hash, null_leaf);
 
     for _ in 0..depth {
-        let next_hash = H::hash(&[current_hash.as_ref(), current_hash.as_ref()].concat());
+        let next_hash = H::hash_inner(current_hash.as_ref(), current_hash.as_ref());
         hashes.insert(
             next_hash,
             Node::Inner {
@@ -168,3 +549,462 @@ pub fn null_nodes<H: Hasher>(depth: usize) -> (HashMap<H::Out, Node<H>>, H::Out)
 
     (hashes, current_hash)
 }
+
+/// Returns the null hashes of the heights 0 to height of a tree with the provided salt, indexed by
+/// height. The null hash of height 0 is the null leaf, see null_leaf_hash.
+pub fn null_hashes<H: TreeHasher>(height: usize, salt: &[u8]) -> Vec<H::Out> {
+    let mut hashes = Vec::with_capacity(height + 1);
+    hashes.push(null_leaf_hash::<H>(salt));
+    for _ in 0..height {
+        let child = hashes[hashes.len() - 1];
+        hashes.push(H::hash_inner(child.as_ref(), child.as_ref()));
+    }
+    hashes
+}
+
+/// Descends the tree from the provided root along the first `depth` bits of the provided key.
+/// Returns the hash of the subtree root at that depth and the sibling hashes along the path,
+/// ordered from the subtree root to the root of the tree.
+pub fn descend<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    key: &Key<D>,
+    depth: usize,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<(NodeHash<H>, Vec<DBValue>), TreeError> {
+    let mut current_hash = root.clone();
+    let mut siblings = Vec::with_capacity(depth);
+    for bit in key.iter().take(depth) {
+        let node = lookup(&current_hash)?;
+        let child_selector = ChildSelector::new(bit);
+        let sibling_hash = node
+            .child_hash(&child_selector.sibling())
+            .map_err(TreeError::NodeError)?;
+        siblings.push(DBValue::from(sibling_hash.hash().as_ref()));
+        current_hash = node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+    siblings.reverse();
+    Ok((current_hash, siblings))
+}
+
+/// Verifies the values at the provided indices against a storage proof of their paths in the tree
+/// with the provided root. Returns false if the number of values differs from the number of
+/// indices.
+fn verify_indices<H: TreeHasher, const D: usize>(
+    indices: &[u64],
+    values: &[&[u8]],
+    proof: &StorageProof,
+    root: &H::Out,
+) -> Result<bool, TreeError> {
+    if indices.len() != values.len() {
+        return Ok(false);
+    }
+    let keys = indices
+        .iter()
+        .map(Key::<D>::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(TreeError::KeyError)?;
+    let items: Vec<(&[u8], &[u8])> = keys
+        .iter()
+        .zip(values.iter())
+        .map(|(key, value)| (key.as_slice(), *value))
+        .collect();
+    Ok(verify_multi::<H, D>(&items, proof, root)?
+        .into_iter()
+        .all(|valid| valid))
+}
+
+/// Verifies that the provided subtree root is the root of the subtree at the provided depth along
+/// the provided key in the tree with the provided root. The proof contains the sibling hashes
+/// along the path from the subtree root to the root of the tree, the bits of the key below the
+/// subtree root are ignored.
+///
+/// Errors:
+/// - IncorrectKeySize: if the key is not D bytes long
+/// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+/// - DecodeNodeHashFailed: if a sibling is not H::LENGTH bytes long
+pub fn verify_subtree<H: TreeHasher, const D: usize>(
+    key: &[u8],
+    depth: usize,
+    subtree_root: &H::Out,
+    proof: &[DBValue],
+    root: &H::Out,
+) -> Result<bool, TreeError> {
+    let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+    if depth > Key::<D>::BITS {
+        return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+            depth,
+            Key::<D>::BITS,
+        )));
+    }
+    if proof.len() != depth {
+        return Ok(false);
+    }
+    let mut hash = *subtree_root;
+    for (bit, sibling) in (0..depth).rev().zip(proof.iter()) {
+        if sibling.len() != H::LENGTH {
+            return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                sibling.to_vec(),
+            )));
+        }
+        let bit = key.bit(bit).map_err(TreeError::KeyError)?;
+        hash = match ChildSelector::new(bit) {
+            ChildSelector::Left => H::hash_inner(hash.as_ref(), sibling),
+            ChildSelector::Right => H::hash_inner(sibling, hash.as_ref()),
+        };
+    }
+    Ok(hash == *root)
+}
+
+/// Verifies an inclusion proof of a value at the provided key against the root of the subtree at
+/// the provided depth along the key, such that a proof can be checked against an intermediate
+/// commitment. The proof holds the Key::<D>::BITS - depth sibling hashes from the leaf to the
+/// subtree root, i.e. the leading siblings of a full proof.
+///
+/// Errors:
+/// - IncorrectKeySize: if the key is not D bytes long
+/// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+/// - DecodeNodeHashFailed: if a sibling is not H::LENGTH bytes long
+pub fn verify_at_depth<H: TreeHasher, const D: usize>(
+    key: &[u8],
+    value: &[u8],
+    proof: &[DBValue],
+    depth: usize,
+    subtree_root: &H::Out,
+) -> Result<bool, TreeError> {
+    let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+    if depth > Key::<D>::BITS {
+        return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+            depth,
+            Key::<D>::BITS,
+        )));
+    }
+    if proof.len() != Key::<D>::BITS - depth {
+        return Ok(false);
+    }
+    let mut hash = proof_leaf_hash::<H>(key.as_slice(), value);
+    for (bit, sibling) in (depth..Key::<D>::BITS).rev().zip(proof.iter()) {
+        if sibling.len() != H::LENGTH {
+            return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                sibling.to_vec(),
+            )));
+        }
+        let bit = key.bit(bit).map_err(TreeError::KeyError)?;
+        hash = match ChildSelector::new(bit) {
+            ChildSelector::Left => H::hash_inner(hash.as_ref(), sibling),
+            ChildSelector::Right => H::hash_inner(sibling, hash.as_ref()),
+        };
+    }
+    Ok(hash == *subtree_root)
+}
+
+/// Verifies that the subtree at the provided depth along the provided key is empty in the tree
+/// with the provided root and salt, i.e. that its root is the null hash of height
+/// Key::<D>::BITS - depth. See verify_subtree for the proof and errors.
+pub fn verify_empty_subtree<H: TreeHasher, const D: usize>(
+    key: &[u8],
+    depth: usize,
+    proof: &[DBValue],
+    root: &H::Out,
+    salt: &[u8],
+) -> Result<bool, TreeError> {
+    let height = Key::<D>::BITS.saturating_sub(depth);
+    let null_hash = null_hashes::<H>(height, salt)[height];
+    verify_subtree::<H, D>(key, depth, &null_hash, proof, root)
+}
+
+/// Descends the tree from the provided root along the provided key until it reaches a default
+/// node. Returns the sibling hashes along the path, ordered from the default node to the root of
+/// the tree, None if the path ends in a non-default leaf, i.e. the key is in the tree.
+pub fn absence_proof<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    key: &Key<D>,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<Option<Vec<DBValue>>, TreeError> {
+    let mut current_hash = root.clone();
+    let mut siblings = Vec::new();
+    for bit in key.iter() {
+        if current_hash.is_default() {
+            break;
+        }
+        let node = lookup(&current_hash)?;
+        let child_selector = ChildSelector::new(bit);
+        let sibling_hash = node
+            .child_hash(&child_selector.sibling())
+            .map_err(TreeError::NodeError)?;
+        siblings.push(DBValue::from(sibling_hash.hash().as_ref()));
+        current_hash = node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+    if !current_hash.is_default() {
+        return Ok(None);
+    }
+    siblings.reverse();
+    Ok(Some(siblings))
+}
+
+/// Returns the number of non-default siblings along the path of the provided key, without
+/// allocating the proof. The descent stops at the first default node as all siblings below it
+/// are default hashes.
+pub fn proof_size_hint<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    key: &Key<D>,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<usize, TreeError> {
+    let mut current_hash = root.clone();
+    let mut count = 0;
+    for bit in key.iter() {
+        if current_hash.is_default() {
+            break;
+        }
+        let node = lookup(&current_hash)?;
+        let child_selector = ChildSelector::new(bit);
+        if !node
+            .child_hash(&child_selector.sibling())
+            .map_err(TreeError::NodeError)?
+            .is_default()
+        {
+            count += 1;
+        }
+        current_hash = node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+    Ok(count)
+}
+
+/// Verifies a non-inclusion proof of the provided key in a tree with the provided salt. The proof
+/// holds the sibling hashes from the first default node along the path of the key to the root,
+/// such that the default node is located at depth proof.len() and its hash is the null hash of
+/// height Key::<D>::BITS - proof.len().
+pub fn verify_absence<H: TreeHasher, const D: usize>(
+    key: &[u8],
+    proof: &[DBValue],
+    root: &H::Out,
+    salt: &[u8],
+) -> Result<bool, TreeError> {
+    let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+    if proof.len() > Key::<D>::BITS {
+        return Ok(false);
+    }
+    let depth = proof.len();
+    let mut hash = null_hashes::<H>(Key::<D>::BITS - depth, salt)[Key::<D>::BITS - depth];
+    // iterate over the bits in the key above the default node in reverse order
+    for (bit, sibling) in (0..depth).rev().zip(proof.iter()) {
+        if sibling.len() != H::LENGTH {
+            return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                sibling.to_vec(),
+            )));
+        }
+        let bit = key.bit(bit).map_err(TreeError::KeyError)?;
+        match ChildSelector::new(bit) {
+            ChildSelector::Left => hash = H::hash_inner(hash.as_ref(), sibling),
+            ChildSelector::Right => hash = H::hash_inner(sibling, hash.as_ref()),
+        }
+    }
+    Ok(hash == *root)
+}
+
+/// Returns the nearest key with a non-default leaf after the provided key if successor is true,
+/// before it otherwise. The descent along the key records the deepest non-default sibling on the
+/// searched side, the neighbor is the nearest leaf of that sibling subtree.
+pub fn neighbor<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    key: &Key<D>,
+    successor: bool,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<Option<Key<D>>, TreeError> {
+    let mut current_hash = root.clone();
+    let mut candidate = None;
+    for (depth, bit) in key.iter().enumerate() {
+        if current_hash.is_default() {
+            break;
+        }
+        let node = lookup(&current_hash)?;
+        let child_selector = ChildSelector::new(bit);
+        // the sibling holds the keys after the key if the key descends left and vice versa
+        if bit != successor {
+            let sibling_hash = node
+                .child_hash(&child_selector.sibling())
+                .map_err(TreeError::NodeError)?;
+            if !sibling_hash.is_default() {
+                candidate = Some((depth, sibling_hash.clone()));
+            }
+        }
+        current_hash = node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+
+    let (depth, mut current_hash) = match candidate {
+        Some(candidate) => candidate,
+        None => return Ok(None),
+    };
+    let mut neighbor = *key;
+    neighbor
+        .set_bit(depth, successor)
+        .map_err(TreeError::KeyError)?;
+    // descend to the leftmost leaf of the subtree for a successor, the rightmost otherwise
+    for depth in depth + 1..Key::<D>::BITS {
+        let node = lookup(&current_hash)?;
+        let near_hash = node
+            .child_hash(&ChildSelector::new(!successor))
+            .map_err(TreeError::NodeError)?;
+        let bit = match near_hash.is_default() {
+            true => successor,
+            false => !successor,
+        };
+        neighbor.set_bit(depth, bit).map_err(TreeError::KeyError)?;
+        current_hash = node
+            .child_hash(&ChildSelector::new(bit))
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+    Ok(Some(neighbor))
+}
+
+/// Descends two trees sharing a database in lockstep and returns the first key, in key order, at
+/// which their leaves differ, None if the roots are equal. At each depth the descent follows the
+/// left children if they differ and the right children otherwise.
+pub fn first_divergence<H: TreeHasher, const D: usize>(
+    first: &NodeHash<H>,
+    second: &NodeHash<H>,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<Option<Key<D>>, TreeError> {
+    if first.hash() == second.hash() {
+        return Ok(None);
+    }
+    let mut key = Key::<D>::default();
+    let (mut first, mut second) = (first.clone(), second.clone());
+    for depth in 0..Key::<D>::BITS {
+        let (first_node, second_node) = (lookup(&first)?, lookup(&second)?);
+        let bit = first_node
+            .child_hash(&ChildSelector::Left)
+            .map_err(TreeError::NodeError)?
+            .hash()
+            == second_node
+                .child_hash(&ChildSelector::Left)
+                .map_err(TreeError::NodeError)?
+                .hash();
+        key.set_bit(depth, bit).map_err(TreeError::KeyError)?;
+        let child_selector = ChildSelector::new(bit);
+        first = first_node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+        second = second_node
+            .child_hash(&child_selector)
+            .map_err(TreeError::NodeError)?
+            .clone();
+    }
+    Ok(Some(key))
+}
+
+/// The maximum depth of a level returned by level_hashes, whose 2^20 hashes are visited one node
+/// lookup at a time.
+pub const MAX_LEVEL_DEPTH: usize = 20;
+
+/// Returns the hashes of the 2^depth nodes at the provided depth of the tree with the provided
+/// root, ordered by key. Default nodes are included such that the level is complete.
+///
+/// Errors:
+/// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree or MAX_LEVEL_DEPTH
+pub fn level_hashes<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    depth: usize,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+) -> Result<Vec<H::Out>, TreeError> {
+    let max_depth = Key::<D>::BITS.min(MAX_LEVEL_DEPTH);
+    if depth > max_depth {
+        return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+            depth, max_depth,
+        )));
+    }
+    let mut level = Vec::from([root.clone()]);
+    for _ in 0..depth {
+        let mut next = Vec::with_capacity(level.len() * 2);
+        for node_hash in level.iter() {
+            let node = lookup(node_hash)?;
+            for child_selector in [ChildSelector::Left, ChildSelector::Right] {
+                let child_hash = node
+                    .child_hash(&child_selector)
+                    .map_err(TreeError::NodeError)?;
+                next.push(child_hash.clone());
+            }
+        }
+        level = next;
+    }
+    Ok(level.iter().map(|node_hash| *node_hash.hash()).collect())
+}
+
+/// Appends the encoded nodes along the path of the provided key to the provided nodes, from the
+/// root down to the leaf or the first default node.
+pub fn path_nodes<H: TreeHasher, const D: usize>(
+    root: &NodeHash<H>,
+    key: &Key<D>,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+    nodes: &mut Vec<DBValue>,
+) -> Result<(), TreeError> {
+    let mut current_hash = root.clone();
+    let mut bits = key.iter();
+    while !current_hash.is_default() {
+        let node = lookup(&current_hash)?;
+        let bit = bits.next();
+        if let Some(bit) = bit {
+            current_hash = node
+                .child_hash(&ChildSelector::new(bit))
+                .map_err(TreeError::NodeError)?
+                .clone();
+        }
+        nodes.push(node.into());
+        if bit.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Traverses the subtree with the provided root depth first and calls visit with the key and node
+/// of every non-default leaf. The subtree root is located at the provided depth and the provided
+/// key holds the path to the subtree root in its first `depth` bits. Default subtrees are skipped.
+pub fn for_each_leaf<H: TreeHasher, const D: usize>(
+    root: NodeHash<H>,
+    key: Key<D>,
+    depth: usize,
+    lookup: impl Fn(&NodeHash<H>) -> Result<Node<H>, TreeError>,
+    mut visit: impl FnMut(Key<D>, Node<H>),
+) -> Result<(), TreeError> {
+    let mut stack = Vec::from([(root, key, depth)]);
+    while let Some((node_hash, key, depth)) = stack.pop() {
+        if node_hash.is_default() {
+            continue;
+        }
+
+        let node = lookup(&node_hash)?;
+        if depth == Key::<D>::BITS {
+            visit(key, node);
+            continue;
+        }
+
+        let mut right_key = key;
+        right_key
+            .set_bit(depth, true)
+            .map_err(TreeError::KeyError)?;
+        for (child_selector, child_key) in [
+            (ChildSelector::Right, right_key),
+            (ChildSelector::Left, key),
+        ] {
+            let child_hash = node
+                .child_hash(&child_selector)
+                .map_err(TreeError::NodeError)?;
+            stack.push((child_hash.clone(), child_key, depth + 1));
+        }
+    }
+    Ok(())
+}
@@ -1,34 +1,150 @@
-use hash_db::{HashDBRef, EMPTY_PREFIX};
+use hash_db::HashDBRef;
+use memory_db::MemoryDB;
 
 use super::{
-    null_nodes, rstd::vec::Vec, ChildSelector, DBValue, DataError, HashMap, Hasher, Key, KeyedTree,
-    Node, NodeHash, TreeError, TreeRecorder,
+    absence_proof,
+    celestia::{celestia_leaves, celestia_proof, celestia_root},
+    descend, first_divergence, for_each_leaf, level_hashes, neighbor,
+    node::{decode_hash, proof_leaf_hash},
+    null_nodes, path_nodes,
+    proof::NoopKey,
+    proof_size_hint,
+    rstd::vec::Vec,
+    AbsenceProof, AuditSample, CachedDB, CelestiaProofOf, ChainedProof, ChainedProofOf,
+    ChildSelector, CircuitWitness, CompactProof, DBValue, DataError, DivergenceProof,
+    FxBuildHasher, HashMap, Key, KeyError, KeyedTree, MerkleProof, MultiHashDBRef, Namespace, Node,
+    NodeError, NodeHash, NodeMemo, StorageProof, SubtreeProof, TreeError, TreeHasher, TreeMetrics,
+    TreeRecorder,
 };
 
 // TreeDBBuilder
 // ================================================================================================
 
+/// The database backend of a TreeDB, either borrowed, borrowed with a shared cache of decoded
+/// nodes, borrowed with multi-get support or owned by the tree when built from a proof
+enum TreeDBStore<'db, H: TreeHasher> {
+    Borrowed(&'db dyn HashDBRef<H, DBValue>),
+    Cached(&'db CachedDB<'db, H>),
+    Multi(&'db dyn HashDBRef<H, DBValue>, &'db dyn MultiHashDBRef<H>),
+    Owned(MemoryDB<H, NoopKey<H>, DBValue>),
+}
+
+impl<'db, H: TreeHasher> TreeDBStore<'db, H> {
+    /// Returns the database backend
+    fn db(&self) -> &dyn HashDBRef<H, DBValue> {
+        match self {
+            TreeDBStore::Borrowed(db) => *db,
+            TreeDBStore::Cached(db) => *db,
+            TreeDBStore::Multi(db, _) => *db,
+            TreeDBStore::Owned(db) => db,
+        }
+    }
+}
+
 /// Used to construct a TreeDB
-pub struct TreeDBBuilder<'db, const D: usize, H: Hasher> {
-    db: &'db dyn HashDBRef<H, DBValue>,
-    root: &'db H::Out,
+pub struct TreeDBBuilder<'db, const D: usize, H: TreeHasher> {
+    db: TreeDBStore<'db, H>,
+    root: H::Out,
     recorder: Option<&'db mut dyn TreeRecorder<H>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
 }
 
-impl<'db, const D: usize, H: Hasher> TreeDBBuilder<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher> TreeDBBuilder<'db, D, H> {
     /// Construct a new TreeDBBuilder
-    pub fn new(db: &'db dyn HashDBRef<H, DBValue>, root: &'db H::Out) -> Result<Self, TreeError> {
+    pub fn new(db: &'db dyn HashDBRef<H, DBValue>, root: &'db H::Out) -> Self {
         //TODO: warm user if default root provided
-        if D > usize::MAX / 8 {
-            return Err(TreeError::DepthTooLarge(D, usize::MAX / 8));
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
+            db: TreeDBStore::Borrowed(db),
+            root: *root,
+            recorder: None,
+            metrics: None,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
+        }
+    }
+
+    /// Construct a new TreeDBBuilder reading the decoded nodes of the tree through the provided
+    /// cache, which is shared with the other trees built on it, see CachedDB. The tree uses the
+    /// salt of the cache, a tree salted otherwise reads the cache as a plain database.
+    pub fn new_cached(cache: &'db CachedDB<'db, H>, root: &'db H::Out) -> Self {
+        let _ = Key::<D>::BITS;
+        Self {
+            db: TreeDBStore::Cached(cache),
+            root: *root,
+            recorder: None,
+            metrics: None,
+            salt: DBValue::from(cache.salt()),
+            prefix: DBValue::default(),
+        }
+    }
+
+    /// Construct a new TreeDBBuilder on a database which fetches many records in a single round
+    /// trip. Batch reads and multi-key proofs fetch the nodes along the paths of their keys with
+    /// one get_many per level of the tree, see MultiHashDBRef.
+    pub fn new_multi<M: MultiHashDBRef<H>>(db: &'db M, root: &'db H::Out) -> Self {
+        let _ = Key::<D>::BITS;
+        Self {
+            db: TreeDBStore::Multi(db, db),
+            root: *root,
+            recorder: None,
+            metrics: None,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
         }
+    }
+
+    /// Construct a new TreeDBBuilder of the tree with the provided root that owns a database
+    /// holding the nodes of the provided storage proof, e.g. to read the keys of a proof received
+    /// from an untrusted party. See from_salted_proof for errors.
+    pub fn from_proof(proof: StorageProof, root: &H::Out) -> Result<Self, TreeError> {
+        Self::from_salted_proof(proof, root, &[])
+    }
+
+    /// Construct a new TreeDBBuilder of the tree with the provided root and salt that owns a
+    /// database holding the nodes of the provided storage proof. Every node of the proof must
+    /// decode and be reachable from the root, see StorageProof::into_checked_memory_db.
+    ///
+    /// Errors:
+    /// - InvalidNode: if a node of the proof can not be decoded
+    /// - MissingNodes: if the node of the root is missing from the proof
+    /// - UnreachableNode: if a node of the proof is not reachable from the root
+    pub fn from_salted_proof(
+        proof: StorageProof,
+        root: &H::Out,
+        salt: &[u8],
+    ) -> Result<Self, TreeError> {
+        let _ = Key::<D>::BITS;
+        let db = proof.into_checked_memory_db::<H, D>(root, salt)?;
         Ok(Self {
-            db,
-            root,
+            db: TreeDBStore::Owned(db),
+            root: *root,
             recorder: None,
+            metrics: None,
+            salt: DBValue::from(salt),
+            prefix: DBValue::default(),
         })
     }
 
+    /// Add a salt to the TreeDBBuilder. The salt is prepended to the value of every leaf prior to
+    /// hashing, including the null leaf, such that two trees with identical content but
+    /// different salts have unlinkable roots. Inclusion proofs of a salted tree are verified by
+    /// providing the concatenation of the salt and the value in place of the value.
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Add a storage prefix to the TreeDBBuilder, the nodes of the tree are read from the database
+    /// under the prefix, see TreeDBMutBuilder::with_prefix
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = DBValue::from(prefix);
+        self
+    }
+
     /// Add a recorder to the TreeDBBuilder
     pub fn with_recorder(mut self, recorder: &'db mut dyn TreeRecorder<H>) -> Self {
         self.recorder = Some(recorder);
@@ -44,19 +160,36 @@ impl<'db, const D: usize, H: Hasher> TreeDBBuilder<'db, D, H> {
         self
     }
 
+    /// Add metrics to the TreeDBBuilder, reporting the node reads of the tree, see TreeMetrics
+    pub fn with_metrics(mut self, metrics: &'db dyn TreeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Add optional metrics to the TreeDBBuilder
+    pub fn with_optional_metrics(mut self, metrics: Option<&'db dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// build a TreeDB
     pub fn build(self) -> TreeDB<'db, D, H> {
-        let (null_nodes, default_root) = null_nodes::<H>(D * 8);
-        let root = if self.root == &H::Out::default() || self.root == &default_root {
+        let (null_nodes, default_root) = null_nodes::<H, FxBuildHasher>(Key::<D>::BITS, &self.salt);
+        let root = if self.root == H::Out::default() || self.root == default_root {
             NodeHash::Default(default_root)
         } else {
-            NodeHash::Database(*self.root)
+            NodeHash::Database(self.root)
         };
         TreeDB {
             db: self.db,
             root,
             recorder: self.recorder.map(core::cell::RefCell::new),
+            metrics: self.metrics,
             null_nodes,
+            node_memo: NodeMemo::empty(),
+            prefetched: core::cell::RefCell::new(HashMap::default()),
+            salt: self.salt,
+            prefix: self.prefix,
         }
     }
 }
@@ -65,17 +198,27 @@ impl<'db, const D: usize, H: Hasher> TreeDBBuilder<'db, D, H> {
 // ================================================================================================
 
 /// An immutable merkle tree db that uses a byte slice key to specify the leaves in the tree.
-pub struct TreeDB<'db, const D: usize, H: Hasher> {
-    db: &'db dyn HashDBRef<H, DBValue>,
+pub struct TreeDB<'db, const D: usize, H: TreeHasher> {
+    db: TreeDBStore<'db, H>,
     root: NodeHash<H>,
     null_nodes: HashMap<H::Out, Node<H>>,
+    node_memo: NodeMemo<H>,
+    prefetched: core::cell::RefCell<HashMap<H::Out, Node<H>>>,
     recorder: Option<core::cell::RefCell<&'db mut dyn TreeRecorder<H>>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
 }
 
-impl<'db, const D: usize, H: Hasher> TreeDB<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher> TreeDB<'db, D, H> {
     /// Return the underlying db of a TreeDB
     pub fn db(&self) -> &dyn HashDBRef<H, DBValue> {
-        self.db
+        self.db.db()
+    }
+
+    /// Return the salt used for leaf hashing, empty if the tree is unsalted
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
     }
 
     /// Return the node associated with the provided hash. Retrieves the node from either the database
@@ -86,10 +229,48 @@ impl<'db, const D: usize, H: Hasher> TreeDB<'db, D, H> {
                 return Err(TreeError::DataError(DataError::InMemoryNotSupported))
             }
             NodeHash::Database(hash) => {
-                let data = self.db.get(hash, EMPTY_PREFIX).ok_or(TreeError::DataError(
-                    DataError::DatabaseDataNotFound(hash.as_ref().to_vec()),
-                ))?;
-                let node: Node<H> = data.try_into().map_err(TreeError::NodeError)?;
+                let prefetched = self.prefetched.borrow().get(hash).cloned();
+                let node = match (self.node_memo.get(hash), prefetched) {
+                    (Some(node), _) => {
+                        if let Some(metrics) = self.metrics {
+                            metrics.cache_hit();
+                        }
+                        node
+                    }
+                    (None, Some(node)) => node,
+                    (None, None) => {
+                        let node = match &self.db {
+                            // the cache decodes the nodes with its own salt and no prefix
+                            TreeDBStore::Cached(cache)
+                                if cache.salt() == &self.salt[..] && self.prefix.is_empty() =>
+                            {
+                                let result = cache.node(hash);
+                                if let Some(metrics) = self.metrics {
+                                    match &result {
+                                        Ok((_, true)) => metrics.cache_hit(),
+                                        Ok((_, false)) => metrics.node_read(),
+                                        Err(TreeError::NodeError(_)) => {
+                                            metrics.node_read();
+                                            metrics.decode_failure();
+                                        }
+                                        Err(_) => {}
+                                    }
+                                }
+                                result?.0
+                            }
+                            store => {
+                                let data = store.db().get(hash, (&self.prefix, None)).ok_or(
+                                    TreeError::DataError(DataError::DatabaseDataNotFound(
+                                        hash.as_ref().to_vec(),
+                                    )),
+                                )?;
+                                self.decode(data)?
+                            }
+                        };
+                        self.node_memo.insert(&node);
+                        node
+                    }
+                };
 
                 if let Some(recorder) = self.recorder.as_ref() {
                     recorder.borrow_mut().record(&node);
@@ -110,6 +291,83 @@ impl<'db, const D: usize, H: Hasher> TreeDB<'db, D, H> {
         Ok(node)
     }
 
+    /// Fetches the nodes along the paths of the provided keys level by level ahead of their
+    /// lookups, issuing a single get_many per level of the tree to a multi-get database, see
+    /// TreeDBBuilder::new_multi. The fetched nodes are held until clear_prefetched, a node that is
+    /// missing from the database is reported by its lookup. Does nothing for other databases.
+    ///
+    /// Errors:
+    /// - NodeError: if a fetched node can not be decoded
+    fn prefetch(&self, keys: &[Key<D>]) -> Result<(), TreeError> {
+        let TreeDBStore::Multi(_, db) = &self.db else {
+            return Ok(());
+        };
+        let known = |hash: &H::Out| {
+            self.node_memo
+                .get(hash)
+                .or_else(|| self.prefetched.borrow().get(hash).cloned())
+        };
+        let mut frontier: Vec<(&Key<D>, NodeHash<H>)> =
+            keys.iter().map(|key| (key, self.root.clone())).collect();
+        for depth in 0..=Key::<D>::BITS {
+            let mut hashes: Vec<H::Out> = frontier
+                .iter()
+                .filter_map(|(_, node_hash)| match node_hash {
+                    NodeHash::Database(hash) if known(hash).is_none() => Some(*hash),
+                    _ => None,
+                })
+                .collect();
+            hashes.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            hashes.dedup();
+            if !hashes.is_empty() {
+                let records = db.get_many(&hashes, (&self.prefix, None));
+                for (hash, data) in hashes.iter().zip(records) {
+                    let Some(data) = data else { continue };
+                    let node = self.decode(data)?;
+                    self.prefetched.borrow_mut().insert(*hash, node);
+                }
+            }
+            if depth == Key::<D>::BITS {
+                break;
+            }
+
+            // descend along each key, the paths end at default nodes
+            let mut next = Vec::with_capacity(frontier.len());
+            for (key, node_hash) in frontier {
+                let NodeHash::Database(hash) = node_hash else {
+                    continue;
+                };
+                let Some(node) = known(&hash) else { continue };
+                let bit = key.bit(depth).map_err(TreeError::KeyError)?;
+                let child_hash = node
+                    .child_hash(&ChildSelector::new(bit))
+                    .map_err(TreeError::NodeError)?;
+                next.push((key, child_hash.clone()));
+            }
+            frontier = next;
+        }
+        Ok(())
+    }
+
+    /// Decodes the provided data of a node read from the database, reporting the read to the
+    /// metrics of the tree
+    fn decode(&self, data: DBValue) -> Result<Node<H>, TreeError> {
+        if let Some(metrics) = self.metrics {
+            metrics.node_read();
+        }
+        Node::decode(data, &self.salt).map_err(|err| {
+            if let Some(metrics) = self.metrics {
+                metrics.decode_failure();
+            }
+            TreeError::NodeError(err)
+        })
+    }
+
+    /// Drops the nodes fetched by prefetch
+    fn clear_prefetched(&self) {
+        self.prefetched.borrow_mut().clear();
+    }
+
     /// Returns a leaf node for the provided key. If the leaf node does not exist, returns None.
     /// If a proof is provided, the sibling hashes along the lookup path are stored in the proof.
     fn lookup_leaf_node(
@@ -133,7 +391,7 @@ impl<'db, const D: usize, H: Hasher> TreeDB<'db, D, H> {
                 let sibling_hash: H::Out = **current_node
                     .child_hash(&child_selector.sibling())
                     .map_err(TreeError::NodeError)?;
-                proof.push(sibling_hash.as_ref().to_vec());
+                proof.push(DBValue::from(sibling_hash.as_ref()));
             }
 
             current_node = self.lookup(child_hash)?;
@@ -141,14 +399,334 @@ impl<'db, const D: usize, H: Hasher> TreeDB<'db, D, H> {
 
         Ok(Some(current_node))
     }
+
+    /// Returns the key-value pairs of all leaves in the subtree at the provided depth along the
+    /// provided key, ordered by key.
+    fn subtree_entries(
+        &self,
+        key: Key<D>,
+        depth: usize,
+    ) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        let lookup = |node_hash: &NodeHash<H>| self.lookup(node_hash);
+        let (subtree_root, _) = descend(&self.root, &key, depth, lookup)?;
+        let mut entries = Vec::new();
+        for_each_leaf(subtree_root, key, depth, lookup, |key, node| {
+            if let Node::Value { value, .. } = node {
+                entries.push((DBValue::from(key.as_slice()), value));
+            }
+        })?;
+        Ok(entries)
+    }
+
+    /// Returns the key-value pairs of all leaves in the tree, ordered by key.
+    pub fn entries(&self) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        self.subtree_entries(Key::<D>::default(), 0)
+    }
+
+    /// Returns the root of the Celestia (LazyLedger) sparse merkle tree holding the leaves of the
+    /// tree, such that proofs of celestia_proof verify against the Go and TypeScript verifiers of
+    /// the specification. The Celestia tree is not the tree stored in the database, its root is
+    /// computed from all leaves and as such is O(n) in the number of leaves.
+    pub fn celestia_root(&self) -> Result<H::Out, TreeError> {
+        Ok(celestia_root::<H>(&celestia_leaves::<H>(self.entries()?)))
+    }
+
+    /// Returns a proof of the value at the provided key in the Celestia sparse merkle tree
+    /// holding the leaves of the tree, see celestia_root. Returns a tuple of form: (value,
+    /// Celestia root, proof). The proof and root are computed from all leaves in a single pass,
+    /// a proof is O(n) in the number of leaves as well. The proof is verified using
+    /// verify_celestia_proof, an empty value verifies that the key is not in the tree.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn celestia_proof(&self, key: &[u8]) -> Result<CelestiaProofOf<H>, TreeError> {
+        Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let leaves = celestia_leaves::<H>(self.entries()?);
+        let (root, proof) = celestia_proof::<H>(&H::hash(key), &leaves);
+        Ok((self.value(key)?, root, proof))
+    }
+
+    /// Deterministically selects count distinct non-default leaves using the provided seed, e.g.
+    /// to answer a proof of retrievability challenge. The n-th draw selects the leaf at index
+    /// H::hash(seed || n as u64 big endian) modulo the number of leaves, in key order, and draws
+    /// already selected leaves are skipped. Returns a tuple of form: (key-value pairs, proof) with
+    /// the pairs in the order selected and the storage proof of their paths, all leaves are
+    /// selected if count exceeds the number of leaves. The sample is verified using verify_multi.
+    pub fn audit_sample(&self, seed: &[u8], count: usize) -> Result<AuditSample, TreeError> {
+        let mut entries: Vec<Option<(DBValue, DBValue)>> =
+            self.entries()?.into_iter().map(Some).collect();
+        let count = count.min(entries.len());
+        let mut sample = Vec::with_capacity(count);
+        let mut draw = 0u64;
+        while sample.len() < count {
+            let hash = H::hash(&[seed, &draw.to_be_bytes()].concat());
+            draw += 1;
+            let mut index = [0u8; 8];
+            let length = hash.as_ref().len().min(8);
+            index[..length].copy_from_slice(&hash.as_ref()[..length]);
+            let index = (u64::from_be_bytes(index) % entries.len() as u64) as usize;
+            if let Some(entry) = entries[index].take() {
+                sample.push(entry);
+            }
+        }
+        let keys: Vec<&[u8]> = sample.iter().map(|(key, _)| &key[..]).collect();
+        let (_, proof) = self.storage_proof(&keys)?;
+        Ok((sample, proof))
+    }
+
+    /// Returns the storage proof of the paths of the provided keys, holding the nodes from the
+    /// root down to the leaf or the first default node of each key. Returns a tuple of form:
+    /// (root, proof).
+    pub fn storage_proof(&self, keys: &[&[u8]]) -> Result<(H::Out, StorageProof), TreeError> {
+        let keys = keys
+            .iter()
+            .map(|key| Key::<D>::new(key).map_err(TreeError::KeyError))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = self.prefetch(&keys).and_then(|_| {
+            let mut nodes = Vec::new();
+            for key in keys.iter() {
+                path_nodes(
+                    &self.root,
+                    key,
+                    |node_hash| self.lookup(node_hash),
+                    &mut nodes,
+                )?;
+            }
+            Ok(nodes)
+        });
+        self.clear_prefetched();
+        Ok((*self.root.hash(), StorageProof::new(result?)))
+    }
+
+    /// Returns the values associated with the provided keys in the order of the keys, None for a
+    /// key that is not in the tree, see KeyedTree::value. The nodes along the paths of the keys are
+    /// fetched with one round trip per level of the tree from a multi-get database, see
+    /// TreeDBBuilder::new_multi.
+    pub fn values(&self, keys: &[&[u8]]) -> Result<Vec<Option<DBValue>>, TreeError> {
+        let keys = keys
+            .iter()
+            .map(|key| Key::<D>::new(key).map_err(TreeError::KeyError))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = self.prefetch(&keys).and_then(|_| {
+            keys.iter()
+                .map(|key| match self.lookup_leaf_node(key, &mut None)? {
+                    Some(node) => Ok(Some(node.value().map_err(TreeError::NodeError)?.clone())),
+                    None => Ok(None),
+                })
+                .collect()
+        });
+        self.clear_prefetched();
+        result
+    }
+
+    /// Returns the (key, value) pairs of all leaves in the tree, ordered by path. Leaves that
+    /// store their key preimage yield the preimage as key, other leaves yield their path.
+    #[cfg(feature = "leaf-keys")]
+    pub fn preimage_entries(&self) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        let mut entries = Vec::new();
+        for_each_leaf(
+            self.root.clone(),
+            Key::<D>::default(),
+            0,
+            |node_hash| self.lookup(node_hash),
+            |key, node| {
+                let preimage = node.preimage().cloned();
+                if let Node::Value { value, .. } = node {
+                    let key = preimage.unwrap_or_else(|| DBValue::from(key.as_slice()));
+                    entries.push((key, value));
+                }
+            },
+        )?;
+        Ok(entries)
+    }
+
+    /// Returns the witness of the inclusion proof of the value at the provided key laid out as
+    /// consumed by a SNARK circuit, see CircuitWitness.
+    pub fn circuit_witness(&self, key: &[u8]) -> Result<CircuitWitness<H, D>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let mut proof = Some(Vec::new());
+        let node = self
+            .lookup_leaf_node(&key, &mut proof)?
+            .expect("the leaf is always returned when recording a proof");
+        let mut siblings = proof.unwrap_or_default();
+        siblings.reverse();
+        let leaf_preimage = node
+            .leaf_preimage(&self.salt)
+            .map_err(TreeError::NodeError)?;
+        CircuitWitness::new(
+            &key,
+            leaf_preimage,
+            *node.hash(),
+            &siblings,
+            *self.root.hash(),
+        )
+    }
+
+    /// Returns the key-value pairs of all leaves in the provided namespace, ordered by key.
+    pub fn namespace_entries(
+        &self,
+        namespace: &Namespace<D>,
+    ) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        self.subtree_entries(namespace.prefix_key(), namespace.bits())
+    }
+
+    /// Returns a proof of the root of the provided namespace subtree.
+    /// Returns a tuple of form: (subtree root, root, proof), the proof is verified using
+    /// Namespace::verify.
+    pub fn namespace_proof(&self, namespace: &Namespace<D>) -> Result<SubtreeProof<H>, TreeError> {
+        self.subtree_proof(namespace.prefix_key().as_slice(), namespace.bits())
+    }
+
+    /// Returns a proof of the root of the subtree at the provided depth along the provided key,
+    /// the bits of the key below the subtree root are ignored. Returns a tuple of form: (subtree
+    /// root, root, proof), the proof is verified using verify_subtree.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    pub fn subtree_proof(&self, key: &[u8], depth: usize) -> Result<SubtreeProof<H>, TreeError> {
+        let (subtree_root, proof) = self.descend_to(key, depth)?;
+        Ok((*subtree_root.hash(), *self.root.hash(), proof))
+    }
+
+    /// Returns a proof that the subtree at the provided depth along the provided key is empty,
+    /// i.e. that no key with the prefix exists. Returns a tuple of form: (root, proof), the proof
+    /// is verified using verify_empty_subtree. Returns None if the subtree is not empty.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    pub fn empty_subtree_proof(
+        &self,
+        key: &[u8],
+        depth: usize,
+    ) -> Result<Option<AbsenceProof<H>>, TreeError> {
+        let (subtree_root, proof) = self.descend_to(key, depth)?;
+        Ok(subtree_root
+            .is_default()
+            .then(|| (*self.root.hash(), proof)))
+    }
+
+    /// Descends the tree along the provided key to the provided depth, returning the subtree root
+    /// and the sibling hashes along the path ordered from the subtree root to the root.
+    fn descend_to(
+        &self,
+        key: &[u8],
+        depth: usize,
+    ) -> Result<(NodeHash<H>, Vec<DBValue>), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        if depth > Key::<D>::BITS {
+            return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+                depth,
+                Key::<D>::BITS,
+            )));
+        }
+        descend(&self.root, &key, depth, |node_hash| self.lookup(node_hash))
+    }
+
+    /// Returns the number of non-default siblings of the inclusion proof of the provided key
+    /// without allocating the proof, i.e. the number of siblings of its compact proof. As such
+    /// the compact proof is H::LENGTH bytes per sibling plus a D byte bitmask.
+    pub fn proof_size_hint(&self, key: &[u8]) -> Result<usize, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        proof_size_hint(&self.root, &key, |node_hash| self.lookup(node_hash))
+    }
+
+    /// Returns the total number of non-default siblings of the inclusion proofs of the provided
+    /// keys, see proof_size_hint.
+    pub fn proofs_size_hint(&self, keys: &[&[u8]]) -> Result<usize, TreeError> {
+        keys.iter()
+            .try_fold(0, |count, key| Ok(count + self.proof_size_hint(key)?))
+    }
+
+    /// Returns the hashes of the 2^depth nodes at the provided depth of the tree ordered by key,
+    /// e.g. to create a LevelCache against which proofs truncated at the depth are verified.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree or MAX_LEVEL_DEPTH
+    pub fn level_nodes(&self, depth: usize) -> Result<Vec<H::Out>, TreeError> {
+        level_hashes::<H, D>(&self.root, depth, |node_hash| self.lookup(node_hash))
+    }
+
+    /// Returns the keys of the nearest non-default leaves before and after the provided key, the
+    /// key itself excluded. Returns a tuple of form: (predecessor, successor).
+    pub fn neighbors(&self, key: &[u8]) -> Result<(Option<DBValue>, Option<DBValue>), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let lookup = |node_hash: &NodeHash<H>| self.lookup(node_hash);
+        let predecessor = neighbor(&self.root, &key, false, lookup)?;
+        let successor = neighbor(&self.root, &key, true, lookup)?;
+        Ok((
+            predecessor.map(|key| DBValue::from(key.as_slice())),
+            successor.map(|key| DBValue::from(key.as_slice())),
+        ))
+    }
+
+    /// Returns a proof of the first key, in key order, at which this tree and the tree with the
+    /// provided root in the same database differ, None if the roots are equal. Both trees are
+    /// assumed to use the salt of this tree. See DivergenceProof.
+    pub fn divergence_proof(
+        &self,
+        other_root: &H::Out,
+    ) -> Result<Option<DivergenceProof<H, D>>, TreeError> {
+        let other = TreeDBBuilder::<D, H>::new(self.db(), other_root)
+            .with_salt(&self.salt)
+            .build();
+        let lookup = |node_hash: &NodeHash<H>| self.lookup(node_hash);
+        let key = match first_divergence::<H, D>(&self.root, &other.root, lookup)? {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+        let first = self.compact_leaf(&key)?;
+        let second = other.compact_leaf(&key)?;
+        DivergenceProof::new(key.as_slice(), first, second).map(Some)
+    }
+
+    /// Returns a proof of the value at the child key in the child tree of depth C * 8 whose root
+    /// is stored at the provided key of this tree. The child tree is read from the database of
+    /// this tree and uses its salt. Returns a tuple of form: (value, root, proof), see
+    /// ChainedProof.
+    ///
+    /// Errors:
+    /// - DecodeNodeHashFailed: if the value at the key is not a hash, i.e. not a child root
+    pub fn chained_proof<const C: usize>(
+        &self,
+        key: &[u8],
+        child_key: &[u8],
+    ) -> Result<ChainedProofOf<H, D, C>, TreeError> {
+        let (child_root, root, proof) = self.proof(key)?;
+        let child_root = child_root.unwrap_or_default();
+        let parent = MerkleProof::<H, D>::from_value(key, &child_root, proof)?;
+        let child_root = decode_hash::<H>(&child_root).map_err(TreeError::NodeError)?;
+
+        let child = TreeDBBuilder::<C, H>::new(self.db(), &child_root)
+            .with_salt(&self.salt)
+            .build();
+        let (value, _, proof) = child.proof(child_key)?;
+        let value_slice = value.as_deref().unwrap_or_default();
+        let child = MerkleProof::<H, C>::from_value(child_key, value_slice, proof)?;
+        Ok((value, root, ChainedProof::new(parent, child)?))
+    }
+
+    /// Returns the value at the provided key, empty if the key is not in the tree, and its
+    /// compact inclusion proof.
+    fn compact_leaf(&self, key: &Key<D>) -> Result<(DBValue, CompactProof), TreeError> {
+        let (value, _, proof) = self.proof(key.as_slice())?;
+        Ok((
+            value.unwrap_or_default(),
+            CompactProof::compress::<H>(&proof, &self.salt),
+        ))
+    }
 }
 
-impl<'db, H: Hasher, const D: usize> KeyedTree<H, D> for TreeDB<'db, D, H> {
+impl<'db, H: TreeHasher, const D: usize> KeyedTree<H, D> for TreeDB<'db, D, H> {
     /// Returns the root of the tree
     fn root(&self) -> &H::Out {
         &self.root
     }
 
+    /// Returns the salt used for leaf hashing
+    fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
     /// Returns the value associated with the given key
     fn value(&self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
@@ -188,6 +766,15 @@ impl<'db, H: Hasher, const D: usize> KeyedTree<H, D> for TreeDB<'db, D, H> {
         }
     }
 
+    /// Returns a non-inclusion proof of the specified key, None if the key is in the tree.
+    /// Returns a tuple of form: (root, proof), the proof holds the sibling hashes from the first
+    /// default node along the path of the key to the root.
+    fn proof_of_absence(&self, key: &[u8]) -> Result<Option<AbsenceProof<H>>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let proof = absence_proof(&self.root, &key, |node_hash| self.lookup(node_hash))?;
+        Ok(proof.map(|proof| (*self.root.hash(), proof)))
+    }
+
     /// Verifies that the given value is in the tree with the given root at the given index
     fn verify(
         key: &[u8],
@@ -196,17 +783,22 @@ impl<'db, H: Hasher, const D: usize> KeyedTree<H, D> for TreeDB<'db, D, H> {
         root: &H::Out,
     ) -> Result<bool, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
-        let mut hash = H::hash(value);
+        let mut hash = proof_leaf_hash::<H>(key.as_slice(), value);
         // iterate over the bits in the key in reverse order
-        for (bit, sibling) in (0..D * 8).rev().zip(proof.iter()) {
+        for (bit, sibling) in (0..Key::<D>::BITS).rev().zip(proof.iter()) {
+            if sibling.len() != H::LENGTH {
+                return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                    sibling.to_vec(),
+                )));
+            }
             let bit = key.bit(bit).map_err(TreeError::KeyError)?;
             let child_selector = ChildSelector::new(bit);
             match child_selector {
                 ChildSelector::Left => {
-                    hash = H::hash(&[hash.as_ref(), sibling].concat());
+                    hash = H::hash_inner(hash.as_ref(), sibling);
                 }
                 ChildSelector::Right => {
-                    hash = H::hash(&[sibling, hash.as_ref()].concat());
+                    hash = H::hash_inner(sibling, hash.as_ref());
                 }
             }
         }
@@ -1,40 +1,163 @@
 use super::{
-    null_nodes,
-    rstd::{vec, vec::Vec},
-    ChildSelector, DBValue, DataError, HashDBRef, HashMap, Hasher, Key, KeyedTreeMut, Node,
-    NodeHash, NodeStorage, TreeError, TreeRecorder,
+    absence_proof,
+    celestia::{celestia_leaves, celestia_proof, celestia_root},
+    descend, entry, for_each_leaf, level_hashes, neighbor,
+    node::{decode_hash, proof_leaf_hash},
+    null_nodes, path_nodes, proof_size_hint, read_removed,
+    rstd::vec::Vec,
+    AbsenceProof, BatchHashDB, CelestiaProofOf, ChangeSet, ChildSelector, CircuitWitness, DBMut,
+    DBValue, DataError, DynTreeRecorder, Entry, FxBuildHasher, HashMap, HashSet, Journal, Key,
+    KeyError, KeyMap, KeyedTreeMut, LeafChange, Monitor, Namespace, Node, NodeError, NodeHash,
+    NodeMemo, NodeStorage, ProofError, StagedStats, StorageProof, SubtreeProof, TreeError,
+    TreeHasher, TreeMetrics, TreeSnapshot, TryHashDB, UpdateProof, Write, WriteBatch,
 };
-use core::cmp::Ordering;
-use hash_db::{HashDB, EMPTY_PREFIX};
+use core::{cmp::Ordering, hash::BuildHasher, marker::PhantomData, mem::size_of};
+use hash_db::{HashDB, Hasher, Prefix};
 
 // TreeDBMutBuilder
 // ================================================================================================
 
-/// TreeDBMutBuilder use to build a TreeDBMut
-pub struct TreeDBMutBuilder<'db, const D: usize, H: Hasher> {
-    db: &'db mut dyn HashDB<H, DBValue>,
+/// TreeDBMutBuilder use to build a TreeDBMut. The internal maps of the TreeDBMut are hashed with
+/// S, which defaults to FxBuildHasher.
+pub struct TreeDBMutBuilder<'db, const D: usize, H: TreeHasher, S = FxBuildHasher> {
+    db: DBMut<'db, H>,
     root: &'db mut H::Out,
-    recorder: Option<&'db mut dyn TreeRecorder<H>>,
+    recorder: Option<&'db mut DynTreeRecorder<'db, H>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
+    max_value_size: Option<usize>,
+    max_staged_nodes: Option<usize>,
+    staged_capacity: usize,
+    reference_counting: bool,
+    journaling: bool,
+    deferred_hashing: bool,
+    hasher: PhantomData<fn() -> S>,
 }
 
-impl<'db, const D: usize, H: Hasher> TreeDBMutBuilder<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> TreeDBMutBuilder<'db, D, H, S> {
     /// Construct a new TreeDBMutBuilder
-    pub fn new(
-        db: &'db mut dyn HashDB<H, DBValue>,
-        root: &'db mut H::Out,
-    ) -> Result<Self, TreeError> {
-        if D > usize::MAX / 8 {
-            return Err(TreeError::DepthTooLarge(D, usize::MAX / 8));
-        }
-        Ok(Self {
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>, root: &'db mut H::Out) -> Self {
+        Self::new_with(DBMut::Infallible(db), root)
+    }
+
+    /// Construct a new TreeDBMutBuilder on a database whose writes can fail. The write errors of a
+    /// commit are returned by TreeDBMut::try_commit, see TryHashDB.
+    pub fn new_fallible(db: &'db mut dyn TryHashDB<H, DBValue>, root: &'db mut H::Out) -> Self {
+        Self::new_with(DBMut::Fallible(db), root)
+    }
+
+    /// Construct a new TreeDBMutBuilder on a database which applies batches of writes atomically.
+    /// Each commit is written as a single WriteBatch rather than one emplace or remove per node
+    /// reference, such that a failed commit leaves no partial writes behind and its error is
+    /// returned by TreeDBMut::try_commit. The commit journal is not written for batched commits
+    /// as they are atomic already, see BatchHashDB.
+    pub fn new_batched(db: &'db mut dyn BatchHashDB<H>, root: &'db mut H::Out) -> Self {
+        Self::new_with(DBMut::Batched(db), root)
+    }
+
+    /// Construct a new TreeDBMutBuilder on the provided database
+    fn new_with(db: DBMut<'db, H>, root: &'db mut H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
             db,
             root,
             recorder: None,
-        })
+            metrics: None,
+            salt: DBValue::default(),
+            prefix: DBValue::default(),
+            max_value_size: None,
+            max_staged_nodes: None,
+            staged_capacity: 0,
+            reference_counting: false,
+            journaling: false,
+            deferred_hashing: false,
+            hasher: PhantomData,
+        }
+    }
+
+    /// Add a salt to the TreeDBMutBuilder. The salt is prepended to the value of every leaf prior to
+    /// hashing, including the null leaf, such that two trees with identical content but
+    /// different salts have unlinkable roots. Inclusion proofs of a salted tree are verified by
+    /// providing the concatenation of the salt and the value in place of the value.
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Add a storage prefix to the TreeDBMutBuilder, e.g. the name of the tree. Every node of the
+    /// tree is read from and written to the database under the prefix, such that a database with
+    /// a prefixing key function, e.g. memory_db::PrefixedKey, keeps many independent trees apart
+    /// even where they hold identical subtrees. The tree must be read with the same prefix.
+    pub fn with_prefix(mut self, prefix: &[u8]) -> Self {
+        self.prefix = DBValue::from(prefix);
+        self
+    }
+
+    /// Add a maximum value size to the TreeDBMutBuilder. Inserting a value larger than the
+    /// maximum value size returns a ValueTooLarge error.
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> Self {
+        self.max_value_size = Some(max_value_size);
+        self
+    }
+
+    /// Add a maximum number of staged nodes to the TreeDBMutBuilder. Once a write leaves more
+    /// nodes staged in memory, the tree is committed to the database automatically, bounding the
+    /// memory used by bulk writes. The change set of an automatic commit is discarded and a write
+    /// error of a fallible database is returned by the write that triggered it. Automatic
+    /// commits are skipped while a transaction is open and for reference counted trees, as they
+    /// would close the transaction or retain the intermediate roots. Writes of many leaves, e.g.
+    /// insert_batch or apply_changeset, commit automatically once they complete rather than
+    /// between their leaves, such that a failed write never leaves a part of it committed.
+    pub fn with_max_staged_nodes(mut self, max_staged_nodes: usize) -> Self {
+        self.max_staged_nodes = Some(max_staged_nodes);
+        self
+    }
+
+    /// Reserve room for the provided number of staged nodes in the TreeDBMutBuilder, such that
+    /// bulk writes of a known size do not reallocate the staged nodes as they grow. See
+    /// TreeDBMut::staged_stats.
+    pub fn with_staged_capacity(mut self, capacity: usize) -> Self {
+        self.staged_capacity = capacity;
+        self
+    }
+
+    /// Enable reference counted persistence. A commit no longer removes the nodes of the previous
+    /// root, instead the database counts the references to each node: one per parent stored in
+    /// the database plus one per committed root. The nodes of a root that is no longer needed are
+    /// removed with release_root, which only removes nodes that no other root references. The
+    /// database must count references, e.g. a MemoryDB or a RefCountedDB.
+    pub fn with_reference_counting(mut self) -> Self {
+        self.reference_counting = true;
+        self
+    }
+
+    /// Enable the commit journal. Before a commit writes any node, the planned writes are recorded
+    /// in a journal in the database along with the old and new roots, and the number of writes
+    /// applied is recorded after each write. A commit interrupted by a crash is completed with
+    /// replay_journal or undone with rollback_journal on restart, a commit fails with
+    /// PendingJournal until then. Journaling costs one write of the journal plus two writes per
+    /// node write. A crash between a node write and the record that follows it leaves the write
+    /// unrecorded, as such backends should persist the writes of a commit in order.
+    pub fn with_journal(mut self) -> Self {
+        self.journaling = true;
+        self
+    }
+
+    /// Enable deferred hashing. An insert or remove marks the path of its key dirty rather than
+    /// rehashing it, and the inner nodes on the dirty paths are rehashed once when the root is
+    /// read, on commit or with rehash, such that the upper nodes shared by the paths of many
+    /// writes are hashed once rather than once per write, as with insert_batch. Values and leaves
+    /// are read including the dirty leaves, reads that depend on node hashes, e.g. proofs, fail
+    /// with UnhashedLeaves until the tree is rehashed.
+    pub fn with_deferred_hashing(mut self) -> Self {
+        self.deferred_hashing = true;
+        self
     }
 
     /// Add a recorder to the TreeDBMutBuilder
-    pub fn with_recorder(mut self, recorder: &'db mut dyn TreeRecorder<H>) -> Self {
+    pub fn with_recorder(mut self, recorder: &'db mut DynTreeRecorder<'db, H>) -> Self {
         self.recorder = Some(recorder);
         self
     }
@@ -42,28 +165,56 @@ impl<'db, const D: usize, H: Hasher> TreeDBMutBuilder<'db, D, H> {
     /// Add an optional recorder to the TreeDBMutBuilder
     pub fn with_optional_recorder<'recorder: 'db>(
         mut self,
-        recorder: Option<&'recorder mut dyn TreeRecorder<H>>,
+        recorder: Option<&'recorder mut DynTreeRecorder<'recorder, H>>,
     ) -> Self {
         self.recorder = recorder.map(|r| r as _);
         self
     }
 
+    /// Add metrics to the TreeDBMutBuilder, reporting the node reads and the nodes written by the
+    /// commits of the tree, see TreeMetrics
+    pub fn with_metrics(mut self, metrics: &'db dyn TreeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Add optional metrics to the TreeDBMutBuilder
+    pub fn with_optional_metrics(mut self, metrics: Option<&'db dyn TreeMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// build a TreeDBMut
-    pub fn build(self) -> TreeDBMut<'db, D, H> {
-        let (null_nodes, default_root) = null_nodes::<H>(D * 8);
+    pub fn build(self) -> TreeDBMut<'db, D, H, S> {
+        let (null_nodes, default_root) = null_nodes::<H, S>(Key::<D>::BITS, &self.salt);
         let root_handle = if self.root == &H::Out::default() || self.root == &default_root {
             NodeHash::Default(default_root)
         } else {
             NodeHash::Database(*self.root)
         };
         TreeDBMut {
-            storage: NodeStorage::empty(),
-            death_row: HashMap::new(),
+            storage: NodeStorage::with_capacity(self.staged_capacity),
+            death_row: HashMap::default(),
             db: self.db,
             root: self.root,
             root_handle,
             null_nodes,
+            node_memo: NodeMemo::empty(),
             recorder: self.recorder.map(core::cell::RefCell::new),
+            metrics: self.metrics,
+            salt: self.salt,
+            prefix: self.prefix,
+            max_value_size: self.max_value_size,
+            max_staged_nodes: self.max_staged_nodes,
+            reference_counting: self.reference_counting,
+            journaling: self.journaling,
+            deferred_hashing: self.deferred_hashing,
+            prepared: false,
+            leaf_memo: NodeMemo::empty(),
+            leaf_changes: KeyMap::default(),
+            dirty_leaves: KeyMap::default(),
+            transactions: Vec::new(),
+            bulk_writes: 0,
         }
     }
 }
@@ -72,75 +223,553 @@ impl<'db, const D: usize, H: Hasher> TreeDBMutBuilder<'db, D, H> {
 // ================================================================================================
 
 /// A mutable merkle tree db that uses a byte slice key to specify the leaves in the tree.
-pub struct TreeDBMut<'db, const D: usize, H: Hasher> {
-    storage: NodeStorage<H>,
-    death_row: HashMap<H::Out, usize>,
-    db: &'db mut dyn HashDB<H, DBValue>,
+pub struct TreeDBMut<'db, const D: usize, H: TreeHasher, S = FxBuildHasher> {
+    storage: NodeStorage<H, S>,
+    death_row: HashMap<H::Out, usize, S>,
+    db: DBMut<'db, H>,
     root: &'db mut H::Out,
     root_handle: NodeHash<H>,
-    null_nodes: HashMap<H::Out, Node<H>>,
-    recorder: Option<core::cell::RefCell<&'db mut dyn TreeRecorder<H>>>,
+    null_nodes: HashMap<H::Out, Node<H>, S>,
+    node_memo: NodeMemo<H>,
+    recorder: Option<core::cell::RefCell<&'db mut DynTreeRecorder<'db, H>>>,
+    metrics: Option<&'db dyn TreeMetrics>,
+    salt: DBValue,
+    prefix: DBValue,
+    max_value_size: Option<usize>,
+    max_staged_nodes: Option<usize>,
+    reference_counting: bool,
+    journaling: bool,
+    deferred_hashing: bool,
+    prepared: bool,
+    leaf_memo: NodeMemo<H, u64>,
+    leaf_changes: KeyMap<DBValue, (Option<DBValue>, Option<DBValue>)>,
+    dirty_leaves: KeyMap<DBValue, Node<H>>,
+    transactions: Vec<Fork<H, S>>,
+    bulk_writes: usize,
 }
 
-impl<'db, const D: usize, H: Hasher> TreeDBMut<'db, D, H> {
-    /// Commit the changes to the database
-    pub fn commit(&mut self) {
-        // iterate over storage and check if the node is in death row
-        for (key, (node, insert_count)) in self.storage.drain() {
-            // check if the node is in death row
-            match self.death_row.remove(&key) {
-                Some(death_count) => {
-                    // compare the death count with the insert count
-                    match insert_count.cmp(&death_count) {
-                        // if they are the same do nothing
-                        Ordering::Equal => {}
-                        // if the count is greater than 0, insert the node to db
-                        Ordering::Greater => {
-                            for _ in 0..insert_count - death_count {
-                                self.db.emplace(key, EMPTY_PREFIX, node.clone().into());
-                            }
-                        }
-                        // if the count is less than 0, delete the node from db
-                        Ordering::Less => {
-                            for _ in 0..death_count - insert_count {
-                                self.db.remove(&key, EMPTY_PREFIX);
-                            }
-                        }
-                    }
-                }
-                // if the node is not in death row, insert the node to db count times
-                None => {
-                    for _ in 0..insert_count {
-                        self.db.emplace(key, EMPTY_PREFIX, node.clone().into());
-                    }
-                }
-            }
+/// The uncommitted changes of a TreeDBMut on top of its committed root, see TreeDBMut::fork. The
+/// nodes of the committed tree are shared with the database, only the uncommitted nodes are
+/// copied. Transactions snapshot the uncommitted changes as a fork as well.
+pub struct Fork<H: TreeHasher, S = FxBuildHasher> {
+    base_root: H::Out,
+    storage: NodeStorage<H, S>,
+    death_row: HashMap<H::Out, usize, S>,
+    root_handle: NodeHash<H>,
+    leaf_changes: KeyMap<DBValue, (Option<DBValue>, Option<DBValue>)>,
+    dirty_leaves: KeyMap<DBValue, Node<H>>,
+}
+
+impl<'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> TreeDBMut<'db, D, H, S> {
+    /// Return the database the tree reads from
+    pub(crate) fn db(&self) -> &dyn HashDB<H, DBValue> {
+        self.db.as_hash_db()
+    }
+
+    /// Return the salt used for leaf hashing, empty if the tree is unsalted
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    /// Return the storage prefix of the nodes, empty if the tree is unprefixed
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Return the maximum value size of the tree, None if the value size is unbounded
+    pub fn max_value_size(&self) -> Option<usize> {
+        self.max_value_size
+    }
+
+    /// Returns statistics of the changes staged in memory, e.g. to enforce a memory budget on a
+    /// long-running writer by committing once the approximate size exceeds the budget. The nodes
+    /// snapshotted by open transactions and forks are not included, see StagedStats.
+    pub fn staged_stats(&self) -> StagedStats {
+        StagedStats {
+            nodes: self.storage.len(),
+            capacity: self.storage.capacity(),
+            removals: self.death_row.len(),
+            approx_bytes: self.storage.approx_bytes()
+                + self.death_row.capacity() * size_of::<(H::Out, usize)>(),
         }
+    }
 
-        for (key, count) in self.death_row.drain() {
-            for _ in 0..count {
-                self.db.remove(&key, EMPTY_PREFIX);
-            }
+    /// Releases the unused capacity of the staged changes, e.g. after a commit of a large batch
+    /// such that the memory reserved for the batch is returned. See staged_stats.
+    pub fn shrink_to_fit(&mut self) {
+        self.storage.shrink_to_fit();
+        self.death_row.shrink_to_fit();
+        self.leaf_changes.shrink_to_fit();
+    }
+
+    /// Commit the changes to the database and returns the changes written, see ChangeSet. Open
+    /// transactions are committed as well, i.e. they can no longer be rolled back.
+    ///
+    /// Panics if a write to a fallible database fails, see try_commit.
+    pub fn commit(&mut self) -> ChangeSet<H> {
+        self.try_commit_into(None)
+            .expect("failed to write the changes to the database")
+    }
+
+    /// Commit the changes to the database and returns the changes written, see commit. If a
+    /// write to a fallible database fails, the writes of the commit applied so far are reverted
+    /// and the error is returned, the changes remain staged such that the commit can be retried.
+    /// See TreeDBMutBuilder::new_fallible.
+    ///
+    /// Errors:
+    /// - WriteFailed: if a write to the database failed
+    pub fn try_commit(&mut self) -> Result<ChangeSet<H>, TreeError> {
+        self.try_commit_into(None)
+    }
+
+    /// Commit the changes to the provided database rather than the database the tree reads from,
+    /// e.g. to read from a shared snapshot and persist each block into its own column. Inserted
+    /// nodes are emplaced and removed nodes are removed in the provided database, see commit. The
+    /// root of the tree is updated, as such subsequent reads of the new nodes require the database
+    /// the tree reads from to see the provided database, e.g. as an overlay.
+    pub fn commit_to(&mut self, db: &mut dyn HashDB<H, DBValue>) -> ChangeSet<H> {
+        self.try_commit_into(Some(DBMut::Infallible(db)))
+            .expect("writes to an infallible database do not fail")
+    }
+
+    /// Commit the changes to the provided database, the database of the tree if None.
+    fn try_commit_into(&mut self, db: Option<DBMut<'_, H>>) -> Result<ChangeSet<H>, TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let mut target = db;
+        let (writes, inserted, removed) = match target.as_ref() {
+            Some(db) => self.plan_writes(db.as_hash_db()),
+            None => self.plan_writes(self.db.as_hash_db()),
+        };
+        let mut db = match target.as_mut() {
+            Some(db) => db.reborrow(),
+            None => self.db.reborrow(),
+        };
+        let roots = (*self.root, *self.root_handle.hash());
+        let written = writes
+            .iter()
+            .fold((0, 0), |(nodes, bytes), write| match write {
+                Write::Emplace(_, data) => (nodes + 1, bytes + data.len()),
+                Write::Remove(..) => (nodes, bytes),
+            });
+        apply_writes(&mut db, writes, roots, &self.prefix, self.journaling)?;
+        if let Some(metrics) = self.metrics {
+            metrics.nodes_written(written.0, written.1);
         }
 
+        self.transactions.clear();
+        self.storage.drain();
+        self.death_row.clear();
         *self.root = *self.root_handle.hash();
         if !self.root_handle.is_default() {
             self.root_handle = NodeHash::Database(*self.root);
         }
+        self.node_memo.clear();
+
+        let leaves = self
+            .leaf_changes
+            .drain()
+            .map(|(key, (old, new))| (key, old, new));
+        Ok(change_set(*self.root, inserted, removed, leaves))
+    }
+
+    /// Returns the writes of a commit of the changes to the provided database
+    fn plan_writes(&self, db: &dyn HashDB<H, DBValue>) -> CommitWrites<H> {
+        if !self.reference_counting {
+            return write_nodes(&self.storage, &self.death_row);
+        }
+        // the nodes of the previous root are kept until the root is released
+        match self.root_handle {
+            NodeHash::InMemory(root) if root != *self.root => {
+                reference_nodes(db, (&self.prefix, None), &self.storage, root)
+            }
+            _ => (Vec::new(), Vec::new(), Vec::new()),
+        }
+    }
+
+    /// Prepares a commit of the changes as the first phase of a two-phase commit, e.g. to commit
+    /// the tree atomically with other storage under an external transaction coordinator. Returns
+    /// the changes the commit will write, see ChangeSet, without writing to the database. The
+    /// tree is frozen until the commit is completed with finalize or discarded with abort, writes
+    /// and commits fail with CommitPrepared in the meantime.
+    ///
+    /// Errors:
+    /// - CommitPrepared: if a commit is prepared already
+    pub fn prepare(&mut self) -> Result<ChangeSet<H>, TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let (_, inserted, removed) = self.plan_writes(self.db.as_hash_db());
+        let leaves = self
+            .leaf_changes
+            .iter()
+            .map(|(key, (old, new))| (key.clone(), old.clone(), new.clone()));
+        self.prepared = true;
+        Ok(change_set(
+            *self.root_handle.hash(),
+            inserted,
+            removed,
+            leaves,
+        ))
+    }
+
+    /// Completes the prepared commit by writing the changes returned by prepare to the database,
+    /// see try_commit. If a write fails the commit remains prepared, such that it can be retried
+    /// or aborted.
+    ///
+    /// Errors:
+    /// - NoPreparedCommit: if no commit is prepared
+    /// - WriteFailed: if a write to the database failed
+    pub fn finalize(&mut self) -> Result<(), TreeError> {
+        if !self.prepared {
+            return Err(TreeError::DataError(DataError::NoPreparedCommit));
+        }
+        self.prepared = false;
+        self.try_commit_into(None).map(|_| ()).inspect_err(|_| {
+            self.prepared = true;
+        })
+    }
+
+    /// Discards the prepared commit along with all uncommitted changes, the tree points at its
+    /// committed root again, see reset_to.
+    ///
+    /// Errors:
+    /// - NoPreparedCommit: if no commit is prepared
+    pub fn abort(&mut self) -> Result<(), TreeError> {
+        if !self.prepared {
+            return Err(TreeError::DataError(DataError::NoPreparedCommit));
+        }
+        self.prepared = false;
+        self.reset_to(*self.root)
+    }
+
+    /// Returns a CommitPrepared error if a commit is prepared, see prepare
+    fn check_unprepared(&self) -> Result<(), TreeError> {
+        match self.prepared {
+            true => Err(TreeError::DataError(DataError::CommitPrepared)),
+            false => Ok(()),
+        }
+    }
+
+    /// Rehashes the paths of the leaves written since the last rehash of a tree with deferred
+    /// hashing, each inner node on the dirty paths is rebuilt and hashed once as with
+    /// insert_batch. The root, commits and writes other than insert and remove rehash the tree
+    /// first, see TreeDBMutBuilder::with_deferred_hashing. If the rehash fails the leaves remain
+    /// dirty.
+    pub fn rehash(&mut self) -> Result<(), TreeError> {
+        if self.dirty_leaves.is_empty() {
+            return Ok(());
+        }
+        let dirty_leaves = core::mem::take(&mut self.dirty_leaves);
+        let written = dirty_leaves
+            .iter()
+            .map(|(key, node)| {
+                Ok((
+                    Key::<D>::new(key).map_err(TreeError::KeyError)?,
+                    node.clone(),
+                ))
+            })
+            .collect::<Result<Vec<_>, TreeError>>()
+            .and_then(|mut leaves| {
+                leaves.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+                self.write_batch(&leaves, &mut Vec::new())
+            });
+        if written.is_err() {
+            self.dirty_leaves = dirty_leaves;
+        }
+        written
+    }
+
+    /// Returns an UnhashedLeaves error if the paths of dirty leaves are not rehashed, see rehash
+    fn check_rehashed(&self) -> Result<(), TreeError> {
+        match self.dirty_leaves.is_empty() {
+            true => Ok(()),
+            false => Err(TreeError::DataError(DataError::UnhashedLeaves)),
+        }
+    }
+
+    /// Releases the provided root of a reference counted tree, see
+    /// TreeDBMutBuilder::with_reference_counting. The reference of the root is removed and nodes
+    /// without references are removed from the database along with their references to their
+    /// children. Returns the hashes of the removed nodes, sorted.
+    ///
+    /// Errors:
+    /// - DatabaseDataNotFound: if a node of the root is not in the database
+    pub fn release_root(&mut self, root: H::Out) -> Result<Vec<H::Out>, TreeError> {
+        let mut removed = Vec::new();
+        let mut stack = Vec::from([NodeHash::<H>::Database(root)]);
+        while let Some(node_hash) = stack.pop() {
+            let hash = node_hash.hash();
+            if node_hash.is_default() || self.null_nodes.contains_key(hash) {
+                continue;
+            }
+
+            let data = self.db.as_hash_db().get(hash, (&self.prefix, None)).ok_or(
+                TreeError::DataError(DataError::DatabaseDataNotFound(hash.as_ref().to_vec())),
+            )?;
+            self.db.as_hash_db_mut().remove(hash, (&self.prefix, None));
+            if self.db.as_hash_db().contains(hash, (&self.prefix, None)) {
+                continue;
+            }
+
+            removed.push(*hash);
+            if let Node::Inner { left, right, .. } =
+                Node::<H>::decode(data, &self.salt).map_err(TreeError::NodeError)?
+            {
+                stack.extend([left, right]);
+            }
+        }
+        self.node_memo.clear();
+        removed.sort();
+        Ok(removed)
+    }
+
+    /// Stages the leaf changes of the provided ChangeSet, e.g. received from another node or a
+    /// replication stream, and checks that the resulting root is the root of the ChangeSet. The
+    /// node hashes of the ChangeSet are not used. If the ChangeSet does not apply the tree is left
+    /// unchanged.
+    ///
+    /// Errors:
+    /// - ValueMismatch: if the value of a leaf is not the old value of its change
+    /// - RootMismatch: if the resulting root is not the root of the ChangeSet
+    pub fn apply_changeset(&mut self, change_set: &ChangeSet<H>) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        self.bulk_write(|tree| {
+            tree.begin_transaction();
+            match tree.apply_leaf_changes(change_set) {
+                Ok(()) => tree.commit_transaction(),
+                Err(err) => {
+                    tree.rollback()?;
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    /// Stages the leaf changes of the provided ChangeSet, see apply_changeset.
+    fn apply_leaf_changes(&mut self, change_set: &ChangeSet<H>) -> Result<(), TreeError> {
+        for (key, old_value, new_value) in change_set.leaves.iter() {
+            if &self.value(key)? != old_value {
+                return Err(TreeError::DataError(DataError::ValueMismatch(key.to_vec())));
+            }
+            self.insert(key, new_value.clone().unwrap_or_default())?;
+        }
+
+        self.rehash()?;
+        let root = self.root_hint();
+        if root != change_set.root {
+            return Err(TreeError::ProofError(ProofError::RootMismatch(
+                change_set.root.as_ref().to_vec(),
+                root.as_ref().to_vec(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Begins a transaction, such that the changes made until the matching commit_transaction can
+    /// be discarded with rollback without touching the database. Transactions nest, each begin
+    /// snapshots the uncommitted changes of the tree.
+    pub fn begin_transaction(&mut self) {
+        let transaction = self.fork();
+        self.transactions.push(transaction);
+    }
+
+    /// Commits the innermost open transaction, its changes are kept as uncommitted changes of the
+    /// tree or of the enclosing transaction.
+    ///
+    /// Errors:
+    /// - NoTransaction: if no transaction is open
+    pub fn commit_transaction(&mut self) -> Result<(), TreeError> {
+        self.transactions
+            .pop()
+            .map(|_| ())
+            .ok_or(TreeError::DataError(DataError::NoTransaction))
+    }
+
+    /// Discards the changes made since the innermost open transaction began and closes it.
+    ///
+    /// Errors:
+    /// - NoTransaction: if no transaction is open
+    pub fn rollback(&mut self) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        let transaction = self
+            .transactions
+            .pop()
+            .ok_or(TreeError::DataError(DataError::NoTransaction))?;
+        self.restore(transaction)
+    }
+
+    /// Discards all uncommitted changes, including those of open transactions, and points the
+    /// tree at the provided root, e.g. to revert to an earlier root on a reorg. The nodes of the
+    /// root must still be stored in the database, i.e. not pruned by a later commit. A default
+    /// root resets the tree to the empty tree.
+    ///
+    /// Errors:
+    /// - DatabaseDataNotFound: if the root node is not in the database
+    pub fn reset_to(&mut self, root: H::Out) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        let (_, default_root) = null_nodes::<H, S>(Key::<D>::BITS, &self.salt);
+        let root_handle = if root == H::Out::default() || root == default_root {
+            NodeHash::Default(default_root)
+        } else if self.db.as_hash_db().contains(&root, (&self.prefix, None)) {
+            NodeHash::Database(root)
+        } else {
+            return Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+                root.as_ref().to_vec(),
+            )));
+        };
+
+        self.storage = NodeStorage::empty();
+        self.death_row.clear();
+        self.leaf_changes.clear();
+        self.dirty_leaves.clear();
+        self.transactions.clear();
+        self.node_memo.clear();
+        *self.root = root;
+        self.root_handle = root_handle;
+        Ok(())
+    }
+
+    /// Returns a fork of the uncommitted changes of the tree. The tree can be switched to the fork
+    /// later on, such that divergent sets of changes can be explored over the same database and
+    /// one of them chosen for commit, see switch.
+    pub fn fork(&self) -> Fork<H, S> {
+        let mut death_row = HashMap::with_capacity_and_hasher(self.death_row.len(), S::default());
+        death_row.extend(self.death_row.iter().map(|(hash, count)| (*hash, *count)));
+        Fork {
+            base_root: *self.root,
+            storage: self.storage.clone(),
+            death_row,
+            root_handle: self.root_handle.clone(),
+            leaf_changes: self.leaf_changes.clone(),
+            dirty_leaves: self.dirty_leaves.clone(),
+        }
+    }
+
+    /// Returns an immutable snapshot of the tree including its uncommitted changes, which is read
+    /// while the tree continues to be mutated, e.g. to serve consistent reads concurrently with
+    /// block building. The uncommitted nodes are copied into the snapshot, see TreeSnapshot. The
+    /// dirty leaves of a tree with deferred hashing are not part of the snapshot, see rehash.
+    pub fn snapshot(&self) -> TreeSnapshot<D, H> {
+        let overlay = self
+            .storage
+            .iter()
+            .map(|(hash, (node, _))| (*hash, node.clone().into()))
+            .collect();
+        TreeSnapshot::new(
+            *self.root_handle.hash(),
+            self.salt.clone(),
+            self.prefix.clone(),
+            overlay,
+        )
+    }
+
+    /// Replaces the uncommitted changes of the tree with those of the provided fork and returns
+    /// a fork of the replaced changes. Open transactions are not affected, a rollback restores
+    /// the changes snapshotted by the transaction.
+    ///
+    /// Errors:
+    /// - StaleFork: if the tree was committed since the fork was taken
+    pub fn switch(&mut self, fork: Fork<H, S>) -> Result<Fork<H, S>, TreeError> {
+        self.check_unprepared()?;
+        let current = self.fork();
+        self.restore(fork)?;
+        Ok(current)
+    }
+
+    /// Replaces the uncommitted changes of the tree with those of the provided fork.
+    ///
+    /// Errors:
+    /// - StaleFork: if the tree was committed since the fork was taken
+    fn restore(&mut self, fork: Fork<H, S>) -> Result<(), TreeError> {
+        if fork.base_root != *self.root {
+            return Err(TreeError::DataError(DataError::StaleFork));
+        }
+        self.storage = fork.storage;
+        self.death_row = fork.death_row;
+        self.root_handle = fork.root_handle;
+        self.leaf_changes = fork.leaf_changes;
+        self.dirty_leaves = fork.dirty_leaves;
+        Ok(())
+    }
+
+    /// Runs the provided write of many leaves with automatic commits deferred until it completes,
+    /// such that a failed write never leaves a part of it committed. Commits automatically once
+    /// the write succeeds, see auto_commit.
+    fn bulk_write<T>(
+        &mut self,
+        write: impl FnOnce(&mut Self) -> Result<T, TreeError>,
+    ) -> Result<T, TreeError> {
+        self.bulk_writes += 1;
+        let result = write(self);
+        self.bulk_writes -= 1;
+        let value = result?;
+        self.auto_commit()?;
+        Ok(value)
+    }
+
+    /// Commits the tree if more nodes than the maximum number of staged nodes are staged, see
+    /// TreeDBMutBuilder::with_max_staged_nodes. Skipped while a transaction or a write of many
+    /// leaves is in progress and for reference counted trees.
+    fn auto_commit(&mut self) -> Result<(), TreeError> {
+        let over_limit = self.max_staged_nodes.is_some_and(|max_staged_nodes| {
+            self.storage.len() + self.dirty_leaves.len() > max_staged_nodes
+        });
+        if over_limit
+            && self.transactions.is_empty()
+            && self.bulk_writes == 0
+            && !self.reference_counting
+        {
+            self.try_commit()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of open transactions
+    pub fn transaction_depth(&self) -> usize {
+        self.transactions.len()
+    }
+
+    /// Return the node associated with the provided hash, see lookup_node. Fails while the paths
+    /// of dirty leaves are not rehashed, as the hashes of the nodes on them are stale.
+    fn lookup(&self, node_hash: &NodeHash<H>) -> Result<Node<H>, TreeError> {
+        self.check_rehashed()?;
+        self.lookup_node(node_hash)
     }
 
     /// Return the node associated with the provided hash. Retrieves the node from either the database,
     /// in memory storage or the null node map if it is a default node.
-    fn lookup(&self, node_hash: &NodeHash<H>) -> Result<Node<H>, TreeError> {
+    fn lookup_node(&self, node_hash: &NodeHash<H>) -> Result<Node<H>, TreeError> {
         let node = match node_hash {
             NodeHash::InMemory(hash) => self.storage.get(hash).cloned().ok_or(
                 TreeError::DataError(DataError::InMemoryDataNotFound(hash.as_ref().to_vec())),
             ),
             NodeHash::Database(hash) => {
-                let data = self.db.get(hash, EMPTY_PREFIX).ok_or(TreeError::DataError(
-                    DataError::DatabaseDataNotFound(hash.as_ref().to_vec()),
-                ))?;
-                let node: Node<H> = data.try_into().map_err(TreeError::NodeError)?;
+                let node = match self.node_memo.get(hash) {
+                    Some(node) => {
+                        if let Some(metrics) = self.metrics {
+                            metrics.cache_hit();
+                        }
+                        node
+                    }
+                    None => {
+                        let data = self.db.as_hash_db().get(hash, (&self.prefix, None)).ok_or(
+                            TreeError::DataError(DataError::DatabaseDataNotFound(
+                                hash.as_ref().to_vec(),
+                            )),
+                        )?;
+                        if let Some(metrics) = self.metrics {
+                            metrics.node_read();
+                        }
+                        let node: Node<H> = Node::decode(data, &self.salt).map_err(|err| {
+                            if let Some(metrics) = self.metrics {
+                                metrics.decode_failure();
+                            }
+                            TreeError::NodeError(err)
+                        })?;
+                        self.node_memo.insert(&node);
+                        node
+                    }
+                };
 
                 if let Some(recorder) = self.recorder.as_ref() {
                     recorder.borrow_mut().record(&node);
@@ -168,7 +797,31 @@ impl<'db, const D: usize, H: Hasher> TreeDBMut<'db, D, H> {
         key: &Key<D>,
         proof: &mut Option<Vec<DBValue>>,
     ) -> Result<Option<Node<H>>, TreeError> {
-        let mut current_node = self.lookup(&self.root_handle)?;
+        if proof.is_some() {
+            self.check_rehashed()?;
+        }
+        self.lookup_leaf_node_at(&self.root_handle, key, proof)
+    }
+
+    /// Returns the leaf node for the provided key including the dirty leaves of a tree with
+    /// deferred hashing, a removed leaf being None. The path of a key that is not dirty is
+    /// unchanged by the dirty leaves, such that it is read without rehashing the tree.
+    fn staged_leaf_node(&self, key: &Key<D>) -> Result<Option<Node<H>>, TreeError> {
+        match self.dirty_leaves.get(key.as_slice()) {
+            Some(node) => Ok(Some(node.clone()).filter(|node| !node.is_default())),
+            None => self.lookup_leaf_node(key, &mut None),
+        }
+    }
+
+    /// Returns the leaf node for the provided key in the tree with the provided root, see
+    /// lookup_leaf_node.
+    fn lookup_leaf_node_at(
+        &self,
+        root: &NodeHash<H>,
+        key: &Key<D>,
+        proof: &mut Option<Vec<DBValue>>,
+    ) -> Result<Option<Node<H>>, TreeError> {
+        let mut current_node = self.lookup_node(root)?;
 
         for bit in key.iter() {
             let child_selector = ChildSelector::new(bit);
@@ -184,15 +837,243 @@ impl<'db, const D: usize, H: Hasher> TreeDBMut<'db, D, H> {
                 let sibling_hash: H::Out = **current_node
                     .child_hash(&child_selector.sibling())
                     .map_err(TreeError::NodeError)?;
-                proof.push(sibling_hash.as_ref().to_vec());
+                proof.push(DBValue::from(sibling_hash.as_ref()));
             }
 
-            current_node = self.lookup(child_hash)?;
+            current_node = self.lookup_node(child_hash)?;
         }
 
         Ok(Some(current_node))
     }
 
+    /// Returns the key-value pairs of all leaves in the subtree at the provided depth along the
+    /// provided key, ordered by key.
+    fn subtree_entries(
+        &self,
+        key: Key<D>,
+        depth: usize,
+    ) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        let lookup = |node_hash: &NodeHash<H>| self.lookup(node_hash);
+        let (subtree_root, _) = descend(&self.root_handle, &key, depth, lookup)?;
+        let mut entries = Vec::new();
+        for_each_leaf(subtree_root, key, depth, lookup, |key, node| {
+            if let Node::Value { value, .. } = node {
+                entries.push((DBValue::from(key.as_slice()), value));
+            }
+        })?;
+        Ok(entries)
+    }
+
+    /// Returns the key-value pairs of all leaves in the tree, ordered by key.
+    pub fn entries(&self) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        self.subtree_entries(Key::<D>::default(), 0)
+    }
+
+    /// Returns the root of the Celestia sparse merkle tree holding the leaves of the tree
+    /// including the uncommitted changes, see TreeDB::celestia_root.
+    pub fn celestia_root(&self) -> Result<H::Out, TreeError> {
+        Ok(celestia_root::<H>(&celestia_leaves::<H>(self.entries()?)))
+    }
+
+    /// Returns a proof of the value at the provided key in the Celestia sparse merkle tree
+    /// holding the leaves of the tree including the uncommitted changes, see
+    /// TreeDB::celestia_proof. Returns a tuple of form: (value, Celestia root, proof).
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if the key is not D bytes long
+    pub fn celestia_proof(&self, key: &[u8]) -> Result<CelestiaProofOf<H>, TreeError> {
+        Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let leaves = celestia_leaves::<H>(self.entries()?);
+        let (root, proof) = celestia_proof::<H>(&H::hash(key), &leaves);
+        Ok((self.value(key)?, root, proof))
+    }
+
+    /// Returns the (key, value) pairs of all leaves in the tree, ordered by path. Leaves that
+    /// store their key preimage yield the preimage as key, other leaves yield their path.
+    #[cfg(feature = "leaf-keys")]
+    pub fn preimage_entries(&self) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        let mut entries = Vec::new();
+        for_each_leaf(
+            self.root_handle.clone(),
+            Key::<D>::default(),
+            0,
+            |node_hash| self.lookup(node_hash),
+            |key, node| {
+                let preimage = node.preimage().cloned();
+                if let Node::Value { value, .. } = node {
+                    let key = preimage.unwrap_or_else(|| DBValue::from(key.as_slice()));
+                    entries.push((key, value));
+                }
+            },
+        )?;
+        Ok(entries)
+    }
+
+    /// Returns the witness of the inclusion proof of the value at the provided key laid out as
+    /// consumed by a SNARK circuit, see CircuitWitness.
+    pub fn circuit_witness(&self, key: &[u8]) -> Result<CircuitWitness<H, D>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let mut proof = Some(Vec::new());
+        let node = self
+            .lookup_leaf_node(&key, &mut proof)?
+            .expect("the leaf is always returned when recording a proof");
+        let mut siblings = proof.unwrap_or_default();
+        siblings.reverse();
+        let leaf_preimage = node
+            .leaf_preimage(&self.salt)
+            .map_err(TreeError::NodeError)?;
+        CircuitWitness::new(
+            &key,
+            leaf_preimage,
+            *node.hash(),
+            &siblings,
+            *self.root_handle.hash(),
+        )
+    }
+
+    /// Returns the key-value pairs of all leaves in the provided namespace, ordered by key.
+    pub fn namespace_entries(
+        &self,
+        namespace: &Namespace<D>,
+    ) -> Result<Vec<(DBValue, DBValue)>, TreeError> {
+        self.subtree_entries(namespace.prefix_key(), namespace.bits())
+    }
+
+    /// Returns a proof of the root of the provided namespace subtree.
+    /// Returns a tuple of form: (subtree root, root, proof), the proof is verified using
+    /// Namespace::verify.
+    pub fn namespace_proof(&self, namespace: &Namespace<D>) -> Result<SubtreeProof<H>, TreeError> {
+        self.subtree_proof(namespace.prefix_key().as_slice(), namespace.bits())
+    }
+
+    /// Returns a proof of the root of the subtree at the provided depth along the provided key,
+    /// the bits of the key below the subtree root are ignored. Returns a tuple of form: (subtree
+    /// root, root, proof), the proof is verified using verify_subtree.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    pub fn subtree_proof(&self, key: &[u8], depth: usize) -> Result<SubtreeProof<H>, TreeError> {
+        let (subtree_root, proof) = self.descend_to(key, depth)?;
+        Ok((*subtree_root.hash(), *self.root_handle.hash(), proof))
+    }
+
+    /// Returns a proof that the subtree at the provided depth along the provided key is empty,
+    /// i.e. that no key with the prefix exists. Returns a tuple of form: (root, proof), the proof
+    /// is verified using verify_empty_subtree. Returns None if the subtree is not empty.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree
+    pub fn empty_subtree_proof(
+        &self,
+        key: &[u8],
+        depth: usize,
+    ) -> Result<Option<AbsenceProof<H>>, TreeError> {
+        let (subtree_root, proof) = self.descend_to(key, depth)?;
+        Ok(subtree_root
+            .is_default()
+            .then(|| (*self.root_handle.hash(), proof)))
+    }
+
+    /// Descends the tree along the provided key to the provided depth, returning the subtree root
+    /// and the sibling hashes along the path ordered from the subtree root to the root.
+    fn descend_to(
+        &self,
+        key: &[u8],
+        depth: usize,
+    ) -> Result<(NodeHash<H>, Vec<DBValue>), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        if depth > Key::<D>::BITS {
+            return Err(TreeError::KeyError(KeyError::BitIndexOutOfBounds(
+                depth,
+                Key::<D>::BITS,
+            )));
+        }
+        descend(&self.root_handle, &key, depth, |node_hash| {
+            self.lookup(node_hash)
+        })
+    }
+
+    /// Returns the storage proof of the paths of the provided keys, holding the nodes from the
+    /// root down to the leaf or the first default node of each key. The proof includes the
+    /// uncommitted nodes, such that a witness can be produced without committing the tree.
+    /// Returns a tuple of form: (root, proof) where root is the uncommitted root.
+    pub fn storage_proof(&self, keys: &[&[u8]]) -> Result<(H::Out, StorageProof), TreeError> {
+        let mut nodes = Vec::new();
+        for key in keys.iter() {
+            let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+            path_nodes(
+                &self.root_handle,
+                &key,
+                |node_hash| self.lookup(node_hash),
+                &mut nodes,
+            )?;
+        }
+        Ok((*self.root_handle.hash(), StorageProof::new(nodes)))
+    }
+
+    /// Returns the number of non-default siblings of the inclusion proof of the provided key
+    /// without allocating the proof, i.e. the number of siblings of its compact proof. As such
+    /// the compact proof is H::LENGTH bytes per sibling plus a D byte bitmask.
+    pub fn proof_size_hint(&self, key: &[u8]) -> Result<usize, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        proof_size_hint(&self.root_handle, &key, |node_hash| self.lookup(node_hash))
+    }
+
+    /// Returns the total number of non-default siblings of the inclusion proofs of the provided
+    /// keys, see proof_size_hint.
+    pub fn proofs_size_hint(&self, keys: &[&[u8]]) -> Result<usize, TreeError> {
+        keys.iter()
+            .try_fold(0, |count, key| Ok(count + self.proof_size_hint(key)?))
+    }
+
+    /// Returns the hashes of the 2^depth nodes at the provided depth of the tree ordered by key,
+    /// e.g. to create a LevelCache against which proofs truncated at the depth are verified.
+    ///
+    /// Errors:
+    /// - BitIndexOutOfBounds: if the depth exceeds the depth of the tree or MAX_LEVEL_DEPTH
+    pub fn level_nodes(&self, depth: usize) -> Result<Vec<H::Out>, TreeError> {
+        level_hashes::<H, D>(&self.root_handle, depth, |node_hash| self.lookup(node_hash))
+    }
+
+    /// Returns the keys of the nearest non-default leaves before and after the provided key, the
+    /// key itself excluded. Returns a tuple of form: (predecessor, successor).
+    pub fn neighbors(&self, key: &[u8]) -> Result<(Option<DBValue>, Option<DBValue>), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let lookup = |node_hash: &NodeHash<H>| self.lookup(node_hash);
+        let predecessor = neighbor(&self.root_handle, &key, false, lookup)?;
+        let successor = neighbor(&self.root_handle, &key, true, lookup)?;
+        Ok((
+            predecessor.map(|key| DBValue::from(key.as_slice())),
+            successor.map(|key| DBValue::from(key.as_slice())),
+        ))
+    }
+
+    /// Returns a leaf node for the provided value at the provided key. Leaf nodes are memoized by
+    /// the FxHash of their value such that inserting an identical value at many keys only hashes
+    /// and encodes the leaf once, a memoized leaf is only returned if its value is equal. The memo
+    /// is bounded as a NodeMemo, the value is not retained beyond the node.
+    fn leaf_node(&mut self, key: &Key<D>, value: &[u8]) -> Node<H> {
+        if value.is_empty() {
+            return Node::new_null_value(&self.salt);
+        }
+        // key-bound leaves are unique to their key and are not memoized
+        if cfg!(feature = "key-bound") {
+            return Node::new_bound_value(key.as_slice(), &self.salt, value, None);
+        }
+        let value_hash = FxBuildHasher::default().hash_one(value);
+        let memoized = self.leaf_memo.get(&value_hash).filter(|node| {
+            node.value()
+                .is_ok_and(|memoized_value| memoized_value[..] == *value)
+        });
+        if let Some(node) = memoized {
+            return node;
+        }
+
+        let node = Node::new_salted_value(&self.salt, value);
+        self.leaf_memo.insert_with_key(value_hash, &node);
+        node
+    }
+
     /// Remove the node associated with the provided hash from the tree.
     fn remove_node(&mut self, node_hash: &NodeHash<H>) {
         match node_hash {
@@ -209,93 +1090,896 @@ impl<'db, const D: usize, H: Hasher> TreeDBMut<'db, D, H> {
         }
     }
 
-    /// Inserts a value at the specified key in the tree. New nodes are stored in memory until
-    /// the tree is committed. This function recursively traverses the tree until it reaches
-    /// the leaf node at the specified key. Old nodes are removed from the tree and replaced
-    /// with new nodes.
-    fn insert_at(
+    /// Returns an error if the value exceeds the maximum value size of the tree.
+    fn check_value_size(&self, value: &[u8]) -> Result<(), TreeError> {
+        match self.max_value_size {
+            Some(max_value_size) if value.len() > max_value_size => Err(TreeError::DataError(
+                DataError::ValueTooLarge(value.len(), max_value_size),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Inserts the provided leaf node at the provided key and updates the root of the tree.
+    /// Returns the old value if it exists. If an expected value is provided, the leaf is only
+    /// replaced if its current value is the expected value. If a proof is provided, the sibling
+    /// hashes along the path of the key are stored in the proof.
+    fn insert_leaf(
         &mut self,
-        current_hash: &NodeHash<H>,
         key: &Key<D>,
-        value: &[u8],
-        key_index: usize,
-    ) -> Result<(Node<H>, Option<DBValue>, bool), TreeError> {
-        // If we have reached the leaf node, create a new leaf node with the specified value.
-        if key_index == D * 8 {
-            let node = Node::new_value(value);
-
-            // fetch the old node if it exists
-            let old_node = match current_hash {
-                NodeHash::InMemory(_) | NodeHash::Database(_) => Some(
-                    self.lookup(current_hash)?
-                        .value()
-                        .map_err(TreeError::NodeError)?
-                        .clone(),
-                ),
-                NodeHash::Default(_) => None,
+        node: Node<H>,
+        expected: Option<Option<&[u8]>>,
+        proof: &mut Option<Vec<DBValue>>,
+    ) -> Result<Option<DBValue>, TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let current_root = self.root_handle.clone();
+        let new_value = node.value().map_err(TreeError::NodeError)?.clone();
+        let (new_root, old_node, changed) =
+            self.insert_at(&current_root, key, node, expected, proof)?;
+
+        if changed {
+            // the first write of a leaf since the last commit records its committed value
+            let new_value = (!new_value.is_empty()).then_some(new_value);
+            self.leaf_changes
+                .entry(DBValue::from(key.as_slice()))
+                .or_insert_with(|| (old_node.clone().filter(|value| !value.is_empty()), None))
+                .1 = new_value;
+
+            self.node_memo.clear();
+            self.remove_node(&current_root);
+            // an empty tree has a default root which is never persisted
+            if new_root.is_default() {
+                self.root_handle = NodeHash::Default(*new_root.hash());
+            } else {
+                self.root_handle = NodeHash::InMemory(*new_root.hash());
+                self.storage.insert(new_root);
+            }
+        }
+
+        self.auto_commit()?;
+        Ok(old_node)
+    }
+
+    /// Returns the keys of all leaves in the tree that have expired at the provided timestamp.
+    fn expired_leaves(&self, now: u64) -> Result<Vec<Key<D>>, TreeError> {
+        let mut expired = Vec::new();
+        for_each_leaf(
+            self.root_handle.clone(),
+            Key::<D>::default(),
+            0,
+            |node_hash| self.lookup(node_hash),
+            |key, node| {
+                if matches!(node.expiry(), Some(expiry) if expiry <= now) {
+                    expired.push(key);
+                }
+            },
+        )?;
+        Ok(expired)
+    }
+
+    /// Inserts the provided value at the provided key with an expiry timestamp and returns the
+    /// old value if it exists. The expiry is stored in the leaf and committed to by the leaf hash,
+    /// an inclusion proof of the leaf is verified by providing the value followed by the big
+    /// endian expiry in place of the value. Expired leaves are removed by `sweep`.
+    pub fn insert_with_expiry(
+        &mut self,
+        key: &[u8],
+        value: DBValue,
+        expiry: u64,
+    ) -> Result<Option<DBValue>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.check_value_size(&value)?;
+        let node = if value.is_empty() {
+            self.leaf_node(&key, &value)
+        } else if cfg!(feature = "key-bound") {
+            Node::new_bound_value(key.as_slice(), &self.salt, &value, Some(expiry))
+        } else {
+            Node::new_expiring_value(&self.salt, &value, expiry)
+        };
+        self.insert_leaf(&key, node, None, &mut None)
+    }
+
+    /// Inserts the provided value at the provided key, storing the provided key preimage in the
+    /// leaf record, and returns the old value if it exists. This allows the original key of a
+    /// leaf in a hashed-key tree to be recovered using `preimage_entries`. The leaf hash commits
+    /// to the preimage, an inclusion proof of the leaf is verified by providing
+    /// key_preimage_value(preimage, value) in place of the value.
+    #[cfg(feature = "leaf-keys")]
+    pub fn insert_with_preimage(
+        &mut self,
+        key: &[u8],
+        preimage: &[u8],
+        value: DBValue,
+    ) -> Result<Option<DBValue>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.check_value_size(&value)?;
+        let node = if value.is_empty() {
+            self.leaf_node(&key, &value)
+        } else {
+            let bound_key = cfg!(feature = "key-bound").then_some(key.as_slice());
+            Node::new_preimage_value(bound_key, &self.salt, preimage, &value)
+        };
+        self.insert_leaf(&key, node, None, &mut None)
+    }
+
+    /// Inserts the provided value at the provided key using only the inclusion proof of the old
+    /// value at the key, an empty old value being a proof of non-inclusion, and returns the old
+    /// value. The proof holds the siblings ordered from the leaf to the root and is verified
+    /// against the current root, including uncommitted changes. The nodes along the path of the
+    /// key are recovered from the proof and staged in memory, such that the write does not read
+    /// from the database. This allows partial-state trees that hold witnesses rather than all
+    /// nodes. The replaced nodes of the path are not removed from the database on commit.
+    ///
+    /// Errors:
+    /// - DepthMismatch: if the number of siblings is not the depth of the tree
+    /// - RootMismatch: if the proof does not verify against the current root
+    pub fn insert_with_proof(
+        &mut self,
+        key: &[u8],
+        value: DBValue,
+        old_value: &[u8],
+        proof: &[DBValue],
+    ) -> Result<Option<DBValue>, TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.check_value_size(&value)?;
+        if proof.len() != Key::<D>::BITS {
+            return Err(TreeError::ProofError(ProofError::DepthMismatch(
+                Key::<D>::BITS,
+                proof.len(),
+            )));
+        }
+
+        let leaf = self.leaf_node(&key, old_value);
+        let node = self.leaf_node(&key, &value);
+        if node.hash() == leaf.hash() {
+            return Ok((!old_value.is_empty()).then(|| DBValue::from(old_value)));
+        }
+
+        // compute the hashes along the path of the key from the leaf to the root
+        let mut hashes = Vec::from([*leaf.hash()]);
+        let mut siblings = Vec::with_capacity(Key::<D>::BITS);
+        for (sibling, bit) in proof.iter().zip((0..Key::<D>::BITS).rev()) {
+            let sibling = decode_hash::<H>(sibling).map_err(TreeError::NodeError)?;
+            let child = hashes[hashes.len() - 1];
+            let hash = match key.bit(bit).map_err(TreeError::KeyError)? {
+                true => H::hash_inner(sibling.as_ref(), child.as_ref()),
+                false => H::hash_inner(child.as_ref(), sibling.as_ref()),
             };
+            hashes.push(hash);
+            siblings.push(sibling);
+        }
+        let root = hashes[hashes.len() - 1];
+        if &root != self.root_handle.hash() {
+            return Err(TreeError::ProofError(ProofError::RootMismatch(
+                self.root_handle.hash().as_ref().to_vec(),
+                root.as_ref().to_vec(),
+            )));
+        }
 
-            // If the new node has the same hash as the current node, return the current node
-            // as the node has not changed.
-            if node.hash() == current_hash.hash() {
-                return Ok((node, old_node, false));
+        // stage the nodes along the path from the root to the leaf, nodes that are staged already
+        // keep their count and the handles of their siblings
+        for (depth, bit) in key.iter().enumerate() {
+            let level = Key::<D>::BITS - depth;
+            let (hash, child, sibling) = (hashes[level], hashes[level - 1], siblings[level - 1]);
+            if self.null_nodes.contains_key(&hash) {
+                break;
             }
+            let child_selector = ChildSelector::new(bit);
+            let sibling = match self.storage.get(&hash) {
+                Some(node) => node
+                    .child_hash(&child_selector.sibling())
+                    .map_err(TreeError::NodeError)?
+                    .clone(),
+                None if self.null_nodes.contains_key(&sibling) => NodeHash::Default(sibling),
+                None => NodeHash::Database(sibling),
+            };
+            let child = match self.null_nodes.contains_key(&child) {
+                true => NodeHash::Default(child),
+                false => NodeHash::InMemory(child),
+            };
+            let (left, right) = match child_selector {
+                ChildSelector::Left => (child, sibling),
+                ChildSelector::Right => (sibling, child),
+            };
+            let node = Node::new_inner(left, right).map_err(TreeError::NodeError)?;
+            self.storage.insert_or_replace(node);
+        }
+        if !leaf.is_default() && self.storage.get(leaf.hash()).is_none() {
+            self.storage.insert(leaf);
+        }
+        if !self.null_nodes.contains_key(&root) {
+            self.root_handle = NodeHash::InMemory(root);
+        }
+
+        self.insert_leaf(&key, node, None, &mut None)
+    }
+
+    /// Returns the entry at the provided key for in-place manipulation, see Entry
+    pub fn entry(&mut self, key: &[u8]) -> Result<Entry<'_, 'db, D, H, S>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let value = self.value(key.as_slice())?;
+        Ok(entry(self, key, value))
+    }
+
+    /// Inserts the provided value at the provided key if the current value is the expected value,
+    /// where None is an absent value. The value is read and written in a single traversal of the
+    /// path of the key, such that no write can interleave between the comparison and the swap. An
+    /// empty value removes the key.
+    ///
+    /// Errors:
+    /// - ValueMismatch: if the current value is not the expected value, the tree is not modified
+    pub fn compare_and_swap(
+        &mut self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: DBValue,
+    ) -> Result<(), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.check_value_size(&new)?;
+        let node = self.leaf_node(&key, &new);
+        self.insert_leaf(&key, node, Some(expected), &mut None)?;
+        Ok(())
+    }
+
+    /// Discards the uncommitted change of the provided key, restoring its committed leaf while
+    /// keeping the uncommitted changes of other keys. Only the path of the key is rebuilt. Returns
+    /// true if the key had an uncommitted change.
+    pub fn revert_key(&mut self, key: &[u8]) -> Result<bool, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.rehash()?;
+        let (_, default_root) = null_nodes::<H, S>(Key::<D>::BITS, &self.salt);
+        let committed_root = if *self.root == H::Out::default() || *self.root == default_root {
+            NodeHash::Default(default_root)
+        } else {
+            NodeHash::Database(*self.root)
+        };
+        let committed = self.lookup_leaf_node_at(&committed_root, &key, &mut None)?;
+        let current = self.lookup_leaf_node(&key, &mut None)?;
+        let leaf_hash = |node: &Option<Node<H>>| {
+            node.as_ref()
+                .filter(|node| !node.is_default())
+                .map(|node| *node.hash())
+        };
+        if leaf_hash(&committed) == leaf_hash(&current) {
+            return Ok(false);
+        }
+
+        let committed = match committed {
+            Some(node) => node,
+            None => self.leaf_node(&key, &[]),
+        };
+        self.insert_leaf(&key, committed, None, &mut None)?;
+        self.leaf_changes.remove(key.as_slice());
+        Ok(true)
+    }
+
+    /// Inserts the provided value at the provided key and returns the old value along with the
+    /// sibling hashes along the path of the key, ordered from the leaf to the root. The siblings
+    /// are collected during the write, such that a witness of the write does not require a
+    /// separate proof traversal. An empty value removes the key.
+    pub fn insert_witnessed(
+        &mut self,
+        key: &[u8],
+        value: DBValue,
+    ) -> Result<(Option<DBValue>, Vec<DBValue>), TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        self.check_value_size(&value)?;
+        let node = self.leaf_node(&key, &value);
+        let mut proof = Some(Vec::with_capacity(Key::<D>::BITS));
+        let old_value = self.insert_leaf(&key, node, None, &mut proof)?;
+        let mut proof = proof.unwrap();
+        proof.reverse();
+        Ok((old_value, proof))
+    }
+
+    /// Inserts the provided value at the provided key and returns an UpdateProof witnessing that
+    /// the write transforms the current root into the new root. The proof is verified without the
+    /// database using verify_update. An empty value removes the key.
+    pub fn insert_and_prove(
+        &mut self,
+        key: &[u8],
+        value: DBValue,
+    ) -> Result<UpdateProof<H, D>, TreeError> {
+        self.rehash()?;
+        let old_root = *self.root_handle.hash();
+        let (old_value, siblings) = self.insert_witnessed(key, value.clone())?;
+        let new_root = *self.root_handle.hash();
+        UpdateProof::new(
+            key,
+            (old_value, old_root),
+            (Some(value), new_root),
+            siblings,
+        )
+    }
+
+    /// Returns the expiry timestamp of the leaf at the provided key, None if the leaf does not
+    /// exist or does not expire.
+    pub fn expiry(&self, key: &[u8]) -> Result<Option<u64>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let node = self.staged_leaf_node(&key)?;
+        Ok(node.and_then(|node| node.expiry()))
+    }
+
+    /// Removes all leaves that have expired at the provided timestamp, i.e. leaves with an expiry
+    /// less than or equal to now, and returns the number of leaves removed. The removals are
+    /// persisted on the next commit.
+    pub fn sweep(&mut self, now: u64) -> Result<usize, TreeError> {
+        self.sweep_with_monitor(now, &mut Monitor::new())
+    }
+
+    /// Removes all leaves that have expired at the provided timestamp, see sweep, reporting
+    /// progress to and checking for cancellation with the provided monitor. Each removed leaf
+    /// counts as processed. Leaves removed before the operation is cancelled remain removed and
+    /// are persisted on the next commit.
+    ///
+    /// Errors:
+    /// - Cancelled: if the cancellation token of the monitor is cancelled
+    pub fn sweep_with_monitor(
+        &mut self,
+        now: u64,
+        monitor: &mut Monitor,
+    ) -> Result<usize, TreeError> {
+        self.rehash()?;
+        let expired = self.expired_leaves(now)?;
+        for key in expired.iter() {
+            monitor.tick()?;
+            let node = self.leaf_node(key, &[]);
+            self.insert_leaf(key, node, None, &mut None)?;
+        }
+        monitor.finish();
+        Ok(expired.len())
+    }
+
+    /// Resets the tree to the empty tree. The nodes of the current tree are removed from the
+    /// database on the next commit. Unlike removing every leaf this requires no hashing and no
+    /// knowledge of the keys in the tree.
+    pub fn clear(&mut self) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        // the root node holds an additional reference, see insert_leaf
+        let root_handle = self.root_handle.clone();
+        self.remove_node(&root_handle);
+        self.remove_subtree(root_handle, Key::<D>::default(), 0)?;
+
+        let (_, default_root) = null_nodes::<H, S>(Key::<D>::BITS, &self.salt);
+        self.root_handle = NodeHash::Default(default_root);
+        self.node_memo.clear();
+        Ok(())
+    }
+
+    /// Removes the leaves with keys in the provided inclusive range. Subtrees that lie entirely
+    /// within the range are replaced by the default node of their level without descending to
+    /// their leaves by key, such that the cost is proportional to the number of affected subtrees
+    /// rather than the length of the range.
+    pub(crate) fn remove_key_range(
+        &mut self,
+        first: &Key<D>,
+        last: &Key<D>,
+    ) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        if first.as_slice() > last.as_slice() {
+            return Ok(());
+        }
+
+        self.bulk_write(|tree| {
+            let current_root = tree.root_handle.clone();
+            let (_, default_root) = null_nodes::<H, S>(Key::<D>::BITS, &tree.salt);
+            let new_root = tree.remove_range_at(
+                current_root.clone(),
+                Key::<D>::default(),
+                0,
+                default_root,
+                (first, last),
+            )?;
+            tree.replace_root(&current_root, new_root)
+        })
+    }
+
+    /// Replaces the provided current root of the tree with the provided new root, which is staged
+    /// in memory unless it is a default node.
+    fn replace_root(
+        &mut self,
+        current_root: &NodeHash<H>,
+        new_root: NodeHash<H>,
+    ) -> Result<(), TreeError> {
+        if new_root.hash() == current_root.hash() {
+            return Ok(());
+        }
+
+        // the root node holds an additional reference, see insert_leaf
+        self.remove_node(current_root);
+        if !new_root.is_default() {
+            let node = self.lookup(&new_root)?;
+            self.storage.insert(node);
+        }
+        self.root_handle = new_root;
+        self.node_memo.clear();
+        Ok(())
+    }
 
-            if !node.is_default() {
-                self.storage.insert(node.clone());
+    /// Inserts the provided (key, value) pairs into the tree in order without stopping at the
+    /// first failed entry, e.g. for import pipelines that tolerate partial success. Returns the
+    /// result of each insert aligned with the entries, the old value of the key or the error of
+    /// the entry, see KeyedTreeMut::insert. A failed entry leaves the tree unchanged. Automatic
+    /// commits are deferred until all entries are inserted, an error of the automatic commit is
+    /// returned as the result of the last entry.
+    pub fn insert_many<K: AsRef<[u8]>>(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, DBValue)>,
+    ) -> Vec<Result<Option<DBValue>, TreeError>> {
+        self.insert_each(entries, |tree, (key, value)| {
+            tree.insert(key.as_ref(), value)
+        })
+    }
+
+    /// Inserts each of the provided entries with the provided insert function and returns the
+    /// results aligned with the entries, see insert_many.
+    pub(crate) fn insert_each<T>(
+        &mut self,
+        entries: impl IntoIterator<Item = T>,
+        mut insert: impl FnMut(&mut Self, T) -> Result<Option<DBValue>, TreeError>,
+    ) -> Vec<Result<Option<DBValue>, TreeError>> {
+        self.bulk_writes += 1;
+        let mut results: Vec<_> = entries
+            .into_iter()
+            .map(|entry| insert(self, entry))
+            .collect();
+        self.bulk_writes -= 1;
+        if let (Err(err), Some(last)) = (self.auto_commit(), results.last_mut()) {
+            *last = Err(err);
+        }
+        results
+    }
+
+    /// Inserts the provided (key, value) pairs into the tree, an empty value removes the key and
+    /// later values of a key replace earlier ones. The paths of all keys are descended together
+    /// and each inner node on them is rebuilt and hashed once for the whole batch, rather than
+    /// once per key whose path passes through it as with repeated inserts. The upper nodes of the
+    /// tree are shared by the paths of most keys, such that this dominates the write cost of
+    /// expensive hashers.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if a key is not D bytes long
+    /// - ValueTooLarge: if a value exceeds the maximum value size of the tree
+    pub fn insert_batch<K: AsRef<[u8]>>(
+        &mut self,
+        entries: impl IntoIterator<Item = (K, DBValue)>,
+    ) -> Result<(), TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let mut leaves = Vec::new();
+        for (key, value) in entries {
+            let key = Key::<D>::new(key.as_ref()).map_err(TreeError::KeyError)?;
+            self.check_value_size(&value)?;
+            leaves.push((key, value));
+        }
+        // the sort is stable, such that the last value of a key is kept
+        leaves.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+        leaves.reverse();
+        leaves.dedup_by(|a, b| a.0 == b.0);
+        leaves.reverse();
+        if leaves.is_empty() {
+            return Ok(());
+        }
+
+        let leaves: Vec<(Key<D>, Node<H>)> = leaves
+            .into_iter()
+            .map(|(key, value)| {
+                let node = self.leaf_node(&key, &value);
+                (key, node)
+            })
+            .collect();
+        self.bulk_write(|tree| tree.write_batch(&leaves, &mut Vec::new()))
+    }
+
+    /// Removes the provided keys from the tree and returns the old values aligned with the keys.
+    /// As with insert_batch the paths of all keys are descended together and each inner node on
+    /// them is rebuilt and hashed once for the whole batch. A key that is repeated returns its
+    /// old value once, as removing it again would return None. The tree is committed
+    /// automatically only once the whole batch is removed, see
+    /// TreeDBMutBuilder::with_max_staged_nodes.
+    ///
+    /// Errors:
+    /// - IncorrectKeySize: if a key is not D bytes long
+    pub fn remove_batch<K: AsRef<[u8]>>(
+        &mut self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<Vec<Option<DBValue>>, TreeError> {
+        self.check_unprepared()?;
+        self.rehash()?;
+        let keys = keys
+            .into_iter()
+            .map(|key| Key::<D>::new(key.as_ref()).map_err(TreeError::KeyError))
+            .collect::<Result<Vec<_>, TreeError>>()?;
+        let mut leaves: Vec<(Key<D>, Node<H>)> = keys
+            .iter()
+            .map(|key| (*key, self.leaf_node(key, &[])))
+            .collect();
+        leaves.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+        leaves.dedup_by(|a, b| a.0 == b.0);
+
+        let mut old_values = Vec::new();
+        self.bulk_write(|tree| tree.write_batch(&leaves, &mut old_values))?;
+        Ok(keys
+            .iter()
+            .map(|key| {
+                old_values
+                    .binary_search_by(|(old_key, _)| old_key.as_slice().cmp(key.as_slice()))
+                    .ok()
+                    .and_then(|index| old_values[index].1.take())
+            })
+            .collect())
+    }
+
+    /// Writes the provided leaves, sorted and unique by key, into the tree and pushes the (key,
+    /// old value) pairs of the changed leaves onto the provided old values, ordered by key.
+    fn write_batch(
+        &mut self,
+        leaves: &[(Key<D>, Node<H>)],
+        old_values: &mut Vec<(Key<D>, Option<DBValue>)>,
+    ) -> Result<(), TreeError> {
+        let current_root = self.root_handle.clone();
+        let new_root = self.insert_batch_at(current_root.clone(), 0, leaves, old_values)?;
+        self.replace_root(&current_root, new_root)
+    }
+
+    /// Inserts the provided leaves, sorted and unique by key, into the subtree with the provided
+    /// root at the provided depth. Returns the handle of the new subtree root, the provided handle
+    /// if the subtree is unchanged. The old values of the changed leaves are pushed onto the
+    /// provided old values. The subtree is rebuilt iteratively with an explicit stack, the
+    /// children of an inner node are rebuilt before the node is rehashed once.
+    fn insert_batch_at(
+        &mut self,
+        root: NodeHash<H>,
+        depth: usize,
+        leaves: &[(Key<D>, Node<H>)],
+        old_values: &mut Vec<(Key<D>, Option<DBValue>)>,
+    ) -> Result<NodeHash<H>, TreeError> {
+        let mut stack = Vec::from([Rebuild::Visit((root, depth, leaves))]);
+        let mut rebuilt: Vec<NodeHash<H>> = Vec::new();
+        while let Some(step) = stack.pop() {
+            let (node_hash, new_node) = match step {
+                Rebuild::Rebuilt(node_hash) => {
+                    rebuilt.push(node_hash);
+                    continue;
+                }
+                Rebuild::Hash(node_hash) => {
+                    let right = rebuilt.pop().unwrap();
+                    let left = rebuilt.pop().unwrap();
+                    let new_node =
+                        Node::<H>::new_inner(left, right).map_err(TreeError::NodeError)?;
+                    (node_hash, new_node)
+                }
+                Rebuild::Visit((node_hash, depth, leaves)) if depth == Key::<D>::BITS => {
+                    let (key, new_node) = &leaves[0];
+                    if new_node.hash() == node_hash.hash() {
+                        rebuilt.push(node_hash);
+                        continue;
+                    }
+
+                    // the first write of a leaf since the last commit records its committed value
+                    let old_value = match node_hash {
+                        NodeHash::Default(_) => None,
+                        _ => Some(
+                            self.lookup(&node_hash)?
+                                .value()
+                                .map_err(TreeError::NodeError)?
+                                .clone(),
+                        ),
+                    };
+                    let old_value = old_value.filter(|value| !value.is_empty());
+                    let new_value = new_node.value().map_err(TreeError::NodeError)?.clone();
+                    let new_value = (!new_value.is_empty()).then_some(new_value);
+                    self.leaf_changes
+                        .entry(DBValue::from(key.as_slice()))
+                        .or_insert_with(|| (old_value.clone(), None))
+                        .1 = new_value;
+                    old_values.push((*key, old_value));
+                    (node_hash, new_node.clone())
+                }
+                Rebuild::Visit((node_hash, depth, leaves)) => {
+                    // the leaves are sorted, such that the leaves of the left subtree come first,
+                    // the left subtree is pushed last such that it is rebuilt first
+                    let node = self.lookup(&node_hash)?;
+                    let split =
+                        leaves.partition_point(|(key, _)| !key.bit(depth).unwrap_or_default());
+                    stack.push(Rebuild::Hash(node_hash));
+                    for (child_selector, child_leaves) in [
+                        (ChildSelector::Right, &leaves[split..]),
+                        (ChildSelector::Left, &leaves[..split]),
+                    ] {
+                        let child_hash = node
+                            .child_hash(&child_selector)
+                            .map_err(TreeError::NodeError)?
+                            .clone();
+                        stack.push(match child_leaves.is_empty() {
+                            true => Rebuild::Rebuilt(child_hash),
+                            false => Rebuild::Visit((child_hash, depth + 1, child_leaves)),
+                        });
+                    }
+                    continue;
+                }
+            };
+            rebuilt.push(self.replace_node(node_hash, new_node));
+        }
+        Ok(rebuilt.pop().unwrap())
+    }
+
+    /// Replaces the node with the provided handle by the provided new node, which is staged in
+    /// memory unless it is a default node. Returns the handle of the new node, the provided handle
+    /// if the node is unchanged.
+    fn replace_node(&mut self, node_hash: NodeHash<H>, new_node: Node<H>) -> NodeHash<H> {
+        if new_node.hash() == node_hash.hash() {
+            return node_hash;
+        }
+        self.remove_node(&node_hash);
+        let new_hash = *new_node.hash();
+        if new_node.is_default() {
+            return NodeHash::Default(new_hash);
+        }
+        self.storage.insert(new_node);
+        NodeHash::InMemory(new_hash)
+    }
+
+    /// Removes the leaves with keys in the provided inclusive range from the subtree at the
+    /// provided key prefix and depth, whose default node has the provided hash. Returns the handle
+    /// of the new subtree root, the provided handle if the subtree is unchanged. The subtree is
+    /// rebuilt iteratively with an explicit stack, see insert_batch_at.
+    fn remove_range_at(
+        &mut self,
+        root: NodeHash<H>,
+        prefix: Key<D>,
+        depth: usize,
+        null_hash: H::Out,
+        (first, last): (&Key<D>, &Key<D>),
+    ) -> Result<NodeHash<H>, TreeError> {
+        let mut stack = Vec::from([Rebuild::Visit((root, prefix, depth, null_hash))]);
+        let mut rebuilt: Vec<NodeHash<H>> = Vec::new();
+        while let Some(step) = stack.pop() {
+            let (node_hash, prefix, depth, null_hash) = match step {
+                Rebuild::Visit(subtree) => subtree,
+                Rebuild::Rebuilt(node_hash) => {
+                    rebuilt.push(node_hash);
+                    continue;
+                }
+                Rebuild::Hash(node_hash) => {
+                    let right = rebuilt.pop().unwrap();
+                    let left = rebuilt.pop().unwrap();
+                    let new_node =
+                        Node::<H>::new_inner(left, right).map_err(TreeError::NodeError)?;
+                    rebuilt.push(self.replace_node(node_hash, new_node));
+                    continue;
+                }
+            };
+            if node_hash.is_default() {
+                rebuilt.push(node_hash);
+                continue;
             }
 
-            self.remove_node(current_hash);
+            // the subtree holds the keys from the prefix followed by zeros to the prefix followed
+            // by ones
+            let mut upper = prefix;
+            for bit in depth..Key::<D>::BITS {
+                upper.set_bit(bit, true).map_err(TreeError::KeyError)?;
+            }
+            if upper.as_slice() < first.as_slice() || prefix.as_slice() > last.as_slice() {
+                rebuilt.push(node_hash);
+                continue;
+            }
+            if first.as_slice() <= prefix.as_slice() && upper.as_slice() <= last.as_slice() {
+                self.remove_subtree(node_hash, prefix, depth)?;
+                rebuilt.push(NodeHash::Default(null_hash));
+                continue;
+            }
 
-            return Ok((node, old_node, true));
+            // the subtree is partially in the range, such that it is an inner node, the left
+            // subtree is pushed last such that it is rebuilt first
+            let node = self.lookup(&node_hash)?;
+            let child_null_hash = match self.null_nodes.get(&null_hash) {
+                Some(null_node) => **null_node
+                    .child_hash(&ChildSelector::Left)
+                    .map_err(TreeError::NodeError)?,
+                None => {
+                    return Err(TreeError::DataError(DataError::DatabaseDataNotFound(
+                        null_hash.as_ref().to_vec(),
+                    )))
+                }
+            };
+            let mut right_prefix = prefix;
+            right_prefix
+                .set_bit(depth, true)
+                .map_err(TreeError::KeyError)?;
+            stack.push(Rebuild::Hash(node_hash));
+            for (child_selector, child_prefix) in [
+                (ChildSelector::Right, right_prefix),
+                (ChildSelector::Left, prefix),
+            ] {
+                let child_hash = node
+                    .child_hash(&child_selector)
+                    .map_err(TreeError::NodeError)?
+                    .clone();
+                stack.push(Rebuild::Visit((
+                    child_hash,
+                    child_prefix,
+                    depth + 1,
+                    child_null_hash,
+                )));
+            }
         }
+        Ok(rebuilt.pop().unwrap())
+    }
 
-        // If we have not reached the leaf node lookup the current node.
-        let mut current_node = self.lookup(current_hash)?;
+    /// Removes the nodes of the subtree with the provided root at the provided key prefix and
+    /// depth, recording the removed leaves in the leaf changes.
+    fn remove_subtree(
+        &mut self,
+        root: NodeHash<H>,
+        prefix: Key<D>,
+        depth: usize,
+    ) -> Result<(), TreeError> {
+        let mut stack = Vec::from([(root, prefix, depth)]);
+        while let Some((node_hash, key, depth)) = stack.pop() {
+            if node_hash.is_default() {
+                continue;
+            }
 
-        // Select the appropriate child based on the key bit at the current index and lookup.
-        let bit = key.bit(key_index).map_err(TreeError::KeyError)?;
-        let child_selector = ChildSelector::new(bit);
-        let child_hash = current_node
-            .child_hash(&child_selector)
-            .map_err(TreeError::NodeError)?;
+            let node = self.lookup(&node_hash)?;
+            self.remove_node(&node_hash);
+            if depth == Key::<D>::BITS {
+                let value = node.value().map_err(TreeError::NodeError)?;
+                if !value.is_empty() {
+                    let old_value = value.clone();
+                    self.leaf_changes
+                        .entry(DBValue::from(key.as_slice()))
+                        .or_insert_with(|| (Some(old_value), None))
+                        .1 = None;
+                }
+                continue;
+            }
+
+            let mut right_key = key;
+            right_key
+                .set_bit(depth, true)
+                .map_err(TreeError::KeyError)?;
+            for (child_selector, child_key) in [
+                (ChildSelector::Right, right_key),
+                (ChildSelector::Left, key),
+            ] {
+                let child_hash = node
+                    .child_hash(&child_selector)
+                    .map_err(TreeError::NodeError)?;
+                stack.push((child_hash.clone(), child_key, depth + 1));
+            }
+        }
+        Ok(())
+    }
 
-        let (child_node, old_node, changed) =
-            self.insert_at(child_hash, key, value, key_index + 1)?;
+    /// Inserts a value at the specified key in the tree. New nodes are stored in memory until
+    /// the tree is committed. This function iteratively traverses the tree from the provided
+    /// root until it reaches the leaf node at the specified key, storing the nodes along the
+    /// path. The path is then traversed in reverse, old nodes are removed from the tree and
+    /// replaced with new nodes. Inner nodes whose children are both default are replaced by the
+    /// default node of their level, such that removals restore the sparse representation of the
+    /// emptied subtrees. Returns the new root node, the old value and whether the tree changed.
+    /// If an expected value is provided and the old value differs, a ValueMismatch error is
+    /// returned before the tree is modified. If a proof is provided, the sibling hashes along the
+    /// path are stored in the proof.
+    fn insert_at(
+        &mut self,
+        root_hash: &NodeHash<H>,
+        key: &Key<D>,
+        node: Node<H>,
+        expected: Option<Option<&[u8]>>,
+        proof: &mut Option<Vec<DBValue>>,
+    ) -> Result<(Node<H>, Option<DBValue>, bool), TreeError> {
+        // Descend the tree selecting the appropriate child based on the key bit at each level.
+        let mut path: Vec<(NodeHash<H>, Node<H>, ChildSelector)> =
+            Vec::with_capacity(Key::<D>::BITS);
+        let mut current_hash = root_hash.clone();
+        for bit in key.iter() {
+            let current_node = self.lookup(&current_hash)?;
+            let child_selector = ChildSelector::new(bit);
+            let child_hash = current_node
+                .child_hash(&child_selector)
+                .map_err(TreeError::NodeError)?
+                .clone();
 
-        if !changed {
-            return Ok((current_node, old_node, false));
+            // store the sibling hash in the proof
+            if let Some(proof) = proof.as_mut() {
+                let sibling_hash: H::Out = **current_node
+                    .child_hash(&child_selector.sibling())
+                    .map_err(TreeError::NodeError)?;
+                proof.push(DBValue::from(sibling_hash.as_ref()));
+            }
+            path.push((current_hash, current_node, child_selector));
+            current_hash = child_hash;
         }
 
-        let child_hash: NodeHash<H> = if child_node.is_default() {
-            NodeHash::Default(*child_node.hash())
-        } else {
-            NodeHash::InMemory(*child_node.hash())
+        // We have reached the leaf node which is replaced with the new leaf node.
+        // fetch the old node if it exists
+        let old_node = match current_hash {
+            NodeHash::InMemory(_) | NodeHash::Database(_) => Some(
+                self.lookup(&current_hash)?
+                    .value()
+                    .map_err(TreeError::NodeError)?
+                    .clone(),
+            ),
+            NodeHash::Default(_) => None,
         };
-        current_node
-            .set_child_hash(&child_selector, child_hash)
-            .map_err(TreeError::NodeError)?;
 
-        if !current_node.is_default() {
-            self.storage.insert(current_node.clone());
+        if let Some(expected) = expected {
+            let expected = expected.filter(|value| !value.is_empty());
+            if old_node.as_deref().filter(|value| !value.is_empty()) != expected {
+                return Err(TreeError::DataError(DataError::ValueMismatch(
+                    key.as_slice().to_vec(),
+                )));
+            }
+        }
+
+        // If the new node has the same hash as the current node, return the root node as the
+        // tree has not changed.
+        if node.hash() == current_hash.hash() {
+            let root = path.into_iter().next().map(|(_, root, _)| root);
+            return Ok((root.unwrap_or(node), old_node, false));
         }
-        self.remove_node(current_hash);
 
-        Ok((current_node, old_node, true))
+        if !node.is_default() {
+            self.storage.insert(node.clone());
+        }
+        self.remove_node(&current_hash);
+
+        // Ascend the tree updating the child hash of each node along the path.
+        let mut child_node = node;
+        while let Some((current_hash, mut current_node, child_selector)) = path.pop() {
+            let child_hash: NodeHash<H> = if child_node.is_default() {
+                NodeHash::Default(*child_node.hash())
+            } else {
+                NodeHash::InMemory(*child_node.hash())
+            };
+            current_node
+                .set_child_hash(&child_selector, child_hash)
+                .map_err(TreeError::NodeError)?;
+
+            if !current_node.is_default() {
+                self.storage.insert(current_node.clone());
+            }
+            self.remove_node(&current_hash);
+
+            child_node = current_node;
+        }
+
+        Ok((child_node, old_node, true))
     }
 }
 
-impl<'db, const D: usize, H: Hasher> KeyedTreeMut<H, D> for TreeDBMut<'db, D, H> {
+impl<'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> KeyedTreeMut<H, D>
+    for TreeDBMut<'db, D, H, S>
+{
     /// Return the root of the tree
     fn root(&mut self) -> &H::Out {
         self.commit();
         self.root
     }
 
+    /// Return the root of the tree including the uncommitted changes, excluding the dirty leaves of
+    /// a tree with deferred hashing, see rehash
+    fn root_hint(&self) -> H::Out {
+        *self.root_handle.hash()
+    }
+
+    /// Return the salt used for leaf hashing
+    fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
     /// Returns the value associated with the provided key. If the key does not exist, returns None.
     fn value(&self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
-        let node = self.lookup_leaf_node(&key, &mut None)?;
+        let node = self.staged_leaf_node(&key)?;
         match node {
             Some(node) => Ok(Some(node.value().map_err(TreeError::NodeError)?.clone())),
             None => Ok(None),
@@ -305,7 +1989,7 @@ impl<'db, const D: usize, H: Hasher> KeyedTreeMut<H, D> for TreeDBMut<'db, D, H>
     /// Returns the leaf associated with the provided key. If the key does not exist, returns None.
     fn leaf(&self, key: &[u8]) -> Result<Option<H::Out>, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
-        let node = self.lookup_leaf_node(&key, &mut None)?;
+        let node = self.staged_leaf_node(&key)?;
         match node {
             Some(node) => Ok(Some(*node.hash())),
             None => Ok(None),
@@ -331,24 +2015,41 @@ impl<'db, const D: usize, H: Hasher> KeyedTreeMut<H, D> for TreeDBMut<'db, D, H>
         }
     }
 
+    /// Returns a non-inclusion proof of the specified key, None if the key is in the tree.
+    /// Returns a tuple of form: (root, proof), the proof holds the sibling hashes from the first
+    /// default node along the path of the key to the root.
+    fn proof_of_absence(&self, key: &[u8]) -> Result<Option<AbsenceProof<H>>, TreeError> {
+        let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
+        let proof = absence_proof(&self.root_handle, &key, |node_hash| self.lookup(node_hash))?;
+        Ok(proof.map(|proof| (*self.root_handle.hash(), proof)))
+    }
+
     /// Inserts the provided value at the provided key address and returns the old value if it exists.
+    /// With deferred hashing the leaf is staged dirty and its path rehashed later, see rehash.
     fn insert(&mut self, key: &[u8], value: DBValue) -> Result<Option<DBValue>, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
-        let current_root = self.root_handle.clone();
-        let (new_root, old_node, changed) = self.insert_at(&current_root, &key, &value, 0)?;
-
-        if changed {
-            self.remove_node(&current_root);
-            self.root_handle = NodeHash::InMemory(*new_root.hash());
-            self.storage.insert(new_root);
+        self.check_value_size(&value)?;
+        let node = self.leaf_node(&key, &value);
+        if !self.deferred_hashing {
+            return self.insert_leaf(&key, node, None, &mut None);
         }
 
-        Ok(old_node)
+        self.check_unprepared()?;
+        let old_node = self.staged_leaf_node(&key)?;
+        self.dirty_leaves
+            .insert(DBValue::from(key.as_slice()), node);
+        self.auto_commit()?;
+        match old_node {
+            Some(node) => Ok(Some(node.value().map_err(TreeError::NodeError)?.clone())),
+            None => Ok(None),
+        }
     }
 
-    /// Removes the value at the provided key address and returns the old value if it exists.
+    /// Removes the value at the provided key address and returns the old value if it exists. The
+    /// value is read and the leaf removed in a single descent, no empty leaf is left behind as the
+    /// path of the key is collapsed into the default nodes of the emptied subtrees, see insert_at.
     fn remove(&mut self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
-        self.insert(key, vec![])
+        self.insert(key, DBValue::new())
     }
 
     /// Verifies that the given value is in the tree with the given root at the given index
@@ -359,20 +2060,200 @@ impl<'db, const D: usize, H: Hasher> KeyedTreeMut<H, D> for TreeDBMut<'db, D, H>
         root: &H::Out,
     ) -> Result<bool, TreeError> {
         let key = Key::<D>::new(key).map_err(TreeError::KeyError)?;
-        let mut hash = H::hash(value);
+        let mut hash = proof_leaf_hash::<H>(key.as_slice(), value);
         // iterate over the bits in the key in reverse order
-        for (bit, sibling) in (0..D * 8).rev().zip(proof.iter()) {
+        for (bit, sibling) in (0..Key::<D>::BITS).rev().zip(proof.iter()) {
+            if sibling.len() != H::LENGTH {
+                return Err(TreeError::NodeError(NodeError::DecodeNodeHashFailed(
+                    sibling.to_vec(),
+                )));
+            }
             let bit = key.bit(bit).map_err(TreeError::KeyError)?;
             let child_selector = ChildSelector::new(bit);
             match child_selector {
                 ChildSelector::Left => {
-                    hash = H::hash(&[hash.as_ref(), sibling].concat());
+                    hash = H::hash_inner(hash.as_ref(), sibling);
                 }
                 ChildSelector::Right => {
-                    hash = H::hash(&[sibling, hash.as_ref()].concat());
+                    hash = H::hash_inner(sibling, hash.as_ref());
                 }
             }
         }
         Ok(hash == *root)
     }
 }
+
+// HELPERS
+// ================================================================================================
+
+/// A step of the iterative rebuild of a subtree, see TreeDBMut::insert_batch_at and
+/// TreeDBMut::remove_range_at. The handles of rebuilt subtrees are pushed onto a stack, from which
+/// the Hash step of an inner node pops the handles of its two children.
+enum Rebuild<T, H: TreeHasher> {
+    /// Rebuild the subtree described by T
+    Visit(T),
+    /// The handle of a subtree that needs no rebuild
+    Rebuilt(NodeHash<H>),
+    /// Rehash the inner node with the provided handle from its rebuilt children
+    Hash(NodeHash<H>),
+}
+
+/// Returns the change set of a commit to the provided root from the provided inserted and removed
+/// node hashes and the provided leaf changes, see ChangeSet.
+fn change_set<H: TreeHasher>(
+    root: H::Out,
+    mut inserted: Vec<H::Out>,
+    mut removed: Vec<H::Out>,
+    leaf_changes: impl Iterator<Item = LeafChange>,
+) -> ChangeSet<H> {
+    let mut leaves: Vec<LeafChange> = leaf_changes.filter(|(_, old, new)| old != new).collect();
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+    inserted.sort();
+    removed.sort();
+    ChangeSet {
+        root,
+        inserted,
+        removed,
+        leaves,
+    }
+}
+
+/// The writes of a commit along with the hashes of the nodes inserted into and removed from the
+/// database
+type CommitWrites<H> = (
+    Vec<Write<H>>,
+    Vec<<H as Hasher>::Out>,
+    Vec<<H as Hasher>::Out>,
+);
+
+/// Returns the writes which insert the staged nodes into the database and remove the nodes on
+/// death row, the count of a node in the database is changed by the difference of its staged and
+/// death row counts.
+fn write_nodes<H: TreeHasher, S: BuildHasher + Default>(
+    storage: &NodeStorage<H, S>,
+    death_row: &HashMap<H::Out, usize, S>,
+) -> CommitWrites<H> {
+    let (mut writes, mut inserted, mut removed) = (Vec::new(), Vec::new(), Vec::new());
+    for (key, (node, insert_count)) in storage.iter() {
+        let death_count = death_row.get(key).copied().unwrap_or(0);
+        match insert_count.cmp(&death_count) {
+            // if they are the same do nothing
+            Ordering::Equal => {}
+            // if the count is greater, insert the node to db
+            Ordering::Greater => {
+                for _ in 0..insert_count - death_count {
+                    writes.push(Write::Emplace(*key, node.clone().into()));
+                }
+                inserted.push(*key);
+            }
+            // if the count is less, delete the node from db
+            Ordering::Less => {
+                for _ in 0..death_count - insert_count {
+                    writes.push(Write::Remove(*key, None));
+                }
+                removed.push(*key);
+            }
+        }
+    }
+
+    for (key, count) in death_row.iter() {
+        if storage.get(key).is_some() {
+            continue;
+        }
+        for _ in 0..*count {
+            writes.push(Write::Remove(*key, None));
+        }
+        removed.push(*key);
+    }
+    (writes, inserted, removed)
+}
+
+/// Returns the writes which add a reference to the node with the provided root hash in the
+/// provided database under the provided prefix, and references from each node that is new to the database to its children,
+/// see TreeDBMutBuilder::with_reference_counting. Nodes that are not in the database are read from
+/// the provided storage.
+fn reference_nodes<H: TreeHasher, S: BuildHasher + Default>(
+    db: &dyn HashDB<H, DBValue>,
+    prefix: Prefix,
+    storage: &NodeStorage<H, S>,
+    root: H::Out,
+) -> CommitWrites<H> {
+    let (mut writes, mut inserted) = (Vec::new(), Vec::new());
+    let mut planned = HashSet::<H::Out, S>::default();
+    let mut stack = Vec::from([root]);
+    while let Some(hash) = stack.pop() {
+        if let Some(data) = db.get(&hash, prefix) {
+            writes.push(Write::Emplace(hash, data));
+            continue;
+        }
+
+        let Some(node) = storage.get(&hash) else {
+            continue;
+        };
+        writes.push(Write::Emplace(hash, node.clone().into()));
+        // a node shared within the new tree is new to the database only once
+        if !planned.insert(hash) {
+            continue;
+        }
+        inserted.push(hash);
+        if let Node::Inner { left, right, .. } = node {
+            for child in [left, right] {
+                if !child.is_default() {
+                    stack.push(*child.hash());
+                }
+            }
+        }
+    }
+    (writes, inserted, Vec::new())
+}
+
+/// Applies the provided writes of a commit from the old root to the new root of the provided
+/// roots to the database under the provided prefix. The writes to a batched database are applied
+/// as a single WriteBatch, see TreeDBMutBuilder::new_batched. Otherwise if journaling is enabled
+/// the writes are journaled before they are applied, see TreeDBMutBuilder::with_journal, and if a
+/// write fails, the writes applied so far are reverted through the HashDB interface of the
+/// database and the error is returned.
+fn apply_writes<H: TreeHasher>(
+    db: &mut DBMut<'_, H>,
+    mut writes: Vec<Write<H>>,
+    roots: (H::Out, H::Out),
+    prefix: &[u8],
+    journaling: bool,
+) -> Result<(), TreeError> {
+    if writes.is_empty() {
+        return Ok(());
+    }
+    if let DBMut::Batched(db) = db {
+        // the batch is applied atomically, as such it is neither journaled nor reverted
+        let batch = WriteBatch::from_writes((prefix, None), &writes);
+        return db
+            .write_batch(&batch)
+            .map_err(|err| TreeError::DataError(DataError::WriteFailed(err)));
+    }
+    if journaling || matches!(db, DBMut::Fallible(_)) {
+        read_removed(db.as_hash_db(), (prefix, None), &mut writes);
+    }
+    let mut journal = Journal::new(roots, prefix, writes);
+    if journaling {
+        journal.begin(db)?;
+    }
+    let result = journal.apply(db);
+    if result.is_err() {
+        journal.revert(db.as_hash_db_mut());
+    }
+    journal.end(db.as_hash_db_mut());
+    result
+}
+
+/// Inserts the (key, value) pairs of the iterator into the tree, see KeyedTreeMut::insert.
+///
+/// Panics if a key is invalid for the depth of the tree or a value exceeds the maximum value size.
+impl<'db, const D: usize, H: TreeHasher, S: BuildHasher + Default> Extend<(Vec<u8>, DBValue)>
+    for TreeDBMut<'db, D, H, S>
+{
+    fn extend<I: IntoIterator<Item = (Vec<u8>, DBValue)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(&key, value).expect("failed to insert entry");
+        }
+    }
+}
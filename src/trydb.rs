@@ -0,0 +1,96 @@
+use super::{
+    rstd::string::String, BatchHashDB, DBValue, DataError, HashDB, Hasher, TreeError, WriteBatch,
+};
+use hash_db::{AsHashDB, Prefix};
+
+// TryHashDB
+// ================================================================================================
+
+/// An extension of HashDB for backends whose writes can fail, e.g. on a full disk or an I/O
+/// error. The HashDB interface of the backend is used for reads and to revert a failed commit, the
+/// writes of a commit are issued through try_emplace and try_remove such that their errors surface
+/// from TreeDBMut::try_commit rather than being lost, see TreeDBMutBuilder::new_fallible.
+pub trait TryHashDB<H: Hasher, T>: AsHashDB<H, T> + Send + Sync {
+    /// Like HashDB::emplace, returns a description of the error if the write failed
+    fn try_emplace(&mut self, key: H::Out, prefix: Prefix, value: T) -> Result<(), String>;
+
+    /// Like HashDB::remove, returns a description of the error if the write failed
+    fn try_remove(&mut self, key: &H::Out, prefix: Prefix) -> Result<(), String>;
+}
+
+// DBMut
+// ================================================================================================
+
+/// The database of a mutable tree, the writes of an infallible database always succeed and the
+/// writes of a batched database are issued as batches
+pub(crate) enum DBMut<'db, H: Hasher> {
+    Infallible(&'db mut dyn HashDB<H, DBValue>),
+    Fallible(&'db mut dyn TryHashDB<H, DBValue>),
+    Batched(&'db mut dyn BatchHashDB<H>),
+}
+
+impl<'db, H: Hasher> DBMut<'db, H> {
+    /// Returns the HashDB interface of the database
+    pub fn as_hash_db(&self) -> &dyn HashDB<H, DBValue> {
+        match self {
+            DBMut::Infallible(db) => &**db,
+            DBMut::Fallible(db) => db.as_hash_db(),
+            DBMut::Batched(db) => db.as_hash_db(),
+        }
+    }
+
+    /// Returns the mutable HashDB interface of the database
+    pub fn as_hash_db_mut(&mut self) -> &mut dyn HashDB<H, DBValue> {
+        match self {
+            DBMut::Infallible(db) => &mut **db,
+            DBMut::Fallible(db) => db.as_hash_db_mut(),
+            DBMut::Batched(db) => db.as_hash_db_mut(),
+        }
+    }
+
+    /// Reborrows the database for a shorter lifetime
+    pub fn reborrow(&mut self) -> DBMut<'_, H> {
+        match self {
+            DBMut::Infallible(db) => DBMut::Infallible(&mut **db),
+            DBMut::Fallible(db) => DBMut::Fallible(&mut **db),
+            DBMut::Batched(db) => DBMut::Batched(&mut **db),
+        }
+    }
+
+    /// Emplaces the provided value, see TryHashDB::try_emplace
+    pub fn try_emplace(
+        &mut self,
+        key: H::Out,
+        prefix: Prefix,
+        value: DBValue,
+    ) -> Result<(), TreeError> {
+        match self {
+            DBMut::Infallible(db) => {
+                db.emplace(key, prefix, value);
+                Ok(())
+            }
+            DBMut::Fallible(db) => db
+                .try_emplace(key, prefix, value)
+                .map_err(|err| TreeError::DataError(DataError::WriteFailed(err))),
+            DBMut::Batched(db) => db
+                .write_batch(&WriteBatch::single(prefix, key, value, 1))
+                .map_err(|err| TreeError::DataError(DataError::WriteFailed(err))),
+        }
+    }
+
+    /// Removes the value with the provided key, see TryHashDB::try_remove
+    pub fn try_remove(&mut self, key: &H::Out, prefix: Prefix) -> Result<(), TreeError> {
+        match self {
+            DBMut::Infallible(db) => {
+                db.remove(key, prefix);
+                Ok(())
+            }
+            DBMut::Fallible(db) => db
+                .try_remove(key, prefix)
+                .map_err(|err| TreeError::DataError(DataError::WriteFailed(err))),
+            DBMut::Batched(db) => db
+                .write_batch(&WriteBatch::single(prefix, *key, DBValue::default(), -1))
+                .map_err(|err| TreeError::DataError(DataError::WriteFailed(err))),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use super::{
+    rstd::vec::Vec, AbsenceProof, ChangeSet, DBValue, DataError, HashDB, Key, KeyedTree,
+    KeyedTreeMut, Proof, TreeDBBuilder, TreeDBMut, TreeDBMutBuilder, TreeError, TreeHasher,
+};
+
+// VersionedTreeDBMutBuilder
+// ================================================================================================
+
+/// Used to construct a VersionedTreeDBMut
+pub struct VersionedTreeDBMutBuilder<'db, const D: usize, H: TreeHasher> {
+    db: &'db mut dyn HashDB<H, DBValue>,
+    root: &'db mut H::Out,
+    salt: DBValue,
+    versions: Vec<H::Out>,
+}
+
+impl<'db, const D: usize, H: TreeHasher> VersionedTreeDBMutBuilder<'db, D, H> {
+    /// Construct a new VersionedTreeDBMutBuilder, the provided root is the first version
+    pub fn new(db: &'db mut dyn HashDB<H, DBValue>, root: &'db mut H::Out) -> Self {
+        // fails the build if the depth of the tree overflows a usize
+        let _ = Key::<D>::BITS;
+        Self {
+            db,
+            root,
+            salt: DBValue::default(),
+            versions: Vec::new(),
+        }
+    }
+
+    /// Add a salt to the VersionedTreeDBMutBuilder, see TreeDBMutBuilder::with_salt
+    pub fn with_salt(mut self, salt: &[u8]) -> Self {
+        self.salt = DBValue::from(salt);
+        self
+    }
+
+    /// Restore the roots of earlier versions, ordered from the first version to the latest
+    /// version, see VersionedTreeDBMut::versions. The latest version must be the provided root and
+    /// the versions must not have been pruned.
+    pub fn with_versions(mut self, versions: Vec<H::Out>) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// build a VersionedTreeDBMut
+    pub fn build(self) -> VersionedTreeDBMut<'db, D, H> {
+        let tree = TreeDBMutBuilder::new(self.db, self.root)
+            .with_salt(&self.salt)
+            .with_reference_counting()
+            .build();
+        // the root of an empty tree is recorded as the default root
+        let mut versions = self.versions;
+        if versions.last() != Some(&tree.root_hint()) {
+            versions.push(tree.root_hint());
+        }
+        VersionedTreeDBMut {
+            tree,
+            versions,
+            pruned: 0,
+        }
+    }
+}
+
+// VersionedTreeDBMut
+// ================================================================================================
+
+/// A mutable merkle tree db that records the root of every committed version and serves reads and
+/// proofs of earlier versions, e.g. for the historical state queries of an archive node. The nodes
+/// of every version are kept in the database with reference counting, see
+/// TreeDBMutBuilder::with_reference_counting, until the version is pruned.
+pub struct VersionedTreeDBMut<'db, const D: usize, H: TreeHasher> {
+    tree: TreeDBMut<'db, D, H>,
+    versions: Vec<H::Out>,
+    pruned: usize,
+}
+
+impl<'db, const D: usize, H: TreeHasher> VersionedTreeDBMut<'db, D, H> {
+    /// Commit the changes to the database as a new version and returns the changes written, see
+    /// TreeDBMut::commit.
+    pub fn commit(&mut self) -> ChangeSet<H> {
+        let change_set = self.tree.commit();
+        self.versions.push(change_set.root);
+        change_set
+    }
+
+    /// Returns the latest version
+    pub fn version(&self) -> u64 {
+        self.versions.len() as u64 - 1
+    }
+
+    /// Returns the roots of the versions, indexed by version. Pruned versions hold the root they
+    /// had when they were committed.
+    pub fn versions(&self) -> &[H::Out] {
+        &self.versions
+    }
+
+    /// Returns the root of the provided version
+    ///
+    /// Errors:
+    /// - UnknownVersion: if the version has not been committed
+    /// - PrunedVersion: if the version has been pruned
+    pub fn root_at(&self, version: u64) -> Result<H::Out, TreeError> {
+        let root = usize::try_from(version)
+            .ok()
+            .and_then(|version| self.versions.get(version))
+            .ok_or(TreeError::DataError(DataError::UnknownVersion(version)))?;
+        if version < self.pruned as u64 {
+            return Err(TreeError::DataError(DataError::PrunedVersion(version)));
+        }
+        Ok(*root)
+    }
+
+    /// Returns the value at the provided key in the provided version
+    ///
+    /// Errors:
+    /// - UnknownVersion: if the version has not been committed
+    /// - PrunedVersion: if the version has been pruned
+    pub fn value_at(&self, version: u64, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        let root = self.root_at(version)?;
+        let db = self.tree.db();
+        TreeDBBuilder::<D, H>::new(&db, &root)
+            .with_salt(self.tree.salt())
+            .build()
+            .value(key)
+    }
+
+    /// Returns an inclusion proof of the value at the provided key in the provided version.
+    /// Returns a tuple of form: (value, root, proof).
+    ///
+    /// Errors:
+    /// - UnknownVersion: if the version has not been committed
+    /// - PrunedVersion: if the version has been pruned
+    pub fn proof_at(&self, version: u64, key: &[u8]) -> Result<Proof<H>, TreeError> {
+        let root = self.root_at(version)?;
+        let db = self.tree.db();
+        TreeDBBuilder::<D, H>::new(&db, &root)
+            .with_salt(self.tree.salt())
+            .build()
+            .proof(key)
+    }
+
+    /// Removes the nodes of the versions before the provided version from the database, nodes
+    /// shared with a retained version are kept. Returns the number of nodes removed.
+    ///
+    /// Errors:
+    /// - UnknownVersion: if the version has not been committed
+    /// - PrunedVersion: if the version has been pruned
+    pub fn prune(&mut self, version: u64) -> Result<usize, TreeError> {
+        self.root_at(version)?;
+        let mut removed = 0;
+        for pruned in self.pruned..version as usize {
+            // a commit without changes shares the reference of the version it repeats
+            if self.versions[pruned] != self.versions[pruned + 1] {
+                removed += self.tree.release_root(self.versions[pruned])?.len();
+            }
+            self.pruned = pruned + 1;
+        }
+        Ok(removed)
+    }
+}
+
+impl<'db, const D: usize, H: TreeHasher> KeyedTreeMut<H, D> for VersionedTreeDBMut<'db, D, H> {
+    /// Returns the root of the tree, pending changes that change the root are committed as a new
+    /// version
+    fn root(&mut self) -> &H::Out {
+        if self.tree.root_hint() == self.versions[self.versions.len() - 1] {
+            self.tree.commit();
+        } else {
+            self.commit();
+        }
+        &self.versions[self.versions.len() - 1]
+    }
+
+    /// Returns the root of the tree including the uncommitted changes
+    fn root_hint(&self) -> H::Out {
+        self.tree.root_hint()
+    }
+
+    /// Returns the salt used for leaf hashing
+    fn salt(&self) -> &[u8] {
+        self.tree.salt()
+    }
+
+    /// Returns the value at the provided key
+    fn value(&self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        self.tree.value(key)
+    }
+
+    /// Returns the leaf hash at the provided key
+    fn leaf(&self, key: &[u8]) -> Result<Option<H::Out>, TreeError> {
+        self.tree.leaf(key)
+    }
+
+    /// Returns an inclusion proof of the value at the provided key
+    fn proof(&self, key: &[u8]) -> Result<Proof<H>, TreeError> {
+        self.tree.proof(key)
+    }
+
+    /// Returns a non-inclusion proof of the provided key, None if the key is in the tree
+    fn proof_of_absence(&self, key: &[u8]) -> Result<Option<AbsenceProof<H>>, TreeError> {
+        self.tree.proof_of_absence(key)
+    }
+
+    /// Inserts the provided value at the provided key and returns the old value
+    fn insert(&mut self, key: &[u8], value: DBValue) -> Result<Option<DBValue>, TreeError> {
+        self.tree.insert(key, value)
+    }
+
+    /// Removes the value at the provided key and returns the old value
+    fn remove(&mut self, key: &[u8]) -> Result<Option<DBValue>, TreeError> {
+        self.tree.remove(key)
+    }
+
+    /// Verifies that the provided value is in the tree with the provided root at the provided key
+    fn verify(
+        key: &[u8],
+        value: &[u8],
+        proof: &[DBValue],
+        root: &H::Out,
+    ) -> Result<bool, TreeError> {
+        TreeDBMut::<'db, D, H>::verify(key, value, proof, root)
+    }
+}